@@ -0,0 +1,17 @@
+// The grammar of the sample programs, the AST is of its trees.
+pub const GRAMMAR_0: &str = "
+S               -> fn_call_or_decl , S | fn_call_or_decl |
+fn_call_or_decl -> fn_call | fn_declaration
+fn_call         -> ID ( args ) ;
+args            -> arg , args | arg |
+arg             -> STRING | INT | ID
+fn_declaration  -> FN ID ( params ) { statements }
+params          -> param , params | param |
+param           -> ID ID
+statements      -> statement statements | statement |
+statement       -> ID ID ; | ID = expressions ; | fn_call | ret
+expressions     -> terms + expressions | terms - expressions | terms
+terms           -> factor * terms | factor / terms | factor
+factor          -> ( expressions ) | INT | ID
+ret             -> RETURN expressions ;
+";
@@ -0,0 +1,618 @@
+use crate::lang::ast::program::BinOp;
+use crate::lang::ast::program::Call;
+use crate::lang::ast::program::Expr;
+use crate::lang::ast::program::FnDecl;
+use crate::lang::ast::program::Literal;
+use crate::lang::ast::program::Param;
+use crate::lang::ast::program::Program;
+use crate::lang::ast::program::Stmt;
+use crate::lang::error::LowerError;
+use crate::lang::error::Span;
+use crate::lang::lexer::token::Token;
+use crate::lang::lexer::token::TokenKind;
+use crate::lang::parser::tree::NodeId;
+use crate::lang::parser::tree::Tree;
+
+// The AST of the tree of a program of GRAMMAR_0. The rules may have been transformed, as by
+// make_ready_for_recursive_decent, as long as the helper rules are named after the rule they were
+// made of: the parts of a rule are gathered through its helpers before being looked at, so a
+// statement is the same whether under statement or inlined in statements__0.
+pub fn lower(tree: &Tree<'_>) -> Result<Program, LowerError> {
+    Lowering {
+        tree,
+    }
+    .program()
+}
+
+
+struct Lowering<'t, 'a> {
+    tree: &'t Tree<'a>,
+}
+
+impl<'t, 'a> Lowering<'t, 'a> {
+    fn program(&self) -> Result<Program, LowerError> {
+        let mut parts = self.parts(self.tree.root(), &["S", "fn_call_or_decl", "fn_call"]);
+        let mut fns = vec![];
+        let mut calls = vec![];
+
+        while !parts.is_done() {
+            if let Some(fn_declaration) = parts.rule("fn_declaration") {
+                fns.push(self.fn_decl(fn_declaration)?);
+            }
+            else if parts.next_is(TokenKind::Id) {
+                let name = parts.token(TokenKind::Id)?;
+                calls.push(self.call(name, &mut parts)?);
+            }
+            else {
+                return Err(parts.unexpected("a function declaration or call"));
+            }
+
+            if !parts.is_done() {
+                parts.token(TokenKind::Comma)?;
+            }
+        }
+
+        let tokens = self.tree.tokens();
+        let span = match (tokens.first(), tokens.last()) {
+            (Some(first), Some(last)) => Span::from(first).to(Span::from(last)),
+            _ => Span {
+                start: 0,
+                end: 0,
+                line: 1,
+            },
+        };
+
+        Ok(Program {
+            fns,
+            calls,
+            span,
+        })
+    }
+
+    fn fn_decl(
+        &self,
+        id: NodeId,
+    ) -> Result<FnDecl, LowerError> {
+        let mut parts = self.parts(id, &["fn_declaration"]);
+
+        let start = parts.token(TokenKind::Fn)?;
+        let name = parts.token(TokenKind::Id)?;
+        parts.token(TokenKind::LeftParen)?;
+        let params = match parts.rule("params") {
+            Some(params) => self.params(params)?,
+            None => vec![],
+        };
+        parts.token(TokenKind::RightParen)?;
+        parts.token(TokenKind::LeftBraces)?;
+        let body = match parts.rule("statements") {
+            Some(statements) => self.statements(statements)?,
+            None => vec![],
+        };
+        let end = parts.token(TokenKind::RightBraces)?;
+        parts.end()?;
+
+        Ok(FnDecl {
+            name: name.text.to_string(),
+            params,
+            body,
+            span: Span::from(&start).to(Span::from(&end)),
+        })
+    }
+
+    fn params(
+        &self,
+        id: NodeId,
+    ) -> Result<Vec<Param>, LowerError> {
+        let mut parts = self.parts(id, &["params"]);
+        let mut params = vec![];
+
+        while !parts.is_done() {
+            let mut param = self.parts(parts.expect_rule("param")?, &["param"]);
+            let ty = param.token(TokenKind::Id)?;
+            let name = param.token(TokenKind::Id)?;
+            param.end()?;
+
+            params.push(Param {
+                ty: ty.text.to_string(),
+                name: name.text.to_string(),
+                span: Span::from(&ty).to(Span::from(&name)),
+            });
+
+            if !parts.is_done() {
+                parts.token(TokenKind::Comma)?;
+            }
+        }
+
+        Ok(params)
+    }
+
+    fn statements(
+        &self,
+        id: NodeId,
+    ) -> Result<Vec<Stmt>, LowerError> {
+        let mut parts = self.parts(id, &["statements", "statement", "ret", "fn_call"]);
+        let mut statements = vec![];
+
+        while !parts.is_done() {
+            statements.push(self.statement(&mut parts)?);
+        }
+
+        Ok(statements)
+    }
+
+    fn statement(
+        &self,
+        parts: &mut Parts<'t, 'a>,
+    ) -> Result<Stmt, LowerError> {
+        if parts.next_is(TokenKind::Return) {
+            let start = parts.token(TokenKind::Return)?;
+            let value = self.expressions(parts.expect_rule("expressions")?)?;
+            let end = parts.token(TokenKind::Semicolon)?;
+            return Ok(Stmt::Return {
+                value,
+                span: Span::from(&start).to(Span::from(&end)),
+            });
+        }
+
+        let first = parts.token(TokenKind::Id)?;
+        if parts.next_is(TokenKind::Id) {
+            let name = parts.token(TokenKind::Id)?;
+            let end = parts.token(TokenKind::Semicolon)?;
+            Ok(Stmt::Decl {
+                ty: first.text.to_string(),
+                name: name.text.to_string(),
+                span: Span::from(&first).to(Span::from(&end)),
+            })
+        }
+        else if parts.next_is(TokenKind::Equal) {
+            parts.token(TokenKind::Equal)?;
+            let value = self.expressions(parts.expect_rule("expressions")?)?;
+            let end = parts.token(TokenKind::Semicolon)?;
+            Ok(Stmt::Assign {
+                name: first.text.to_string(),
+                value,
+                span: Span::from(&first).to(Span::from(&end)),
+            })
+        }
+        else if parts.next_is(TokenKind::LeftParen) {
+            Ok(Stmt::Call(self.call(first, parts)?))
+        }
+        else {
+            Err(parts.unexpected("a declaration, an assignment or a call"))
+        }
+    }
+
+    // The rest of a call after the name, up to and including the semicolon.
+    fn call(
+        &self,
+        name: Token<'a>,
+        parts: &mut Parts<'t, 'a>,
+    ) -> Result<Call, LowerError> {
+        parts.token(TokenKind::LeftParen)?;
+        let args = match parts.rule("args") {
+            Some(args) => self.args(args)?,
+            None => vec![],
+        };
+        parts.token(TokenKind::RightParen)?;
+        let end = parts.token(TokenKind::Semicolon)?;
+
+        Ok(Call {
+            name: name.text.to_string(),
+            args,
+            span: Span::from(&name).to(Span::from(&end)),
+        })
+    }
+
+    fn args(
+        &self,
+        id: NodeId,
+    ) -> Result<Vec<Expr>, LowerError> {
+        let mut parts = self.parts(id, &["args"]);
+        let mut args = vec![];
+
+        while !parts.is_done() {
+            let mut arg = self.parts(parts.expect_rule("arg")?, &["arg"]);
+            args.push(self.atom(&mut arg)?);
+            arg.end()?;
+
+            if !parts.is_done() {
+                parts.token(TokenKind::Comma)?;
+            }
+        }
+
+        Ok(args)
+    }
+
+
+    fn expressions(
+        &self,
+        id: NodeId,
+    ) -> Result<Expr, LowerError> {
+        let ops = [
+            (TokenKind::Plus, BinOp::Add),
+            (TokenKind::Minus, BinOp::Sub),
+        ];
+        self.binary(id, "expressions", "terms", &ops, Self::terms)
+    }
+
+    fn terms(
+        &self,
+        id: NodeId,
+    ) -> Result<Expr, LowerError> {
+        let ops = [
+            (TokenKind::Star, BinOp::Mul),
+            (TokenKind::Slash, BinOp::Div),
+        ];
+        self.binary(id, "terms", "factor", &ops, Self::factor)
+    }
+
+    // Left associative as the operators are meant to be, although the rules recurse to the right:
+    // the operands of the chain are gathered first, then grouped from the left.
+    fn binary(
+        &self,
+        id: NodeId,
+        rule: &str,
+        operand_rule: &str,
+        ops: &[(TokenKind, BinOp)],
+        operand: fn(&Self, NodeId) -> Result<Expr, LowerError>,
+    ) -> Result<Expr, LowerError> {
+        let mut parts = self.parts(id, &[rule]);
+        let mut expr = operand(self, parts.expect_rule(operand_rule)?)?;
+
+        while !parts.is_done() {
+            let op = match ops.iter().find(|(tk, _)| parts.next_is(*tk)) {
+                Some((tk, op)) => {
+                    parts.token(*tk)?;
+                    *op
+                },
+                None => {
+                    let expecting = ops.iter().map(|it| it.0.name()).collect::<Vec<_>>();
+                    return Err(parts.unexpected(&expecting.join(" or ")));
+                },
+            };
+            let right = operand(self, parts.expect_rule(operand_rule)?)?;
+
+            let span = expr.span().to(right.span());
+            expr = Expr::Binary {
+                op,
+                left: Box::new(expr),
+                right: Box::new(right),
+                span,
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn factor(
+        &self,
+        id: NodeId,
+    ) -> Result<Expr, LowerError> {
+        let mut parts = self.parts(id, &["factor"]);
+
+        let expr = if parts.next_is(TokenKind::LeftParen) {
+            parts.token(TokenKind::LeftParen)?;
+            let expr = self.expressions(parts.expect_rule("expressions")?)?;
+            parts.token(TokenKind::RightParen)?;
+            expr
+        }
+        else {
+            self.atom(&mut parts)?
+        };
+        parts.end()?;
+
+        Ok(expr)
+    }
+
+    fn atom(
+        &self,
+        parts: &mut Parts<'t, 'a>,
+    ) -> Result<Expr, LowerError> {
+        if parts.next_is(TokenKind::Int) {
+            let token = parts.token(TokenKind::Int)?;
+            let value = token.text.parse::<i64>().map_err(|err| LowerError {
+                message: format!("invalid integer: {}, error: {}", token.text, err),
+                span: Some(Span::from(&token)),
+            })?;
+            Ok(Expr::Literal(Literal::Int(value), Span::from(&token)))
+        }
+        else if parts.next_is(TokenKind::String) {
+            let token = parts.token(TokenKind::String)?;
+            Ok(Expr::Literal(
+                Literal::Str(token.text.to_string()),
+                Span::from(&token),
+            ))
+        }
+        else if parts.next_is(TokenKind::Id) {
+            let token = parts.token(TokenKind::Id)?;
+            Ok(Expr::Var(token.text.to_string(), Span::from(&token)))
+        }
+        else {
+            Err(parts.unexpected("INT, STRING or ID"))
+        }
+    }
+
+
+    // The children of the node, those of the rules in through, or of their helpers, replaced by
+    // their own children, and so on down. EPSILON is left out.
+    fn parts(
+        &self,
+        id: NodeId,
+        through: &[&str],
+    ) -> Parts<'t, 'a> {
+        let mut ids = vec![];
+        let mut stack = self.tree.children(id).collect::<Vec<_>>();
+        stack.reverse();
+
+        while let Some(child) = stack.pop() {
+            let node = self.tree.node(child);
+            if node.token_kind() == Some(TokenKind::Epsilon) {
+                continue;
+            }
+
+            if !node.is_token() && through.contains(&base_name(node.name())) {
+                let len = stack.len();
+                stack.extend(self.tree.children(child));
+                stack[len..].reverse();
+            }
+            else {
+                ids.push(child);
+            }
+        }
+
+        Parts {
+            tree: self.tree,
+            of: id,
+            ids,
+            at: 0,
+        }
+    }
+}
+
+// The rule a helper rule was made of, see Rules::find_new_indexed_name.
+fn base_name(name: &str) -> &str {
+    name.split_once("__").map_or(name, |it| it.0)
+}
+
+
+// The parts of a node as gathered by Lowering::parts, taken one by one.
+struct Parts<'t, 'a> {
+    tree: &'t Tree<'a>,
+    of: NodeId,
+    ids: Vec<NodeId>,
+    at: usize,
+}
+
+impl<'a> Parts<'_, 'a> {
+    fn is_done(&self) -> bool {
+        self.at == self.ids.len()
+    }
+
+    fn next_is(
+        &self,
+        token_kind: TokenKind,
+    ) -> bool {
+        self.ids
+            .get(self.at)
+            .is_some_and(|it| self.tree.node(*it).token_kind() == Some(token_kind))
+    }
+
+    fn token(
+        &mut self,
+        token_kind: TokenKind,
+    ) -> Result<Token<'a>, LowerError> {
+        let token = match self.ids.get(self.at) {
+            Some(id) if self.next_is(token_kind) => self.tree.node(*id).token().copied(),
+            _ => None,
+        };
+
+        match token {
+            Some(token) => {
+                self.at += 1;
+                Ok(token)
+            },
+            None => Err(self.unexpected(token_kind.upper_name())),
+        }
+    }
+
+    fn rule(
+        &mut self,
+        name: &str,
+    ) -> Option<NodeId> {
+        let id = *self.ids.get(self.at)?;
+        let node = self.tree.node(id);
+        if node.is_token() || base_name(node.name()) != name {
+            return None;
+        }
+
+        self.at += 1;
+        Some(id)
+    }
+
+    fn expect_rule(
+        &mut self,
+        name: &str,
+    ) -> Result<NodeId, LowerError> {
+        self.rule(name).ok_or_else(|| self.unexpected(name))
+    }
+
+    fn end(&self) -> Result<(), LowerError> {
+        match self.is_done() {
+            true => Ok(()),
+            false => Err(self.unexpected("nothing more")),
+        }
+    }
+
+    fn unexpected(
+        &self,
+        expecting: &str,
+    ) -> LowerError {
+        let found = self.ids.get(self.at).copied();
+        let got = found.map_or("nothing".to_string(), |it| {
+            self.tree.node(it).name().to_string()
+        });
+
+        LowerError {
+            message: format!(
+                "{}: expecting {}, got: {}",
+                self.tree.node(self.of).name(),
+                expecting,
+                got
+            ),
+            span: found.and_then(|it| {
+                self.tree
+                    .preorder(it)
+                    .find_map(|it| self.tree.node(it).token())
+                    .map(Span::from)
+            }),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::lang::ast::grammar::GRAMMAR_0;
+    use crate::lang::ast::lower::lower;
+    use crate::lang::ast::program::Expr;
+    use crate::lang::ast::program::Literal;
+    use crate::lang::ast::program::Stmt;
+    use crate::lang::lexer::v0::Lexer;
+    use crate::lang::parser::rules::Rules;
+    use crate::lang::parser::tree::Tree;
+    use crate::lang::parser_impl::backtracking_parser::parse_with_backtracking;
+    use crate::lang::parser_impl::recursive_descent_parser::recursive_descent_parse;
+
+    const PROGRAM: &str = "\
+fn my_thing42(int j, string q) {
+    x1 = 1 * 30;
+    x2 = x3 / 10;
+    int y;
+    y = x4 + 2;
+    int z;
+    print(\"foo\\\"bar \\some thing\");
+    z = x5 * y;
+    print(z);
+    int x0;
+    return x0 + 0;
+},
+print(\"done\");
+";
+
+    #[test]
+    fn test_lower() {
+        let mut rules: Rules = GRAMMAR_0.try_into().unwrap();
+        rules.make_ready_for_recursive_decent(128).unwrap();
+        let lexer: Lexer = PROGRAM.into();
+        let node = recursive_descent_parse(&rules, lexer.into_iter()).unwrap();
+        let program = lower(&Tree::from(&node)).unwrap();
+
+        assert_eq!(program.fns.len(), 1);
+        let fn_decl = &program.fns[0];
+        assert_eq!(fn_decl.name, "my_thing42");
+        assert_eq!(
+            fn_decl
+                .params
+                .iter()
+                .map(|it| (it.ty.as_str(), it.name.as_str()))
+                .collect::<Vec<_>>(),
+            [("int", "j"), ("string", "q")]
+        );
+        let fn_end = PROGRAM.find("},").unwrap() + 1;
+        assert_eq!(
+            &PROGRAM[fn_decl.span.start..fn_decl.span.end],
+            &PROGRAM[..fn_end]
+        );
+
+        assert_eq!(fn_decl.body.len(), 10);
+        match &fn_decl.body[0] {
+            Stmt::Assign {
+                name,
+                value,
+                span,
+            } => {
+                assert_eq!(name, "x1");
+                assert_eq!(value.to_string(), "(1 * 30)");
+                assert_eq!(&PROGRAM[span.start..span.end], "x1 = 1 * 30;");
+                assert_eq!(span.line, 2);
+            },
+            stmt => panic!("expecting an assignment, got: {:?}", stmt),
+        }
+        match &fn_decl.body[2] {
+            Stmt::Decl {
+                ty,
+                name,
+                ..
+            } => assert_eq!((ty.as_str(), name.as_str()), ("int", "y")),
+            stmt => panic!("expecting a declaration, got: {:?}", stmt),
+        }
+        match &fn_decl.body[5] {
+            Stmt::Call(call) => {
+                assert_eq!(call.name, "print");
+                assert!(matches!(
+                    &call.args[..],
+                    [Expr::Literal(Literal::Str(text), _)] if text == "foo\\\"bar \\some thing"
+                ));
+            },
+            stmt => panic!("expecting a call, got: {:?}", stmt),
+        }
+        match &fn_decl.body[9] {
+            Stmt::Return {
+                value,
+                span,
+            } => {
+                assert_eq!(value.to_string(), "(x0 + 0)");
+                assert_eq!(span.line, 11);
+            },
+            stmt => panic!("expecting a return, got: {:?}", stmt),
+        }
+
+        assert_eq!(program.calls.len(), 1);
+        assert_eq!(program.calls[0].args[0].to_string(), "\"done\"");
+
+        // The same of the tree of the rules not made ready for recursive descent.
+        let mut rules: Rules = GRAMMAR_0.try_into().unwrap();
+        rules.eliminate_left_recursions();
+        let tokens = Lexer::parse(PROGRAM).unwrap();
+        let node = parse_with_backtracking(&rules, tokens.into_iter()).unwrap();
+        assert_eq!(lower(&Tree::from(&node)).unwrap(), program);
+    }
+
+    #[test]
+    fn test_lower_expressions() {
+        let mut rules: Rules = GRAMMAR_0.try_into().unwrap();
+        rules.make_ready_for_recursive_decent(128).unwrap();
+
+        for (expression, expected) in [
+            ("a - b - c", "((a - b) - c)"),
+            ("a - (b - c)", "(a - (b - c))"),
+            ("1 + 2 * 3 / x - 4", "((1 + ((2 * 3) / x)) - 4)"),
+            ("((7))", "7"),
+        ] {
+            let source = format!("fn f() {{ return {}; }}", expression);
+            let lexer: Lexer = source.as_str().into();
+            let node = recursive_descent_parse(&rules, lexer.into_iter()).unwrap();
+            let program = lower(&Tree::from(&node)).unwrap();
+
+            match &program.fns[0].body[..] {
+                [Stmt::Return {
+                    value, ..
+                }] => assert_eq!(value.to_string(), expected),
+                body => panic!("expecting a return, got: {:?}", body),
+            }
+        }
+    }
+
+    #[test]
+    fn test_lower_unknown_tree() {
+        let rules: Rules = "S -> ID ; S | EPSILON".try_into().unwrap();
+        let lexer: Lexer = "x;".into();
+        let node = recursive_descent_parse(&rules, lexer.into_iter()).unwrap();
+
+        let err = lower(&Tree::from(&node)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "S: expecting LEFT_PAREN, got: SEMICOLON at 1/1"
+        );
+    }
+}
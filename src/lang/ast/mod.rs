@@ -0,0 +1,3 @@
+pub mod grammar;
+pub mod lower;
+pub mod program;
@@ -0,0 +1,161 @@
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use crate::lang::error::Span;
+
+// The typed tree of a program of GRAMMAR_0, see lower::lower for making one of a parse tree.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Program {
+    pub fns: Vec<FnDecl>,
+    // Calls outside of any function.
+    pub calls: Vec<Call>,
+    pub span: Span,
+}
+
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FnDecl {
+    pub name: String,
+    pub params: Vec<Param>,
+    pub body: Vec<Stmt>,
+    pub span: Span,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Param {
+    pub ty: String,
+    pub name: String,
+    pub span: Span,
+}
+
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Stmt {
+    Decl {
+        ty: String,
+        name: String,
+        span: Span,
+    },
+    Assign {
+        name: String,
+        value: Expr,
+        span: Span,
+    },
+    Call(Call),
+    Return {
+        value: Expr,
+        span: Span,
+    },
+}
+
+impl Stmt {
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Decl {
+                span, ..
+            }
+            | Stmt::Assign {
+                span, ..
+            }
+            | Stmt::Return {
+                span, ..
+            } => *span,
+            Stmt::Call(call) => call.span,
+        }
+    }
+}
+
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Call {
+    pub name: String,
+    pub args: Vec<Expr>,
+    pub span: Span,
+}
+
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl Display for BinOp {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        match self {
+            BinOp::Add => write!(f, "+"),
+            BinOp::Sub => write!(f, "-"),
+            BinOp::Mul => write!(f, "*"),
+            BinOp::Div => write!(f, "/"),
+        }
+    }
+}
+
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Literal {
+    Int(i64),
+    // As written, without the quotes, escapes not undone.
+    Str(String),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Expr {
+    Binary {
+        op: BinOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+        span: Span,
+    },
+    Call(Call),
+    Literal(Literal, Span),
+    Var(String, Span),
+}
+
+impl Expr {
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Binary {
+                span, ..
+            }
+            | Expr::Literal(_, span)
+            | Expr::Var(_, span) => *span,
+            Expr::Call(call) => call.span,
+        }
+    }
+}
+
+impl Display for Expr {
+    // Each binary expression in parentheses, to show how the operands are grouped.
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        match self {
+            Expr::Binary {
+                op,
+                left,
+                right,
+                ..
+            } => write!(f, "({} {} {})", left, op, right),
+            Expr::Call(call) => {
+                write!(f, "{}(", call.name)?;
+                for (i, arg) in call.args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            },
+            Expr::Literal(Literal::Int(value), _) => write!(f, "{}", value),
+            Expr::Literal(Literal::Str(value), _) => write!(f, "\"{}\"", value),
+            Expr::Var(name, _) => write!(f, "{}", name),
+        }
+    }
+}
@@ -0,0 +1,611 @@
+use std::error::Error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use crate::lang::lexer::token::Token;
+use crate::lang::lexer::token::TokenKind;
+use crate::lang::lexer::v0::LexerError;
+
+// Where a token is in the text, positions are byte offsets.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+}
+
+impl From<&Token<'_>> for Span {
+    fn from(token: &Token<'_>) -> Self {
+        Self {
+            start: token.start_pos,
+            end: token.end_pos,
+            line: token.line,
+        }
+    }
+}
+
+impl Span {
+    // From the start of this one to the end of the other, which comes later.
+    pub fn to(
+        self,
+        other: Span,
+    ) -> Span {
+        Span {
+            start: self.start,
+            end: other.end,
+            line: self.line,
+        }
+    }
+}
+
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum LexError {
+    UnexpectedCharInNumber {
+        line: usize,
+        char: char,
+    },
+    UnterminatedString {
+        start: usize,
+        text: String,
+    },
+    UnexpectedChar {
+        line: usize,
+        position: usize,
+        char: char,
+    },
+    // Once an error is returned the lexer returns only this.
+    AfterError,
+}
+
+impl Display for LexError {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        match self {
+            LexError::UnexpectedCharInNumber {
+                line,
+                char,
+            } => write!(
+                f,
+                "unexpected char while reading number, line={} char={}",
+                line, char
+            ),
+            LexError::UnterminatedString {
+                start,
+                text,
+            } => {
+                write!(f, "unterminated string at: {} => {}", start, text)
+            },
+            LexError::UnexpectedChar {
+                line,
+                position,
+                char,
+            } => write!(
+                f,
+                "unexpected character at line={} pos={}: {}",
+                line, position, char
+            ),
+            LexError::AfterError => write!(f, "lexer has previously encountered an error"),
+        }
+    }
+}
+
+impl Error for LexError {
+}
+
+
+// What is wrong with rules, lists of rules are in the order the rules are in.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum GrammarError {
+    // A line of the rules description not understood.
+    Syntax {
+        line: String,
+        error: String,
+    },
+    NoRules,
+    // (rule, error) of rules failing Rule::validate.
+    InvalidRules(Vec<(String, String)>),
+    DuplicateRules(Vec<String>),
+    DuplicateRecursionEliminationNums(Vec<usize>),
+    MissingRules(Vec<String>),
+    NoAlternative(Vec<String>),
+    EmptyAlternative(Vec<String>),
+    LeftRecursiveExpression {
+        rule: String,
+        alt_no: usize,
+    },
+    StartRule(String),
+    Predicates(Vec<String>),
+    NotBacktrackFree {
+        rule: String,
+        alt_nos: (usize, usize),
+        intersection: Vec<TokenKind>,
+        // The conflicts found, one per line.
+        explanation: String,
+    },
+    NotFixed {
+        max_loop: usize,
+    },
+    NotChomskyNormalForm,
+    ZeroLookahead,
+    NotLlK {
+        k: usize,
+        rule: String,
+        alt_nos: (usize, usize),
+        // The token sequences both alternatives start with, as text.
+        intersection: Vec<String>,
+    },
+    NotLl1 {
+        rule: String,
+        alt_nos: (usize, usize),
+        token: TokenKind,
+    },
+    // The kind of table, LR(1) or LALR(1), and the conflicts, one per action.
+    LrConflicts {
+        kind: String,
+        conflicts: Vec<String>,
+    },
+    UselessRules {
+        non_productive: Vec<String>,
+        unreachable: Vec<String>,
+    },
+    NonProductiveStartRule(String),
+    // Rules deriving no sentence at all.
+    NonProductiveRules(Vec<String>),
+    // Of a compiled grammar.
+    UnknownRule(String),
+    UnsupportedVersion {
+        version: Option<u64>,
+        expecting: u32,
+    },
+    Json(String),
+    Io {
+        path: String,
+        error: String,
+    },
+}
+
+impl GrammarError {
+    // The rule the error is about, the first one when about several.
+    pub fn rule(&self) -> Option<&str> {
+        match self {
+            GrammarError::Syntax {
+                ..
+            } => None,
+            GrammarError::NoRules => None,
+            GrammarError::InvalidRules(rules) => rules.first().map(|it| it.0.as_str()),
+            GrammarError::DuplicateRules(rules)
+            | GrammarError::MissingRules(rules)
+            | GrammarError::NoAlternative(rules)
+            | GrammarError::EmptyAlternative(rules)
+            | GrammarError::Predicates(rules) => rules.first().map(String::as_str),
+            GrammarError::DuplicateRecursionEliminationNums(_) => None,
+            GrammarError::LeftRecursiveExpression {
+                rule, ..
+            } => Some(rule),
+            GrammarError::StartRule(rule) => Some(rule),
+            GrammarError::NotBacktrackFree {
+                rule, ..
+            } => Some(rule),
+            GrammarError::NotFixed {
+                ..
+            } => None,
+            GrammarError::NotChomskyNormalForm | GrammarError::ZeroLookahead => None,
+            GrammarError::NotLlK {
+                rule, ..
+            }
+            | GrammarError::NotLl1 {
+                rule, ..
+            } => Some(rule),
+            GrammarError::LrConflicts {
+                ..
+            } => None,
+            GrammarError::UselessRules {
+                non_productive,
+                unreachable,
+            } => non_productive
+                .first()
+                .or(unreachable.first())
+                .map(String::as_str),
+            GrammarError::NonProductiveStartRule(rule) | GrammarError::UnknownRule(rule) => {
+                Some(rule)
+            },
+            GrammarError::NonProductiveRules(rules) => rules.first().map(String::as_str),
+            GrammarError::UnsupportedVersion {
+                ..
+            }
+            | GrammarError::Json(_)
+            | GrammarError::Io {
+                ..
+            } => None,
+        }
+    }
+}
+
+impl Display for GrammarError {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        let joined = |it: &[String]| it.join(", ");
+
+        match self {
+            GrammarError::Syntax { error, .. } => write!(f, "{}", error),
+            GrammarError::NoRules => write!(f, "no rules, expecting at least the start rule `S`"),
+            GrammarError::InvalidRules(rules) => write!(
+                f,
+                "{}",
+                rules
+                    .iter()
+                    .map(|it| it.1.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" | ")
+            ),
+            GrammarError::DuplicateRules(rules) => {
+                write!(f, "duplicate rules: {}", joined(rules))
+            },
+            GrammarError::DuplicateRecursionEliminationNums(nums) => write!(
+                f,
+                "duplicate recursion elimination rule: {}",
+                nums.iter()
+                    .map(|it| it.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            GrammarError::MissingRules(rules) => write!(f, "missing rules: {}", joined(rules)),
+            GrammarError::NoAlternative(rules) => {
+                write!(f, "rules have no alternative: {}", joined(rules))
+            },
+            GrammarError::EmptyAlternative(rules) => {
+                write!(f, "rules have empty alternative: {}", joined(rules))
+            },
+            GrammarError::LeftRecursiveExpression { rule, alt_no } => write!(
+                f,
+                "left recursive alternative in expression rule without a declared operator, rule={}, alt_no={}",
+                rule, alt_no,
+            ),
+            GrammarError::StartRule(rule) => write!(
+                f,
+                "expecting the first rule to be `S` as start rule, but got: {}",
+                rule
+            ),
+            GrammarError::Predicates(rules) => write!(
+                f,
+                "predicates are only supported by the packrat parser, rules: {}",
+                joined(rules)
+            ),
+            GrammarError::NotBacktrackFree {
+                rule,
+                alt_nos,
+                intersection,
+                explanation,
+            } => write!(
+                f,
+                "grammar is not backtrack free, alts intersect, rule={} i={}, j={}, intersection={:?}\n{}",
+                rule, alt_nos.1, alt_nos.0, intersection, explanation,
+            ),
+            GrammarError::NotFixed { .. } => {
+                write!(f, "max loop reached but grammar was not fixed")
+            },
+            GrammarError::NotChomskyNormalForm => write!(f, "rules are not in Chomsky normal form"),
+            GrammarError::ZeroLookahead => write!(f, "k must be at least 1"),
+            GrammarError::NotLlK {
+                k,
+                rule,
+                alt_nos,
+                intersection,
+            } => write!(
+                f,
+                "grammar is not LL({}), alts intersect, rule={} i={}, j={}, intersection=[{}]",
+                k,
+                rule,
+                alt_nos.1,
+                alt_nos.0,
+                joined(intersection),
+            ),
+            GrammarError::NotLl1 {
+                rule,
+                alt_nos,
+                token,
+            } => write!(
+                f,
+                "grammar is not LL(1), conflicting alts on the same token, rule={} i={}, j={}, token={}",
+                rule,
+                alt_nos.1,
+                alt_nos.0,
+                token.upper_name(),
+            ),
+            GrammarError::LrConflicts { kind, conflicts } => {
+                write!(f, "grammar is not {}, conflicts:\n{}", kind, conflicts.join("\n"))
+            },
+            GrammarError::UselessRules {
+                non_productive,
+                unreachable,
+            } => write!(
+                f,
+                "grammar has useless rules, non-productive: <{}>, unreachable: <{}>",
+                joined(non_productive),
+                joined(unreachable),
+            ),
+            GrammarError::NonProductiveStartRule(rule) => write!(
+                f,
+                "start rule is non-productive, it can not derive any input: {}",
+                rule
+            ),
+            GrammarError::NonProductiveRules(rules) => {
+                write!(f, "rules derive no sentence: {}", joined(rules))
+            },
+            GrammarError::UnknownRule(rule) => write!(f, "unknown rule: {}", rule),
+            GrammarError::UnsupportedVersion {
+                version: Some(version),
+                expecting,
+            } => write!(
+                f,
+                "compiled grammar format version {} is not supported, expecting: {}",
+                version, expecting
+            ),
+            GrammarError::UnsupportedVersion { version: None, .. } => {
+                write!(f, "not a compiled grammar, no version")
+            },
+            GrammarError::Json(error) => write!(f, "{}", error),
+            GrammarError::Io { path, error } => write!(f, "{}: {}", path, error),
+        }
+    }
+}
+
+impl Error for GrammarError {
+}
+
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ParseErrorKind {
+    Grammar(GrammarError),
+    Lex(LexError, Span),
+    // The rule being parsed if known, the tokens it could go on with, EOF for the end of input,
+    // and what came instead, None if the input just ended.
+    UnexpectedToken {
+        rule: Option<String>,
+        expected: Vec<TokenKind>,
+        found: Option<(TokenKind, Span)>,
+    },
+    // Anything else, an action failing for one.
+    Other(String),
+}
+
+impl ParseErrorKind {
+    pub fn unexpected_token(
+        rule: Option<&str>,
+        expected: impl IntoIterator<Item = TokenKind>,
+        found: Option<&Token<'_>>,
+    ) -> Self {
+        let mut expected = expected
+            .into_iter()
+            .filter(|it| !it.is_epsilon())
+            .collect::<Vec<_>>();
+        expected.sort();
+        expected.dedup();
+
+        ParseErrorKind::UnexpectedToken {
+            rule: rule.map(str::to_string),
+            expected,
+            found: found.map(|it| (it.token_kind, Span::from(it))),
+        }
+    }
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        match self {
+            ParseErrorKind::Grammar(err) => write!(f, "invalid rules: {}", err),
+            ParseErrorKind::Lex(err, span) => write!(
+                f,
+                "lexer error, position: {} line: {}, error: {}",
+                span.start, span.line, err
+            ),
+            ParseErrorKind::UnexpectedToken {
+                rule,
+                expected,
+                found,
+            } => {
+                if let Some(rule) = rule {
+                    write!(f, "rule: {} /// ", rule)?;
+                }
+                let expected = expected.iter().map(|it| it.name()).collect::<Vec<_>>();
+                match found {
+                    Some((tk, span)) => write!(
+                        f,
+                        "unexpected token, expecting one of tokens: {}, got: {} at {}/{}",
+                        expected.join(", "),
+                        tk.name(),
+                        span.line,
+                        span.start
+                    ),
+                    None => write!(
+                        f,
+                        "unexpected end of input, expecting one of tokens: {}",
+                        expected.join(", ")
+                    ),
+                }
+            },
+            ParseErrorKind::Other(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<LexerError> for ParseErrorKind {
+    fn from(err: LexerError) -> Self {
+        let span = Span {
+            start: err.position,
+            end: err.position,
+            line: err.line,
+        };
+        ParseErrorKind::Lex(err.error, span)
+    }
+}
+
+
+// A tree the lowering to the AST does not know the shape of, at the offending token if there is
+// one.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LowerError {
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+impl Display for LowerError {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        match self.span {
+            Some(span) => write!(f, "{} at {}/{}", self.message, span.line, span.start),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl Error for LowerError {
+}
+
+
+// Of importing a grammar written for another tool.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ImportError {
+    Grammar(GrammarError),
+    // The source not understood, in the words of the importer.
+    Syntax(String),
+    // The warnings tell why each alternative of the start rule was dropped.
+    NoAlternativeLeft {
+        start_rule: String,
+        warnings: Vec<String>,
+    },
+}
+
+impl Display for ImportError {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        match self {
+            ImportError::Grammar(err) => write!(f, "{}", err),
+            ImportError::Syntax(error) => write!(f, "{}", error),
+            ImportError::NoAlternativeLeft {
+                start_rule,
+                warnings,
+            } => write!(
+                f,
+                "start rule {} has no alternative left, warnings: {}",
+                start_rule,
+                warnings.join("; ")
+            ),
+        }
+    }
+}
+
+impl Error for ImportError {
+}
+
+impl From<GrammarError> for ImportError {
+    fn from(err: GrammarError) -> Self {
+        ImportError::Grammar(err)
+    }
+}
+
+
+// Of folding a tree parsed with transformed rules back into the shape of the original ones.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum FoldError {
+    Grammar(GrammarError),
+    // Neither an original rule nor one made of an original rule.
+    UnknownRule(String),
+    // No derivation of the rule gives the children, by name.
+    NoDerivation { rule: String, children: Vec<String> },
+}
+
+impl Display for FoldError {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        match self {
+            FoldError::Grammar(err) => write!(f, "{}", err),
+            FoldError::UnknownRule(rule) => {
+                write!(f, "rule is not in the original rules: {}", rule)
+            },
+            FoldError::NoDerivation {
+                rule,
+                children,
+            } => {
+                write!(f, "can not fold {} over: {}", rule, children.join(" "))
+            },
+        }
+    }
+}
+
+impl Error for FoldError {
+}
+
+impl From<GrammarError> for FoldError {
+    fn from(err: GrammarError) -> Self {
+        FoldError::Grammar(err)
+    }
+}
+
+
+// Of generating a parser from rules.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum GenerateError {
+    Grammar(GrammarError),
+    ExpressionRules(Vec<String>),
+    // Two rules with the same function name, the second one and that name.
+    FnNameClash { rule: String, fn_name: String },
+    Io { path: String, error: String },
+}
+
+impl Display for GenerateError {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        match self {
+            GenerateError::Grammar(err) => write!(f, "{}", err),
+            GenerateError::ExpressionRules(rules) => write!(
+                f,
+                "expression rules are not supported by the generator, rules: {}",
+                rules.join(", ")
+            ),
+            GenerateError::FnNameClash {
+                rule,
+                fn_name,
+            } => write!(
+                f,
+                "rule name clashes with another rule once turned into a function name: {} => {}",
+                rule, fn_name
+            ),
+            GenerateError::Io {
+                path,
+                error,
+            } => {
+                write!(
+                    f,
+                    "could not write generated parser to: {}, error: {}",
+                    path, error
+                )
+            },
+        }
+    }
+}
+
+impl Error for GenerateError {
+}
+
+impl From<GrammarError> for GenerateError {
+    fn from(err: GrammarError) -> Self {
+        GenerateError::Grammar(err)
+    }
+}
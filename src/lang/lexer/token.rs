@@ -1,7 +1,10 @@
 use std::fmt::Display;
 use std::fmt::Formatter;
 
-#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Ord, PartialOrd)]
+use serde::Deserialize;
+use serde::Serialize;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Ord, PartialOrd, Serialize, Deserialize)]
 pub enum TokenKind {
     Error,
     Eof,
@@ -24,6 +27,7 @@ pub enum TokenKind {
     Star,
     Minus,
     Plus,
+    Caret,
 }
 
 impl TokenKind {
@@ -50,6 +54,7 @@ impl TokenKind {
             Self::Star,
             Self::Minus,
             Self::Plus,
+            Self::Caret,
         ]
         .to_vec()
     }
@@ -71,6 +76,7 @@ impl TokenKind {
             "*" => Ok(Self::Star),
             "-" => Ok(Self::Minus),
             "+" => Ok(Self::Plus),
+            "^" => Ok(Self::Caret),
             _ => Err(format!("unknown TokenKind representation: {}", repr)),
         }
     }
@@ -93,6 +99,7 @@ impl TokenKind {
             "*" => Ok(Self::Star),
             "-" => Ok(Self::Minus),
             "+" => Ok(Self::Plus),
+            "^" => Ok(Self::Caret),
             _ => Err(format!("unknown TokenKind representation: {}", repr)),
         }
     }
@@ -121,6 +128,7 @@ impl TokenKind {
             "star" => Ok(Self::Star),
             "minus" => Ok(Self::Minus),
             "plus" => Ok(Self::Plus),
+            "caret" => Ok(Self::Caret),
             _ => Err(format!("unknown TokenKind name: {}", repr)),
         }
     }
@@ -148,6 +156,7 @@ impl TokenKind {
             Self::Star => "star",
             Self::Minus => "minus",
             Self::Plus => "plus",
+            Self::Caret => "caret",
         }
     }
 
@@ -174,6 +183,7 @@ impl TokenKind {
             Self::Star => "STAR",
             Self::Minus => "MINUS",
             Self::Plus => "PLUS",
+            Self::Caret => "CARET",
         }
     }
 
@@ -194,6 +204,7 @@ impl TokenKind {
             Self::Star => Some("*"),
             Self::Minus => Some("-"),
             Self::Plus => Some("+"),
+            Self::Caret => Some("^"),
             _ => None,
         }
     }
@@ -1,8 +1,10 @@
+use std::error::Error;
 use std::fmt::Display;
 use std::fmt::Formatter;
 
 use log::trace;
 
+use crate::lang::error::LexError;
 use crate::lang::lexer::token::Token;
 use crate::lang::lexer::token::TokenKind;
 
@@ -50,11 +52,11 @@ impl<'a> From<&'a str> for TextCharIter<'a> {
 
 // =============================================================================
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct LexerError {
     pub position: usize,
     pub line: usize,
-    pub error: String,
+    pub error: LexError,
 }
 
 impl Display for LexerError {
@@ -70,6 +72,9 @@ impl Display for LexerError {
     }
 }
 
+impl Error for LexerError {
+}
+
 pub type LexerResult<'a> = Result<Token<'a>, LexerError>;
 
 
@@ -169,7 +174,7 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    fn scan_number(&mut self) -> Result<(), String> {
+    fn scan_number(&mut self) -> Result<(), LexError> {
         while self.iter.current_char.is_some() {
             let c = self.iter.current_char.unwrap();
             if ('0'..='9').contains(&c) {
@@ -177,10 +182,10 @@ impl<'a> Lexer<'a> {
             }
             else if c.is_ascii_alphabetic() {
                 self.is_error = true;
-                return Err(format!(
-                    "unexpected char while reading number, line={} char={}",
-                    self.current_line, c
-                ));
+                return Err(LexError::UnexpectedCharInNumber {
+                    line: self.current_line,
+                    char: c,
+                });
             }
             else {
                 return Ok(());
@@ -190,7 +195,7 @@ impl<'a> Lexer<'a> {
         Ok(())
     }
 
-    fn scan_string(&mut self) -> Result<(), String> {
+    fn scan_string(&mut self) -> Result<(), LexError> {
         let start = self.iter.pos;
         self.in_escape = false;
         self.iter.next();
@@ -222,17 +227,16 @@ impl<'a> Lexer<'a> {
                 },
                 None => {
                     self.is_error = true;
-                    return Err(format!(
-                        "unterminated string at: {} => {}",
+                    return Err(LexError::UnterminatedString {
                         start,
-                        &self.iter.text[self.buffer_start..]
-                    ));
+                        text: self.iter.text[self.buffer_start..].to_string(),
+                    });
                 },
             }
         }
     }
 
-    fn read_next(&mut self) -> Result<Option<bool>, String> {
+    fn read_next(&mut self) -> Result<Option<bool>, LexError> {
         self.start_buffer();
 
         if self.iter.has() {
@@ -300,6 +304,11 @@ impl<'a> Lexer<'a> {
                     self.token_kind = TokenKind::Minus;
                     Ok(Some(true))
                 },
+                '^' => {
+                    self.add_to_buffer_and_next();
+                    self.token_kind = TokenKind::Caret;
+                    Ok(Some(true))
+                },
                 '=' => {
                     self.add_to_buffer_and_next();
                     self.token_kind = TokenKind::Equal;
@@ -325,12 +334,11 @@ impl<'a> Lexer<'a> {
                     self.token_kind = TokenKind::RightBracket;
                     Ok(Some(true))
                 },
-                _ => Err(format!(
-                    "unexpected character at line={} pos={}: {}",
-                    self.current_line,
-                    self.iter.pos,
-                    self.iter.current_char.unwrap()
-                )),
+                _ => Err(LexError::UnexpectedChar {
+                    line: self.current_line,
+                    position: self.iter.pos,
+                    char: self.iter.current_char.unwrap(),
+                }),
             };
         }
 
@@ -341,9 +349,9 @@ impl<'a> Lexer<'a> {
         Ok(None)
     }
 
-    pub fn read_token(&mut self) -> Result<Option<Token<'a>>, String> {
+    pub fn read_token(&mut self) -> Result<Option<Token<'a>>, LexError> {
         if self.is_error {
-            return Err("lexer has previously encountered an error".to_string());
+            return Err(LexError::AfterError);
         }
 
         loop {
@@ -448,6 +456,7 @@ impl<'a> Iterator for LexerIter<'a> {
 #[cfg(test)]
 mod tests {
     use super::Lexer;
+    use crate::lang::error::LexError;
     use crate::lang::lexer::token::TokenKind;
 
     #[test]
@@ -511,4 +520,28 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_errors() {
+        let err = Lexer::parse("x = 12ab;").err().unwrap();
+        assert_eq!(
+            err.error,
+            LexError::UnexpectedCharInNumber {
+                line: 1,
+                char: 'a'
+            }
+        );
+
+        let mut lexer: Lexer = "s = \"open".into();
+        lexer.read_token().unwrap();
+        lexer.read_token().unwrap();
+        assert!(matches!(
+            lexer.read_token(),
+            Err(LexError::UnterminatedString {
+                start: 4,
+                ..
+            })
+        ));
+        assert_eq!(lexer.read_token().err().unwrap(), LexError::AfterError);
+    }
 }
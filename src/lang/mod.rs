@@ -1,4 +1,9 @@
+pub mod ast;
+pub mod error;
 pub mod lexer;
 pub mod parser;
+pub mod parser_export;
+pub mod parser_gen;
 pub mod parser_impl;
+pub mod parser_import;
 pub(crate) mod util;
@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+
+use crate::lang::lexer::token::Token;
+use crate::lang::parser::rule::AltRef;
+
+// Semantic actions, like yacc's `{ $$ = ... }`: the value of a token comes from the token action,
+// that of a rule from the action of the alternative it was parsed by, given the values of the
+// alternative's rules and tokens, EPSILON having none. An alternative without an action passes the
+// value of its only part through.
+//
+// The backtracking parser drops the values of what it backtracks over, the actions that computed
+// them have run all the same.
+pub struct Actions<V> {
+    token: Box<dyn Fn(&Token<'_>) -> V>,
+    alts: HashMap<AltRef, AltAction<V>>,
+}
+
+type AltAction<V> = Box<dyn Fn(Vec<V>) -> V>;
+
+impl<V> Actions<V> {
+    pub fn new(token: impl Fn(&Token<'_>) -> V + 'static) -> Self {
+        Self {
+            token: Box::new(token),
+            alts: HashMap::new(),
+        }
+    }
+
+    pub fn add(
+        &mut self,
+        alt: AltRef,
+        action: impl Fn(Vec<V>) -> V + 'static,
+    ) {
+        self.alts.insert(alt, Box::new(action));
+    }
+
+    pub fn token_value(
+        &self,
+        token: &Token<'_>,
+    ) -> V {
+        (self.token)(token)
+    }
+
+    pub fn reduce(
+        &self,
+        alt: &AltRef,
+        mut values: Vec<V>,
+    ) -> Result<V, String> {
+        match self.alts.get(alt) {
+            Some(action) => Ok(action(values)),
+            None if values.len() == 1 => Ok(values.pop().unwrap()),
+            None => Err(format!(
+                "no action for {} and it has {} values to pass through",
+                alt,
+                values.len()
+            )),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use crate::lang::lexer::token::TokenKind;
+    use crate::lang::lexer::v0::Lexer;
+    use crate::lang::parser::actions::Actions;
+    use crate::lang::parser::rule::AltRef;
+    use crate::lang::parser::rules::Rules;
+    use crate::lang::parser_impl::backtracking_parser::parse_with_backtracking_actions;
+    use crate::lang::parser_impl::recursive_descent_parser::recursive_descent_parse_actions;
+
+    #[derive(Debug, PartialEq)]
+    enum Value {
+        Token(TokenKind, String),
+        Int(i64),
+        Sum(Vec<i64>),
+    }
+
+    fn int_of(value: &Value) -> i64 {
+        match value {
+            Value::Int(int) => *int,
+            Value::Token(TokenKind::Int, text) => text.parse().unwrap(),
+            _ => panic!("not an int"),
+        }
+    }
+
+    fn actions_of(
+        rules: &Rules,
+        calls: &Rc<Cell<usize>>,
+    ) -> Actions<Value> {
+        let mut actions = Actions::new(|it| Value::Token(it.token_kind, it.text.to_string()));
+        let alt = |name: &str, alt_no: usize| AltRef::new(alt_no, &rules.get_rule_by_name(name));
+
+        // S -> statement S | EPSILON
+        actions.add(alt("S", 0), |mut values| {
+            let rest = match values.pop().unwrap() {
+                Value::Sum(rest) => rest,
+                _ => panic!("not a sum"),
+            };
+            Value::Sum([vec![int_of(&values[0])], rest].concat())
+        });
+        actions.add(alt("S", 1), |_| Value::Sum(vec![]));
+        // statement -> ID = expr ; | RETURN expr ;
+        actions.add(alt("statement", 0), |values| Value::Int(int_of(&values[2])));
+        actions.add(alt("statement", 1), |values| {
+            Value::Int(-int_of(&values[1]))
+        });
+        // expr -> ( expr ) | INT + expr | INT
+        actions.add(alt("expr", 0), |values| Value::Int(int_of(&values[1])));
+        let counted = Rc::clone(calls);
+        actions.add(alt("expr", 1), move |values| {
+            counted.set(counted.get() + 1);
+            Value::Int(int_of(&values[0]) + int_of(&values[2]))
+        });
+        actions
+    }
+
+    #[test]
+    fn test_actions() {
+        let rules: Rules = "
+S         -> statement S | EPSILON
+statement -> ID = expr ; | RETURN expr ;
+expr      -> ( expr ) | INT + expr | INT
+        "
+        .try_into()
+        .unwrap();
+        let program = "x = 1 + (2 + 3); return 4;";
+        let expected = Value::Sum(vec![6, -4]);
+
+        // Backtracking from `INT + expr` to `INT` drops the value of the INT matched already.
+        let calls = Rc::new(Cell::new(0));
+        let actions = actions_of(&rules, &calls);
+        let tokens = Lexer::parse(program).unwrap();
+        let value = parse_with_backtracking_actions(&rules, &actions, tokens.into_iter());
+        assert_eq!(value.unwrap(), expected);
+        assert_eq!(calls.get(), 2);
+
+        let mut ready = rules.deep_clone();
+        ready.make_ready_for_recursive_decent(128).unwrap();
+        let calls = Rc::new(Cell::new(0));
+        let mut actions = actions_of(&ready, &calls);
+        // expr -> ( expr ) | INT expr__0, expr__0 -> + expr | EPSILON
+        let alt = |name: &str, alt_no: usize| AltRef::new(alt_no, &ready.get_rule_by_name(name));
+        actions.add(alt("expr", 1), |values| {
+            Value::Int(int_of(&values[0]) + int_of(&values[1]))
+        });
+        actions.add(alt("expr__0", 0), |values| Value::Int(int_of(&values[1])));
+        actions.add(alt("expr__0", 1), |_| Value::Int(0));
+        let lexer: Lexer = program.into();
+        let value = recursive_descent_parse_actions(&ready, &actions, lexer.into_iter());
+        assert_eq!(value.unwrap(), expected);
+
+        // Operators parsed by precedence climbing reduce as they are wrapped.
+        let rules: Rules = "
+%left + -
+S -> S + S | S - S | INT
+        "
+        .try_into()
+        .unwrap();
+        let mut actions = Actions::new(|it| Value::Token(it.token_kind, it.text.to_string()));
+        let alt = |alt_no: usize| AltRef::new(alt_no, &rules.get_rule_by_name("S"));
+        actions.add(alt(0), |values| {
+            Value::Int(int_of(&values[0]) + int_of(&values[2]))
+        });
+        actions.add(alt(1), |values| {
+            Value::Int(int_of(&values[0]) - int_of(&values[2]))
+        });
+        let lexer: Lexer = "1 - 2 + 3 - 4".into();
+        let value = recursive_descent_parse_actions(&rules, &actions, lexer.into_iter());
+        assert_eq!(value.unwrap(), Value::Int(-2));
+
+        // EPSILON has no value to pass through.
+        let actions = Actions::new(|_| Value::Int(0));
+        let lexer: Lexer = program.into();
+        let err = recursive_descent_parse_actions(&ready, &actions, lexer.into_iter())
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.error(),
+            "no action for AltRef[expr__0#1] and it has 0 values to pass through"
+        );
+    }
+}
@@ -0,0 +1,453 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use crate::lang::lexer::token::TokenKind;
+use crate::lang::parser::rule::display_of_rule_part;
+use crate::lang::parser::rule::AltRef;
+use crate::lang::parser::rule::RulePart;
+use crate::lang::parser::rules::Rules;
+use crate::lang::parser::rules::StartSet;
+
+// Two alternatives of a rule which both start with the same token, so the parser can not choose
+// between them by looking at it. `input` is a shortest input reaching the choice, ending with the
+// token.
+pub struct Conflict {
+    pub rule: String,
+    pub alts: (usize, usize),
+    pub token: TokenKind,
+    pub input: Vec<TokenKind>,
+    pub derivations: (Derivation, Derivation),
+}
+
+// The alternatives taken from the conflicting alternative down to the one matching the token.
+// When `followed` the last alternative derives nothing and the token follows its rule instead.
+pub struct Derivation {
+    pub steps: Vec<AltRef>,
+    pub followed: bool,
+}
+
+impl Display for Conflict {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        let input = self
+            .input
+            .iter()
+            .map(|tk| display_of_rule_part(&RulePart::Token(*tk)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        write!(
+            f,
+            "rule={} alts {} and {} both start with {}, input: {}",
+            self.rule,
+            self.alts.0,
+            self.alts.1,
+            display_of_rule_part(&RulePart::Token(self.token)),
+            input,
+        )?;
+
+        let derivations = [
+            (self.alts.0, &self.derivations.0),
+            (self.alts.1, &self.derivations.1),
+        ];
+        for (alt_no, derivation) in derivations {
+            let label = format!("alt {}: ", alt_no);
+            for (step_no, step) in derivation.steps.iter().enumerate() {
+                let indent = match step_no {
+                    0 => label.clone(),
+                    _ => " ".repeat(label.len()),
+                };
+                write!(f, "\n  {}{}", indent, display_of_alt(step))?;
+            }
+            if derivation.followed {
+                let last = derivation.steps.last().unwrap();
+                write!(
+                    f,
+                    "\n  {}then {} follows {}",
+                    " ".repeat(label.len()),
+                    display_of_rule_part(&RulePart::Token(self.token)),
+                    last.rule().borrow().name(),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn display_of_alt(alt_ref: &AltRef) -> String {
+    let rule = alt_ref.rule().borrow();
+    let parts = rule.alternatives[alt_ref.alt_no()]
+        .iter()
+        .map(display_of_rule_part)
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!("{} -> {}", rule.name(), parts)
+}
+
+
+// Every conflict of the grammar, one per pair of alternatives and token in both their start sets.
+pub fn explain_conflicts(rules: &Rules) -> Vec<Conflict> {
+    let explainer = Explainer::new(rules);
+    let expression_rules = rules.expression_rules();
+
+    let mut conflicts = vec![];
+    for rule in rules.rules() {
+        let rule_ref = rule.borrow();
+        let is_operator_alt = |alt_no: usize| {
+            expression_rules
+                .get(rule_ref.name())
+                .is_some_and(|it| it.is_operator_alt(alt_no))
+        };
+
+        for i in 1..rule_ref.alternatives.len() {
+            for j in 0..i {
+                if !is_operator_alt(i) && !is_operator_alt(j) {
+                    conflicts.extend(explainer.explain(rule_ref.name(), j, i));
+                }
+            }
+        }
+    }
+
+    conflicts
+}
+
+// The conflicts between two alternatives of a rule, one per token in both their start sets.
+pub fn explain_conflict(
+    rules: &Rules,
+    rule_name: &str,
+    alt0: usize,
+    alt1: usize,
+) -> Vec<Conflict> {
+    Explainer::new(rules).explain(rule_name, alt0, alt1)
+}
+
+
+struct Explainer<'r> {
+    rules: &'r Rules,
+    start: StartSet,
+    nullable: HashSet<String>,
+
+    // Shortest derivation of a rule starting with a token, as the alternatives taken.
+    first_paths: HashMap<(String, TokenKind), Vec<AltRef>>,
+    // Shortest input derived from a rule, and shortest input before the rule is entered.
+    yields: HashMap<String, Vec<TokenKind>>,
+    prefixes: HashMap<String, Vec<TokenKind>>,
+}
+
+impl<'r> Explainer<'r> {
+    fn new(rules: &'r Rules) -> Self {
+        let mut explainer = Self {
+            rules,
+            start: rules.start_set(),
+            nullable: rules.nullable_set(),
+            first_paths: HashMap::new(),
+            yields: HashMap::new(),
+            prefixes: HashMap::new(),
+        };
+        explainer.first_paths = explainer.first_paths();
+        explainer.yields = explainer.yields();
+        explainer.prefixes = explainer.prefixes();
+        explainer
+    }
+
+    fn explain(
+        &self,
+        rule_name: &str,
+        alt0: usize,
+        alt1: usize,
+    ) -> Vec<Conflict> {
+        let rule = self.rules.get_rule_by_name(rule_name);
+        let set0 = &self.start[&(rule_name.to_string(), alt0)];
+        let set1 = &self.start[&(rule_name.to_string(), alt1)];
+
+        let mut tokens = set0.intersection(set1).cloned().collect::<Vec<_>>();
+        tokens.sort();
+
+        tokens
+            .into_iter()
+            .map(|token| {
+                let mut input = self.prefixes.get(rule_name).cloned().unwrap_or_default();
+                input.push(token);
+
+                Conflict {
+                    rule: rule_name.to_string(),
+                    alts: (alt0, alt1),
+                    token,
+                    input,
+                    derivations: (
+                        self.derivation(AltRef::new(alt0, &rule), token),
+                        self.derivation(AltRef::new(alt1, &rule), token),
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    fn derivation(
+        &self,
+        alt_ref: AltRef,
+        token: TokenKind,
+    ) -> Derivation {
+        let path = {
+            let rule = alt_ref.rule().borrow();
+            self.first_path_of_parts(&rule.alternatives[alt_ref.alt_no()], token)
+        };
+
+        match path {
+            Some(path) => {
+                let mut steps = vec![alt_ref];
+                steps.extend(path);
+                Derivation {
+                    steps,
+                    followed: false,
+                }
+            },
+            // Not a first token of the alternative, so it derives nothing and the token is in the
+            // follow set of the rule.
+            None => Derivation {
+                steps: vec![alt_ref],
+                followed: true,
+            },
+        }
+    }
+
+    fn first_paths(&self) -> HashMap<(String, TokenKind), Vec<AltRef>> {
+        let mut first_paths: HashMap<(String, TokenKind), Vec<AltRef>> = HashMap::new();
+
+        loop {
+            let mut any_change = false;
+
+            for rule in self.rules.rules() {
+                let name = rule.borrow().name().to_string();
+                for (alt_no, alt) in rule.borrow().alternatives.iter().enumerate() {
+                    for (token, path) in self.first_paths_of_parts(alt, &first_paths) {
+                        let key = (name.clone(), token);
+                        let is_shorter = first_paths
+                            .get(&key)
+                            .is_none_or(|known| path.len() + 1 < known.len());
+                        if is_shorter {
+                            let mut steps = vec![AltRef::new(alt_no, rule)];
+                            steps.extend(path);
+                            first_paths.insert(key, steps);
+                            any_change = true;
+                        }
+                    }
+                }
+            }
+
+            if !any_change {
+                break;
+            }
+        }
+
+        first_paths
+    }
+
+    // The tokens parts may start with, each with the shortest known path below the parts.
+    fn first_paths_of_parts(
+        &self,
+        parts: &[RulePart],
+        first_paths: &HashMap<(String, TokenKind), Vec<AltRef>>,
+    ) -> Vec<(TokenKind, Vec<AltRef>)> {
+        let mut found: Vec<(TokenKind, Vec<AltRef>)> = vec![];
+
+        for part in parts {
+            match part {
+                RulePart::Token(tk) if tk.is_epsilon() => continue,
+                RulePart::Token(tk) => {
+                    found.push((*tk, vec![]));
+                    break;
+                },
+                RulePart::Predicate(..) => continue,
+                RulePart::Rule(rule) => {
+                    let name = rule.borrow().name().to_string();
+                    for ((rule_name, token), path) in first_paths {
+                        if *rule_name == name {
+                            found.push((*token, path.clone()));
+                        }
+                    }
+                    if !self.nullable.contains(&name) {
+                        break;
+                    }
+                },
+            }
+        }
+
+        found
+    }
+
+    fn first_path_of_parts(
+        &self,
+        parts: &[RulePart],
+        token: TokenKind,
+    ) -> Option<Vec<AltRef>> {
+        self.first_paths_of_parts(parts, &self.first_paths)
+            .into_iter()
+            .filter(|(tk, _)| *tk == token)
+            .map(|(_, path)| path)
+            .min_by_key(|path| path.len())
+    }
+
+    fn yields(&self) -> HashMap<String, Vec<TokenKind>> {
+        let mut yields: HashMap<String, Vec<TokenKind>> = HashMap::new();
+
+        loop {
+            let mut any_change = false;
+
+            for rule in self.rules.rules() {
+                let name = rule.borrow().name().to_string();
+                for alt in &rule.borrow().alternatives {
+                    let Some(alt_yield) = yield_of_parts(alt, &yields)
+                    else {
+                        continue;
+                    };
+                    let is_shorter = yields
+                        .get(&name)
+                        .is_none_or(|known| alt_yield.len() < known.len());
+                    if is_shorter {
+                        yields.insert(name.clone(), alt_yield);
+                        any_change = true;
+                    }
+                }
+            }
+
+            if !any_change {
+                break;
+            }
+        }
+
+        yields
+    }
+
+    fn prefixes(&self) -> HashMap<String, Vec<TokenKind>> {
+        let mut prefixes: HashMap<String, Vec<TokenKind>> = HashMap::new();
+        if let Some(start) = self.rules.rules().first() {
+            prefixes.insert(start.borrow().name().to_string(), vec![]);
+        }
+
+        loop {
+            let mut any_change = false;
+
+            for rule in self.rules.rules() {
+                let Some(prefix) = prefixes.get(rule.borrow().name()).cloned()
+                else {
+                    continue;
+                };
+
+                for alt in &rule.borrow().alternatives {
+                    let mut running = prefix.clone();
+                    for part in alt {
+                        if let RulePart::Rule(part_rule) = part {
+                            let name = part_rule.borrow().name().to_string();
+                            let is_shorter = prefixes
+                                .get(&name)
+                                .is_none_or(|known| running.len() < known.len());
+                            if is_shorter {
+                                prefixes.insert(name, running.clone());
+                                any_change = true;
+                            }
+                        }
+
+                        match yield_of_parts(std::slice::from_ref(part), &self.yields) {
+                            Some(part_yield) => running.extend(part_yield),
+                            None => break,
+                        }
+                    }
+                }
+            }
+
+            if !any_change {
+                break;
+            }
+        }
+
+        prefixes
+    }
+}
+
+fn yield_of_parts(
+    parts: &[RulePart],
+    yields: &HashMap<String, Vec<TokenKind>>,
+) -> Option<Vec<TokenKind>> {
+    let mut tokens = vec![];
+    for part in parts {
+        match part {
+            RulePart::Token(tk) if tk.is_epsilon() => {},
+            RulePart::Token(tk) => tokens.push(*tk),
+            RulePart::Predicate(..) => {},
+            RulePart::Rule(rule) => tokens.extend(yields.get(rule.borrow().name())?.clone()),
+        }
+    }
+    Some(tokens)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::lang::error::GrammarError;
+    use crate::lang::lexer::token::TokenKind;
+    use crate::lang::parser::conflicts::explain_conflicts;
+    use crate::lang::parser::rules::Rules;
+
+    #[test]
+    fn test_explain_nested_conflict() {
+        let rules: Rules = "
+S          -> statement S | EPSILON
+statement  -> ID ID ; | assignment | call
+assignment -> ID = INT ;
+call       -> ID ( ) ;
+        "
+        .try_into()
+        .unwrap();
+
+        let conflicts = explain_conflicts(&rules);
+        assert_eq!(conflicts.len(), 3);
+        assert!(conflicts.iter().all(|it| it.token == TokenKind::Id));
+
+        let conflict = conflicts.iter().find(|it| it.alts == (1, 2)).unwrap();
+        assert_eq!(conflict.input, vec![TokenKind::Id]);
+        assert_eq!(
+            conflict.to_string(),
+            "\
+rule=statement alts 1 and 2 both start with ID, input: ID
+  alt 1: statement -> assignment
+         assignment -> ID = INT ;
+  alt 2: statement -> call
+         call -> ID ( ) ;"
+        );
+
+        let err = rules.is_backtrack_free().err().unwrap();
+        assert!(err
+            .to_string()
+            .contains("\nrule=statement alts 0 and 1 both start with ID, input: ID\n"));
+        assert!(matches!(
+            err,
+            GrammarError::NotBacktrackFree { rule, alt_nos: (0, 1), .. } if rule == "statement"
+        ));
+    }
+
+    #[test]
+    fn test_explain_follow_conflict() {
+        let rules: Rules = "
+S     -> FN name ID
+name  -> ID |
+        "
+        .try_into()
+        .unwrap();
+
+        let conflicts = explain_conflicts(&rules);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(
+            conflicts[0].to_string(),
+            "\
+rule=name alts 0 and 1 both start with ID, input: FN ID
+  alt 0: name -> ID
+  alt 1: name -> EPSILON
+         then ID follows name"
+        );
+    }
+}
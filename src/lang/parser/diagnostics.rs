@@ -0,0 +1,429 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use crate::lang::error::GrammarError;
+use crate::lang::parser::rule::Rule;
+use crate::lang::parser::rule::RulePart;
+use crate::lang::parser::rules::Rules;
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+
+// A problem of the rules, at a rule, or an alternative of it, when it is about one.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub rule: Option<String>,
+    pub alt_no: Option<usize>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn error(
+        rule: Option<&str>,
+        alt_no: Option<usize>,
+        message: String,
+    ) -> Self {
+        Self {
+            severity: Severity::Error,
+            rule: rule.map(str::to_string),
+            alt_no,
+            message,
+        }
+    }
+
+    pub fn warning(
+        rule: &str,
+        message: String,
+    ) -> Self {
+        Self {
+            severity: Severity::Warning,
+            rule: Some(rule.to_string()),
+            alt_no: None,
+            message,
+        }
+    }
+
+    // One per rule the error is about.
+    pub fn of_error(err: &GrammarError) -> Vec<Self> {
+        let each = |rules: &[String], message: &str| {
+            rules
+                .iter()
+                .map(|it| Self::error(Some(it), None, message.to_string()))
+                .collect()
+        };
+
+        match err {
+            GrammarError::InvalidRules(rules) => rules
+                .iter()
+                .map(|(rule, message)| Self::error(Some(rule), None, message.clone()))
+                .collect(),
+            GrammarError::DuplicateRules(rules) => each(rules, "duplicate rule"),
+            GrammarError::MissingRules(rules) => each(rules, "missing rule, referred to only"),
+            GrammarError::NoAlternative(rules) => each(rules, "rule has no alternative"),
+            GrammarError::EmptyAlternative(rules) => each(rules, "rule has an empty alternative"),
+            GrammarError::Predicates(rules) => {
+                each(rules, "predicates are only supported by the packrat parser")
+            },
+            GrammarError::LeftRecursiveExpression {
+                rule,
+                alt_no,
+            } => vec![Self::error(
+                Some(rule),
+                Some(*alt_no),
+                "left recursive alternative without a declared operator".to_string(),
+            )],
+            GrammarError::NotBacktrackFree {
+                rule,
+                alt_nos,
+                ..
+            } => {
+                vec![Self::error(Some(rule), Some(alt_nos.1), err.to_string())]
+            },
+            err => vec![Self::error(err.rule(), None, err.to_string())],
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(f, "{}", self.severity)?;
+        if let Some(rule) = &self.rule {
+            write!(f, " at {}", rule)?;
+        }
+        if let Some(alt_no) = self.alt_no {
+            write!(f, "#{}", alt_no)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|it| it.severity == Severity::Error)
+}
+
+// The errors and warnings, once per rule and message. A missing rule, or one referred to but
+// never given an alternative, is only reported as such: failing validation and deriving no input
+// follow from it.
+pub(crate) fn diagnostics_of(
+    rules: &Rules,
+    errors: &[GrammarError],
+) -> Vec<Diagnostic> {
+    let is_bare_err = |err: &GrammarError| {
+        matches!(
+            err,
+            GrammarError::MissingRules(_) | GrammarError::NoAlternative(_)
+        )
+    };
+    let bare: HashSet<&String> = errors
+        .iter()
+        .filter_map(|err| match err {
+            GrammarError::MissingRules(rules) | GrammarError::NoAlternative(rules) => Some(rules),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+    let is_bare = |it: &Diagnostic| it.rule.as_ref().is_some_and(|rule| bare.contains(rule));
+
+    let mut diagnostics: Vec<Diagnostic> = vec![];
+    for err in errors {
+        for diagnostic in Diagnostic::of_error(err) {
+            if is_bare(&diagnostic) && !is_bare_err(err) {
+                continue;
+            }
+            if !diagnostics.contains(&diagnostic) {
+                diagnostics.push(diagnostic);
+            }
+        }
+    }
+    diagnostics.extend(warnings_of(rules).into_iter().filter(|it| !is_bare(it)));
+    diagnostics
+}
+
+// Rules valid but of no use: unreachable, reachable only through rules deriving no input or only
+// the empty one, deriving no input themselves, deriving only the empty input, and predicates the
+// other parsers reject.
+fn warnings_of(rules: &Rules) -> Vec<Diagnostic> {
+    let mut warnings = vec![];
+    let non_productive: HashSet<String> = rules.non_productive_rules().into_iter().collect();
+    let unreachable: HashSet<String> = rules.unreachable_rules().into_iter().collect();
+    let reachable_productively = reachable_through(rules, &non_productive);
+    let non_empty = non_empty_rules(rules, &non_productive);
+    let empty_only: HashSet<String> = rules
+        .rules()
+        .iter()
+        .map(|it| it.borrow().name().to_string())
+        .filter(|it| !non_empty.contains(it))
+        .collect();
+    let reachable_non_empty = reachable_through(rules, &empty_only);
+
+    for rule in rules.rules() {
+        let rule = rule.borrow();
+        let name = rule.name();
+
+        if unreachable.contains(name) {
+            warnings.push(Diagnostic::warning(
+                name,
+                "unused rule, unreachable from the start rule".to_string(),
+            ));
+        }
+        else if !reachable_productively.contains(name) {
+            warnings.push(Diagnostic::warning(
+                name,
+                "rule is only reachable through rules deriving no input".to_string(),
+            ));
+        }
+        else if !reachable_non_empty.contains(name) {
+            warnings.push(Diagnostic::warning(
+                name,
+                "rule is only reachable through rules deriving nothing but EPSILON".to_string(),
+            ));
+        }
+
+        if non_productive.contains(name) {
+            warnings.push(Diagnostic::warning(
+                name,
+                non_productive_message(&rule, &non_productive),
+            ));
+        }
+        else if !non_empty.contains(name) {
+            warnings.push(Diagnostic::warning(
+                name,
+                "rule derives nothing but EPSILON".to_string(),
+            ));
+        }
+
+        if rule
+            .alternatives
+            .iter()
+            .any(|alt| alt.iter().any(RulePart::is_predicate))
+        {
+            warnings.push(Diagnostic::warning(
+                name,
+                "predicates are only supported by the packrat parser".to_string(),
+            ));
+        }
+    }
+
+    warnings
+}
+
+// Names the rules deriving no input every alternative of a rule deriving none depends on.
+fn non_productive_message(
+    rule: &Rule,
+    non_productive: &HashSet<String>,
+) -> String {
+    let mut depends_on: Vec<String> = vec![];
+    let parts = rule.alternatives.iter().flat_map(|alt| alt.iter());
+    for part in parts.map(RulePart::predicate_target) {
+        if part.is_rule()
+            && non_productive.contains(&part.name())
+            && !depends_on.contains(&part.name())
+        {
+            depends_on.push(part.name());
+        }
+    }
+
+    if depends_on.is_empty() {
+        "rule derives no input, it has no alternative".to_string()
+    }
+    else {
+        format!(
+            "rule derives no input, it depends on rules deriving none: {}",
+            depends_on.join(", ")
+        )
+    }
+}
+
+// Rules reachable from the start rule without going through the excluded ones, which are
+// reachable themselves but not expanded.
+fn reachable_through(
+    rules: &Rules,
+    excluded: &HashSet<String>,
+) -> HashSet<String> {
+    let mut reachable = HashSet::new();
+    let mut queue = rules
+        .rules()
+        .first()
+        .cloned()
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    while let Some(rule) = queue.pop() {
+        let name = rule.borrow().name().to_string();
+        if !reachable.insert(name.clone()) || excluded.contains(&name) {
+            continue;
+        }
+        for alt in &rule.borrow().alternatives {
+            for part in alt.iter().map(RulePart::predicate_target) {
+                if part.is_rule() {
+                    queue.push(part.get_rule());
+                }
+            }
+        }
+    }
+
+    reachable
+}
+
+// Rules deriving some input other than the empty one.
+fn non_empty_rules(
+    rules: &Rules,
+    non_productive: &HashSet<String>,
+) -> HashSet<String> {
+    let mut non_empty: HashSet<String> = HashSet::new();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for rule in rules.rules() {
+            let rule = rule.borrow();
+            if non_empty.contains(rule.name()) {
+                continue;
+            }
+
+            let derives_some = rule.alternatives.iter().any(|alt| {
+                let productive = alt
+                    .iter()
+                    .all(|part| !part.is_rule() || !non_productive.contains(&part.name()));
+                productive
+                    && alt.iter().any(|part| match part {
+                        RulePart::Token(tk) => !tk.is_epsilon(),
+                        RulePart::Rule(_) => non_empty.contains(&part.name()),
+                        RulePart::Predicate(..) => false,
+                    })
+            });
+            if derives_some {
+                non_empty.insert(rule.name().to_string());
+                changed = true;
+            }
+        }
+    }
+
+    non_empty
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::lang::error::GrammarError;
+    use crate::lang::parser::diagnostics::has_errors;
+    use crate::lang::parser::diagnostics::Diagnostic;
+    use crate::lang::parser::diagnostics::Severity;
+    use crate::lang::parser::rules::Rules;
+
+    #[test]
+    fn test_diagnostics() {
+        let rules: Rules = "
+S       -> stmt S | EPSILON
+stmt    -> ID = expr ; | loop | empty ID
+expr    -> INT | ID | expr
+loop    -> loop ; | hidden loop
+hidden  -> INT
+empty   -> EPSILON | nothing
+nothing -> EPSILON
+unused  -> ID
+        "
+        .try_into()
+        .unwrap();
+
+        let diagnostics = rules.diagnostics();
+        assert!(has_errors(&diagnostics));
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|it| it.to_string().lines().next().unwrap().to_string())
+                .collect::<Vec<_>>(),
+            [
+                "error at expr: invalid rule, rule_name=expr error=pointless rule: a singly sub-rule refers to the same rule, self=Rule[expr -> INT | ID | expr], rule=Rule[expr -> INT | ID | expr]",
+                "warning at loop: rule derives no input, it depends on rules deriving none: loop",
+                "warning at empty: rule derives nothing but EPSILON",
+                "warning at hidden: rule is only reachable through rules deriving no input",
+                "warning at nothing: rule is only reachable through rules deriving nothing but EPSILON",
+                "warning at nothing: rule derives nothing but EPSILON",
+                "warning at unused: unused rule, unreachable from the start rule",
+            ]
+        );
+
+        // All at once, where validate() stops at the first.
+        let rules: Rules = "
+S    -> a | b
+a    -> INT | INT
+b    -> c ID
+        "
+        .try_into()
+        .unwrap();
+        let diagnostics = rules.diagnostics();
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|it| (it.severity, it.rule.as_deref().unwrap()))
+                .collect::<Vec<_>>(),
+            [
+                (Severity::Error, "a"),
+                (Severity::Error, "c"),
+                (Severity::Warning, "b"),
+            ]
+        );
+        assert_eq!(
+            diagnostics[1],
+            Diagnostic::error(Some("c"), None, "rule has no alternative".to_string())
+        );
+        assert_eq!(
+            diagnostics[2],
+            Diagnostic::warning(
+                "b",
+                "rule derives no input, it depends on rules deriving none: c".to_string()
+            )
+        );
+
+        // The rule a predicate looks ahead with counts as a dependency.
+        let rules: Rules = "
+S     -> ID | guard
+guard -> &cycle ID tail
+cycle -> cycle ID
+tail  -> tail INT
+        "
+        .try_into()
+        .unwrap();
+        assert!(rules.diagnostics().contains(&Diagnostic::warning(
+            "guard",
+            "rule derives no input, it depends on rules deriving none: cycle, tail".to_string()
+        )));
+    }
+
+    #[test]
+    fn test_no_rules() {
+        let rules = Rules::new();
+        assert_eq!(rules.validate(), Err(GrammarError::NoRules));
+        assert_eq!(
+            rules.diagnostics(),
+            [Diagnostic::error(
+                None,
+                None,
+                "no rules, expecting at least the start rule `S`".to_string()
+            )]
+        );
+    }
+}
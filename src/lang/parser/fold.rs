@@ -0,0 +1,524 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use crate::lang::error::FoldError;
+use crate::lang::lexer::token::Token;
+use crate::lang::parser::node::Node;
+use crate::lang::parser::rule::RulePart;
+use crate::lang::parser::rules::Rules;
+use crate::lang::parser::transform::AltOrigin;
+use crate::lang::parser::transform::Helper;
+use crate::lang::parser::transform::TransformLog;
+
+// A folded subtree, turned into nodes once the whole tree is folded.
+enum Tree<'a> {
+    Token(Token<'a>),
+    Rule {
+        rule: usize,
+        alt: usize,
+        children: Vec<Rc<Tree<'a>>>,
+    },
+}
+
+// Rewrites a tree parsed with transformed rules into the shape the original rules give it: the
+// children of helper rules go to their parent, under the original alternatives the log tells the
+// transformed ones came from. That brings back left-associative chains from left recursion
+// elimination. Where the log is not enough, for alternatives of one rule substituted into
+// another, the children of the original rule are derived again with its alternatives.
+pub fn fold<'a>(
+    tree: &Rc<RefCell<Node<'a>>>,
+    original: &Rules,
+    log: &TransformLog,
+) -> Result<Rc<RefCell<Node<'a>>>, FoldError> {
+    original.validate()?;
+
+    let folder = Folder {
+        original,
+        log,
+        index_of: original
+            .rules()
+            .iter()
+            .enumerate()
+            .map(|(index, rule)| (rule.borrow().name().to_string(), index))
+            .collect(),
+    };
+
+    let tree = folder.fold(tree)?;
+    let mut num = 0;
+    Ok(folder.node_of(&tree, None, &mut num))
+}
+
+
+struct Folder<'r> {
+    original: &'r Rules,
+    log: &'r TransformLog,
+    index_of: HashMap<String, usize>,
+}
+
+impl Folder<'_> {
+    fn fold<'a>(
+        &self,
+        node: &Rc<RefCell<Node<'a>>>,
+    ) -> Result<Rc<Tree<'a>>, FoldError> {
+        let name = node
+            .borrow()
+            .rule_part()
+            .get_rule()
+            .borrow()
+            .name()
+            .to_string();
+        let rule = match self.index_of.get(self.log.origin_of_rule(&name)) {
+            None => return Err(FoldError::UnknownRule(name)),
+            Some(rule) => *rule,
+        };
+
+        let mut replay = Replay {
+            rule,
+            alts: None,
+            items: vec![],
+        };
+        if self.replay(node, &mut replay)? {
+            if let Some(tree) = self.finish(replay) {
+                return Ok(tree);
+            }
+        }
+
+        let mut items = vec![];
+        self.flatten(node, &mut items)?;
+
+        let mut derivation = Derivation {
+            rules: self.original,
+            items: &items,
+            memo: HashMap::new(),
+            in_progress: HashSet::new(),
+        };
+        derivation
+            .derive(rule, 0, items.len())
+            .ok_or_else(|| FoldError::NoDerivation {
+                rule: name,
+                children: items.iter().map(|it| self.name_of(it)).collect(),
+            })
+    }
+
+    // Follows the alternatives of the node and of its helper rules back to the original ones,
+    // false if the log does not tell them apart.
+    fn replay<'a>(
+        &self,
+        node: &Rc<RefCell<Node<'a>>>,
+        replay: &mut Replay<'a>,
+    ) -> Result<bool, FoldError> {
+        let name = node.borrow().rule_part().name();
+        if !self.narrow(replay, self.log.origin_of_alt(&name, node.borrow().alt())) {
+            return Ok(false);
+        }
+
+        for child in node.borrow().children() {
+            let child_ref = child.borrow();
+            match child_ref.rule_part() {
+                RulePart::Token(tk) if tk.is_epsilon() => {},
+                RulePart::Token(_) => {
+                    let token = child_ref.token().expect("token node without a token");
+                    replay.items.push(Rc::new(Tree::Token(token)));
+                },
+                RulePart::Rule(rule) => match self.log.generated(rule.borrow().name()) {
+                    // The rest of the alternative, the chain goes on under the one done so far.
+                    Some(generated) if generated.helper == Helper::LeftRecursion => {
+                        let Some(done) = self.close(replay)
+                        else {
+                            return Ok(false);
+                        };
+                        replay.alts = None;
+                        replay.items = vec![done];
+                        if !self.replay(child, replay)? {
+                            return Ok(false);
+                        }
+                    },
+                    Some(_) => {
+                        if !self.replay(child, replay)? {
+                            return Ok(false);
+                        }
+                    },
+                    None => replay.items.push(self.fold(child)?),
+                },
+                RulePart::Predicate(..) => unreachable!("rejected by validate"),
+            }
+        }
+
+        Ok(true)
+    }
+
+    // Keeps the alternatives an origin allows, false if it names another rule or leaves none.
+    fn narrow(
+        &self,
+        replay: &mut Replay<'_>,
+        origin: &AltOrigin,
+    ) -> bool {
+        if origin.is_empty() {
+            return true;
+        }
+
+        let name = self.original.rules()[replay.rule]
+            .borrow()
+            .name()
+            .to_string();
+        if origin.iter().any(|(rule, _)| *rule != name) {
+            return false;
+        }
+
+        let alts = origin
+            .iter()
+            .map(|(_, alt_no)| *alt_no)
+            .filter(|it| replay.alts.as_ref().is_none_or(|alts| alts.contains(it)))
+            .collect::<Vec<_>>();
+        replay.alts = Some(alts);
+        replay.alts.as_ref().is_some_and(|it| !it.is_empty())
+    }
+
+    // The items under the one alternative left, if they are what it derives.
+    fn close<'a>(
+        &self,
+        replay: &mut Replay<'a>,
+    ) -> Option<Rc<Tree<'a>>> {
+        let alt = match replay.alts.as_deref() {
+            Some([alt]) => *alt,
+            _ => return None,
+        };
+
+        let parts = self.original.rules()[replay.rule].borrow().alternatives[alt].clone();
+        let mut parts = parts.iter().filter(|it| !it.is_epsilon());
+        let mut items = replay.items.iter();
+        loop {
+            match (parts.next(), items.next()) {
+                (None, None) => break,
+                (Some(RulePart::Token(tk)), Some(item)) => match item.as_ref() {
+                    Tree::Token(token) if token.token_kind == *tk => {},
+                    _ => return None,
+                },
+                (Some(RulePart::Rule(rule)), Some(item)) => match item.as_ref() {
+                    Tree::Rule {
+                        rule: folded, ..
+                    } if self.original.rules()[*folded].borrow().name() == rule.borrow().name() => {
+                    },
+                    _ => return None,
+                },
+                _ => return None,
+            }
+        }
+
+        Some(Rc::new(Tree::Rule {
+            rule: replay.rule,
+            alt,
+            children: std::mem::take(&mut replay.items),
+        }))
+    }
+
+    // The last alternative of a chain, or only the chain when it ended in an epsilon helper.
+    fn finish<'a>(
+        &self,
+        mut replay: Replay<'a>,
+    ) -> Option<Rc<Tree<'a>>> {
+        match (&replay.alts, replay.items.as_slice()) {
+            (None, [done]) => match done.as_ref() {
+                Tree::Rule {
+                    rule, ..
+                } if *rule == replay.rule => Some(Rc::clone(done)),
+                _ => None,
+            },
+            _ => self.close(&mut replay),
+        }
+    }
+
+    // The children of a node with those of helper rules in their place, epsilon left out.
+    fn flatten<'a>(
+        &self,
+        node: &Rc<RefCell<Node<'a>>>,
+        items: &mut Vec<Rc<Tree<'a>>>,
+    ) -> Result<(), FoldError> {
+        for child in node.borrow().children() {
+            let child_ref = child.borrow();
+            match child_ref.rule_part() {
+                RulePart::Token(tk) if tk.is_epsilon() => {},
+                RulePart::Token(_) => {
+                    let token = child_ref.token().expect("token node without a token");
+                    items.push(Rc::new(Tree::Token(token)));
+                },
+                RulePart::Rule(rule) if self.log.generated(rule.borrow().name()).is_some() => {
+                    self.flatten(child, items)?;
+                },
+                RulePart::Rule(_) => items.push(self.fold(child)?),
+                RulePart::Predicate(..) => unreachable!("rejected by validate"),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn name_of(
+        &self,
+        item: &Tree<'_>,
+    ) -> String {
+        match item {
+            Tree::Token(token) => token.token_kind.upper_name().to_string(),
+            Tree::Rule {
+                rule, ..
+            } => self.original.rules()[*rule].borrow().name().to_string(),
+        }
+    }
+
+    fn node_of<'a>(
+        &self,
+        tree: &Tree<'a>,
+        parent: Option<&Rc<RefCell<Node<'a>>>>,
+        num: &mut usize,
+    ) -> Rc<RefCell<Node<'a>>> {
+        let rule_part = match tree {
+            Tree::Token(token) => RulePart::Token(token.token_kind),
+            Tree::Rule {
+                rule, ..
+            } => RulePart::Rule(Rc::clone(&self.original.rules()[*rule])),
+        };
+
+        let node: Rc<RefCell<Node<'a>>> = match parent {
+            None => Node::new(rule_part, *num).into(),
+            Some(parent) => Node::new_with_parent(rule_part, *num, parent).into(),
+        };
+        *num += 1;
+
+        match tree {
+            Tree::Token(token) => node.borrow_mut().set_token(*token),
+            Tree::Rule {
+                alt,
+                children,
+                ..
+            } => {
+                node.borrow_mut().set_alt(*alt);
+                let children = children
+                    .iter()
+                    .map(|it| self.node_of(it, Some(&node), num))
+                    .collect();
+                node.borrow_mut().set_children(children);
+            },
+        }
+
+        node
+    }
+}
+
+
+// The original alternative being replayed: the ones still possible, None before any is known,
+// and the items so far.
+struct Replay<'a> {
+    rule: usize,
+    alts: Option<Vec<usize>>,
+    items: Vec<Rc<Tree<'a>>>,
+}
+
+
+// Derives a rule over a span of items with the original alternatives, already folded rules
+// match as a whole. The first alternative and the longest leading rule win, so with an
+// ambiguous grammar chains still come out left-associative.
+struct Derivation<'r, 'i, 'a> {
+    rules: &'r Rules,
+    items: &'i [Rc<Tree<'a>>],
+    memo: HashMap<(usize, usize, usize), Option<Rc<Tree<'a>>>>,
+    in_progress: HashSet<(usize, usize, usize)>,
+}
+
+impl<'a> Derivation<'_, '_, 'a> {
+    fn derive(
+        &mut self,
+        rule: usize,
+        start: usize,
+        end: usize,
+    ) -> Option<Rc<Tree<'a>>> {
+        let key = (rule, start, end);
+        if let Some(derived) = self.memo.get(&key) {
+            return derived.clone();
+        }
+        // A cycle through rules deriving nothing more.
+        if !self.in_progress.insert(key) {
+            return None;
+        }
+
+        let alternatives = self.rules.rules()[rule].borrow().alternatives.clone();
+        let derived = alternatives.iter().enumerate().find_map(|(alt, parts)| {
+            self.derive_parts(parts, start, end).map(|children| {
+                Rc::new(Tree::Rule {
+                    rule,
+                    alt,
+                    children,
+                })
+            })
+        });
+
+        self.in_progress.remove(&key);
+        self.memo.insert(key, derived.clone());
+        derived
+    }
+
+    fn derive_parts(
+        &mut self,
+        parts: &[RulePart],
+        start: usize,
+        end: usize,
+    ) -> Option<Vec<Rc<Tree<'a>>>> {
+        let Some((part, rest)) = parts.split_first()
+        else {
+            return (start == end).then(Vec::new);
+        };
+
+        match part {
+            RulePart::Token(tk) if tk.is_epsilon() => self.derive_parts(rest, start, end),
+            RulePart::Token(tk) => match self.items.get(start).map(|it| it.as_ref()) {
+                Some(Tree::Token(token)) if token.token_kind == *tk && start < end => {
+                    let mut children = vec![Rc::clone(&self.items[start])];
+                    children.extend(self.derive_parts(rest, start + 1, end)?);
+                    Some(children)
+                },
+                _ => None,
+            },
+            RulePart::Rule(rule) => {
+                let rule = self
+                    .rules
+                    .rules()
+                    .iter()
+                    .position(|it| it.borrow().name() == rule.borrow().name())?;
+
+                // A rule folded already.
+                if let Some(Tree::Rule {
+                    rule: folded, ..
+                }) = self.items.get(start).map(|it| it.as_ref())
+                {
+                    if *folded == rule && start < end {
+                        if let Some(rest) = self.derive_parts(rest, start + 1, end) {
+                            let mut children = vec![Rc::clone(&self.items[start])];
+                            children.extend(rest);
+                            return Some(children);
+                        }
+                    }
+                }
+
+                for split in (start..=end).rev() {
+                    if let Some(derived) = self.derive(rule, start, split) {
+                        if let Some(rest) = self.derive_parts(rest, split, end) {
+                            let mut children = vec![derived];
+                            children.extend(rest);
+                            return Some(children);
+                        }
+                    }
+                }
+                None
+            },
+            RulePart::Predicate(..) => unreachable!("rejected by validate"),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::lang::lexer::v0::Lexer;
+    use crate::lang::parser::fold::fold;
+    use crate::lang::parser::node::display_of;
+    use crate::lang::parser::rules::Rules;
+    use crate::lang::parser::transform::Pass;
+    use crate::lang::parser_impl::recursive_descent_parser::recursive_descent_parse;
+
+    #[test]
+    fn test_fold_left_recursion() {
+        let rules: Rules = "
+S    -> expr ;
+expr -> expr + term | expr - term | term
+term -> INT | ( expr )
+        "
+        .try_into()
+        .unwrap();
+        let transformed = rules
+            .transform(&[Pass::MakeReadyForRecursiveDescent {
+                max_loop: 16,
+            }])
+            .unwrap();
+
+        let lexer: Lexer = "1 - 2 + 3;".into();
+        let tree = recursive_descent_parse(&transformed.rules, lexer.into_iter()).unwrap();
+        assert!(display_of(&tree).contains("expr__0"));
+
+        let folded = fold(&tree, &rules, &transformed.log).unwrap();
+        assert_eq!(
+            display_of(&folded),
+            "\
+S
+|_ expr
+|____ expr
+|______ expr
+|________ term
+|__________ INT[1]
+|______ MINUS[-]
+|______ term
+|________ INT[2]
+|____ PLUS[+]
+|____ term
+|______ INT[3]
+|_ SEMICOLON[;]"
+        );
+        assert_eq!(folded.borrow().children()[0].borrow().alt(), 0);
+    }
+
+    #[test]
+    fn test_fold_common_prefix() {
+        let rules: Rules = "
+S          -> statement S | EPSILON
+statement  -> ID ( args ) ; | ID ( ) ; | ID = INT ;
+args       -> args , INT | INT
+        "
+        .try_into()
+        .unwrap();
+        let transformed = rules
+            .transform(&[Pass::MakeReadyForRecursiveDescent {
+                max_loop: 16,
+            }])
+            .unwrap();
+
+        // The alternatives of statement__0 substitute args into statement, which only the
+        // derivation sorts out.
+        assert!(transformed
+            .log
+            .origin_of_alt("statement__0", 1)
+            .iter()
+            .any(|(rule, _)| rule == "args"));
+
+        let lexer: Lexer = "f(1, 2); x = 3;".into();
+        let tree = recursive_descent_parse(&transformed.rules, lexer.into_iter()).unwrap();
+
+        let folded = fold(&tree, &rules, &transformed.log).unwrap();
+        assert_eq!(
+            display_of(&folded),
+            "\
+S
+|_ statement
+|____ ID[f]
+|____ LEFT_PAREN[(]
+|____ args
+|______ args
+|________ INT[1]
+|______ COMMA[,]
+|______ INT[2]
+|____ RIGHT_PAREN[)]
+|____ SEMICOLON[;]
+|_ S
+|____ statement
+|______ ID[x]
+|______ EQUAL[=]
+|______ INT[3]
+|______ SEMICOLON[;]
+|____ S"
+        );
+
+        let statements = folded.borrow().children()[1].borrow().children()[0].clone();
+        assert_eq!(statements.borrow().alt(), 2);
+        assert_eq!(folded.borrow().num(), 0);
+        assert_eq!(statements.borrow().num(), 12);
+    }
+}
@@ -0,0 +1,428 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::rc::Rc;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::lang::error::GrammarError;
+use crate::lang::lexer::token::TokenKind;
+use crate::lang::parser::precedence::Precedence;
+use crate::lang::parser::rule::Predicate;
+use crate::lang::parser::rule::Rule;
+use crate::lang::parser::rule::RulePart;
+use crate::lang::parser::rules::Rules;
+
+// The index of a rule in its grammar, the start rule is 0.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub struct RuleId(usize);
+
+impl RuleId {
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub struct AltId {
+    rule: RuleId,
+    alt_no: usize,
+}
+
+impl AltId {
+    pub fn new(
+        rule: RuleId,
+        alt_no: usize,
+    ) -> Self {
+        Self {
+            rule,
+            alt_no,
+        }
+    }
+
+    pub fn rule(&self) -> RuleId {
+        self.rule
+    }
+
+    pub fn alt_no(&self) -> usize {
+        self.alt_no
+    }
+}
+
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Symbol {
+    Rule(RuleId),
+    Token(TokenKind),
+    Predicate(Predicate, Box<Symbol>),
+}
+
+impl Symbol {
+    pub fn is_epsilon(&self) -> bool {
+        matches!(self, Symbol::Token(TokenKind::Epsilon))
+    }
+}
+
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct GrammarRule {
+    name: String,
+    recursion_elimination_num: usize,
+    alternatives: Vec<Vec<Symbol>>,
+}
+
+impl GrammarRule {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn recursion_elimination_num(&self) -> usize {
+        self.recursion_elimination_num
+    }
+
+    pub fn alternatives(&self) -> &[Vec<Symbol>] {
+        &self.alternatives
+    }
+}
+
+
+// Rules frozen once transformations are done. Rules live in an arena and refer to each other by
+// RuleId, so a grammar has no reference cycles, compares and hashes by index, and can be shared
+// across threads.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Grammar {
+    rules: Vec<GrammarRule>,
+    ids: BTreeMap<String, RuleId>,
+    precedence: Precedence,
+}
+
+impl Grammar {
+    pub fn start(&self) -> RuleId {
+        RuleId(0)
+    }
+
+    pub fn rules(&self) -> &[GrammarRule] {
+        &self.rules
+    }
+
+    pub fn rule(
+        &self,
+        id: RuleId,
+    ) -> &GrammarRule {
+        &self.rules[id.0]
+    }
+
+    pub fn rule_ids(&self) -> impl Iterator<Item = RuleId> {
+        (0..self.rules.len()).map(RuleId)
+    }
+
+    pub fn rule_id(
+        &self,
+        name: &str,
+    ) -> Option<RuleId> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn alt_ids(
+        &self,
+        id: RuleId,
+    ) -> impl Iterator<Item = AltId> {
+        (0..self.rule(id).alternatives.len()).map(move |alt_no| AltId::new(id, alt_no))
+    }
+
+    pub fn alt(
+        &self,
+        id: AltId,
+    ) -> &[Symbol] {
+        &self.rule(id.rule).alternatives[id.alt_no]
+    }
+
+    pub fn precedence(&self) -> &Precedence {
+        &self.precedence
+    }
+
+    pub fn nullable_set(&self) -> HashSet<RuleId> {
+        let mut nullable = vec![false; self.rules.len()];
+
+        loop {
+            let mut any_change = false;
+
+            for id in self.rule_ids() {
+                if nullable[id.0] {
+                    continue;
+                }
+
+                let is_nullable = self.rule(id).alternatives.iter().any(|alt| {
+                    alt.iter().all(|symbol| match symbol {
+                        Symbol::Token(tk) => tk.is_epsilon(),
+                        Symbol::Rule(rule) => nullable[rule.0],
+                        Symbol::Predicate(..) => true,
+                    })
+                });
+
+                if is_nullable {
+                    nullable[id.0] = true;
+                    any_change = true;
+                }
+            }
+
+            if !any_change {
+                break;
+            }
+        }
+
+        self.rule_ids().filter(|it| nullable[it.0]).collect()
+    }
+
+    // The tokens each rule can start with, EPSILON if it is nullable.
+    pub fn first_set(&self) -> HashMap<RuleId, HashSet<TokenKind>> {
+        let nullable = self.nullable_set();
+        let mut first: Vec<HashSet<TokenKind>> = vec![HashSet::new(); self.rules.len()];
+
+        loop {
+            let mut any_change = false;
+
+            for id in self.rule_ids() {
+                for alt in &self.rule(id).alternatives {
+                    let mut rhs: HashSet<TokenKind> = HashSet::new();
+                    for symbol in alt {
+                        match symbol {
+                            Symbol::Token(tk) if tk.is_epsilon() => {},
+                            Symbol::Token(tk) => {
+                                rhs.insert(*tk);
+                                break;
+                            },
+                            Symbol::Rule(rule) => {
+                                rhs.extend(first[rule.0].iter().filter(|it| !it.is_epsilon()));
+                                if !nullable.contains(rule) {
+                                    break;
+                                }
+                            },
+                            Symbol::Predicate(..) => {},
+                        }
+                    }
+
+                    let len = first[id.0].len();
+                    first[id.0].extend(rhs);
+                    any_change = any_change || first[id.0].len() != len;
+                }
+            }
+
+            if !any_change {
+                break;
+            }
+        }
+
+        for id in &nullable {
+            first[id.0].insert(TokenKind::Epsilon);
+        }
+        self.rule_ids().zip(first).collect()
+    }
+
+    // Back to mutable rules, for transforming them further.
+    pub fn to_rules(&self) -> Rules {
+        let rules: Vec<Rc<RefCell<Rule>>> = self
+            .rules
+            .iter()
+            .map(|rule| Rule::new(rule.name.clone(), rule.recursion_elimination_num).into())
+            .collect();
+
+        for (rule, grammar_rule) in rules.iter().zip(&self.rules) {
+            rule.borrow_mut().alternatives = grammar_rule
+                .alternatives
+                .iter()
+                .map(|alt| alt.iter().map(|it| rule_part_of(it, &rules)).collect())
+                .collect();
+        }
+
+        let mut rules = Rules::from_rules(rules);
+        rules.set_precedence(self.precedence.clone());
+        rules
+    }
+
+    pub fn display_of_symbol(
+        &self,
+        symbol: &Symbol,
+    ) -> String {
+        match symbol {
+            Symbol::Rule(id) => self.rule(*id).name.clone(),
+            Symbol::Token(tk) => tk.repr_or_name().to_uppercase(),
+            Symbol::Predicate(predicate, symbol) => {
+                format!("{}{}", predicate.repr(), self.display_of_symbol(symbol))
+            },
+        }
+    }
+}
+
+impl TryFrom<&Rules> for Grammar {
+    type Error = GrammarError;
+
+    fn try_from(rules: &Rules) -> Result<Self, Self::Error> {
+        let mut ids: BTreeMap<String, RuleId> = BTreeMap::new();
+        let mut duplicates = vec![];
+        for (index, rule) in rules.rules().iter().enumerate() {
+            let name = rule.borrow().name().to_string();
+            if ids.insert(name.clone(), RuleId(index)).is_some() {
+                duplicates.push(name);
+            }
+        }
+        if !duplicates.is_empty() {
+            return Err(GrammarError::DuplicateRules(duplicates));
+        }
+
+        let mut grammar_rules = vec![];
+        for rule in rules.rules() {
+            let rule = rule.borrow();
+            let mut alternatives = vec![];
+            for alt in &rule.alternatives {
+                alternatives.push(
+                    alt.iter()
+                        .map(|it| symbol_of(it, &ids))
+                        .collect::<Result<Vec<_>, _>>()?,
+                );
+            }
+            grammar_rules.push(GrammarRule {
+                name: rule.name().to_string(),
+                recursion_elimination_num: rule.recursion_elimination_num(),
+                alternatives,
+            });
+        }
+
+        Ok(Self {
+            rules: grammar_rules,
+            ids,
+            precedence: rules.precedence().clone(),
+        })
+    }
+}
+
+impl Display for Grammar {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(f, "Grammar[")?;
+        for line in self.precedence.to_string().lines() {
+            write!(f, "\n  {}", line)?;
+        }
+        for rule in &self.rules {
+            let alternatives = rule
+                .alternatives
+                .iter()
+                .map(|alt| {
+                    alt.iter()
+                        .map(|it| self.display_of_symbol(it))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .collect::<Vec<_>>()
+                .join(" | ");
+            write!(f, "\n  {: <20} -> {}", rule.name, alternatives)?;
+        }
+        write!(f, "\n]")
+    }
+}
+
+
+fn symbol_of(
+    part: &RulePart,
+    ids: &BTreeMap<String, RuleId>,
+) -> Result<Symbol, GrammarError> {
+    match part {
+        RulePart::Rule(rule) => {
+            let name = rule.borrow().name().to_string();
+            match ids.get(&name) {
+                Some(id) => Ok(Symbol::Rule(*id)),
+                None => Err(GrammarError::MissingRules(vec![name])),
+            }
+        },
+        RulePart::Token(tk) => Ok(Symbol::Token(*tk)),
+        RulePart::Predicate(predicate, part) => Ok(Symbol::Predicate(
+            *predicate,
+            Box::new(symbol_of(part, ids)?),
+        )),
+    }
+}
+
+fn rule_part_of(
+    symbol: &Symbol,
+    rules: &[Rc<RefCell<Rule>>],
+) -> RulePart {
+    match symbol {
+        Symbol::Rule(id) => RulePart::Rule(Rc::clone(&rules[id.0])),
+        Symbol::Token(tk) => RulePart::Token(*tk),
+        Symbol::Predicate(predicate, symbol) => {
+            RulePart::Predicate(*predicate, Box::new(rule_part_of(symbol, rules)))
+        },
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::lang::lexer::token::TokenKind;
+    use crate::lang::parser::grammar::AltId;
+    use crate::lang::parser::grammar::Grammar;
+    use crate::lang::parser::grammar::Symbol;
+    use crate::lang::parser::rules::Rules;
+
+    fn is_send_and_sync<T: Send + Sync>() {
+    }
+
+    #[test]
+    fn test_grammar_of_rules() {
+        let rules: Rules = "
+%left + -
+S       -> expr ; S | EPSILON
+expr    -> term expr_r
+expr_r  -> + term expr_r | - term expr_r | EPSILON
+term    -> ( expr ) | INT | ID
+        "
+        .try_into()
+        .unwrap();
+
+        let grammar = Grammar::try_from(&rules).unwrap();
+        is_send_and_sync::<Grammar>();
+
+        assert_eq!(
+            grammar.to_string(),
+            rules.to_string().replacen("Rules[", "Grammar[", 1)
+        );
+        assert_eq!(grammar.to_rules().to_string(), rules.to_string());
+
+        let expr = grammar.rule_id("expr").unwrap();
+        let term = grammar.rule_id("term").unwrap();
+        assert_eq!(grammar.start().index(), 0);
+        let expr_r = grammar.rule_id("expr_r").unwrap();
+        assert_eq!(
+            grammar.alt(AltId::new(expr, 0)),
+            [Symbol::Rule(term), Symbol::Rule(expr_r)]
+        );
+        assert_eq!(grammar.alt_ids(term).count(), 3);
+
+        // Same sets as the rules', computed on another thread.
+        let (nullable, first) = std::thread::scope(|scope| {
+            scope
+                .spawn(|| (grammar.nullable_set(), grammar.first_set()))
+                .join()
+                .unwrap()
+        });
+        let names = |ids: HashSet<_>| {
+            ids.into_iter()
+                .map(|it| grammar.rule(it).name().to_string())
+                .collect::<HashSet<_>>()
+        };
+        assert_eq!(names(nullable), rules.nullable_set());
+        for id in grammar.rule_ids() {
+            assert_eq!(first[&id], rules.first_set()[grammar.rule(id).name()]);
+        }
+        assert!(first[&term].contains(&TokenKind::Id));
+    }
+}
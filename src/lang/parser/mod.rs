@@ -1,3 +1,13 @@
+pub mod actions;
+pub mod conflicts;
+pub mod diagnostics;
+pub mod fold;
+pub mod grammar;
 pub mod node;
+pub mod normal_form;
+pub mod precedence;
 pub mod rule;
 pub mod rules;
+pub mod sentences;
+pub mod transform;
+pub mod tree;
@@ -1,10 +1,12 @@
 use std::cell::RefCell;
-use std::cmp::max;
+use std::error::Error;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::fmt::Formatter;
 use std::rc::Rc;
+use std::rc::Weak;
 
+use crate::lang::error::ParseErrorKind;
 use crate::lang::lexer::token::Token;
 use crate::lang::parser::rule::RulePart;
 
@@ -14,7 +16,8 @@ pub struct Node<'a> {
 
     token: Option<Token<'a>>,
 
-    parent: Option<Rc<RefCell<Node<'a>>>>,
+    // Weak, the children keep the tree alive from the root down and there is no cycle.
+    parent: Option<Weak<RefCell<Node<'a>>>>,
     children: Vec<Rc<RefCell<Node<'a>>>>,
 
     num: usize,
@@ -50,7 +53,7 @@ impl<'a> Node<'a> {
             rule_part,
             alt_no: None,
             token: None,
-            parent: Some(Rc::clone(parent)),
+            parent: Some(Rc::downgrade(parent)),
             children: vec![],
             num,
         };
@@ -93,6 +96,21 @@ impl<'a> Node<'a> {
         });
     }
 
+    pub fn set_alt(
+        &mut self,
+        alt_no: usize,
+    ) {
+        if !self.rule_part.is_rule() {
+            panic!("node is not a rule");
+        }
+
+        if alt_no >= self.rule_part.get_rule().borrow().alternatives.len() {
+            panic!("alt does not exist: {}, rule: {}", alt_no, self.rule_part);
+        }
+
+        self.alt_no = Some(alt_no);
+    }
+
 
     pub fn has_alt(&self) -> bool {
         if !self.rule_part.is_rule() {
@@ -138,31 +156,20 @@ impl<'a> Node<'a> {
         self.num
     }
 
-    pub fn next_num(&self) -> usize {
-        if let Some(parent) = self.parent.as_ref() {
-            return parent.borrow().next_num();
-        }
-
-        self.max_num() + 1
-    }
-
-    fn max_num(&self) -> usize {
-        let mut next_num = self.num();
-
-        for r in &self.children {
-            next_num = max(next_num, r.borrow().max_num())
-        }
-
-        next_num
-    }
-
 
     pub fn rule_part(&self) -> &RulePart {
         &self.rule_part
     }
 
-    pub fn parent(&self) -> &Option<Rc<RefCell<Node<'a>>>> {
-        &self.parent
+    pub fn parent(&self) -> Option<Rc<RefCell<Node<'a>>>> {
+        self.parent.as_ref().and_then(Weak::upgrade)
+    }
+
+    pub fn set_parent(
+        &mut self,
+        parent: &Rc<RefCell<Node<'a>>>,
+    ) {
+        self.parent = Some(Rc::downgrade(parent));
     }
 
 
@@ -202,15 +209,6 @@ impl<'a> Node<'a> {
     }
 }
 
-impl Drop for Node<'_> {
-    fn drop(&mut self) {
-        self.parent = None;
-        // TODO is this needed?
-        // TODO is this enough? should we recurse?
-        self.children.clear();
-    }
-}
-
 impl<'a> From<Node<'a>> for Rc<RefCell<Node<'a>>> {
     fn from(node: Node<'a>) -> Self {
         Rc::new(RefCell::new(node))
@@ -255,22 +253,38 @@ fn display_of0(
 
 pub type ParseResult<'a> = Result<Rc<RefCell<Node<'a>>>, ParseError<'a>>;
 
+// The error as data in kind, and as text in the words of the parser in error.
 pub struct ParseError<'a> {
     partial_tree: Rc<RefCell<Node<'a>>>,
+    kind: ParseErrorKind,
     error: String,
 }
 
 impl<'a> ParseError<'a> {
-    pub fn new(
+    pub fn of(
+        partial_tree: &Rc<RefCell<Node<'a>>>,
+        kind: ParseErrorKind,
+    ) -> Self {
+        let error = kind.to_string();
+        Self::with_error(partial_tree, kind, error)
+    }
+
+    pub fn with_error(
         partial_tree: &Rc<RefCell<Node<'a>>>,
+        kind: ParseErrorKind,
         error: String,
     ) -> Self {
         Self {
             partial_tree: Rc::clone(partial_tree),
+            kind,
             error,
         }
     }
 
+    pub fn kind(&self) -> &ParseErrorKind {
+        &self.kind
+    }
+
     pub fn error(&self) -> &str {
         &self.error
     }
@@ -297,3 +311,6 @@ impl Display for ParseError<'_> {
         write!(f, "ParseError[{}]", self.error)
     }
 }
+
+impl Error for ParseError<'_> {
+}
@@ -0,0 +1,845 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use crate::lang::error::GrammarError;
+use crate::lang::lexer::token::TokenKind;
+use crate::lang::parser::rule::Rule;
+use crate::lang::parser::rule::RulePart;
+use crate::lang::parser::rules::Rules;
+
+// How an alternative of a normal form derives in the original rules: a tree of original
+// alternatives with the parts of the alternative at its leaves, Part(n) being the n-th part once
+// the helper rules it refers to are unfolded. A Part at the root passes the alternative through
+// to whatever its part derives.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Derivation {
+    Part(usize),
+    Alt {
+        rule: String,
+        alt_no: usize,
+        children: Vec<Derivation>,
+    },
+}
+
+impl Derivation {
+    fn of_alt(
+        rule: &str,
+        alt_no: usize,
+        alt: &[RulePart],
+    ) -> Self {
+        Derivation::Alt {
+            rule: rule.to_string(),
+            alt_no,
+            children: (0..num_parts(alt)).map(Derivation::Part).collect(),
+        }
+    }
+
+    // Part(at) replaced by inner, which has len parts of its own.
+    fn substitute(
+        &self,
+        at: usize,
+        inner: &Derivation,
+        len: usize,
+    ) -> Self {
+        match self {
+            Derivation::Part(n) if *n == at => inner.shifted(at),
+            Derivation::Part(n) if *n > at => Derivation::Part(n + len - 1),
+            Derivation::Part(n) => Derivation::Part(*n),
+            Derivation::Alt {
+                rule,
+                alt_no,
+                children,
+            } => Derivation::Alt {
+                rule: rule.clone(),
+                alt_no: *alt_no,
+                children: children
+                    .iter()
+                    .map(|it| it.substitute(at, inner, len))
+                    .collect(),
+            },
+        }
+    }
+
+    fn shifted(
+        &self,
+        by: usize,
+    ) -> Self {
+        match self {
+            Derivation::Part(n) => Derivation::Part(n + by),
+            Derivation::Alt {
+                rule,
+                alt_no,
+                children,
+            } => Derivation::Alt {
+                rule: rule.clone(),
+                alt_no: *alt_no,
+                children: children.iter().map(|it| it.shifted(by)).collect(),
+            },
+        }
+    }
+}
+
+type Alt = (Vec<RulePart>, Option<Derivation>);
+
+
+// A copy of rules brought into a normal form step by step, the original rules are left as they
+// were. Every alternative keeps its derivation in the original rules, except the alternatives of
+// helper rules, which only stand for a part of the alternative referring to them.
+pub struct NormalForm {
+    original: Rules,
+    rules: Rules,
+    // Per rule, per alternative. None for Greibach normal form, left recursion elimination
+    // turns the derivations inside out.
+    derivations: Option<HashMap<String, Vec<Option<Derivation>>>>,
+}
+
+impl NormalForm {
+    pub fn new(rules: &Rules) -> Result<Self, GrammarError> {
+        rules.validate()?;
+
+        let copy = Rules::from_rules(rules.deep_clone().rules().clone());
+        let derivations = copy
+            .rules()
+            .iter()
+            .map(|rule| {
+                let rule = rule.borrow();
+                let derivations = rule
+                    .alternatives
+                    .iter()
+                    .enumerate()
+                    .map(|(alt_no, alt)| Some(Derivation::of_alt(rule.name(), alt_no, alt)))
+                    .collect();
+                (rule.name().to_string(), derivations)
+            })
+            .collect();
+
+        Ok(Self {
+            original: rules.clone(),
+            rules: copy,
+            derivations: Some(derivations),
+        })
+    }
+
+    // Every rule either derives a single token or two rules, only the start rule may derive
+    // EPSILON and then no rule refers to it.
+    pub fn chomsky(rules: &Rules) -> Result<Self, GrammarError> {
+        let mut normal_form = Self::new(rules)?;
+        normal_form.eliminate_epsilon_rules();
+        normal_form.eliminate_unit_rules();
+        normal_form.separate_tokens();
+        normal_form.binarize();
+        normal_form.rules.validate()?;
+        Ok(normal_form)
+    }
+
+    // Every rule derives a token followed by rules, only the start rule may derive EPSILON and
+    // then no rule refers to it.
+    pub fn greibach(rules: &Rules) -> Result<Self, GrammarError> {
+        let mut normal_form = Self::chomsky(rules)?;
+        normal_form.derivations = None;
+        normal_form.start_with_tokens();
+        normal_form.eliminate_useless_rules();
+        normal_form.rules.validate()?;
+        Ok(normal_form)
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    pub fn original(&self) -> &Rules {
+        &self.original
+    }
+
+    pub fn has_derivations(&self) -> bool {
+        self.derivations.is_some()
+    }
+
+    // None for helper rules, and for all the rules of a Greibach normal form.
+    pub fn derivation(
+        &self,
+        rule_name: &str,
+        alt_no: usize,
+    ) -> Option<&Derivation> {
+        self.derivations
+            .as_ref()
+            .and_then(|it| it.get(rule_name))
+            .and_then(|it| it[alt_no].as_ref())
+    }
+
+    // Alternatives deriving the empty input go, every other alternative is repeated with each
+    // combination of its nullable rules left out. Rules deriving nothing else go as well. If the
+    // start rule is nullable it keeps an EPSILON alternative, moving what it derives to a new rule
+    // when other rules refer to it.
+    pub fn eliminate_epsilon_rules(&mut self) {
+        let start = self.rules.rules()[0].borrow().name().to_string();
+        if self.rules.nullable_set().contains(&start) {
+            self.separate_start();
+        }
+
+        let nullable = self.rules.nullable_set();
+        let empty = self.empty_derivations(&nullable);
+
+        for rule in self.rules.rules().clone() {
+            let name = rule.borrow().name().to_string();
+            let mut alts = vec![];
+
+            for (alt, derivation) in self.alts_of(&name) {
+                let nullable_at = alt
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, part)| part.is_rule() && nullable.contains(&part.name()))
+                    .map(|(at, _)| at)
+                    .collect::<Vec<_>>();
+
+                for left_out in 0..(1usize << nullable_at.len()) {
+                    let left_out = nullable_at
+                        .iter()
+                        .enumerate()
+                        .filter(|(bit, _)| left_out & (1 << bit) != 0)
+                        .map(|(_, at)| *at)
+                        .collect::<Vec<_>>();
+
+                    let parts = alt
+                        .iter()
+                        .enumerate()
+                        .filter(|(at, part)| !part.is_epsilon() && !left_out.contains(at))
+                        .map(|(_, part)| part.clone())
+                        .collect::<Vec<_>>();
+                    if parts.is_empty() || (parts.len() == 1 && parts[0].name() == name) {
+                        continue;
+                    }
+
+                    let derivation = derivation.as_ref().and_then(|derivation| {
+                        let mut derivation = derivation.clone();
+                        for at in left_out.iter().rev() {
+                            let inner = empty.get(&alt[*at].name())?;
+                            derivation = derivation.substitute(*at, inner, 0);
+                        }
+                        Some(derivation)
+                    });
+                    alts.push((parts, derivation));
+                }
+            }
+
+            if name == start && nullable.contains(&start) {
+                alts.push((vec![TokenKind::Epsilon.into()], empty.get(&start).cloned()));
+            }
+            self.set_alts(&rule, alts);
+        }
+
+        self.eliminate_useless_rules();
+    }
+
+    // Alternatives of a single rule are replaced by the alternatives of that rule, or of the
+    // rule it is a unit alternative of in turn.
+    pub fn eliminate_unit_rules(&mut self) {
+        let alts_of = self
+            .rules
+            .rules()
+            .iter()
+            .map(|rule| {
+                let name = rule.borrow().name().to_string();
+                let alts = self.alts_of(&name);
+                (name, alts)
+            })
+            .collect::<HashMap<_, _>>();
+
+        for rule in self.rules.rules().clone() {
+            let name = rule.borrow().name().to_string();
+            let mut alts = vec![];
+
+            // The rules the rule derives by unit alternatives, how it does, in order of distance.
+            let mut units = vec![(name.clone(), Some(Derivation::Part(0)))];
+            let mut seen = HashSet::from([name]);
+            let mut i = 0;
+            while i < units.len() {
+                let (unit, through) = units[i].clone();
+                i += 1;
+
+                for (alt, derivation) in &alts_of[&unit] {
+                    let derivation = match (&through, derivation) {
+                        (Some(through), Some(derivation)) => {
+                            Some(through.substitute(0, derivation, num_parts(alt)))
+                        },
+                        _ => None,
+                    };
+
+                    if alt.len() == 1 && alt[0].is_rule() {
+                        if seen.insert(alt[0].name()) {
+                            units.push((alt[0].name(), derivation));
+                        }
+                    }
+                    else {
+                        alts.push((alt.clone(), derivation));
+                    }
+                }
+            }
+
+            self.set_alts(&rule, alts);
+        }
+
+        self.eliminate_useless_rules();
+    }
+
+    // =========================================================================
+
+    fn alts_of(
+        &self,
+        rule_name: &str,
+    ) -> Vec<Alt> {
+        let rule = self.rules.get_rule_by_name(rule_name);
+        let alts = rule.borrow().alternatives.clone();
+        let derivations = match &self.derivations {
+            Some(derivations) => derivations[rule_name].clone(),
+            None => vec![None; alts.len()],
+        };
+        alts.into_iter().zip(derivations).collect()
+    }
+
+    // Duplicated alternatives are left out, the first one stays, and EPSILON goes last.
+    fn set_alts(
+        &mut self,
+        rule: &Rc<RefCell<Rule>>,
+        alts: Vec<Alt>,
+    ) {
+        let mut kept: Vec<Alt> = vec![];
+        for alt in alts {
+            if !kept.iter().any(|it| it.0 == alt.0) {
+                kept.push(alt);
+            }
+        }
+        if let Some(at) = kept
+            .iter()
+            .position(|(alt, _)| alt.len() == 1 && alt[0].is_epsilon())
+        {
+            let epsilon = kept.remove(at);
+            kept.push(epsilon);
+        }
+
+        let (alts, derivations): (Vec<_>, Vec<_>) = kept.into_iter().unzip();
+        let name = rule.borrow().name().to_string();
+        rule.borrow_mut().alternatives = alts;
+        if let Some(all) = &mut self.derivations {
+            all.insert(name, derivations);
+        }
+        self.changed(self.rules.rules().clone());
+    }
+
+    fn add_rule(
+        &mut self,
+        base_name: &str,
+        alts: Vec<Alt>,
+    ) -> Rc<RefCell<Rule>> {
+        let name = self.rules.find_new_indexed_name(base_name);
+        let num = self.rules.max_recursion_elimination_num() + 1;
+        let rule: Rc<RefCell<Rule>> = Rule::new(name, num).into();
+
+        let mut rules = self.rules.rules().clone();
+        rules.push(Rc::clone(&rule));
+        self.changed(rules);
+        if let Some(all) = &mut self.derivations {
+            all.insert(rule.borrow().name().to_string(), vec![]);
+        }
+
+        self.set_alts(&rule, alts);
+        rule
+    }
+
+    // Also drops the cached sets of the rules.
+    fn changed(
+        &mut self,
+        rules: Vec<Rc<RefCell<Rule>>>,
+    ) {
+        self.rules = Rules::from_rules(rules);
+    }
+
+    // The start rule derives what it did through a new rule, which takes its place wherever it
+    // is referred to.
+    fn separate_start(&mut self) {
+        let start = Rc::clone(&self.rules.rules()[0]);
+        let start_name = start.borrow().name().to_string();
+        let is_referred = self.rules.rules().iter().any(|rule| {
+            rule.borrow()
+                .alternatives
+                .iter()
+                .any(|alt| alt.iter().any(|part| part.name() == start_name))
+        });
+        if !is_referred {
+            return;
+        }
+
+        let moved = self.add_rule(&start_name, self.alts_of(&start_name));
+        for rule in self.rules.rules() {
+            for alt in &mut rule.borrow_mut().alternatives {
+                for part in alt.iter_mut() {
+                    if matches!(part, RulePart::Rule(rule) if Rc::ptr_eq(rule, &start)) {
+                        *part = RulePart::Rule(Rc::clone(&moved));
+                    }
+                }
+            }
+        }
+        self.set_alts(
+            &start,
+            vec![(vec![RulePart::Rule(moved)], Some(Derivation::Part(0)))],
+        );
+    }
+
+    // For every nullable rule, one way it derives the empty input.
+    fn empty_derivations(
+        &self,
+        nullable: &HashSet<String>,
+    ) -> HashMap<String, Derivation> {
+        let mut empty: HashMap<String, Derivation> = HashMap::new();
+
+        loop {
+            let mut any_change = false;
+
+            for rule in self.rules.rules() {
+                let name = rule.borrow().name().to_string();
+                if !nullable.contains(&name) || empty.contains_key(&name) {
+                    continue;
+                }
+
+                for (alt, derivation) in self.alts_of(&name) {
+                    let derives_empty = alt
+                        .iter()
+                        .all(|part| part.is_epsilon() || empty.contains_key(&part.name()));
+                    if !derives_empty {
+                        continue;
+                    }
+                    if let Some(mut derivation) = derivation {
+                        for (at, part) in alt.iter().enumerate().rev() {
+                            if part.is_rule() {
+                                derivation = derivation.substitute(at, &empty[&part.name()], 0);
+                            }
+                        }
+                        empty.insert(name, derivation);
+                        any_change = true;
+                    }
+                    break;
+                }
+            }
+
+            if !any_change {
+                break;
+            }
+        }
+
+        empty
+    }
+
+    // Rules deriving no input and the alternatives referring to them go, then the rules which
+    // can not be reached from the start rule. The start rule stays.
+    fn eliminate_useless_rules(&mut self) {
+        let productive = self.productive_rules();
+        for rule in self.rules.rules().clone() {
+            let name = rule.borrow().name().to_string();
+            let alts = self
+                .alts_of(&name)
+                .into_iter()
+                .filter(|(alt, _)| {
+                    alt.iter()
+                        .all(|part| !part.is_rule() || productive.contains(&part.name()))
+                })
+                .collect();
+            self.set_alts(&rule, alts);
+        }
+
+        let mut reachable = HashSet::new();
+        let mut queue = vec![Rc::clone(&self.rules.rules()[0])];
+        while let Some(rule) = queue.pop() {
+            if !reachable.insert(rule.borrow().name().to_string()) {
+                continue;
+            }
+            for alt in &rule.borrow().alternatives {
+                queue.extend(alt.iter().filter(|it| it.is_rule()).map(RulePart::get_rule));
+            }
+        }
+
+        let (kept, removed): (Vec<_>, Vec<_>) = self
+            .rules
+            .rules()
+            .iter()
+            .cloned()
+            .partition(|it| reachable.contains(it.borrow().name()));
+        for rule in removed {
+            // Break the reference cycles among the removed rules.
+            rule.borrow_mut().alternatives.clear();
+            if let Some(all) = &mut self.derivations {
+                all.remove(rule.borrow().name());
+            }
+        }
+        self.changed(kept);
+    }
+
+    fn productive_rules(&self) -> HashSet<String> {
+        let mut productive = HashSet::new();
+
+        loop {
+            let mut any_change = false;
+            for rule in self.rules.rules() {
+                let rule = rule.borrow();
+                if productive.contains(rule.name()) {
+                    continue;
+                }
+                if rule.alternatives.iter().any(|alt| {
+                    alt.iter()
+                        .all(|part| part.is_token() || productive.contains(&part.name()))
+                }) {
+                    productive.insert(rule.name().to_string());
+                    any_change = true;
+                }
+            }
+            if !any_change {
+                return productive;
+            }
+        }
+    }
+
+    // Tokens in alternatives of more than one part are moved to a rule deriving only the token,
+    // one per token kind.
+    fn separate_tokens(&mut self) {
+        let mut helpers: HashMap<TokenKind, Rc<RefCell<Rule>>> = HashMap::new();
+
+        for rule in self.rules.rules().clone() {
+            let num_alts = rule.borrow().alternatives.len();
+            for alt_no in 0..num_alts {
+                let alt = rule.borrow().alternatives[alt_no].clone();
+                if alt.len() < 2 {
+                    continue;
+                }
+
+                let mut parts = vec![];
+                for part in alt {
+                    match part {
+                        RulePart::Token(tk) => {
+                            let helper = match helpers.get(&tk) {
+                                Some(helper) => Rc::clone(helper),
+                                None => {
+                                    let helper = self
+                                        .add_rule(tk.upper_name(), vec![(vec![tk.into()], None)]);
+                                    helpers.insert(tk, Rc::clone(&helper));
+                                    helper
+                                },
+                            };
+                            parts.push(RulePart::Rule(helper));
+                        },
+                        part => parts.push(part),
+                    }
+                }
+                rule.borrow_mut().alternatives[alt_no] = parts;
+            }
+        }
+    }
+
+    // Alternatives of more than two parts become the first part followed by a new rule deriving
+    // the rest, the same way.
+    fn binarize(&mut self) {
+        for rule in self.rules.rules().clone() {
+            let name = rule.borrow().name().to_string();
+            let num_alts = rule.borrow().alternatives.len();
+            for alt_no in 0..num_alts {
+                let alt = rule.borrow().alternatives[alt_no].clone();
+                let alt = self.binarized(&name, alt);
+                rule.borrow_mut().alternatives[alt_no] = alt;
+            }
+        }
+    }
+
+    fn binarized(
+        &mut self,
+        rule_name: &str,
+        mut alt: Vec<RulePart>,
+    ) -> Vec<RulePart> {
+        if alt.len() <= 2 {
+            return alt;
+        }
+
+        let rest = alt.split_off(1);
+        let rest = self.binarized(rule_name, rest);
+        let helper = self.add_rule(rule_name, vec![(rest, None)]);
+        alt.push(RulePart::Rule(helper));
+        alt
+    }
+
+    // From Chomsky normal form, with the rules in order: alternatives starting with an earlier
+    // rule have it substituted, then direct left recursion is eliminated, with the helper
+    // rules deriving one or more of the repeated parts so that no EPSILON is needed. The last
+    // rule then starts with tokens only, substituting backwards makes the rest do so too.
+    fn start_with_tokens(&mut self) {
+        let ordered = self.rules.rules().clone();
+        let index_of = |part: &RulePart| {
+            ordered
+                .iter()
+                .position(|it| part.is_rule() && it.borrow().name() == part.name())
+        };
+        let mut helpers = vec![];
+
+        for (i, rule) in ordered.iter().enumerate() {
+            self.substitute_first(rule, |part| index_of(part).is_some_and(|j| j < i));
+
+            let name = rule.borrow().name().to_string();
+            let (recursive, others): (Vec<_>, Vec<_>) = rule
+                .borrow()
+                .alternatives
+                .iter()
+                .cloned()
+                .partition(|alt| alt[0].name() == name);
+            if recursive.is_empty() {
+                continue;
+            }
+
+            // The parts repeated after the rule, alone and followed by the helper.
+            let helper = self.add_rule(&name, vec![]);
+            let with_helper = |alts: Vec<Vec<RulePart>>| {
+                let mut with = alts.iter().map(|it| (it.clone(), None)).collect::<Vec<_>>();
+                for mut alt in alts {
+                    alt.push(RulePart::Rule(Rc::clone(&helper)));
+                    with.push((alt, None));
+                }
+                with
+            };
+            let repeated = recursive.into_iter().map(|it| it[1..].to_vec()).collect();
+            let helper_alts = with_helper(repeated);
+            let alts = with_helper(others);
+            self.set_alts(&helper, helper_alts);
+            self.set_alts(rule, alts);
+            helpers.push(helper);
+        }
+
+        for rule in ordered.iter().rev().chain(helpers.iter()) {
+            self.substitute_first(rule, RulePart::is_rule);
+        }
+    }
+
+    // Until no alternative starts with a part matching, the part is replaced by the
+    // alternatives of its rule.
+    fn substitute_first(
+        &mut self,
+        rule: &Rc<RefCell<Rule>>,
+        matches: impl Fn(&RulePart) -> bool,
+    ) {
+        loop {
+            let alts = rule.borrow().alternatives.clone();
+            if !alts.iter().any(|alt| matches(&alt[0])) {
+                return;
+            }
+
+            let mut substituted = vec![];
+            for alt in alts {
+                if matches(&alt[0]) {
+                    for first in &alt[0].get_rule().borrow().alternatives {
+                        let mut first = first.clone();
+                        first.extend_from_slice(&alt[1..]);
+                        substituted.push((first, None));
+                    }
+                }
+                else {
+                    substituted.push((alt, None));
+                }
+            }
+            self.set_alts(rule, substituted);
+        }
+    }
+}
+
+
+pub fn is_chomsky_normal_form(rules: &Rules) -> bool {
+    is_normal_form(rules, |alt| match alt {
+        [RulePart::Token(tk)] => !tk.is_epsilon(),
+        [first, second] => first.is_rule() && second.is_rule(),
+        _ => false,
+    })
+}
+
+pub fn is_greibach_normal_form(rules: &Rules) -> bool {
+    is_normal_form(rules, |alt| match alt {
+        [RulePart::Token(tk), rest @ ..] => !tk.is_epsilon() && rest.iter().all(RulePart::is_rule),
+        _ => false,
+    })
+}
+
+fn is_normal_form(
+    rules: &Rules,
+    is_normal: impl Fn(&[RulePart]) -> bool,
+) -> bool {
+    let start = match rules.rules().first() {
+        None => return true,
+        Some(start) => start.borrow().name().to_string(),
+    };
+    let mut is_start_referred = false;
+    let mut has_epsilon = false;
+
+    for rule in rules.rules() {
+        let rule = rule.borrow();
+        for alt in &rule.alternatives {
+            if rule.name() == start && alt.len() == 1 && alt[0].is_epsilon() {
+                has_epsilon = true;
+            }
+            else if !is_normal(alt) {
+                return false;
+            }
+            is_start_referred |= alt.iter().any(|part| part.name() == start);
+        }
+    }
+
+    !(has_epsilon && is_start_referred)
+}
+
+fn num_parts(alt: &[RulePart]) -> usize {
+    alt.iter().filter(|it| !it.is_epsilon()).count()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::lang::parser::normal_form::is_chomsky_normal_form;
+    use crate::lang::parser::normal_form::is_greibach_normal_form;
+    use crate::lang::parser::normal_form::Derivation;
+    use crate::lang::parser::normal_form::NormalForm;
+    use crate::lang::parser::rules::Rules;
+
+    fn grammar() -> Rules {
+        "
+S    -> S , item | item |
+item -> ID opt
+opt  -> INT |
+        "
+        .try_into()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_eliminate_epsilon_rules() {
+        let rules = grammar();
+        let mut normal_form = NormalForm::new(&rules).unwrap();
+        normal_form.eliminate_epsilon_rules();
+
+        assert_eq!(
+            normal_form.rules().to_string(),
+            "\
+Rules[
+  S                    -> S__0 | EPSILON
+  item                 -> ID opt | ID
+  opt                  -> INT
+  S__0                 -> S__0 , item | , item | item
+]"
+        );
+        assert!(normal_form.rules().validate().is_ok());
+
+        // `S__0 -> , item` is `S -> S , item` with S deriving nothing.
+        assert_eq!(
+            normal_form.derivation("S__0", 1),
+            Some(&Derivation::Alt {
+                rule: "S".to_string(),
+                alt_no: 0,
+                children: vec![
+                    Derivation::Alt {
+                        rule: "S".to_string(),
+                        alt_no: 2,
+                        children: vec![],
+                    },
+                    Derivation::Part(0),
+                    Derivation::Part(1),
+                ],
+            })
+        );
+        assert_eq!(normal_form.derivation("S", 0), Some(&Derivation::Part(0)));
+    }
+
+    #[test]
+    fn test_eliminate_unit_rules() {
+        let rules: Rules = "
+S    -> expr
+expr -> expr + term | term
+term -> INT | ( expr )
+        "
+        .try_into()
+        .unwrap();
+        let mut normal_form = NormalForm::new(&rules).unwrap();
+        normal_form.eliminate_unit_rules();
+
+        assert_eq!(
+            normal_form.rules().to_string(),
+            "\
+Rules[
+  S                    -> expr + term | INT | ( expr )
+  expr                 -> expr + term | INT | ( expr )
+  term                 -> INT | ( expr )
+]"
+        );
+        assert_eq!(
+            normal_form.derivation("S", 1),
+            Some(&Derivation::Alt {
+                rule: "S".to_string(),
+                alt_no: 0,
+                children: vec![Derivation::Alt {
+                    rule: "expr".to_string(),
+                    alt_no: 1,
+                    children: vec![Derivation::Alt {
+                        rule: "term".to_string(),
+                        alt_no: 0,
+                        children: vec![Derivation::Part(0)],
+                    }],
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_chomsky_normal_form() {
+        let rules = grammar();
+        let normal_form = NormalForm::chomsky(&rules).unwrap();
+
+        assert!(!is_chomsky_normal_form(&rules));
+        assert!(is_chomsky_normal_form(normal_form.rules()));
+        assert_eq!(
+            normal_form.rules().to_string(),
+            "\
+Rules[
+  S                    -> S__0 S__1 | COMMA__0 item | ID__0 opt | ID | EPSILON
+  item                 -> ID__0 opt | ID
+  opt                  -> INT
+  S__0                 -> S__0 S__0__0 | COMMA__0 item | ID__0 opt | ID
+  COMMA__0             -> ,
+  ID__0                -> ID
+  S__1                 -> COMMA__0 item
+  S__0__0              -> COMMA__0 item
+]"
+        );
+        assert_eq!(normal_form.derivation("COMMA__0", 0), None);
+    }
+
+    #[test]
+    fn test_greibach_normal_form() {
+        let rules: Rules = "
+S    -> expr
+expr -> expr + term | term
+term -> INT | ( expr )
+        "
+        .try_into()
+        .unwrap();
+        let normal_form = NormalForm::greibach(&rules).unwrap();
+
+        assert!(!is_greibach_normal_form(&rules));
+        assert!(is_greibach_normal_form(normal_form.rules()));
+        assert!(!normal_form.has_derivations());
+        assert_eq!(
+            normal_form.rules().to_string(),
+            "\
+Rules[
+  S                    -> INT S__0 | ( expr__1 S__0 | INT expr__2 S__0 | ( expr__1 expr__2 S__0 | INT | ( S__1
+  term                 -> INT | ( term__0
+  RIGHT_PAREN__0       -> )
+  S__0                 -> + term
+  S__1                 -> INT RIGHT_PAREN__0 | ( expr__1 RIGHT_PAREN__0 | INT expr__2 RIGHT_PAREN__0 | ( expr__1 expr__2 RIGHT_PAREN__0
+  expr__1              -> INT RIGHT_PAREN__0 | ( expr__1 RIGHT_PAREN__0 | INT expr__2 RIGHT_PAREN__0 | ( expr__1 expr__2 RIGHT_PAREN__0
+  term__0              -> INT RIGHT_PAREN__0 | ( expr__1 RIGHT_PAREN__0 | INT expr__2 RIGHT_PAREN__0 | ( expr__1 expr__2 RIGHT_PAREN__0
+  expr__2              -> + term | + term expr__2
+]"
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::lang::error::GrammarError;
+use crate::lang::lexer::token::TokenKind;
+use crate::lang::parser::rule::Rule;
+use crate::lang::parser::rule::RulePart;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Fixity {
+    Left,
+    Right,
+    Prefix,
+}
+
+impl Fixity {
+    pub fn directive(&self) -> &'static str {
+        match self {
+            Fixity::Left => "%left",
+            Fixity::Right => "%right",
+            Fixity::Prefix => "%prefix",
+        }
+    }
+
+    fn from_directive(directive: &str) -> Result<Self, String> {
+        match directive {
+            "%left" => Ok(Fixity::Left),
+            "%right" => Ok(Fixity::Right),
+            "%prefix" => Ok(Fixity::Prefix),
+            _ => Err(format!(
+                "unknown declaration: {}, expecting one of: %left, %right, %prefix",
+                directive
+            )),
+        }
+    }
+}
+
+
+// Operator declarations, one level per line as in yacc: `%left + -`, `%left * /`, `%prefix -`
+// and `%right ^`. Later lines bind tighter, precedence starts from 1.
+#[derive(Clone, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Precedence {
+    levels: Vec<(Fixity, Vec<TokenKind>)>,
+}
+
+impl Precedence {
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+
+    // Errors are syntax errors of the line.
+    pub fn declare(
+        &mut self,
+        line: &str,
+    ) -> Result<(), GrammarError> {
+        let syntax = |error: String| GrammarError::Syntax {
+            line: line.to_string(),
+            error,
+        };
+        let mut parts = line.split(' ').map(str::trim).filter(|it| !it.is_empty());
+
+        let fixity = Fixity::from_directive(parts.next().unwrap_or_default()).map_err(syntax)?;
+
+        let mut operators = vec![];
+        for part in parts {
+            let tk = TokenKind::from_repr(part)
+                .or_else(|_| TokenKind::from_name(part))
+                .map_err(syntax)?;
+            let already = match fixity {
+                Fixity::Prefix => self.prefix(tk).is_some(),
+                _ => self.binary(tk).is_some(),
+            };
+            if already || operators.contains(&tk) {
+                return Err(syntax(format!(
+                    "operator declared twice: {}, declaration: {}",
+                    tk.repr_or_name(),
+                    line
+                )));
+            }
+            operators.push(tk);
+        }
+
+        if operators.is_empty() {
+            return Err(syntax(format!("declaration without operators: {}", line)));
+        }
+
+        self.levels.push((fixity, operators));
+        Ok(())
+    }
+
+    pub fn binary(
+        &self,
+        tk: TokenKind,
+    ) -> Option<(usize, Fixity)> {
+        self.levels
+            .iter()
+            .enumerate()
+            .find(|(_, (fixity, operators))| *fixity != Fixity::Prefix && operators.contains(&tk))
+            .map(|(level, (fixity, _))| (level + 1, *fixity))
+    }
+
+    pub fn prefix(
+        &self,
+        tk: TokenKind,
+    ) -> Option<usize> {
+        self.levels
+            .iter()
+            .enumerate()
+            .find(|(_, (fixity, operators))| *fixity == Fixity::Prefix && operators.contains(&tk))
+            .map(|(level, _)| level + 1)
+    }
+
+    // A rule with at least one `rule op rule` alternative where op is a declared binary operator.
+    // Its `op rule` alternatives with a declared prefix op are prefix operators, anything else is
+    // an operand.
+    pub fn expression_rule(
+        &self,
+        rule: &Rule,
+    ) -> Option<ExpressionRule> {
+        let is_self = |part: &RulePart| part.is_rule() && part.name() == rule.name();
+
+        let mut expression = ExpressionRule::default();
+        for (alt_no, alt) in rule.alternatives.iter().enumerate() {
+            match alt.as_slice() {
+                [left, RulePart::Token(tk), right] if is_self(left) && is_self(right) => {
+                    if let Some((precedence, fixity)) = self.binary(*tk) {
+                        expression.binary.insert(*tk, (alt_no, precedence, fixity));
+                    }
+                },
+                [RulePart::Token(tk), operand] if is_self(operand) => {
+                    if let Some(precedence) = self.prefix(*tk) {
+                        expression.prefix.insert(*tk, (alt_no, precedence));
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        match expression.binary.is_empty() {
+            true => None,
+            false => Some(expression),
+        }
+    }
+}
+
+impl Display for Precedence {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        let lines = self
+            .levels
+            .iter()
+            .map(|(fixity, operators)| {
+                let operators = operators
+                    .iter()
+                    .map(|it| it.repr_or_name())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                format!("{} {}", fixity.directive(), operators)
+            })
+            .collect::<Vec<_>>();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+
+#[derive(Clone, Default, Debug)]
+pub struct ExpressionRule {
+    // Operator to (alt_no, precedence, fixity).
+    pub binary: HashMap<TokenKind, (usize, usize, Fixity)>,
+    // Operator to (alt_no, precedence).
+    pub prefix: HashMap<TokenKind, (usize, usize)>,
+}
+
+impl ExpressionRule {
+    pub fn is_operator_alt(
+        &self,
+        alt_no: usize,
+    ) -> bool {
+        self.binary.values().any(|it| it.0 == alt_no)
+            || self.prefix.values().any(|it| it.0 == alt_no)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::lang::error::GrammarError;
+    use crate::lang::lexer::token::TokenKind;
+    use crate::lang::parser::precedence::Fixity;
+    use crate::lang::parser::rules::Rules;
+
+    #[test]
+    fn test_expression_rule() {
+        let rules: Rules = "
+%left + -
+%left * /
+%prefix -
+%right ^
+S    -> expr ;
+expr -> expr + expr | expr - expr | expr * expr | expr ^ expr | - expr | ( expr ) | INT
+        "
+        .try_into()
+        .unwrap();
+
+        let precedence = rules.precedence();
+        assert_eq!(precedence.binary(TokenKind::Minus), Some((1, Fixity::Left)));
+        assert_eq!(
+            precedence.binary(TokenKind::Caret),
+            Some((4, Fixity::Right))
+        );
+        assert_eq!(precedence.prefix(TokenKind::Minus), Some(3));
+        assert_eq!(precedence.prefix(TokenKind::Plus), None);
+
+        let expression_rules = rules.expression_rules();
+        assert_eq!(expression_rules.len(), 1);
+
+        let expr = &expression_rules["expr"];
+        assert_eq!(expr.binary[&TokenKind::Star], (2, 2, Fixity::Left));
+        assert_eq!(expr.prefix[&TokenKind::Minus], (4, 3));
+        assert!(expr.is_operator_alt(3));
+        assert!(!expr.is_operator_alt(5));
+    }
+
+    #[test]
+    fn test_invalid_declarations() {
+        let rules: Result<Rules, _> = "
+%left + -
+%left -
+S -> INT
+        "
+        .try_into();
+        let err = rules.err().unwrap();
+        assert!(err.to_string().starts_with("operator declared twice: -"));
+        assert!(matches!(err, GrammarError::Syntax { line, .. } if line == "%left -"));
+
+        let rules: Result<Rules, _> = "
+%nonassoc +
+S -> INT
+        "
+        .try_into();
+        assert!(rules
+            .err()
+            .unwrap()
+            .to_string()
+            .starts_with("unknown declaration: %nonassoc"));
+    }
+}
@@ -10,7 +10,10 @@ use std::rc::Rc;
 
 use lazy_static::lazy_static;
 use regex::Regex;
+use serde::Deserialize;
+use serde::Serialize;
 
+use crate::lang::error::GrammarError;
 use crate::lang::lexer::token::TokenKind;
 
 lazy_static! {
@@ -54,6 +57,10 @@ impl AltRef {
     pub fn alt_no(&self) -> usize {
         self.alt_no
     }
+
+    pub fn rule(&self) -> &Rc<RefCell<Rule>> {
+        &self.rule
+    }
 }
 
 impl PartialEq for AltRef {
@@ -110,10 +117,29 @@ impl Ord for AltRef {
 }
 
 
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Predicate {
+    And,
+    Not,
+}
+
+impl Predicate {
+    pub fn repr(&self) -> &'static str {
+        match self {
+            Predicate::And => "&",
+            Predicate::Not => "!",
+        }
+    }
+}
+
+
 #[derive(Clone, Eq)]
 pub enum RulePart {
     Rule(Rc<RefCell<Rule>>),
     Token(TokenKind),
+    // Syntactic predicate, `&part` succeeds if part matches and `!part` if it does not, neither
+    // consumes any token. Only the packrat parser supports them.
+    Predicate(Predicate, Box<RulePart>),
 }
 
 impl RulePart {
@@ -125,14 +151,27 @@ impl RulePart {
         matches!(self, RulePart::Rule(_))
     }
 
+    pub fn is_predicate(&self) -> bool {
+        matches!(self, RulePart::Predicate(..))
+    }
+
     pub fn is_epsilon(&self) -> bool {
         self.is_token() && *self.get_token_kind() == TokenKind::Epsilon
     }
 
+    // The part a predicate, or a chain of predicates, applies to.
+    pub fn predicate_target(&self) -> &RulePart {
+        match self {
+            RulePart::Predicate(_, part) => part.predicate_target(),
+            part => part,
+        }
+    }
+
     pub fn get_rule(&self) -> Rc<RefCell<Rule>> {
         match self {
             RulePart::Rule(rule) => Rc::clone(rule),
             RulePart::Token(tk) => panic!("token kind is not a rule: {}", tk.repr_or_name()),
+            RulePart::Predicate(..) => panic!("predicate is not a rule: {}", self.name()),
         }
     }
 
@@ -144,6 +183,7 @@ impl RulePart {
                     .map_or_else(|_| "?".to_string(), |it| it.name.to_string())
             ),
             RulePart::Token(tk) => tk,
+            RulePart::Predicate(..) => panic!("predicate is not a token kind: {}", self.name()),
         }
     }
 
@@ -151,6 +191,7 @@ impl RulePart {
         match self {
             RulePart::Rule(rule) => rule.borrow().name.to_string(),
             RulePart::Token(tk) => tk.upper_name().to_string(),
+            RulePart::Predicate(predicate, part) => format!("{}{}", predicate.repr(), part.name()),
         }
     }
 }
@@ -181,11 +222,17 @@ impl PartialEq for RulePart {
         match self {
             RulePart::Rule(my_rule) => match other {
                 RulePart::Rule(other_rule) => my_rule.borrow().name == other_rule.borrow().name,
-                RulePart::Token(_) => false,
+                _ => false,
             },
             RulePart::Token(my_token_kind) => match other {
-                RulePart::Rule(_) => false,
                 RulePart::Token(other_token_kind) => my_token_kind == other_token_kind,
+                _ => false,
+            },
+            RulePart::Predicate(my_predicate, my_part) => match other {
+                RulePart::Predicate(other_predicate, other_part) => {
+                    my_predicate == other_predicate && my_part == other_part
+                },
+                _ => false,
             },
         }
     }
@@ -211,10 +258,7 @@ pub fn display_of_vec_rule_part(
     .to_string();
 
     for r in rule_parts {
-        display += &match r {
-            RulePart::Rule(rule) => rule.borrow().name.to_string(),
-            RulePart::Token(token_kind) => token_kind.upper_name().to_string(),
-        };
+        display += &r.name();
         display += ", ";
     }
 
@@ -287,7 +331,20 @@ impl Rule {
         self.alternatives.len()
     }
 
-    pub fn validate(&self) -> Result<(), String> {
+    pub fn validate(&self) -> Result<(), GrammarError> {
+        match self.problems().into_iter().next() {
+            Some(problem) => Err(GrammarError::InvalidRules(vec![(
+                self.name().to_string(),
+                problem,
+            )])),
+            None => Ok(()),
+        }
+    }
+
+    // Every check failing, validate() stops at the first.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+
         let set = self
             .alternatives
             .iter()
@@ -311,7 +368,7 @@ impl Rule {
                         .join("-")
                 })
                 .collect::<Vec<_>>();
-            return Err(format!(
+            problems.push(format!(
                 "duplicates: <{}> vs <{}>",
                 list.join(", "),
                 set.iter().cloned().collect::<Vec<_>>().join(", ")
@@ -322,9 +379,9 @@ impl Rule {
         // Such as the rule: some_rule -> some_rule foo bar | some_rule baz quo
         if !self.alternatives.iter().any(|it| {
             // Find any rule that does not start with recursion, if not, error.
-            it.is_empty() || it[0].is_token() || it[0].get_rule().borrow().name != self.name
+            it.is_empty() || !it[0].is_rule() || it[0].get_rule().borrow().name != self.name
         }) {
-            return Err(format!(
+            problems.push(format!(
                 "infinitely recursive rule: all sub-rules recurse to the same rule, self={}",
                 self
             ));
@@ -336,7 +393,7 @@ impl Rule {
             // Find any sub-rule which is single and will recurse to self, if found, error.
             it.len() == 1 && it[0].is_rule() && it[0].get_rule().borrow().name == self.name
         }) {
-            return Err(format!(
+            problems.push(format!(
                 "pointless rule: a singly sub-rule refers to the same rule, self={}",
                 self
             ));
@@ -347,17 +404,17 @@ impl Rule {
             .iter()
             .any(|it| it.len() > 1 && it.contains(&RulePart::Token(TokenKind::Epsilon)))
         {
-            return Err(format!(
+            problems.push(format!(
                 "alternative with len more than 1 contains epsilon, self={}",
                 self
             ));
         }
 
         if self.alternatives.is_empty() {
-            return Err(format!("empty rule, self={}", self));
+            problems.push(format!("empty rule, self={}", self));
         }
 
-        Ok(())
+        problems
     }
 
 
@@ -386,10 +443,7 @@ impl Display for Rule {
             .iter()
             .map(|it| {
                 it.iter()
-                    .map(|it| match it {
-                        RulePart::Rule(rule) => rule.borrow().name.to_string(),
-                        RulePart::Token(tk) => tk.repr_or_name().to_uppercase(),
-                    })
+                    .map(display_of_rule_part)
                     .intersperse(" ".to_string())
                     .collect::<String>()
             })
@@ -399,6 +453,16 @@ impl Display for Rule {
     }
 }
 
+pub fn display_of_rule_part(part: &RulePart) -> String {
+    match part {
+        RulePart::Rule(rule) => rule.borrow().name.to_string(),
+        RulePart::Token(tk) => tk.repr_or_name().to_uppercase(),
+        RulePart::Predicate(predicate, part) => {
+            format!("{}{}", predicate.repr(), display_of_rule_part(part))
+        },
+    }
+}
+
 impl Hash for Rule {
     fn hash<H: Hasher>(
         &self,
@@ -563,6 +627,7 @@ mod tests {
             .validate()
             .err()
             .unwrap()
+            .to_string()
             .starts_with("infinitely recursive rule"),);
     }
 
@@ -590,6 +655,7 @@ mod tests {
             .validate()
             .err()
             .unwrap()
+            .to_string()
             .starts_with("infinitely recursive rule"),);
     }
 
@@ -618,6 +684,7 @@ mod tests {
             .validate()
             .err()
             .unwrap()
+            .to_string()
             .starts_with("pointless rule"),);
     }
 
@@ -5,18 +5,33 @@ use std::fmt::Display;
 use std::fmt::Formatter;
 use std::rc::Rc;
 
+use crate::lang::error::GrammarError;
 use crate::lang::lexer::token::TokenKind;
+use crate::lang::parser::conflicts::explain_conflict;
+use crate::lang::parser::diagnostics::diagnostics_of;
+use crate::lang::parser::diagnostics::Diagnostic;
+use crate::lang::parser::precedence::ExpressionRule;
+use crate::lang::parser::precedence::Precedence;
 use crate::lang::parser::rule::ensure_is_valid_rule_name;
-use crate::lang::parser::rule::AltRef;
+use crate::lang::parser::rule::Predicate;
 use crate::lang::parser::rule::Rule;
 use crate::lang::parser::rule::RulePart;
+use crate::lang::parser::transform::Helper;
+use crate::lang::parser::transform::Pass;
+use crate::lang::parser::transform::TransformLog;
+use crate::lang::parser::transform::Transformed;
 use crate::lang::util::extend;
 
+// The tokens each alternative starts with, by rule name and alternative number.
+pub type StartSet = HashMap<(String, usize), HashSet<TokenKind>>;
+
 pub struct Rules {
     rules: Vec<Rc<RefCell<Rule>>>,
+    precedence: Precedence,
     first_set: RefCell<Option<HashMap<String, HashSet<TokenKind>>>>,
     follow_set: RefCell<Option<HashMap<String, HashSet<TokenKind>>>>,
-    start_set: RefCell<Option<HashMap<AltRef, HashSet<TokenKind>>>>,
+    start_set: RefCell<Option<StartSet>>,
+    nullable_set: RefCell<Option<HashSet<String>>>,
 }
 
 impl Rules {
@@ -27,14 +42,17 @@ impl Rules {
     pub fn from_rules(rules: Vec<Rc<RefCell<Rule>>>) -> Self {
         Self {
             rules,
+            precedence: Precedence::default(),
             first_set: RefCell::new(None),
             follow_set: RefCell::new(None),
             start_set: RefCell::new(None),
+            nullable_set: RefCell::new(None),
         }
     }
 
-    pub fn parse(rules_description: &str) -> Result<Self, String> {
+    pub fn parse(rules_description: &str) -> Result<Self, GrammarError> {
         let mut rules: Vec<Rc<RefCell<Rule>>> = vec![];
+        let mut precedence = Precedence::default();
 
         let mut next_recursion_elimination_num = 0usize;
         let mut num = move || {
@@ -49,14 +67,25 @@ impl Rules {
             .map(str::trim)
             .filter(|it| !it.is_empty())
         {
+            let syntax = |error: String| GrammarError::Syntax {
+                line: line.to_string(),
+                error,
+            };
+
+            if line.starts_with('%') {
+                precedence.declare(line)?;
+                continue;
+            }
+
             let mut name_to_description = line.splitn(2, "->");
 
             let name = {
                 let name = name_to_description
                     .next()
-                    .ok_or_else(|| format!("invalid rule description, missing name: {}", line))?
+                    .ok_or_else(|| format!("invalid rule description, missing name: {}", line))
+                    .map_err(syntax)?
                     .trim();
-                ensure_is_valid_rule_name(name)?;
+                ensure_is_valid_rule_name(name).map_err(syntax)?;
                 name
             };
             let description = {
@@ -64,7 +93,8 @@ impl Rules {
                     .next()
                     .ok_or_else(|| {
                         format!("invalid rule description, missing description: {}", line)
-                    })?
+                    })
+                    .map_err(syntax)?
                     .trim()
             };
 
@@ -90,43 +120,22 @@ impl Rules {
             for alternatives in description.split('|').map(str::trim) {
                 rule.borrow_mut().add_alt();
                 for alt in alternatives.split(' ').map(str::trim) {
-                    match TokenKind::from_repr_including_epsilon(alt)
-                        .or_else(|_| TokenKind::from_name(alt))
-                    {
-                        Ok(token_kind) => {
-                            // It's a token, add it as a token.
-                            rule.borrow_mut().push_last(token_kind.into());
-                        },
-                        Err(_) => {
-                            // It's a rule.
-                            if !alt.is_empty() {
-                                ensure_is_valid_rule_name(alt)?;
-                            }
-                            let to_add = match rules.iter().find(|it| it.borrow().name() == alt) {
-                                None => {
-                                    // No rule already created for this name, create new
-                                    let new: Rule = Rule::new(alt.to_string(), num());
-                                    let new: Rc<RefCell<Rule>> = new.into();
-                                    rules.push(Rc::clone(&new));
-                                    new
-                                },
-                                Some(already) => {
-                                    // A rule already for this name exists, reuse it.
-                                    Rc::clone(already)
-                                },
-                            };
-                            rule.borrow_mut().push_last(to_add.into());
-                        },
-                    }
+                    let to_add = parse_rule_part(alt, &mut rules, &mut num).map_err(syntax)?;
+                    rule.borrow_mut().push_last(to_add);
                 }
             }
 
             if name_to_description.next().is_some() {
-                return Err(format!("invalid count of rule parts for rule: {}", line));
+                return Err(syntax(format!(
+                    "invalid count of rule parts for rule: {}",
+                    line
+                )));
             }
         }
 
-        Ok(Self::from_rules(rules))
+        let mut rules = Self::from_rules(rules);
+        rules.precedence = precedence;
+        Ok(rules)
     }
 
 
@@ -147,23 +156,98 @@ impl Rules {
         &self.rules
     }
 
+    // What a parse tree grows from: the start rule, or EPSILON when there is none, so that a
+    // parser has a root to report validate()'s NoRules with.
+    pub fn start_part(&self) -> RulePart {
+        match self.rules.first() {
+            Some(start) => start.into(),
+            None => TokenKind::Epsilon.into(),
+        }
+    }
+
+    pub fn precedence(&self) -> &Precedence {
+        &self.precedence
+    }
+
+    pub(super) fn set_precedence(
+        &mut self,
+        precedence: Precedence,
+    ) {
+        self.precedence = precedence;
+    }
+
+    // Rules parsed by precedence climbing, left out of left recursion elimination and left
+    // factoring.
+    pub fn expression_rules(&self) -> HashMap<String, ExpressionRule> {
+        self.rules
+            .iter()
+            .filter_map(|rule| {
+                self.precedence
+                    .expression_rule(&rule.borrow())
+                    .map(|it| (rule.borrow().name().to_string(), it))
+            })
+            .collect()
+    }
+
+
+    pub fn validate(&self) -> Result<(), GrammarError> {
+        self.validate_peg()?;
+
+        // Predicates, only the packrat parser understands them.
+        {
+            let with_predicates = self
+                .rules
+                .iter()
+                .filter(|it| {
+                    it.borrow()
+                        .alternatives
+                        .iter()
+                        .any(|alt| alt.iter().any(RulePart::is_predicate))
+                })
+                .map(|it| it.borrow().name().to_string())
+                .collect::<Vec<_>>();
+            if !with_predicates.is_empty() {
+                return Err(GrammarError::Predicates(with_predicates));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Same as validate() but predicates are allowed.
+    pub fn validate_peg(&self) -> Result<(), GrammarError> {
+        match self.grammar_errors().into_iter().next() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    // Every error validate_peg() finds, it stops at the first.
+    fn grammar_errors(&self) -> Vec<GrammarError> {
+        if self.rules.is_empty() {
+            return vec![GrammarError::NoRules];
+        }
+
+        let mut errors = vec![];
 
-    pub fn validate(&self) -> Result<(), String> {
         // Sub-rule error.
         {
             let mut sub_rule_errors = vec![];
             for r in &self.rules {
-                if let Err(err) = r.borrow().validate() {
-                    sub_rule_errors.push(format!(
-                        "invalid rule, rule_name={} error={}, rule={}",
-                        r.borrow().name(),
-                        err,
-                        r.borrow(),
+                for err in r.borrow().problems() {
+                    sub_rule_errors.push((
+                        r.borrow().name().to_string(),
+                        format!(
+                            "invalid rule, rule_name={} error={}, rule={}",
+                            r.borrow().name(),
+                            err,
+                            r.borrow(),
+                        ),
                     ));
                 }
             }
             if !sub_rule_errors.is_empty() {
-                return Err(sub_rule_errors.join(" | "));
+                errors.push(GrammarError::InvalidRules(sub_rule_errors));
             }
         }
 
@@ -177,7 +261,7 @@ impl Rules {
                 }
             }
             if !duplicate_rules.is_empty() {
-                return Err(format!("duplicate rules: {}", duplicate_rules.join(", ")));
+                errors.push(GrammarError::DuplicateRules(duplicate_rules));
             }
         }
 
@@ -185,19 +269,14 @@ impl Rules {
         {
             let numbers = get_sorted_recursion_elimination_numbers(self);
             let mut duplicate_numbers = vec![];
-            for i in 0..numbers.len() - 1 {
-                if numbers[i] == numbers[i + 1] && !duplicate_numbers.contains(&i) {
-                    duplicate_numbers.push(i);
+            for i in 1..numbers.len() {
+                if numbers[i - 1] == numbers[i] && !duplicate_numbers.contains(&(i - 1)) {
+                    duplicate_numbers.push(i - 1);
                 }
             }
             if !duplicate_numbers.is_empty() {
-                return Err(format!(
-                    "duplicate recursion elimination rule: {}",
-                    duplicate_numbers
-                        .into_iter()
-                        .map(|it| it.to_string())
-                        .collect::<Vec<_>>()
-                        .join(", ")
+                errors.push(GrammarError::DuplicateRecursionEliminationNums(
+                    duplicate_numbers,
                 ));
             }
         }
@@ -215,7 +294,7 @@ impl Rules {
                 }
 
                 for alt in &r.borrow().alternatives {
-                    for part in alt {
+                    for part in alt.iter().map(RulePart::predicate_target) {
                         if part.is_rule() {
                             if !rules.has_rule(part.get_rule().borrow().name()) {
                                 missing.insert(part.name());
@@ -231,10 +310,9 @@ impl Rules {
                 find_missing_rule(self, r, &mut seen, &mut missing);
             }
             if !missing.is_empty() {
-                return Err(format!(
-                    "missing rules: {}",
-                    missing.into_iter().collect::<Vec<_>>().join(", ")
-                ));
+                let mut missing = missing.into_iter().collect::<Vec<_>>();
+                missing.sort();
+                errors.push(GrammarError::MissingRules(missing));
             }
         }
 
@@ -247,10 +325,7 @@ impl Rules {
                 }
             }
             if !no_alternative.is_empty() {
-                return Err(format!(
-                    "rules have no alternative: {}",
-                    no_alternative.join(", "),
-                ));
+                errors.push(GrammarError::NoAlternative(no_alternative));
             }
         }
 
@@ -265,43 +340,46 @@ impl Rules {
                 }
             }
             if !empty_alternative.is_empty() {
-                return Err(format!(
-                    "rules have empty alternative: {}",
-                    empty_alternative.join(", "),
-                ));
+                errors.push(GrammarError::EmptyAlternative(empty_alternative));
+            }
+        }
+
+        // Left recursion in an expression rule is only allowed through a declared operator.
+        {
+            let mut expression_rules = self.expression_rules().into_iter().collect::<Vec<_>>();
+            expression_rules.sort_by(|a, b| a.0.cmp(&b.0));
+            for (name, expression) in expression_rules {
+                let rule = self.get_rule_by_name(&name);
+                for (alt_no, alt) in rule.borrow().alternatives.iter().enumerate() {
+                    if !expression.is_operator_alt(alt_no)
+                        && alt
+                            .first()
+                            .is_some_and(|it| it.is_rule() && it.name() == name)
+                    {
+                        errors.push(GrammarError::LeftRecursiveExpression {
+                            rule: name.clone(),
+                            alt_no,
+                        });
+                    }
+                }
             }
         }
 
         // Start rule is S
         {
-            if self.rules[0].borrow().name() != "S" {
-                return Err(format!(
-                    "expecting the first rule to be `S` as start rule, but got: {}",
-                    self.rules[0].borrow().name(),
-                ));
+            if let Some(start) = self.rules.first() {
+                if start.borrow().name() != "S" {
+                    errors.push(GrammarError::StartRule(start.borrow().name().to_string()));
+                }
             }
         }
 
-        Ok(())
+        errors
     }
 
-    pub fn get_error(&self) -> Option<String> {
-        let invalid = self.rules.iter().find(|it| it.borrow().validate().is_err());
-        if invalid.is_some() {
-            return Some(format!(
-                "invalid rule: {}",
-                invalid.unwrap().borrow().name()
-            ));
-        }
-
-        let mut seen = HashSet::new();
-        for i in &self.rules {
-            if seen.contains(i.borrow().name()) {
-                return Some(format!("duplicate rule: {}", i.borrow().name()));
-            }
-            seen.insert(i.borrow().name().to_string());
-        }
-        None
+    // Every error and the warnings, such as unreachable rules, in one go.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        diagnostics_of(self, &self.grammar_errors())
     }
 
     pub fn has_rule(
@@ -311,33 +389,52 @@ impl Rules {
         self.rules.iter().any(|it| it.borrow().name() == name)
     }
 
-    pub fn is_backtrack_free(&self) -> Result<(), String> {
+    pub fn is_backtrack_free(&self) -> Result<(), GrammarError> {
         let start = self.start_set();
+        let expression_rules = self.expression_rules();
 
         for r in &self.rules {
             if r.borrow().alternatives.len() < 2 {
                 continue;
             }
 
+            // Operator alternatives are chosen by the operator following an operand.
+            let is_operator_alt = |alt_no: usize| {
+                expression_rules
+                    .get(r.borrow().name())
+                    .is_some_and(|it| it.is_operator_alt(alt_no))
+            };
+
             let alt_starts: HashMap<_, _> = (0..r.borrow().alternatives.len())
-                .map(|alt_no| AltRef::new(alt_no, r))
-                .map(|alt_ref| (alt_ref.alt_no(), start[&alt_ref].clone()))
+                .map(|alt_no| {
+                    (
+                        alt_no,
+                        start[&(r.borrow().name().to_string(), alt_no)].clone(),
+                    )
+                })
                 .collect();
 
             for i in 1..r.borrow().alternatives.len() {
                 for j in 0..i {
+                    if is_operator_alt(i) || is_operator_alt(j) {
+                        continue;
+                    }
                     let set0 = &alt_starts[&i];
                     let set1 = &alt_starts[&j];
                     if set0.intersection(set1).count() > 0 {
-                        return Err(format!(
-                                "grammar is not backtrack free, alts intersect, rule={} i={}, j={} => {:?} <vs> {:?}, intersection={:?}",
-                                r.borrow().name(),
-                                i,
-                                j,
-                                set0,
-                                set1,
-                                set0.intersection(set1),
-                        ));
+                        let explanations = explain_conflict(self, r.borrow().name(), j, i)
+                            .iter()
+                            .map(|it| it.to_string())
+                            .collect::<Vec<_>>()
+                            .join("\n");
+                        let mut intersection = set0.intersection(set1).cloned().collect::<Vec<_>>();
+                        intersection.sort();
+                        return Err(GrammarError::NotBacktrackFree {
+                            rule: r.borrow().name().to_string(),
+                            alt_nos: (j, i),
+                            intersection,
+                            explanation: explanations,
+                        });
                     }
                 }
             }
@@ -352,9 +449,13 @@ impl Rules {
         *self.first_set.borrow_mut() = None;
         *self.follow_set.borrow_mut() = None;
         *self.start_set.borrow_mut() = None;
+        *self.nullable_set.borrow_mut() = None;
     }
 
-    fn put_epsilon_last(&mut self) {
+    fn put_epsilon_last(
+        &mut self,
+        log: &mut TransformLog,
+    ) {
         for r in &self.rules {
             let len = r.borrow().alternatives.len();
             if len > 1 {
@@ -370,6 +471,7 @@ impl Rules {
                 if let Some(epsilon_index) = epsilon_index {
                     if epsilon_index != len - 1 {
                         rule.alternatives.swap(epsilon_index, len - 1);
+                        log.swap_alts(rule.name(), epsilon_index, len - 1);
                     }
                 }
             }
@@ -378,13 +480,13 @@ impl Rules {
 
     // =========================================================================
 
-    fn max_recursion_elimination_num(&self) -> usize {
+    pub(super) fn max_recursion_elimination_num(&self) -> usize {
         get_sorted_recursion_elimination_numbers(self)
             .last()
             .map_or(0, |it| *it)
     }
 
-    fn find_new_indexed_name(
+    pub(super) fn find_new_indexed_name(
         &self,
         name: &str,
     ) -> String {
@@ -398,7 +500,10 @@ impl Rules {
         panic!("indexes exhausted for: {}", name);
     }
 
-    fn eliminate_direct_left_recursions0(&mut self) -> bool {
+    fn eliminate_direct_left_recursions0(
+        &mut self,
+        log: &mut TransformLog,
+    ) -> bool {
         self.clear_cache();
 
         let mut next = self.max_recursion_elimination_num() + 1;
@@ -409,12 +514,14 @@ impl Rules {
         };
 
         let mut any_change = false;
+        let expression_rules = self.expression_rules();
 
         loop {
             let mut new_rule_to_add: Option<Rc<RefCell<Rule>>> = None;
 
             for rule in &self.rules {
-                let has_any_recursive_sub_rule = has_recursive_rule(&rule.borrow());
+                let has_any_recursive_sub_rule = has_recursive_rule(&rule.borrow())
+                    && !expression_rules.contains_key(rule.borrow().name());
                 any_change = has_any_recursive_sub_rule;
 
                 let name = if has_any_recursive_sub_rule {
@@ -433,34 +540,46 @@ impl Rules {
                         new_rule
                     };
 
-                    let recursive_rules: Vec<Vec<RulePart>> = {
-                        let partition_index = rule
-                            .borrow_mut()
-                            .alternatives
-                            .iter_mut()
-                            .partition_in_place(|it| {
-                                !it.is_empty()
-                                    && it[0].is_rule()
-                                    // Risky bet: if it's borrowed, it's ourselves!
-                                    && it[0].get_rule().try_borrow().map_or(true, |it| {
-                                    it.name() == name.as_ref().unwrap()
-                                })
-                            });
-                        rule.borrow_mut()
-                            .alternatives
+                    let (recursive_rules, mut recursive_origins): (Vec<Vec<RulePart>>, Vec<_>) = {
+                        let name = name.as_ref().unwrap();
+                        let alternatives = std::mem::take(&mut rule.borrow_mut().alternatives);
+                        let mut tagged: Vec<_> =
+                            alternatives.into_iter().zip(log.take_alts(name)).collect();
+                        let partition_index = tagged.iter_mut().partition_in_place(|(it, _)| {
+                            !it.is_empty()
+                                && it[0].is_rule()
+                                // Risky bet: if it's borrowed, it's ourselves!
+                                && it[0]
+                                    .get_rule()
+                                    .try_borrow()
+                                    .map_or(true, |it| it.name() == name)
+                        });
+                        let recursive_rules = tagged
                             .drain(0..partition_index)
-                            .map(|mut it| {
+                            .map(|(mut it, origin)| {
                                 it.remove(0);
                                 it.push(RulePart::Rule(Rc::clone(&new_rule)));
-                                it
+                                (it, origin)
                             })
-                            .collect()
+                            .unzip();
+
+                        let (remaining, remaining_origins) = tagged.into_iter().unzip();
+                        rule.borrow_mut().alternatives = remaining;
+                        log.set_alts(name, remaining_origins);
+                        recursive_rules
                     };
 
                     new_rule.borrow_mut().alternatives = recursive_rules;
                     // epsilon rule.
                     new_rule.borrow_mut().add_alt();
                     new_rule.borrow_mut().push_last(TokenKind::Epsilon.into());
+                    recursive_origins.push(vec![]);
+                    log.generate(
+                        new_rule.borrow().name(),
+                        name.as_ref().unwrap(),
+                        Helper::LeftRecursion,
+                        recursive_origins,
+                    );
 
                     for remaining_rule in &mut rule.borrow_mut().alternatives {
                         remaining_rule.push(RulePart::Rule(Rc::clone(&new_rule)))
@@ -489,10 +608,13 @@ impl Rules {
         any_change
     }
 
-    fn eliminate_direct_left_recursions(&mut self) {
+    fn eliminate_direct_left_recursions(
+        &mut self,
+        log: &mut TransformLog,
+    ) {
         self.clear_cache();
 
-        while self.eliminate_direct_left_recursions0() {}
+        while self.eliminate_direct_left_recursions0(log) {}
     }
 
     // ---------------------------------
@@ -511,11 +633,21 @@ impl Rules {
     }
 
     fn find_i_and_s(&mut self) -> Option<(usize, usize, usize)> {
+        let expression_rules = self.expression_rules();
+        let is_expression =
+            |rule: &Rc<RefCell<Rule>>| expression_rules.contains_key(rule.borrow().name());
+
         for i in 1..=self.max_recursion_elimination_num() {
             if let Some(rule_i) = self.try_find_rule_by_recursion_num(i) {
+                if is_expression(&rule_i) {
+                    continue;
+                }
                 for s in 0..i {
                     assert_ne!(s, i);
                     if let Some(rule_s) = self.try_find_rule_by_recursion_num(s) {
+                        if is_expression(&rule_s) {
+                            continue;
+                        }
                         for (rule_i_alt_num, rule_i_alt) in
                             rule_i.borrow().alternatives.iter().enumerate()
                         {
@@ -543,10 +675,13 @@ impl Rules {
             .unwrap_or_else(|| panic!("no rule with recursion num: {}", recursion_num))
     }
 
-    fn eliminate_indirect_left_recursions(&mut self) -> bool {
+    fn eliminate_indirect_left_recursions(
+        &mut self,
+        log: &mut TransformLog,
+    ) -> bool {
         self.clear_cache();
 
-        self.eliminate_direct_left_recursions();
+        self.eliminate_direct_left_recursions(log);
 
         let mut any_change = false;
 
@@ -555,15 +690,20 @@ impl Rules {
             let rule_i: Rc<RefCell<Rule>> = self.find_rule_by_recursion_num(i);
 
             let mut rule_i_alt = rule_i.borrow_mut().alternatives.remove(i_alt_index);
+            let rule_i_origin = log.remove_alt(rule_i.borrow().name(), i_alt_index);
 
             assert_eq!(rule_i_alt.remove(0).name(), rule_s.borrow().name());
 
-            for s_alt in &rule_s.borrow().alternatives {
+            for (s_alt_no, s_alt) in rule_s.borrow().alternatives.iter().enumerate() {
                 let mut fix: Vec<RulePart> = s_alt.clone();
                 fix.append(&mut rule_i_alt.clone());
 
                 if fix.len() != 1 || !fix[0].is_epsilon() || !rule_i.borrow().has_epsilon() {
                     rule_i.borrow_mut().alternatives.push(fix);
+
+                    let mut origin = log.origin_of_alt(rule_s.borrow().name(), s_alt_no).clone();
+                    origin.extend(rule_i_origin.clone());
+                    log.push_alt(rule_i.borrow().name(), origin);
                 }
             }
 
@@ -571,7 +711,7 @@ impl Rules {
         }
 
         if any_change {
-            self.put_epsilon_last();
+            self.put_epsilon_last(log);
         }
 
         if let Err(err) = self.validate() {
@@ -585,13 +725,21 @@ impl Rules {
     }
 
     pub fn eliminate_left_recursions(&mut self) {
+        let mut log = TransformLog::new(self);
+        self.eliminate_left_recursions_with(&mut log);
+    }
+
+    fn eliminate_left_recursions_with(
+        &mut self,
+        log: &mut TransformLog,
+    ) {
         self.clear_cache();
 
         if let Err(err) = self.validate() {
             panic!("rules are not valid: {}", err);
         }
 
-        while self.eliminate_indirect_left_recursions() {}
+        while self.eliminate_indirect_left_recursions(log) {}
 
         if let Err(err) = self.validate() {
             panic!("rules are not valid: {}", err);
@@ -675,6 +823,19 @@ impl Rules {
     }
 
 
+    // Sets computed earlier, loaded along with the rules, so that they are not computed again.
+    pub(crate) fn prime_sets(
+        &self,
+        first: HashMap<String, HashSet<TokenKind>>,
+        follow: HashMap<String, HashSet<TokenKind>>,
+        start: StartSet,
+    ) {
+        self.first_set.replace(Some(first));
+        self.follow_set.replace(Some(follow));
+        self.start_set.replace(Some(start));
+    }
+
+
     pub fn follow_set(&self) -> HashMap<String, HashSet<TokenKind>> {
         if self.follow_set.borrow().is_none() {
             let calc = self.follow_set0();
@@ -741,7 +902,7 @@ impl Rules {
     }
 
 
-    pub fn start_set(&self) -> HashMap<AltRef, HashSet<TokenKind>> {
+    pub fn start_set(&self) -> StartSet {
         if self.start_set.borrow().is_none() {
             let calc = self.start_set0();
             self.start_set.replace(Some(calc));
@@ -750,7 +911,7 @@ impl Rules {
         return self.start_set.borrow().as_ref().unwrap().clone();
     }
 
-    fn start_set0(&self) -> HashMap<AltRef, HashSet<TokenKind>> {
+    fn start_set0(&self) -> StartSet {
         if let Err(err) = self.validate() {
             panic!("invalid rule: {}", err);
         }
@@ -758,32 +919,478 @@ impl Rules {
         let first = self.first_set();
         let follow = self.follow_set();
 
-        let mut start: HashMap<AltRef, HashSet<TokenKind>> = HashMap::new();
+        let mut start: StartSet = HashMap::new();
 
         for rule in &self.rules {
             for (alt_no, alt) in rule.borrow().alternatives.iter().enumerate() {
-                let mut alt_0_first = first[&alt[0].name()].clone();
+                let mut alt_start = first_of_parts(alt, &first);
+                if alt_start.remove(&TokenKind::Epsilon) {
+                    extend(&mut alt_start, follow[rule.borrow().name()].clone());
+                }
+
+                start.insert((rule.borrow().name().to_string(), alt_no), alt_start);
+            }
+        }
 
-                let rule_start = if alt_0_first.contains(&TokenKind::Epsilon) {
-                    alt_0_first.remove(&TokenKind::Epsilon);
-                    extend(&mut alt_0_first, follow[rule.borrow().name()].clone());
-                    alt_0_first
+        start
+    }
+
+
+    // start_set() with EOF, which follow_set() leaves out, in the start of the nullable
+    // alternatives of the rules the input may end after.
+    pub fn start_set_with_eof(&self) -> StartSet {
+        let first = self.first_set();
+        let ending = self.ending_rules();
+        let mut start = self.start_set();
+
+        for rule in &self.rules {
+            if !ending.contains(rule.borrow().name()) {
+                continue;
+            }
+            for (alt_no, alt) in rule.borrow().alternatives.iter().enumerate() {
+                if first_of_parts(alt, &first).contains(&TokenKind::Epsilon) {
+                    start
+                        .get_mut(&(rule.borrow().name().to_string(), alt_no))
+                        .unwrap()
+                        .insert(TokenKind::Eof);
                 }
-                else {
-                    alt_0_first
-                };
+            }
+        }
 
-                start.insert(AltRef::new(alt_no, rule), rule_start);
+        start
+    }
+
+    // follow_set() with EOF in the follow of the rules the input may end after.
+    pub fn follow_set_with_eof(&self) -> HashMap<String, HashSet<TokenKind>> {
+        let ending = self.ending_rules();
+        let mut follow = self.follow_set();
+
+        for (rule, follow) in &mut follow {
+            if ending.contains(rule) {
+                follow.insert(TokenKind::Eof);
+            }
+        }
+
+        follow
+    }
+
+    // The start rule, and the rules ending an alternative of one of them, but for nullable parts
+    // after.
+    fn ending_rules(&self) -> HashSet<String> {
+        let nullable = self.nullable_set();
+        let mut ending: HashSet<String> = self
+            .rules
+            .first()
+            .map(|it| it.borrow().name().to_string())
+            .into_iter()
+            .collect();
+
+        let mut any_change = true;
+        while any_change {
+            any_change = false;
+
+            for rule in &self.rules {
+                if !ending.contains(rule.borrow().name()) {
+                    continue;
+                }
+
+                for alt in &rule.borrow().alternatives {
+                    for part in alt.iter().rev() {
+                        match part {
+                            RulePart::Token(tk) if tk.is_epsilon() => continue,
+                            RulePart::Rule(_) => {
+                                any_change |= ending.insert(part.name());
+                                if !nullable.contains(&part.name()) {
+                                    break;
+                                }
+                            },
+                            _ => break,
+                        }
+                    }
+                }
+            }
+        }
+
+        ending
+    }
+
+
+    pub fn nullable_set(&self) -> HashSet<String> {
+        if self.nullable_set.borrow().is_none() {
+            let calc = self.nullable_set0();
+            self.nullable_set.replace(Some(calc));
+        }
+
+        self.nullable_set.borrow().as_ref().unwrap().clone()
+    }
+
+    fn nullable_set0(&self) -> HashSet<String> {
+        let mut nullable: HashSet<String> = HashSet::new();
+
+        loop {
+            let mut any_change = false;
+
+            for rule in &self.rules {
+                if nullable.contains(rule.borrow().name()) {
+                    continue;
+                }
+
+                let is_nullable = rule.borrow().alternatives.iter().any(|alt| {
+                    alt.iter().all(|part| match part {
+                        RulePart::Token(tk) => tk.is_epsilon(),
+                        RulePart::Rule(_) => nullable.contains(&part.name()),
+                        RulePart::Predicate(..) => true,
+                    })
+                });
+
+                if is_nullable {
+                    nullable.insert(rule.borrow().name().to_string());
+                    any_change = true;
+                }
+            }
+
+            if !any_change {
+                break;
+            }
+        }
+
+        nullable
+    }
+
+    // =========================================================================
+
+    // FIRST_k: the token strings of length k a rule can start with, or shorter if the rule
+    // derives less than k tokens.
+    pub fn first_k_set(
+        &self,
+        k: usize,
+    ) -> HashMap<String, HashSet<Vec<TokenKind>>> {
+        if let Err(err) = self.validate() {
+            panic!("invalid rule: {}", err);
+        }
+
+        let mut first: HashMap<String, HashSet<Vec<TokenKind>>> = self
+            .rules
+            .iter()
+            .map(|it| (it.borrow().name().to_string(), HashSet::new()))
+            .collect();
+
+        loop {
+            let mut any_change = false;
+
+            for rule in &self.rules {
+                for alt in &rule.borrow().alternatives {
+                    let alt_first = first_k_of_parts(alt, &first, k);
+                    let rule_first = first.get_mut(rule.borrow().name()).unwrap();
+                    any_change = extend(rule_first, alt_first) || any_change;
+                }
+            }
+
+            if !any_change {
+                break;
+            }
+        }
+
+        first
+    }
+
+    // FOLLOW_k: the token strings of length k that may come after a rule, or shorter if they end
+    // with EOF.
+    pub fn follow_k_set(
+        &self,
+        k: usize,
+    ) -> HashMap<String, HashSet<Vec<TokenKind>>> {
+        let first = self.first_k_set(k);
+
+        let mut follow: HashMap<String, HashSet<Vec<TokenKind>>> = self
+            .rules
+            .iter()
+            .map(|it| (it.borrow().name().to_string(), HashSet::new()))
+            .collect();
+
+        // Unlike in follow_set(), where it is left out, the input may end after the start rule.
+        if let Some(start) = self.rules.first() {
+            follow
+                .get_mut(start.borrow().name())
+                .unwrap()
+                .insert(vec![TokenKind::Eof]);
+        }
+
+        loop {
+            let mut any_change = false;
+
+            for rule in &self.rules {
+                for alt in &rule.borrow().alternatives {
+                    for (i, part) in alt.iter().enumerate() {
+                        if !part.is_rule() {
+                            continue;
+                        }
+
+                        let trailer = concat_k(
+                            &first_k_of_parts(&alt[i + 1..], &first, k),
+                            &follow[rule.borrow().name()],
+                            k,
+                        );
+                        let part_follow = follow.get_mut(&part.name()).unwrap();
+                        any_change = extend(part_follow, trailer) || any_change;
+                    }
+                }
+            }
+
+            if !any_change {
+                break;
+            }
+        }
+
+        follow
+    }
+
+    pub fn start_k_set(
+        &self,
+        k: usize,
+    ) -> HashMap<(String, usize), HashSet<Vec<TokenKind>>> {
+        let first = self.first_k_set(k);
+        let follow = self.follow_k_set(k);
+
+        let mut start = HashMap::new();
+        for rule in &self.rules {
+            for (alt_no, alt) in rule.borrow().alternatives.iter().enumerate() {
+                let alt_start = concat_k(
+                    &first_k_of_parts(alt, &first, k),
+                    &follow[rule.borrow().name()],
+                    k,
+                );
+                start.insert((rule.borrow().name().to_string(), alt_no), alt_start);
             }
         }
 
         start
     }
 
+    // Strong LL(k): the alternatives of each rule are told apart by the next k tokens.
+    pub fn is_ll_k(
+        &self,
+        k: usize,
+    ) -> Result<(), GrammarError> {
+        if k == 0 {
+            return Err(GrammarError::ZeroLookahead);
+        }
+
+        let start = self.start_k_set(k);
+        let expression_rules = self.expression_rules();
+
+        for r in &self.rules {
+            let is_operator_alt = |alt_no: usize| {
+                expression_rules
+                    .get(r.borrow().name())
+                    .is_some_and(|it| it.is_operator_alt(alt_no))
+            };
+
+            for i in 1..r.borrow().alternatives.len() {
+                for j in 0..i {
+                    if is_operator_alt(i) || is_operator_alt(j) {
+                        continue;
+                    }
+
+                    let set0 = &start[&(r.borrow().name().to_string(), i)];
+                    let set1 = &start[&(r.borrow().name().to_string(), j)];
+                    let mut intersection = set0
+                        .intersection(set1)
+                        .map(|it| {
+                            it.iter()
+                                .map(|tk| tk.repr_or_name())
+                                .collect::<Vec<_>>()
+                                .join(" ")
+                        })
+                        .collect::<Vec<_>>();
+                    if !intersection.is_empty() {
+                        intersection.sort();
+                        return Err(GrammarError::NotLlK {
+                            k,
+                            rule: r.borrow().name().to_string(),
+                            alt_nos: (j, i),
+                            intersection,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+
+    // Rules which can not be reached from the start rule, in declaration order.
+    pub fn unreachable_rules(&self) -> Vec<String> {
+        let mut reachable: HashSet<String> = HashSet::new();
+
+        if let Some(start) = self.rules.first() {
+            let mut queue: Vec<Rc<RefCell<Rule>>> = vec![Rc::clone(start)];
+            reachable.insert(start.borrow().name().to_string());
+
+            while let Some(rule) = queue.pop() {
+                for alt in &rule.borrow().alternatives {
+                    // The rule a predicate looks ahead with is reachable too.
+                    let parts = alt.iter().map(RulePart::predicate_target);
+                    for part in parts.filter(|it| it.is_rule()) {
+                        if reachable.insert(part.name()) {
+                            queue.push(part.get_rule());
+                        }
+                    }
+                }
+            }
+        }
+
+        self.rules
+            .iter()
+            .map(|it| it.borrow().name().to_string())
+            .filter(|it| !reachable.contains(it))
+            .collect()
+    }
+
+    // Rules which can never derive a string of terminals, in declaration order.
+    // Such as: a -> b ID, b -> a INT
+    pub fn non_productive_rules(&self) -> Vec<String> {
+        let productive = self.productive_rules();
+
+        self.rules
+            .iter()
+            .map(|it| it.borrow().name().to_string())
+            .filter(|it| !productive.contains(it))
+            .collect()
+    }
+
+    fn productive_rules(&self) -> HashSet<String> {
+        let mut productive: HashSet<String> = HashSet::new();
+
+        loop {
+            let mut any_change = false;
+
+            for rule in &self.rules {
+                if productive.contains(rule.borrow().name()) {
+                    continue;
+                }
+
+                // A predicate consumes no input, as EPSILON.
+                let is_productive = rule.borrow().alternatives.iter().any(|alt| {
+                    alt.iter().all(|part| {
+                        part.is_token() || part.is_predicate() || productive.contains(&part.name())
+                    })
+                });
+
+                if is_productive {
+                    productive.insert(rule.borrow().name().to_string());
+                    any_change = true;
+                }
+            }
+
+            if !any_change {
+                break;
+            }
+        }
+
+        productive
+    }
+
+    pub fn is_free_of_useless_rules(&self) -> Result<(), GrammarError> {
+        let non_productive = self.non_productive_rules();
+        let unreachable = self.unreachable_rules();
+
+        if non_productive.is_empty() && unreachable.is_empty() {
+            Ok(())
+        }
+        else {
+            Err(GrammarError::UselessRules {
+                non_productive,
+                unreachable,
+            })
+        }
+    }
+
+    // Non-productive rules go first (along with every alternative referring to them), only then
+    // unreachable rules are known.
+    pub fn eliminate_useless_rules(&mut self) -> Result<bool, GrammarError> {
+        let mut log = TransformLog::new(self);
+        self.eliminate_useless_rules_with(&mut log)
+    }
+
+    fn eliminate_useless_rules_with(
+        &mut self,
+        log: &mut TransformLog,
+    ) -> Result<bool, GrammarError> {
+        self.clear_cache();
+
+        let non_productive: HashSet<String> = self.non_productive_rules().into_iter().collect();
+
+        if let Some(start) = self.rules.first() {
+            if non_productive.contains(start.borrow().name()) {
+                return Err(GrammarError::NonProductiveStartRule(
+                    start.borrow().name().to_string(),
+                ));
+            }
+        }
+
+        for rule in &self.rules {
+            if non_productive.contains(rule.borrow().name()) {
+                continue;
+            }
+            let keep = rule
+                .borrow()
+                .alternatives
+                .iter()
+                .map(|alt| {
+                    !alt.iter()
+                        .map(RulePart::predicate_target)
+                        .any(|part| part.is_rule() && non_productive.contains(&part.name()))
+                })
+                .collect::<Vec<_>>();
+            let mut keep_alt = keep.iter();
+            rule.borrow_mut()
+                .alternatives
+                .retain(|_| *keep_alt.next().unwrap());
+            log.retain_alts(rule.borrow().name(), &keep);
+        }
+        let any_non_productive = self.remove_rules(&non_productive, log);
+
+        let unreachable: HashSet<String> = self.unreachable_rules().into_iter().collect();
+        let any_unreachable = self.remove_rules(&unreachable, log);
+
+        Ok(any_non_productive || any_unreachable)
+    }
+
+    fn remove_rules(
+        &mut self,
+        names: &HashSet<String>,
+        log: &mut TransformLog,
+    ) -> bool {
+        if names.is_empty() {
+            return false;
+        }
+
+        let (removed, kept): (Vec<_>, Vec<_>) = self
+            .rules
+            .drain(..)
+            .partition(|it| names.contains(it.borrow().name()));
+
+        // Break the reference cycles among the removed rules.
+        for r in &removed {
+            r.borrow_mut().alternatives.clear();
+            log.remove_rule(r.borrow().name());
+        }
+
+        self.rules = kept;
+        self.clear_cache();
+
+        !removed.is_empty()
+    }
+
     // =========================================================================
 
     // Why this implementation? because it's late and I'm tired.
-    fn eliminate_left_common_prefix(&mut self) -> bool {
+    fn eliminate_left_common_prefix(
+        &mut self,
+        log: &mut TransformLog,
+    ) -> bool {
         fn cmp_prefix(
             alt0: &Vec<RulePart>,
             alt1: &Vec<RulePart>,
@@ -802,12 +1409,16 @@ impl Rules {
 
         self.clear_cache();
 
+        let expression_rules = self.expression_rules();
+
         let mut new_rule_to_add: Option<Rc<RefCell<Rule>>> = None;
         'exit: for rule in &self.rules {
-            if rule.borrow().alternatives.len() < 2 {
+            if rule.borrow().alternatives.len() < 2
+                || expression_rules.contains_key(rule.borrow().name())
+            {
                 continue;
             }
-            println!("at rule: {}", rule.borrow().name());
+            let rule_name = rule.borrow().name().to_string();
 
             let mut prefix_len: Option<usize> = None;
             let mut alt_index: Option<usize> = None;
@@ -844,6 +1455,9 @@ impl Rules {
                         new_rule
                     };
 
+                    let origin = log.remove_alt(&rule_name, alt_index.unwrap());
+                    let mut new_rule_origins = vec![origin.clone()];
+
                     let (common_prefix, suffix) = {
                         let mut work_alt =
                             rule.borrow_mut().alternatives.remove(alt_index.unwrap());
@@ -864,6 +1478,7 @@ impl Rules {
                     rule.borrow_mut()
                         .alternatives
                         .insert(alt_index.unwrap(), replace);
+                    log.insert_alt(&rule_name, alt_index.unwrap(), origin);
 
                     loop {
                         let mut index: Option<usize> = None;
@@ -889,10 +1504,20 @@ impl Rules {
                                 for s in suffix {
                                     new_rule.borrow_mut().push_last(s);
                                 }
+
+                                let origin = log.remove_alt(&rule_name, index);
+                                log.extend_alt(&rule_name, alt_index.unwrap(), origin.clone());
+                                new_rule_origins.push(origin);
                             },
                         }
                     }
 
+                    log.generate(
+                        new_rule.borrow().name(),
+                        &rule_name,
+                        Helper::CommonPrefix,
+                        new_rule_origins,
+                    );
                     new_rule_to_add = Some(new_rule);
                     break 'exit;
                 },
@@ -919,10 +1544,9 @@ impl Rules {
                     )
                 }
                 else if has_epsilon && !empty_indexes.is_empty() {
-                    new_rule
-                        .borrow_mut()
-                        .alternatives
-                        .remove(empty_indexes.pop().unwrap());
+                    let empty_index = empty_indexes.pop().unwrap();
+                    new_rule.borrow_mut().alternatives.remove(empty_index);
+                    log.remove_alt(new_rule.borrow().name(), empty_index);
                 }
                 else if !empty_indexes.is_empty() {
                     new_rule.borrow_mut().alternatives[empty_indexes.pop().unwrap()]
@@ -931,13 +1555,13 @@ impl Rules {
 
                 self.rules.push(new_rule);
 
-                self.eliminate_left_common_prefix();
+                self.eliminate_left_common_prefix(log);
                 true
             },
         };
 
         if any_change {
-            self.put_epsilon_last();
+            self.put_epsilon_last(log);
         }
 
         self.clear_cache();
@@ -949,16 +1573,96 @@ impl Rules {
     pub fn make_ready_for_recursive_decent(
         &mut self,
         max_loop: usize,
-    ) -> Result<(), String> {
+    ) -> Result<(), GrammarError> {
+        let mut log = TransformLog::new(self);
+        self.make_ready_for_recursive_decent_with(max_loop, &mut log)
+    }
+
+    fn make_ready_for_recursive_decent_with(
+        &mut self,
+        max_loop: usize,
+        log: &mut TransformLog,
+    ) -> Result<(), GrammarError> {
         for _ in 0..max_loop {
-            self.eliminate_left_recursions();
-            match self.eliminate_left_common_prefix() {
+            self.eliminate_left_recursions_with(log);
+            match self.eliminate_left_common_prefix(log) {
                 true => self.clear_cache(),
                 false => return Ok(()),
             }
         }
 
-        Err("max loop reached but grammar was not fixed".to_string())
+        Err(GrammarError::NotFixed {
+            max_loop,
+        })
+    }
+
+    // Applies the passes in order to a copy of the rules, logging where every rule and
+    // alternative of the result comes from.
+    pub fn transform(
+        &self,
+        passes: &[Pass],
+    ) -> Result<Transformed, GrammarError> {
+        self.validate()?;
+
+        let mut rules = self.deep_clone();
+        let mut log = TransformLog::new(&rules);
+
+        for pass in passes {
+            log.begin(*pass);
+            match pass {
+                Pass::EliminateLeftRecursions => rules.eliminate_left_recursions_with(&mut log),
+                Pass::EliminateLeftCommonPrefix => {
+                    while rules.eliminate_left_common_prefix(&mut log) {}
+                },
+                Pass::EliminateUselessRules => {
+                    rules.eliminate_useless_rules_with(&mut log)?;
+                },
+                Pass::MakeReadyForRecursiveDescent {
+                    max_loop,
+                } => rules.make_ready_for_recursive_decent_with(*max_loop, &mut log)?,
+            }
+        }
+
+        Ok(Transformed {
+            rules,
+            log,
+        })
+    }
+
+    // Unlike clone, which shares the rules, the copy can be changed without affecting self.
+    pub fn deep_clone(&self) -> Self {
+        let copies: HashMap<String, Rc<RefCell<Rule>>> = self
+            .rules
+            .iter()
+            .map(|rule| {
+                let rule = rule.borrow();
+                let copy = Rule::new(rule.name().to_string(), rule.recursion_elimination_num());
+                (rule.name().to_string(), copy.into())
+            })
+            .collect();
+
+        for rule in &self.rules {
+            let alternatives = rule
+                .borrow()
+                .alternatives
+                .iter()
+                .map(|alt| {
+                    alt.iter()
+                        .map(|part| copy_of_rule_part(part, &copies))
+                        .collect()
+                })
+                .collect();
+            copies[rule.borrow().name()].borrow_mut().alternatives = alternatives;
+        }
+
+        let mut rules = Self::from_rules(
+            self.rules
+                .iter()
+                .map(|rule| Rc::clone(&copies[rule.borrow().name()]))
+                .collect(),
+        );
+        rules.precedence = self.precedence.clone();
+        rules
     }
 }
 
@@ -967,7 +1671,7 @@ impl PartialEq for Rules {
         &self,
         other: &Self,
     ) -> bool {
-        self.rules == other.rules
+        self.rules == other.rules && self.precedence == other.precedence
     }
 }
 
@@ -984,9 +1688,11 @@ impl Clone for Rules {
     fn clone(&self) -> Self {
         Self {
             rules: self.rules.clone(),
+            precedence: self.precedence.clone(),
             first_set: RefCell::new(None),
             follow_set: RefCell::new(None),
             start_set: RefCell::new(None),
+            nullable_set: RefCell::new(None),
         }
     }
 }
@@ -1001,6 +1707,11 @@ impl Display for Rules {
         }
 
         write!(f, "Rules[")?;
+        if !self.precedence.is_empty() {
+            for line in self.precedence.to_string().lines() {
+                write!(f, "\n  {}", line)?;
+            }
+        }
         for r in &self.rules {
             let stringify = r.borrow().to_string();
             let mut split = stringify.split("->");
@@ -1017,8 +1728,50 @@ impl Display for Rules {
     }
 }
 
+fn parse_rule_part(
+    part: &str,
+    rules: &mut Vec<Rc<RefCell<Rule>>>,
+    num: &mut impl FnMut() -> usize,
+) -> Result<RulePart, String> {
+    if let Ok(token_kind) =
+        TokenKind::from_repr_including_epsilon(part).or_else(|_| TokenKind::from_name(part))
+    {
+        // It's a token, add it as a token.
+        return Ok(token_kind.into());
+    }
+
+    // It's a predicate on the rest of the part.
+    for predicate in [Predicate::And, Predicate::Not] {
+        if let Some(rest) = part.strip_prefix(predicate.repr()) {
+            if !rest.is_empty() {
+                let target = parse_rule_part(rest, rules, num)?;
+                return Ok(RulePart::Predicate(predicate, Box::new(target)));
+            }
+        }
+    }
+
+    // It's a rule.
+    if !part.is_empty() {
+        ensure_is_valid_rule_name(part)?;
+    }
+    let rule = match rules.iter().find(|it| it.borrow().name() == part) {
+        None => {
+            // No rule already created for this name, create new
+            let new: Rule = Rule::new(part.to_string(), num());
+            let new: Rc<RefCell<Rule>> = new.into();
+            rules.push(Rc::clone(&new));
+            new
+        },
+        Some(already) => {
+            // A rule already for this name exists, reuse it.
+            Rc::clone(already)
+        },
+    };
+    Ok(rule.into())
+}
+
 impl TryFrom<&str> for Rules {
-    type Error = String;
+    type Error = GrammarError;
 
     fn try_from(rules_description: &str) -> Result<Self, Self::Error> {
         Self::parse(rules_description)
@@ -1026,6 +1779,89 @@ impl TryFrom<&str> for Rules {
 }
 
 
+// Every x.y of the two sets, cut to k tokens. Nothing follows EOF.
+fn concat_k(
+    prefixes: &HashSet<Vec<TokenKind>>,
+    suffixes: &HashSet<Vec<TokenKind>>,
+    k: usize,
+) -> HashSet<Vec<TokenKind>> {
+    let mut concat = HashSet::new();
+
+    for prefix in prefixes {
+        if prefix.len() >= k || prefix.last() == Some(&TokenKind::Eof) {
+            concat.insert(prefix[..prefix.len().min(k)].to_vec());
+            continue;
+        }
+        for suffix in suffixes {
+            let mut joined = prefix.clone();
+            joined.extend(suffix.iter().take(k - prefix.len()));
+            concat.insert(joined);
+        }
+    }
+
+    concat
+}
+
+// The FIRST of a sequence of parts, with EPSILON when all of them are nullable.
+fn first_of_parts(
+    parts: &[RulePart],
+    first: &HashMap<String, HashSet<TokenKind>>,
+) -> HashSet<TokenKind> {
+    let mut parts_first = HashSet::new();
+
+    for part in parts {
+        let part_first = &first[&part.name()];
+        extend(
+            &mut parts_first,
+            part_first.iter().filter(|it| !it.is_epsilon()).cloned(),
+        );
+        if !part_first.contains(&TokenKind::Epsilon) {
+            return parts_first;
+        }
+    }
+
+    parts_first.insert(TokenKind::Epsilon);
+    parts_first
+}
+
+fn first_k_of_parts(
+    parts: &[RulePart],
+    first: &HashMap<String, HashSet<Vec<TokenKind>>>,
+    k: usize,
+) -> HashSet<Vec<TokenKind>> {
+    let mut parts_first: HashSet<Vec<TokenKind>> = [vec![]].into_iter().collect();
+
+    for part in parts {
+        let part_first = match part {
+            RulePart::Token(tk) if tk.is_epsilon() => continue,
+            RulePart::Token(tk) => [vec![*tk]].into_iter().collect(),
+            _ => first[&part.name()].clone(),
+        };
+        parts_first = concat_k(&parts_first, &part_first, k);
+        if parts_first.is_empty() {
+            break;
+        }
+    }
+
+    parts_first
+}
+
+fn copy_of_rule_part(
+    part: &RulePart,
+    copies: &HashMap<String, Rc<RefCell<Rule>>>,
+) -> RulePart {
+    match part {
+        RulePart::Token(tk) => RulePart::Token(*tk),
+        RulePart::Rule(rule) => match copies.get(rule.borrow().name()) {
+            Some(copy) => RulePart::Rule(Rc::clone(copy)),
+            None => RulePart::Rule(Rc::clone(rule)),
+        },
+        RulePart::Predicate(predicate, target) => {
+            RulePart::Predicate(*predicate, Box::new(copy_of_rule_part(target, copies)))
+        },
+    }
+}
+
 fn has_recursive_rule(rule: &Rule) -> bool {
     if rule.alternatives.is_empty() {
         return false;
@@ -1199,7 +2035,7 @@ Rules[
 
     #[test]
     fn test_parse() {
-        let rules: Result<Rules, String> = proper_grammar().try_into();
+        let rules: Result<Rules, _> = proper_grammar().try_into();
         let rules = rules.unwrap();
 
         assert!(rules.validate().is_ok());
@@ -1208,7 +2044,7 @@ Rules[
 
     #[test]
     fn test_eliminate_direct_left_recursions() {
-        let rules: Result<Rules, String> = recursive_grammar().try_into();
+        let rules: Result<Rules, _> = recursive_grammar().try_into();
         let mut rules = rules.unwrap();
 
         assert_eq!(
@@ -1217,7 +2053,7 @@ Rules[
         );
 
         println!("{}", rules);
-        rules.eliminate_direct_left_recursions();
+        rules.eliminate_direct_left_recursions(&mut TransformLog::new(&rules));
 
         assert!(rules.validate().is_ok());
 
@@ -1230,7 +2066,7 @@ Rules[
     // TODO make sure the the output is correct, adjust the expected output and enable the test.
     #[test]
     fn test_eliminate_indirect_left_recursions0() {
-        let rules: Result<Rules, String> = indirect_recursive_grammar0().try_into();
+        let rules: Result<Rules, _> = indirect_recursive_grammar0().try_into();
         let mut rules = rules.unwrap();
 
         let before = rules.to_string();
@@ -1253,7 +2089,7 @@ Rules[
 
     #[test]
     fn test_eliminate_indirect_left_recursions1() {
-        let rules: Result<Rules, String> = indirect_recursive_grammar1().try_into();
+        let rules: Result<Rules, _> = indirect_recursive_grammar1().try_into();
         let mut rules = rules.unwrap();
 
         let before = rules.to_string();
@@ -1278,7 +2114,7 @@ Rules[
     fn test_epsilon_rule() {
         let r = "S -> S ID | EPSILON";
 
-        let rules: Result<Rules, String> = r.try_into();
+        let rules: Result<Rules, _> = r.try_into();
         let rules = rules.unwrap();
         println!("{}", rules);
         rules.validate().unwrap();
@@ -1302,7 +2138,7 @@ Rules[
         r1 -> STRING
         ";
 
-        let rules: Result<Rules, String> = r.try_into();
+        let rules: Result<Rules, _> = r.try_into();
         let rules = rules.unwrap();
         println!("{}", rules);
         rules.validate().unwrap();
@@ -1328,6 +2164,34 @@ Rules[
         assert!(r1.contains(&TokenKind::String));
     }
 
+    #[test]
+    fn test_follow_set_with_eof() {
+        let r = "\
+        S  -> r0 ; S | EPSILON
+        r0 -> ID r1
+        r1 -> INT | EPSILON
+        ";
+
+        let rules: Result<Rules, _> = r.try_into();
+        let rules = rules.unwrap();
+
+        // Only tokens, EOF being no token of the rules.
+        let follow = rules.follow_set();
+        assert!(follow["S"].is_empty());
+        assert_eq!(follow["r1"], [TokenKind::Semicolon].into_iter().collect());
+        rules.is_backtrack_free().unwrap();
+
+        let follow = rules.follow_set_with_eof();
+        assert_eq!(follow["S"], [TokenKind::Eof].into_iter().collect());
+        assert_eq!(follow["r1"], [TokenKind::Semicolon].into_iter().collect());
+
+        assert_eq!(
+            rules.start_set_with_eof()[&("S".to_string(), 1)],
+            [TokenKind::Eof].into_iter().collect()
+        );
+        assert!(rules.start_set()[&("S".to_string(), 1)].is_empty());
+    }
+
     #[test]
     fn test_something0() {
         let r = "\
@@ -1336,7 +2200,7 @@ Rules[
         r2 -> EPSILON
         ";
 
-        let rules: Result<Rules, String> = r.try_into();
+        let rules: Result<Rules, _> = r.try_into();
         let mut rules = rules.unwrap();
         rules.eliminate_left_recursions();
         println!("{}", rules);
@@ -1363,7 +2227,7 @@ Rules[
         r1 -> ID
         ";
 
-        let rules: Result<Rules, String> = r.try_into();
+        let rules: Result<Rules, _> = r.try_into();
         let mut rules = rules.unwrap();
         rules.eliminate_left_recursions();
         println!("{}", rules);
@@ -1374,4 +2238,201 @@ Rules[
 
         println!("{}", rules);
     }
+
+    #[test]
+    fn test_nullable_set() {
+        let r = "\
+        S  -> r0 r1 | ID
+        r0 -> r1 r1 | STRING
+        r1 -> INT | EPSILON
+        ";
+
+        let rules: Result<Rules, _> = r.try_into();
+        let rules = rules.unwrap();
+        rules.validate().unwrap();
+
+        let nullable = rules.nullable_set();
+
+        assert_eq!(nullable.len(), 3);
+        assert!(nullable.contains("S"));
+        assert!(nullable.contains("r0"));
+        assert!(nullable.contains("r1"));
+    }
+
+    #[test]
+    fn test_non_productive_rules() {
+        let r = "\
+        S  -> r0 | ID
+        r0 -> r1 ID
+        r1 -> r0 INT
+        ";
+
+        let rules: Result<Rules, _> = r.try_into();
+        let rules = rules.unwrap();
+        rules.validate().unwrap();
+
+        assert_eq!(rules.non_productive_rules(), vec!["r0", "r1"]);
+        assert!(rules.unreachable_rules().is_empty());
+        assert!(rules.is_free_of_useless_rules().is_err());
+    }
+
+    #[test]
+    fn test_unreachable_rules() {
+        let r = "\
+        S  -> r0 | ID
+        r0 -> INT
+        r1 -> r2 STRING
+        r2 -> r1 | ID
+        ";
+
+        let rules: Result<Rules, _> = r.try_into();
+        let rules = rules.unwrap();
+        rules.validate().unwrap();
+
+        assert_eq!(rules.unreachable_rules(), vec!["r1", "r2"]);
+        assert!(rules.non_productive_rules().is_empty());
+    }
+
+    #[test]
+    fn test_eliminate_useless_rules() {
+        let r = "\
+        S  -> r0 | r2 | ID
+        r0 -> r1 ID
+        r1 -> r0 INT
+        r2 -> STRING
+        r3 -> r2
+        ";
+
+        let rules: Result<Rules, _> = r.try_into();
+        let mut rules = rules.unwrap();
+        rules.validate().unwrap();
+
+        assert!(rules.eliminate_useless_rules().unwrap());
+        rules.validate().unwrap();
+        rules.is_free_of_useless_rules().unwrap();
+
+        assert_eq!(
+            rules.to_string().trim(),
+            "\
+Rules[
+  S                    -> r2 | ID
+  r2                   -> STRING
+]
+        "
+            .trim()
+        );
+
+        assert!(!rules.eliminate_useless_rules().unwrap());
+    }
+
+    #[test]
+    fn test_useless_rules_with_predicates() {
+        let r = "\
+        S     -> &ahead ids bc
+        ahead -> ab STRING
+        ab    -> ID ab INT | ID INT
+        ids   -> ID ids | ID
+        bc    -> INT bc STRING | INT STRING
+        ";
+
+        let rules: Result<Rules, _> = r.try_into();
+        let mut rules = rules.unwrap();
+
+        assert!(rules.unreachable_rules().is_empty());
+        assert!(rules.non_productive_rules().is_empty());
+        assert!(!rules.eliminate_useless_rules().unwrap());
+
+        // An alternative looking ahead with a non-productive rule goes along with it.
+        let r = "\
+        S  -> !r0 ID | INT
+        r0 -> r0 ID
+        ";
+
+        let rules: Result<Rules, _> = r.try_into();
+        let mut rules = rules.unwrap();
+
+        assert_eq!(rules.non_productive_rules(), vec!["r0"]);
+        assert!(rules.eliminate_useless_rules().unwrap());
+        rules.is_free_of_useless_rules().unwrap();
+    }
+
+    #[test]
+    fn test_eliminate_useless_rules_non_productive_start() {
+        let r = "\
+        S  -> r0 ID
+        r0 -> S INT
+        ";
+
+        let rules: Result<Rules, _> = r.try_into();
+        let mut rules = rules.unwrap();
+
+        assert!(rules.eliminate_useless_rules().is_err());
+    }
+
+    #[test]
+    fn test_first_k_set() {
+        let r = "\
+        S         -> statement S | EPSILON
+        statement -> ID ID ; | ID = INT ;
+        ";
+
+        let rules: Result<Rules, _> = r.try_into();
+        let rules = rules.unwrap();
+
+        let first = rules.first_k_set(2);
+        let expected: HashSet<Vec<TokenKind>> = [
+            vec![TokenKind::Id, TokenKind::Id],
+            vec![TokenKind::Id, TokenKind::Equal],
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(first["statement"], expected);
+        assert!(first["S"].contains(&vec![]));
+
+        let follow = rules.follow_k_set(2);
+        let expected: HashSet<Vec<TokenKind>> = [
+            vec![TokenKind::Eof],
+            vec![TokenKind::Id, TokenKind::Id],
+            vec![TokenKind::Id, TokenKind::Equal],
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(follow["statement"], expected);
+
+        assert!(matches!(
+            rules.is_ll_k(1),
+            Err(GrammarError::NotLlK { k: 1, rule, .. }) if rule == "statement"
+        ));
+        assert_eq!(rules.is_ll_k(0), Err(GrammarError::ZeroLookahead));
+        rules.is_ll_k(2).unwrap();
+    }
+
+    #[test]
+    fn test_predicates() {
+        let r = "\
+        S  -> &r0 r1 | !ID INT
+        r0 -> ID
+        r1 -> ID !&INT
+        ";
+
+        let rules: Result<Rules, _> = r.try_into();
+        let rules = rules.unwrap();
+
+        assert_eq!(
+            rules.get_rule_by_name("S").borrow().to_string(),
+            "Rule[S -> &r0 r1 | !ID INT]"
+        );
+        assert_eq!(
+            rules.get_rule_by_name("r1").borrow().to_string(),
+            "Rule[r1 -> ID !&INT]"
+        );
+
+        rules.validate_peg().unwrap();
+        assert!(rules
+            .validate()
+            .err()
+            .unwrap()
+            .to_string()
+            .starts_with("predicates are only supported by the packrat parser, rules: S, r1"));
+    }
 }
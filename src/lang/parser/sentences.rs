@@ -0,0 +1,421 @@
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+
+use crate::lang::error::GrammarError;
+use crate::lang::lexer::token::TokenKind;
+use crate::lang::parser::rule::Rule;
+use crate::lang::parser::rule::RulePart;
+use crate::lang::parser::rules::Rules;
+
+// Random sentences of the grammar. Past the depth budget, or once the token budget is spent, only
+// the alternatives terminating soonest are taken, before that the chance of taking one of them
+// grows with the depth. Predicates are not checked, sentences of a grammar using them may be
+// rejected by the parsers.
+pub struct SentenceGenerator<'a> {
+    rules: &'a Rules,
+    rng: XorShift,
+    max_depth: usize,
+    max_tokens: usize,
+    // The least depth of a derivation from each alternative, by rule name.
+    heights: HashMap<String, Vec<usize>>,
+}
+
+impl<'a> SentenceGenerator<'a> {
+    pub fn new(
+        rules: &'a Rules,
+        seed: u64,
+        max_depth: usize,
+        max_tokens: usize,
+    ) -> Result<Self, GrammarError> {
+        rules.validate()?;
+
+        let heights = heights_of(rules);
+        let non_productive = rules
+            .rules()
+            .iter()
+            .map(|it| it.borrow().name().to_string())
+            .filter(|it| heights[it].iter().all(|it| *it == usize::MAX))
+            .collect::<Vec<_>>();
+        if !non_productive.is_empty() {
+            return Err(GrammarError::NonProductiveRules(non_productive));
+        }
+
+        Ok(Self {
+            rules,
+            rng: XorShift::new(seed),
+            max_depth,
+            max_tokens,
+            heights,
+        })
+    }
+
+    pub fn tokens(&mut self) -> Vec<TokenKind> {
+        let mut tokens = vec![];
+        let start = self.rules.rules().first().unwrap().borrow();
+        self.generate(&start, 0, &mut tokens);
+        tokens
+    }
+
+    // A sentence as text the lexer reads back into the same tokens.
+    pub fn source(&mut self) -> String {
+        let tokens = self.tokens();
+        source_of(&tokens, self.rng.next())
+    }
+
+    fn generate(
+        &mut self,
+        rule: &Rule,
+        depth: usize,
+        tokens: &mut Vec<TokenKind>,
+    ) {
+        let heights = &self.heights[rule.name()];
+        let least = *heights.iter().min().unwrap();
+        let remaining = self.max_depth.saturating_sub(depth);
+
+        let terminating = (0..heights.len())
+            .filter(|it| heights[*it] == least)
+            .collect::<Vec<_>>();
+        let candidates = match remaining <= least || tokens.len() >= self.max_tokens {
+            true => terminating,
+            false => {
+                // depth / max_depth of the time, the soonest terminating ones only.
+                match self.rng.below(self.max_depth) < depth {
+                    true => terminating,
+                    false => (0..heights.len())
+                        .filter(|it| heights[*it] <= remaining)
+                        .collect(),
+                }
+            },
+        };
+        let alt_no = candidates[self.rng.below(candidates.len())];
+
+        for part in &rule.alternatives[alt_no] {
+            match part {
+                RulePart::Rule(rule) => self.generate(&rule.borrow(), depth + 1, tokens),
+                RulePart::Token(tk) if tk.is_epsilon() => {},
+                RulePart::Token(tk) => tokens.push(*tk),
+                RulePart::Predicate(_, _) => {},
+            }
+        }
+    }
+}
+
+// All sentences of at most max_len tokens, shortest first. Predicates are not checked.
+pub fn sentences_up_to(
+    rules: &Rules,
+    max_len: usize,
+) -> Result<Sentences<'_>, GrammarError> {
+    rules.validate()?;
+
+    Ok(Sentences {
+        rules,
+        max_len,
+        len: 0,
+        derived: rules
+            .rules()
+            .iter()
+            .map(|it| (it.borrow().name().to_string(), BTreeSet::new()))
+            .collect(),
+        next: vec![].into_iter(),
+    })
+}
+
+// The sentences of one length are derived once those shorter are used up, a search stopping at
+// a short sentence never derives the longer ones.
+pub struct Sentences<'r> {
+    rules: &'r Rules,
+    max_len: usize,
+    len: usize,
+    // The sentences of fewer than len tokens each rule derives.
+    derived: HashMap<String, BTreeSet<Vec<TokenKind>>>,
+    next: std::vec::IntoIter<Vec<TokenKind>>,
+}
+
+impl Sentences<'_> {
+    // Grows the sentences of each rule to those of len tokens, until no rule derives any more.
+    fn derive(&mut self) {
+        let len = self.len;
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for rule in self.rules.rules() {
+                let rule = rule.borrow();
+                for alt in &rule.alternatives {
+                    let mut derived: BTreeSet<Vec<TokenKind>> = BTreeSet::from([vec![]]);
+                    for part in alt {
+                        let continuations = match part {
+                            RulePart::Rule(rule) => self.derived[rule.borrow().name()].clone(),
+                            RulePart::Token(tk) if tk.is_epsilon() => continue,
+                            RulePart::Token(tk) => BTreeSet::from([vec![*tk]]),
+                            RulePart::Predicate(_, _) => continue,
+                        };
+                        derived = derived
+                            .iter()
+                            .flat_map(|prefix| {
+                                continuations
+                                    .iter()
+                                    .filter(|it| prefix.len() + it.len() <= len)
+                                    .map(|it| [prefix.as_slice(), it.as_slice()].concat())
+                                    .collect::<Vec<_>>()
+                            })
+                            .collect();
+                    }
+
+                    let known = self.derived.get_mut(rule.name()).unwrap();
+                    let count = known.len();
+                    known.extend(derived);
+                    changed |= known.len() != count;
+                }
+            }
+        }
+
+        let start = self
+            .rules
+            .rules()
+            .first()
+            .unwrap()
+            .borrow()
+            .name()
+            .to_string();
+        self.next = self.derived[&start]
+            .iter()
+            .filter(|it| it.len() == len)
+            .cloned()
+            .collect::<Vec<_>>()
+            .into_iter();
+    }
+}
+
+impl Iterator for Sentences<'_> {
+    type Item = Vec<TokenKind>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(sentence) = self.next.next() {
+                return Some(sentence);
+            }
+            if self.len > self.max_len {
+                return None;
+            }
+            self.derive();
+            self.len += 1;
+        }
+    }
+}
+
+// Tokens as text, identifiers, numbers and strings picked from the seed. A line is broken after
+// `;`, `{` and `}`, lines are indented by the braces they are in.
+pub fn source_of(
+    tokens: &[TokenKind],
+    seed: u64,
+) -> String {
+    const IDS: [&str; 8] = ["a", "b", "c", "f", "g", "x", "y", "z"];
+
+    let mut rng = XorShift::new(seed);
+    let mut source = String::new();
+    let mut indent: usize = 0;
+    for tk in tokens {
+        if *tk == TokenKind::RightBraces {
+            indent = indent.saturating_sub(1);
+        }
+        if source.ends_with('\n') || source.is_empty() {
+            source += &"    ".repeat(indent);
+        }
+        else {
+            source.push(' ');
+        }
+
+        match tk {
+            TokenKind::Id => source += IDS[rng.below(IDS.len())],
+            TokenKind::Int => source += &rng.below(100).to_string(),
+            TokenKind::String => source += &format!("\"s{}\"", rng.below(10)),
+            tk => source += tk.repr_or_name(),
+        }
+
+        match tk {
+            TokenKind::LeftBraces => {
+                indent += 1;
+                source.push('\n');
+            },
+            TokenKind::RightBraces | TokenKind::Semicolon => source.push('\n'),
+            _ => {},
+        }
+    }
+    source
+}
+
+
+// The least depth of a derivation from each alternative, usize::MAX for those deriving nothing.
+fn heights_of(rules: &Rules) -> HashMap<String, Vec<usize>> {
+    let mut heights: HashMap<String, Vec<usize>> = rules
+        .rules()
+        .iter()
+        .map(|it| {
+            let rule = it.borrow();
+            (
+                rule.name().to_string(),
+                vec![usize::MAX; rule.alternatives.len()],
+            )
+        })
+        .collect();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for rule in rules.rules() {
+            let rule = rule.borrow();
+            for (alt_no, alt) in rule.alternatives.iter().enumerate() {
+                let mut height = 1;
+                for part in alt {
+                    if let RulePart::Rule(target) = part {
+                        let least = *heights[target.borrow().name()].iter().min().unwrap();
+                        height = height.max(least.saturating_add(1));
+                    }
+                }
+
+                let known = &mut heights.get_mut(rule.name()).unwrap()[alt_no];
+                if height < *known {
+                    *known = height;
+                    changed = true;
+                }
+            }
+        }
+    }
+    heights
+}
+
+// xorshift64*, good enough for picking alternatives and reproducible from the seed.
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        // Zero is a fixed point.
+        Self(match seed {
+            0 => 0x9E37_79B9_7F4A_7C15,
+            seed => seed,
+        })
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(
+        &mut self,
+        n: usize,
+    ) -> usize {
+        match n {
+            0 => 0,
+            n => (self.next() % n as u64) as usize,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::lang::lexer::token::TokenKind;
+    use crate::lang::lexer::v0::Lexer;
+    use crate::lang::parser::node::display_of;
+    use crate::lang::parser::rules::Rules;
+    use crate::lang::parser::sentences::sentences_up_to;
+    use crate::lang::parser::sentences::source_of;
+    use crate::lang::parser::sentences::SentenceGenerator;
+    use crate::lang::parser_impl::backtracking_parser::parse_with_backtracking;
+    use crate::lang::parser_impl::recursive_descent_parser::recursive_descent_parse;
+
+    #[test]
+    fn test_generated_sentences_parse() {
+        let rules: Rules = "
+S           -> statement S | EPSILON
+statement   -> ID = expressions ; | RETURN expressions ; | FN ID ( ) { body }
+body        -> statement body | EPSILON
+expressions -> terms + expressions | terms - expressions | terms
+terms       -> factor * terms | factor / terms | factor
+factor      -> ( expressions ) | INT | ID | STRING
+        "
+        .try_into()
+        .unwrap();
+        let mut ready = rules.deep_clone();
+        ready.make_ready_for_recursive_decent(128).unwrap();
+
+        let mut generator = SentenceGenerator::new(&rules, 7, 12, 60).unwrap();
+        for _ in 0..50 {
+            let tokens = generator.tokens();
+            let source = source_of(&tokens, 1);
+
+            let lexed = Lexer::parse(&source).unwrap();
+            let kinds = lexed.iter().map(|it| it.token_kind).collect::<Vec<_>>();
+            assert_eq!(kinds[..kinds.len() - 1], tokens);
+
+            let backtracking = parse_with_backtracking(&rules, lexed.into_iter()).unwrap();
+            let lexer: Lexer = source.as_str().into();
+            let recursive = recursive_descent_parse(&ready, lexer.into_iter()).unwrap();
+            assert_eq!(
+                display_of(&backtracking).matches("ID[").count(),
+                display_of(&recursive).matches("ID[").count()
+            );
+        }
+
+        // Reproducible from the seed.
+        let mut first = SentenceGenerator::new(&rules, 42, 8, 30).unwrap();
+        let mut second = SentenceGenerator::new(&rules, 42, 8, 30).unwrap();
+        assert_eq!(first.source(), second.source());
+
+        let tokens = [
+            TokenKind::Fn,
+            TokenKind::Id,
+            TokenKind::LeftParen,
+            TokenKind::RightParen,
+            TokenKind::LeftBraces,
+            TokenKind::Return,
+            TokenKind::Int,
+            TokenKind::Semicolon,
+            TokenKind::RightBraces,
+        ];
+        let source = source_of(&tokens, 3);
+        assert!(source.starts_with("fn "));
+        assert!(source.contains("( ) {\n    return "));
+        assert!(source.ends_with(";\n}\n"));
+    }
+
+    #[test]
+    fn test_sentences_up_to() {
+        let rules: Rules = "
+S    -> expr | EPSILON
+expr -> expr + INT | INT
+        "
+        .try_into()
+        .unwrap();
+
+        let sentences = sentences_up_to(&rules, 3).unwrap().collect::<Vec<_>>();
+        assert_eq!(
+            sentences,
+            [
+                vec![],
+                vec![TokenKind::Int],
+                vec![TokenKind::Int, TokenKind::Plus, TokenKind::Int],
+            ]
+        );
+
+        let rules: Rules = "
+S -> ( S ) S | EPSILON
+        "
+        .try_into()
+        .unwrap();
+
+        // The Catalan numbers 1, 1, 2 and 5 of balanced parentheses.
+        assert_eq!(sentences_up_to(&rules, 6).unwrap().count(), 9);
+        // Only the lengths taken are derived.
+        let mut sentences = sentences_up_to(&rules, 1000).unwrap();
+        assert_eq!(sentences.next(), Some(vec![]));
+        assert_eq!(
+            sentences.next(),
+            Some(vec![TokenKind::LeftParen, TokenKind::RightParen])
+        );
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use crate::lang::parser::rule::display_of_rule_part;
+use crate::lang::parser::rules::Rules;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Pass {
+    EliminateLeftRecursions,
+    EliminateLeftCommonPrefix,
+    EliminateUselessRules,
+    MakeReadyForRecursiveDescent { max_loop: usize },
+}
+
+impl Pass {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pass::EliminateLeftRecursions => "eliminate_left_recursions",
+            Pass::EliminateLeftCommonPrefix => "eliminate_left_common_prefix",
+            Pass::EliminateUselessRules => "eliminate_useless_rules",
+            Pass::MakeReadyForRecursiveDescent {
+                ..
+            } => "make_ready_for_recursive_descent",
+        }
+    }
+}
+
+
+// Why a helper rule was generated.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Helper {
+    // `A -> A a | b` became `A -> b A'` and `A' -> a A' | EPSILON`.
+    LeftRecursion,
+    // `A -> p a | p b` became `A -> p A'` and `A' -> a | b`.
+    CommonPrefix,
+}
+
+impl Display for Helper {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        match self {
+            Helper::LeftRecursion => write!(f, "left recursion"),
+            Helper::CommonPrefix => write!(f, "common prefix"),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct GeneratedRule {
+    // The rule whose alternatives were moved into the helper, maybe a helper itself.
+    pub from: String,
+    pub pass: &'static str,
+    pub helper: Helper,
+}
+
+
+// The original alternatives an alternative was built from, as (rule, alt_no) of the grammar the
+// transformation started with. One for an alternative which was kept, possibly with a helper
+// rule appended or a prefix moved out, several when alternatives were merged by prefix
+// factoring or substituted into each other by indirect recursion elimination, in the order their
+// parts appear. None for an added epsilon alternative.
+pub type AltOrigin = Vec<(String, usize)>;
+
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct TransformLog {
+    passes: Vec<&'static str>,
+    generated: HashMap<String, GeneratedRule>,
+    origins: HashMap<String, Vec<AltOrigin>>,
+    removed: Vec<String>,
+}
+
+impl TransformLog {
+    pub(crate) fn new(rules: &Rules) -> Self {
+        let origins = rules
+            .rules()
+            .iter()
+            .map(|rule| {
+                let name = rule.borrow().name().to_string();
+                let alt_origins = (0..rule.borrow().alternatives.len())
+                    .map(|alt_no| vec![(name.clone(), alt_no)])
+                    .collect();
+                (name, alt_origins)
+            })
+            .collect();
+
+        Self {
+            origins,
+            ..Default::default()
+        }
+    }
+
+    pub fn passes(&self) -> &Vec<&'static str> {
+        &self.passes
+    }
+
+    pub fn generated(
+        &self,
+        rule_name: &str,
+    ) -> Option<&GeneratedRule> {
+        self.generated.get(rule_name)
+    }
+
+    // The rule of the original grammar a rule stands for, itself unless it is a helper.
+    pub fn origin_of_rule<'s>(
+        &'s self,
+        rule_name: &'s str,
+    ) -> &'s str {
+        match self.generated.get(rule_name) {
+            None => rule_name,
+            Some(generated) => self.origin_of_rule(&generated.from),
+        }
+    }
+
+    pub fn origin_of_alt(
+        &self,
+        rule_name: &str,
+        alt_no: usize,
+    ) -> &AltOrigin {
+        &self.origins[rule_name][alt_no]
+    }
+
+    pub fn removed_rules(&self) -> &Vec<String> {
+        &self.removed
+    }
+
+    // ---------------------------------
+    // Mirrors the changes made to the alternatives of the rules.
+
+    pub(crate) fn begin(
+        &mut self,
+        pass: Pass,
+    ) {
+        self.passes.push(pass.name());
+    }
+
+    pub(crate) fn generate(
+        &mut self,
+        rule_name: &str,
+        from: &str,
+        helper: Helper,
+        origins: Vec<AltOrigin>,
+    ) {
+        let generated = GeneratedRule {
+            from: from.to_string(),
+            pass: self.passes.last().copied().unwrap_or_default(),
+            helper,
+        };
+        self.generated.insert(rule_name.to_string(), generated);
+        self.origins.insert(rule_name.to_string(), origins);
+    }
+
+    pub(crate) fn take_alts(
+        &mut self,
+        rule_name: &str,
+    ) -> Vec<AltOrigin> {
+        self.origins.remove(rule_name).unwrap_or_default()
+    }
+
+    pub(crate) fn set_alts(
+        &mut self,
+        rule_name: &str,
+        origins: Vec<AltOrigin>,
+    ) {
+        self.origins.insert(rule_name.to_string(), origins);
+    }
+
+    pub(crate) fn push_alt(
+        &mut self,
+        rule_name: &str,
+        origin: AltOrigin,
+    ) {
+        self.alts_mut(rule_name).push(origin);
+    }
+
+    pub(crate) fn insert_alt(
+        &mut self,
+        rule_name: &str,
+        alt_no: usize,
+        origin: AltOrigin,
+    ) {
+        self.alts_mut(rule_name).insert(alt_no, origin);
+    }
+
+    pub(crate) fn remove_alt(
+        &mut self,
+        rule_name: &str,
+        alt_no: usize,
+    ) -> AltOrigin {
+        self.alts_mut(rule_name).remove(alt_no)
+    }
+
+    pub(crate) fn extend_alt(
+        &mut self,
+        rule_name: &str,
+        alt_no: usize,
+        origin: AltOrigin,
+    ) {
+        self.alts_mut(rule_name)[alt_no].extend(origin);
+    }
+
+    pub(crate) fn swap_alts(
+        &mut self,
+        rule_name: &str,
+        alt_no0: usize,
+        alt_no1: usize,
+    ) {
+        self.alts_mut(rule_name).swap(alt_no0, alt_no1);
+    }
+
+    pub(crate) fn retain_alts(
+        &mut self,
+        rule_name: &str,
+        keep: &[bool],
+    ) {
+        let mut keep = keep.iter();
+        self.alts_mut(rule_name).retain(|_| *keep.next().unwrap());
+    }
+
+    pub(crate) fn remove_rule(
+        &mut self,
+        rule_name: &str,
+    ) {
+        self.origins.remove(rule_name);
+        self.removed.push(rule_name.to_string());
+    }
+
+    fn alts_mut(
+        &mut self,
+        rule_name: &str,
+    ) -> &mut Vec<AltOrigin> {
+        self.origins.entry(rule_name.to_string()).or_default()
+    }
+}
+
+
+// The outcome of Rules::transform, the input grammar is left as it was.
+pub struct Transformed {
+    pub rules: Rules,
+    pub log: TransformLog,
+}
+
+impl Display for Transformed {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(f, "Transformed[\n  passes: {}", self.log.passes.join(", "))?;
+
+        for rule in self.rules.rules() {
+            let rule = rule.borrow();
+            if let Some(generated) = self.log.generated(rule.name()) {
+                write!(
+                    f,
+                    "\n  {} <= {}, {} by {}",
+                    rule.name(),
+                    generated.from,
+                    generated.helper,
+                    generated.pass,
+                )?;
+            }
+
+            for (alt_no, alt) in rule.alternatives.iter().enumerate() {
+                let alt = alt
+                    .iter()
+                    .map(display_of_rule_part)
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let origin = match self.log.origin_of_alt(rule.name(), alt_no).as_slice() {
+                    [] => "added".to_string(),
+                    origin => origin
+                        .iter()
+                        .map(|(rule_name, alt_no)| format!("{}#{}", rule_name, alt_no))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                };
+                write!(f, "\n  {: <20} -> {: <30} <= {}", rule.name(), alt, origin)?;
+            }
+        }
+
+        if !self.log.removed.is_empty() {
+            write!(f, "\n  removed: {}", self.log.removed.join(", "))?;
+        }
+        write!(f, "\n]")
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::lang::parser::rules::Rules;
+    use crate::lang::parser::transform::Helper;
+    use crate::lang::parser::transform::Pass;
+
+    #[test]
+    fn test_left_recursion_provenance() {
+        let rules: Rules = "
+S    -> expr ;
+expr -> expr + term | expr - term | term
+term -> INT | ( expr )
+        "
+        .try_into()
+        .unwrap();
+        let before = rules.to_string();
+
+        let transformed = rules
+            .transform(&[Pass::MakeReadyForRecursiveDescent {
+                max_loop: 16,
+            }])
+            .unwrap();
+        assert_eq!(rules.to_string(), before);
+
+        let log = &transformed.log;
+        assert_eq!(log.passes(), &vec!["make_ready_for_recursive_descent"]);
+        assert_eq!(
+            log.generated("expr__0").unwrap().helper,
+            Helper::LeftRecursion
+        );
+        assert_eq!(log.origin_of_rule("expr__0"), "expr");
+        assert_eq!(log.origin_of_alt("expr", 0), &vec![("expr".to_string(), 2)]);
+        assert_eq!(
+            log.origin_of_alt("expr__0", 1),
+            &vec![("expr".to_string(), 1)]
+        );
+        assert!(log.origin_of_alt("expr__0", 2).is_empty());
+    }
+
+    #[test]
+    fn test_common_prefix_provenance() {
+        let rules: Rules = "
+S         -> statement S | EPSILON
+statement -> ID ID ; | ID = INT ; | RETURN INT ;
+unused    -> INT
+        "
+        .try_into()
+        .unwrap();
+
+        let transformed = rules
+            .transform(&[Pass::EliminateUselessRules, Pass::EliminateLeftCommonPrefix])
+            .unwrap();
+        assert_eq!(
+            transformed.to_string(),
+            "\
+Transformed[
+  passes: eliminate_useless_rules, eliminate_left_common_prefix
+  S                    -> statement S                    <= S#0
+  S                    -> EPSILON                        <= S#1
+  statement            -> ID statement__0                <= statement#0, statement#1
+  statement            -> RETURN INT ;                   <= statement#2
+  statement__0 <= statement, common prefix by eliminate_left_common_prefix
+  statement__0         -> ID ;                           <= statement#0
+  statement__0         -> = INT ;                        <= statement#1
+  removed: unused
+]"
+        );
+    }
+
+    #[test]
+    fn test_indirect_recursion_provenance() {
+        let rules: Rules = "
+S -> t ID | INT
+t -> S STRING | FN
+        "
+        .try_into()
+        .unwrap();
+
+        // `t -> S STRING` has S substituted, then the direct recursion `t -> t ID STRING` goes.
+        let transformed = rules.transform(&[Pass::EliminateLeftRecursions]).unwrap();
+        let log = &transformed.log;
+        let origin = |rule: &str, alt_no: usize| (rule.to_string(), alt_no);
+        assert_eq!(
+            log.origin_of_alt("t", 1),
+            &vec![origin("S", 1), origin("t", 0)]
+        );
+        assert_eq!(
+            log.origin_of_alt("t__0", 0),
+            &vec![origin("S", 0), origin("t", 0)]
+        );
+        assert_eq!(log.origin_of_rule("t__0"), "t");
+    }
+}
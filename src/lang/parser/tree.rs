@@ -0,0 +1,666 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Debug;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::lang::error::ParseErrorKind;
+use crate::lang::lexer::token::Token;
+use crate::lang::lexer::token::TokenKind;
+use crate::lang::parser::node::Node;
+use crate::lang::parser::rule::RulePart;
+use crate::lang::parser::rules::Rules;
+
+// A node of a Tree, numbered in the order the nodes were added.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+impl Display for NodeId {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+
+#[derive(Clone, Debug)]
+pub struct TreeNode<'a> {
+    // Of the rule, token or predicate, shared by the nodes of the same one.
+    name: Arc<str>,
+    token_kind: Option<TokenKind>,
+    alt_no: Option<usize>,
+    token: Option<Token<'a>>,
+
+    parent: Option<NodeId>,
+    first_child: Option<NodeId>,
+    last_child: Option<NodeId>,
+    prev_sibling: Option<NodeId>,
+    next_sibling: Option<NodeId>,
+}
+
+impl<'a> TreeNode<'a> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_token(&self) -> bool {
+        self.token_kind.is_some()
+    }
+
+    pub fn token_kind(&self) -> Option<TokenKind> {
+        self.token_kind
+    }
+
+    pub fn alt_no(&self) -> Option<usize> {
+        self.alt_no
+    }
+
+    pub fn token(&self) -> Option<&Token<'a>> {
+        self.token.as_ref()
+    }
+
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn first_child(&self) -> Option<NodeId> {
+        self.first_child
+    }
+
+    pub fn last_child(&self) -> Option<NodeId> {
+        self.last_child
+    }
+
+    pub fn prev_sibling(&self) -> Option<NodeId> {
+        self.prev_sibling
+    }
+
+    pub fn next_sibling(&self) -> Option<NodeId> {
+        self.next_sibling
+    }
+}
+
+
+// A parse tree in a single arena, the links between the nodes are NodeIds rather than pointers.
+// Clones share the arena, and unlike Node it can be sent to other threads.
+#[derive(Clone, Debug)]
+pub struct Tree<'a> {
+    nodes: Arc<[TreeNode<'a>]>,
+    root: NodeId,
+}
+
+impl<'a> Tree<'a> {
+    pub fn root(&self) -> NodeId {
+        self.root
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn node(
+        &self,
+        id: NodeId,
+    ) -> &TreeNode<'a> {
+        &self.nodes[id.0]
+    }
+
+    pub fn parent(
+        &self,
+        id: NodeId,
+    ) -> Option<NodeId> {
+        self.node(id).parent
+    }
+
+    pub fn children(
+        &self,
+        id: NodeId,
+    ) -> Children<'_, 'a> {
+        Children {
+            tree: self,
+            next: self.node(id).first_child,
+        }
+    }
+
+    // The node and all below it, parents before their children.
+    pub fn preorder(
+        &self,
+        id: NodeId,
+    ) -> Preorder<'_, 'a> {
+        Preorder {
+            tree: self,
+            top: id,
+            next: Some(id),
+        }
+    }
+
+    // The tokens the tree was parsed from, in order.
+    pub fn tokens(&self) -> Vec<Token<'a>> {
+        self.preorder(self.root())
+            .filter_map(|id| self.node(id).token)
+            .collect()
+    }
+
+    // Back to a Node of the rules the tree was parsed by, the nodes keep their numbers.
+    pub fn to_node(
+        &self,
+        rules: &Rules,
+    ) -> Rc<RefCell<Node<'a>>> {
+        let rules_by_name = rules
+            .rules()
+            .iter()
+            .map(|rule| (rule.borrow().name().to_string(), Rc::clone(rule)))
+            .collect::<HashMap<_, _>>();
+        let node_of = |id: NodeId| -> Node<'a> {
+            let tree_node = self.node(id);
+            let rule_part = match tree_node.token_kind {
+                Some(token_kind) => RulePart::Token(token_kind),
+                None => RulePart::Rule(Rc::clone(&rules_by_name[tree_node.name()])),
+            };
+            let mut node = Node::new(rule_part, id.0);
+            if let Some(alt_no) = tree_node.alt_no {
+                node.set_alt(alt_no);
+            }
+            if let Some(token) = tree_node.token {
+                node.set_token(token);
+            }
+            node
+        };
+
+        let root: Rc<RefCell<Node<'a>>> = node_of(self.root).into();
+        let mut stack = vec![(self.root, Rc::clone(&root))];
+        while let Some((id, node)) = stack.pop() {
+            for child_id in self.children(id) {
+                let child: Rc<RefCell<Node<'a>>> = node_of(child_id).into();
+                child.borrow_mut().set_parent(&node);
+                node.borrow_mut().append_child(&child);
+                stack.push((child_id, child));
+            }
+        }
+
+        root
+    }
+}
+
+impl<'a> From<&Rc<RefCell<Node<'a>>>> for Tree<'a> {
+    // Without recursion, deep trees included, the nodes are numbered in preorder.
+    fn from(root: &Rc<RefCell<Node<'a>>>) -> Self {
+        let mut builder = TreeBuilder::new();
+        let mut stack = vec![(Rc::clone(root), None)];
+
+        while let Some((node, parent)) = stack.pop() {
+            let node = node.borrow();
+            let id = builder.add(parent, node.rule_part());
+            if node.rule_part().is_rule() && node.has_alt() {
+                builder.set_alt(id, node.alt());
+            }
+            if let Some(token) = node.token() {
+                builder.set_token(id, *token);
+            }
+
+            for child in node.children().iter().rev() {
+                stack.push((Rc::clone(child), Some(id)));
+            }
+        }
+
+        builder.build()
+    }
+}
+
+impl Display for Tree<'_> {
+    // As display_of() shows the Node the tree was made of.
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        let mut stack = vec![(self.root(), 0)];
+
+        while let Some((id, level)) = stack.pop() {
+            let node = self.node(id);
+            if level > 0 {
+                let multiplier = match level {
+                    1 => 1,
+                    _ => 2,
+                };
+                write!(f, "\n|{} ", "_".repeat(level * multiplier))?;
+            }
+            write!(f, "{}", node.name)?;
+            if let Some(token) = &node.token {
+                write!(f, "[{}]", token.text)?;
+            }
+
+            let children = self.children(id).collect::<Vec<_>>();
+            stack.extend(children.into_iter().rev().map(|it| (it, level + 1)));
+        }
+
+        Ok(())
+    }
+}
+
+
+pub type TreeParseResult<'a> = Result<Tree<'a>, TreeParseError<'a>>;
+
+// As ParseError, for the parsers building a Tree. The kind is boxed to keep the results small.
+pub struct TreeParseError<'a> {
+    partial_tree: Tree<'a>,
+    kind: Box<ParseErrorKind>,
+    error: String,
+}
+
+impl<'a> TreeParseError<'a> {
+    pub fn of(
+        partial_tree: Tree<'a>,
+        kind: ParseErrorKind,
+    ) -> Self {
+        let error = kind.to_string();
+        Self::with_error(partial_tree, kind, error)
+    }
+
+    pub fn with_error(
+        partial_tree: Tree<'a>,
+        kind: ParseErrorKind,
+        error: String,
+    ) -> Self {
+        Self {
+            partial_tree,
+            kind: Box::new(kind),
+            error,
+        }
+    }
+
+    pub fn kind(&self) -> &ParseErrorKind {
+        &self.kind
+    }
+
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+
+    pub fn partial_tree(&self) -> &Tree<'a> {
+        &self.partial_tree
+    }
+}
+
+impl Debug for TreeParseError<'_> {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(f, "ParseError[{}]", self.error)
+    }
+}
+
+impl Display for TreeParseError<'_> {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(f, "ParseError[{}]", self.error)
+    }
+}
+
+impl Error for TreeParseError<'_> {
+}
+
+
+pub struct Children<'t, 'a> {
+    tree: &'t Tree<'a>,
+    next: Option<NodeId>,
+}
+
+impl Iterator for Children<'_, '_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.next?;
+        self.next = self.tree.node(id).next_sibling;
+        Some(id)
+    }
+}
+
+
+pub struct Preorder<'t, 'a> {
+    tree: &'t Tree<'a>,
+    top: NodeId,
+    next: Option<NodeId>,
+}
+
+impl Iterator for Preorder<'_, '_> {
+    type Item = NodeId;
+
+    // Down to the first child, else to the next sibling of the node or of the closest ancestor
+    // having one, not leaving the subtree of top.
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.next?;
+        let mut node = self.tree.node(id);
+
+        self.next = node.first_child;
+        let mut at = id;
+        while self.next.is_none() && at != self.top {
+            self.next = node.next_sibling;
+            at = node.parent.unwrap();
+            node = self.tree.node(at);
+        }
+
+        Some(id)
+    }
+}
+
+
+// Adds the nodes of a Tree one by one, each in constant time.
+#[derive(Default)]
+pub struct TreeBuilder<'a> {
+    nodes: Vec<TreeNode<'a>>,
+    names: HashMap<String, Arc<str>>,
+    // The first node, unless it was wrapped.
+    root: Option<NodeId>,
+}
+
+impl<'a> TreeBuilder<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The first node is the root, all the others need a parent, the new node is its last child.
+    pub fn add(
+        &mut self,
+        parent: Option<NodeId>,
+        rule_part: &RulePart,
+    ) -> NodeId {
+        match rule_part {
+            RulePart::Token(token_kind) => {
+                self.add_named(parent, token_kind.upper_name(), Some(*token_kind))
+            },
+            _ => self.add_named(parent, &rule_part.name(), None),
+        }
+    }
+
+    // As add(), for the parsers knowing their rules by name only.
+    pub fn add_rule(
+        &mut self,
+        parent: Option<NodeId>,
+        name: &str,
+    ) -> NodeId {
+        self.add_named(parent, name, None)
+    }
+
+    pub fn add_token(
+        &mut self,
+        parent: NodeId,
+        token_kind: TokenKind,
+    ) -> NodeId {
+        self.add_named(Some(parent), token_kind.upper_name(), Some(token_kind))
+    }
+
+    fn add_named(
+        &mut self,
+        parent: Option<NodeId>,
+        name: &str,
+        token_kind: Option<TokenKind>,
+    ) -> NodeId {
+        if parent.is_none() != self.nodes.is_empty() {
+            panic!("the root, and only the root, has no parent: {}", name);
+        }
+
+        let id = NodeId(self.nodes.len());
+        let name = match self.names.get(name) {
+            Some(name) => Arc::clone(name),
+            None => {
+                let shared: Arc<str> = name.into();
+                self.names.insert(name.to_string(), Arc::clone(&shared));
+                shared
+            },
+        };
+
+        if parent.is_none() {
+            self.root = Some(id);
+        }
+
+        let prev_sibling = parent.and_then(|parent| self.nodes[parent.0].last_child);
+        if let Some(parent) = parent {
+            let parent = &mut self.nodes[parent.0];
+            if parent.first_child.is_none() {
+                parent.first_child = Some(id);
+            }
+            parent.last_child = Some(id);
+        }
+        if let Some(prev_sibling) = prev_sibling {
+            self.nodes[prev_sibling.0].next_sibling = Some(id);
+        }
+
+        self.nodes.push(TreeNode {
+            name,
+            token_kind,
+            alt_no: None,
+            token: None,
+            parent,
+            first_child: None,
+            last_child: None,
+            prev_sibling,
+            next_sibling: None,
+        });
+        id
+    }
+
+    // The node takes the place of a new node of the same rule, and becomes its only child.
+    pub fn wrap(
+        &mut self,
+        id: NodeId,
+    ) -> NodeId {
+        let wrapper = NodeId(self.nodes.len());
+        let node = &self.nodes[id.0];
+        let (parent, prev_sibling, next_sibling) =
+            (node.parent, node.prev_sibling, node.next_sibling);
+
+        self.nodes.push(TreeNode {
+            name: Arc::clone(&node.name),
+            token_kind: node.token_kind,
+            alt_no: None,
+            token: None,
+            parent,
+            first_child: Some(id),
+            last_child: Some(id),
+            prev_sibling,
+            next_sibling,
+        });
+
+        match parent {
+            Some(parent) => {
+                let parent = &mut self.nodes[parent.0];
+                if parent.first_child == Some(id) {
+                    parent.first_child = Some(wrapper);
+                }
+                if parent.last_child == Some(id) {
+                    parent.last_child = Some(wrapper);
+                }
+            },
+            None => self.root = Some(wrapper),
+        }
+        if let Some(prev_sibling) = prev_sibling {
+            self.nodes[prev_sibling.0].next_sibling = Some(wrapper);
+        }
+        if let Some(next_sibling) = next_sibling {
+            self.nodes[next_sibling.0].prev_sibling = Some(wrapper);
+        }
+
+        let node = &mut self.nodes[id.0];
+        node.parent = Some(wrapper);
+        node.prev_sibling = None;
+        node.next_sibling = None;
+        wrapper
+    }
+
+    pub fn node(
+        &self,
+        id: NodeId,
+    ) -> &TreeNode<'a> {
+        &self.nodes[id.0]
+    }
+
+    pub fn parent(
+        &self,
+        id: NodeId,
+    ) -> Option<NodeId> {
+        self.nodes[id.0].parent
+    }
+
+    pub fn set_alt(
+        &mut self,
+        id: NodeId,
+        alt_no: usize,
+    ) {
+        self.nodes[id.0].alt_no = Some(alt_no);
+    }
+
+    pub fn set_token(
+        &mut self,
+        id: NodeId,
+        token: Token<'a>,
+    ) {
+        self.nodes[id.0].token = Some(token);
+    }
+
+    pub fn build(self) -> Tree<'a> {
+        let root = self.root.expect("a tree needs a root");
+
+        Tree {
+            nodes: self.nodes.into(),
+            root,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use crate::lang::lexer::token::TokenKind;
+    use crate::lang::lexer::v0::Lexer;
+    use crate::lang::parser::node::display_of;
+    use crate::lang::parser::rule::RulePart;
+    use crate::lang::parser::rules::Rules;
+    use crate::lang::parser::tree::NodeId;
+    use crate::lang::parser::tree::Tree;
+    use crate::lang::parser::tree::TreeBuilder;
+    use crate::lang::parser_impl::recursive_descent_parser::recursive_descent_parse;
+    use crate::lang::parser_impl::recursive_descent_parser::recursive_descent_parse_tree;
+
+    #[test]
+    fn test_tree_of_node() {
+        let rules: Rules = "
+S       -> stmt S | EPSILON
+stmt    -> ID = INT ; | RETURN ID ;
+        "
+        .try_into()
+        .unwrap();
+        let lexer: Lexer = "a = 1; return a;".into();
+        let node = recursive_descent_parse(&rules, lexer.into_iter()).unwrap();
+
+        let tree = Tree::from(&node);
+        assert_eq!(tree.to_string(), display_of(&node));
+        assert_eq!(tree.num_nodes(), 12);
+
+        let root = tree.root();
+        assert_eq!(tree.node(root).name(), "S");
+        assert_eq!(tree.node(root).alt_no(), Some(0));
+        assert_eq!(tree.parent(root), None);
+
+        let children = tree.children(root).collect::<Vec<_>>();
+        assert_eq!(children, [NodeId(1), NodeId(6)]);
+        assert_eq!(tree.node(children[0]).next_sibling(), Some(children[1]));
+        assert_eq!(tree.node(children[1]).prev_sibling(), Some(children[0]));
+        assert_eq!(tree.node(root).last_child(), Some(children[1]));
+
+        let assign = tree.children(children[0]).collect::<Vec<_>>();
+        assert_eq!(tree.node(assign[2]).token_kind(), Some(TokenKind::Int));
+        assert_eq!(tree.node(assign[2]).token().unwrap().text, "1");
+        assert!(assign
+            .iter()
+            .all(|it| tree.parent(*it) == Some(children[0])));
+
+        // Numbered in preorder, the subtree of the second statement last.
+        assert!(tree
+            .preorder(root)
+            .enumerate()
+            .all(|(num, id)| id.index() == num));
+        assert_eq!(tree.preorder(children[0]).count(), 5);
+        assert_eq!(
+            tree.tokens().iter().map(|it| it.text).collect::<Vec<_>>(),
+            ["a", "=", "1", ";", "return", "a", ";"]
+        );
+
+        // Clones share the nodes, also with other threads.
+        let clone = tree.clone();
+        assert!(std::ptr::eq(tree.node(root), clone.node(root)));
+        let num_nodes = thread::scope(|s| s.spawn(|| clone.num_nodes()).join().unwrap());
+        assert_eq!(num_nodes, 12);
+    }
+
+    #[test]
+    fn test_parse_tree() {
+        let rules: Rules = "
+%left + -
+%left * /
+S -> S + S | S - S | S * S | S / S | ( S ) | INT | ID
+        "
+        .try_into()
+        .unwrap();
+        let program = "1 + 2 * 3 - a";
+
+        let lexer: Lexer = program.into();
+        let tree = recursive_descent_parse_tree(&rules, lexer.into_iter()).unwrap();
+        let lexer: Lexer = program.into();
+        let node = recursive_descent_parse(&rules, lexer.into_iter()).unwrap();
+        assert_eq!(tree.to_string(), display_of(&node));
+        assert_eq!(display_of(&tree.to_node(&rules)), display_of(&node));
+
+        // The operators wrapped the first expression, up to the root.
+        let root = tree.root();
+        assert_ne!(root, NodeId(0));
+        assert_eq!(tree.parent(root), None);
+        assert_eq!(tree.node(root).alt_no(), Some(1));
+        let children = tree.children(root).collect::<Vec<_>>();
+        assert_eq!(tree.node(children[1]).token().unwrap().text, "-");
+        assert_eq!(tree.node(children[0]).next_sibling(), Some(children[1]));
+        assert_eq!(tree.node(children[2]).prev_sibling(), Some(children[1]));
+        assert_eq!(tree.preorder(root).count(), tree.num_nodes());
+
+        let lexer: Lexer = "1 + * 2".into();
+        let err = recursive_descent_parse_tree(&rules, lexer.into_iter()).unwrap_err();
+        assert_eq!(err.partial_tree().tokens().len(), 2);
+    }
+
+    #[test]
+    fn test_tree_builder() {
+        let rules: Rules = "S -> ID S | EPSILON".try_into().unwrap();
+        let s: RulePart = rules.rules().first().unwrap().into();
+
+        let mut builder = TreeBuilder::new();
+        let mut parent = builder.add(None, &s);
+        for _ in 0..10_000 {
+            builder.add(Some(parent), &RulePart::Token(TokenKind::Id));
+            parent = builder.add(Some(parent), &s);
+        }
+        builder.add(Some(parent), &RulePart::Token(TokenKind::Epsilon));
+        let tree = builder.build();
+
+        assert_eq!(tree.num_nodes(), 20_002);
+        assert_eq!(tree.preorder(tree.root()).count(), 20_002);
+        assert_eq!(tree.preorder(NodeId(20_000)).count(), 2);
+        assert_eq!(tree.node(NodeId(20_001)).name(), "EPSILON");
+        assert_eq!(tree.parent(NodeId(20_001)), Some(NodeId(20_000)));
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+
+use crate::lang::lexer::token::TokenKind;
+use crate::lang::parser::precedence::Fixity;
+use crate::lang::parser::rule::RulePart;
+use crate::lang::parser::rules::Rules;
+
+// The rules as a combined ANTLR4 grammar. Parser rules have to start lowercase in ANTLR, so `S`
+// becomes `s`. Tokens with a fixed text are literals, the others get lexer rules. Operator
+// alternatives of expression rules are ordered by precedence, tightest first, as ANTLR takes
+// precedence from the order of left-recursive alternatives. Predicates become comments.
+pub fn antlr4_of(
+    rules: &Rules,
+    grammar_name: &str,
+) -> String {
+    let names = parser_rule_names(rules);
+    let expression_rules = rules.expression_rules();
+
+    let mut lines = vec![format!("grammar {};", grammar_name)];
+
+    if !rules.precedence().is_empty() {
+        lines.push(String::new());
+        for line in rules.precedence().to_string().lines() {
+            lines.push(format!("// {}", line));
+        }
+    }
+
+    let mut used_tokens: Vec<TokenKind> = vec![];
+    for rule in rules.rules() {
+        let rule = rule.borrow();
+
+        // alt_no to (precedence, right associative) of the operator alternatives.
+        let mut operators: HashMap<usize, (usize, bool)> = HashMap::new();
+        if let Some(expression) = expression_rules.get(rule.name()) {
+            for (alt_no, precedence, fixity) in expression.binary.values() {
+                operators.insert(*alt_no, (*precedence, *fixity == Fixity::Right));
+            }
+            for (alt_no, precedence) in expression.prefix.values() {
+                operators.insert(*alt_no, (*precedence, false));
+            }
+        }
+
+        let mut alt_nos = (0..rule.alternatives.len()).collect::<Vec<_>>();
+        alt_nos.sort_by_key(|alt_no| match operators.get(alt_no) {
+            Some((precedence, _)) => (0, usize::MAX - precedence),
+            None => (1, 0),
+        });
+
+        lines.push(String::new());
+        lines.push(names[rule.name()].clone());
+        for (index, alt_no) in alt_nos.into_iter().enumerate() {
+            let mut parts = rule.alternatives[alt_no]
+                .iter()
+                .filter(|it| !it.is_epsilon())
+                .map(|it| antlr4_of_rule_part(it, &names, &mut used_tokens))
+                .collect::<Vec<_>>();
+            if operators.get(&alt_no).is_some_and(|(_, right)| *right) {
+                parts.insert(0, "<assoc=right>".to_string());
+            }
+
+            let separator = match index {
+                0 => ":",
+                _ => "|",
+            };
+            lines.push(
+                format!("    {} {}", separator, parts.join(" "))
+                    .trim_end()
+                    .to_string(),
+            );
+        }
+        lines.push("    ;".to_string());
+    }
+
+    let lexer_rules = used_tokens
+        .iter()
+        .filter_map(|tk| lexer_rule(*tk).map(|it| (tk.upper_name(), it)))
+        .chain([("WS", "[ \\t\\r\\n]+ -> skip")])
+        .collect::<Vec<_>>();
+    let width = lexer_rules
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap();
+    lines.push(String::new());
+    for (name, rule) in lexer_rules {
+        lines.push(format!("{: <width$} : {} ;", name, rule));
+    }
+
+    lines.join("\n") + "\n"
+}
+
+// Rule names starting uppercase are lowercased, with `_` appended while taken.
+fn parser_rule_names(rules: &Rules) -> HashMap<String, String> {
+    let mut taken: Vec<String> = rules
+        .rules()
+        .iter()
+        .map(|it| it.borrow().name().to_string())
+        .filter(|it| !it.starts_with(|c: char| c.is_ascii_uppercase()))
+        .collect();
+
+    rules
+        .rules()
+        .iter()
+        .map(|rule| {
+            let name = rule.borrow().name().to_string();
+            if !name.starts_with(|c: char| c.is_ascii_uppercase()) {
+                return (name.clone(), name);
+            }
+
+            let mut renamed = name[..1].to_lowercase() + &name[1..];
+            while taken.contains(&renamed) {
+                renamed.push('_');
+            }
+            taken.push(renamed.clone());
+            (name, renamed)
+        })
+        .collect()
+}
+
+fn antlr4_of_rule_part(
+    part: &RulePart,
+    names: &HashMap<String, String>,
+    used_tokens: &mut Vec<TokenKind>,
+) -> String {
+    match part {
+        RulePart::Rule(rule) => names[rule.borrow().name()].clone(),
+        RulePart::Token(tk) => {
+            if !used_tokens.contains(tk) {
+                used_tokens.push(*tk);
+            }
+            match tk.repr() {
+                Some(repr) => format!("'{}'", repr.replace('\\', "\\\\").replace('\'', "\\'")),
+                None => tk.upper_name().to_string(),
+            }
+        },
+        RulePart::Predicate(predicate, target) => {
+            format!("/* {}{} */", predicate.repr(), target.name())
+        },
+    }
+}
+
+fn lexer_rule(tk: TokenKind) -> Option<&'static str> {
+    match tk {
+        TokenKind::Id => Some("[a-zA-Z_] [a-zA-Z_0-9]*"),
+        TokenKind::Int => Some("[0-9]+"),
+        TokenKind::String => Some("'\"' ( ~[\"\\\\] | '\\\\' . )* '\"'"),
+        _ => None,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::lang::parser::rules::Rules;
+    use crate::lang::parser_export::antlr4::antlr4_of;
+
+    #[test]
+    fn test_antlr4_of() {
+        let rules: Rules = "
+%left + -
+%left *
+%right ^
+S    -> expr ; S | EPSILON
+expr -> ( expr ) | expr + expr | expr ^ expr | expr * expr | ID
+s    -> STRING
+        "
+        .try_into()
+        .unwrap();
+
+        assert_eq!(
+            antlr4_of(&rules, "Expressions"),
+            "\
+grammar Expressions;
+
+// %left + -
+// %left *
+// %right ^
+
+s_
+    : expr ';' s_
+    |
+    ;
+
+expr
+    : <assoc=right> expr '^' expr
+    | expr '*' expr
+    | expr '+' expr
+    | '(' expr ')'
+    | ID
+    ;
+
+s
+    : STRING
+    ;
+
+ID     : [a-zA-Z_] [a-zA-Z_0-9]* ;
+STRING : '\"' ( ~[\"\\\\] | '\\\\' . )* '\"' ;
+WS     : [ \\t\\r\\n]+ -> skip ;
+"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use crate::lang::parser::rule::RulePart;
+use crate::lang::parser::rules::Rules;
+
+// The rules as a Graphviz digraph, an edge per referenced rule labeled with the alternatives
+// referring to it. The start rule is drawn bold, predicates are dashed.
+pub fn dot_of(rules: &Rules) -> String {
+    let mut dot = "digraph rules {\n    node [shape=box];\n".to_string();
+
+    for (index, rule) in rules.rules().iter().enumerate() {
+        let style = match index {
+            0 => " [style=bold]",
+            _ => "",
+        };
+        dot += &format!("    \"{}\"{};\n", escape(rule.borrow().name()), style);
+    }
+
+    for rule in rules.rules() {
+        // (target, alternatives, through a predicate only), in order of first reference.
+        let mut edges: Vec<(String, Vec<usize>, bool)> = vec![];
+
+        for (alt_no, alt) in rule.borrow().alternatives.iter().enumerate() {
+            for part in alt {
+                let RulePart::Rule(target) = part.predicate_target()
+                else {
+                    continue;
+                };
+                let target = target.borrow().name().to_string();
+                let is_predicate = part.is_predicate();
+
+                match edges.iter_mut().find(|(it, _, _)| *it == target) {
+                    None => edges.push((target, vec![alt_no], is_predicate)),
+                    Some((_, alts, only_predicate)) => {
+                        if alts.last() != Some(&alt_no) {
+                            alts.push(alt_no);
+                        }
+                        *only_predicate = *only_predicate && is_predicate;
+                    },
+                }
+            }
+        }
+
+        for (target, alts, only_predicate) in edges {
+            let alts = alts
+                .iter()
+                .map(|it| it.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let style = match only_predicate {
+                true => ", style=dashed",
+                false => "",
+            };
+            dot += &format!(
+                "    \"{}\" -> \"{}\" [label=\"{}\"{}];\n",
+                escape(rule.borrow().name()),
+                escape(&target),
+                alts,
+                style,
+            );
+        }
+    }
+
+    dot += "}\n";
+    dot
+}
+
+fn escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::lang::parser::rules::Rules;
+    use crate::lang::parser_export::dot::dot_of;
+
+    #[test]
+    fn test_dot_of() {
+        let rules: Rules = "
+S    -> expr ; S | EPSILON
+expr -> expr + term | term | ( expr )
+term -> INT
+        "
+        .try_into()
+        .unwrap();
+
+        assert_eq!(
+            dot_of(&rules),
+            "\
+digraph rules {
+    node [shape=box];
+    \"S\" [style=bold];
+    \"expr\";
+    \"term\";
+    \"S\" -> \"expr\" [label=\"0\"];
+    \"S\" -> \"S\" [label=\"0\"];
+    \"expr\" -> \"expr\" [label=\"0, 2\"];
+    \"expr\" -> \"term\" [label=\"0, 1\"];
+}
+"
+        );
+    }
+}
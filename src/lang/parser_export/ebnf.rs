@@ -0,0 +1,147 @@
+use crate::lang::lexer::token::TokenKind;
+use crate::lang::parser::rule::RulePart;
+use crate::lang::parser::rules::Rules;
+
+// The rules in the EBNF notation of the W3C XML specification. Tokens with a fixed text are
+// quoted, the others are given lexical rules after the grammar rules. An epsilon alternative
+// makes the others optional, precedence declarations and predicates become comments.
+pub fn ebnf_of(rules: &Rules) -> String {
+    let mut lines = vec![];
+
+    if !rules.precedence().is_empty() {
+        for line in rules.precedence().to_string().lines() {
+            lines.push(format!("/* {} */", line));
+        }
+    }
+
+    let width = rules
+        .rules()
+        .iter()
+        .map(|it| it.borrow().name().len())
+        .max()
+        .unwrap_or_default();
+
+    let mut used_tokens: Vec<TokenKind> = vec![];
+    for rule in rules.rules() {
+        let rule = rule.borrow();
+
+        let mut alternatives = vec![];
+        let mut optional = false;
+        for alt in &rule.alternatives {
+            if alt.iter().all(|it| it.is_epsilon()) {
+                optional = true;
+                continue;
+            }
+            let alt = alt
+                .iter()
+                .filter(|it| !it.is_epsilon())
+                .map(|it| ebnf_of_rule_part(it, &mut used_tokens))
+                .collect::<Vec<_>>()
+                .join(" ");
+            alternatives.push(alt);
+        }
+
+        let indent = " ".repeat(width);
+        let (open, close, continuation) = match optional {
+            true => ("( ", " )?", "  "),
+            false => ("", "", ""),
+        };
+        match alternatives.is_empty() {
+            true => lines.push(format!("{: <width$} ::= ''", rule.name())),
+            false => {
+                let last = alternatives.len() - 1;
+                for (alt_no, alt) in alternatives.iter().enumerate() {
+                    let close = if alt_no == last {
+                        close
+                    }
+                    else {
+                        ""
+                    };
+                    lines.push(match alt_no {
+                        0 => format!("{: <width$} ::= {}{}{}", rule.name(), open, alt, close),
+                        _ => format!("{}   {}| {}{}", indent, continuation, alt, close),
+                    });
+                }
+            },
+        }
+    }
+
+    let lexical = used_tokens
+        .iter()
+        .filter_map(|tk| lexical_rule(*tk).map(|it| (tk.upper_name(), it)))
+        .collect::<Vec<_>>();
+    if !lexical.is_empty() {
+        lines.push(String::new());
+        let width = lexical.iter().map(|(name, _)| name.len()).max().unwrap();
+        for (name, rule) in lexical {
+            lines.push(format!("{: <width$} ::= {}", name, rule));
+        }
+    }
+
+    lines.join("\n") + "\n"
+}
+
+fn ebnf_of_rule_part(
+    part: &RulePart,
+    used_tokens: &mut Vec<TokenKind>,
+) -> String {
+    match part {
+        RulePart::Rule(rule) => rule.borrow().name().to_string(),
+        RulePart::Token(tk) => {
+            if !used_tokens.contains(tk) {
+                used_tokens.push(*tk);
+            }
+            match tk.repr() {
+                Some(repr) if repr.contains('\'') => format!("\"{}\"", repr),
+                Some(repr) => format!("'{}'", repr),
+                None => tk.upper_name().to_string(),
+            }
+        },
+        RulePart::Predicate(predicate, target) => {
+            format!("/* {}{} */", predicate.repr(), target.name())
+        },
+    }
+}
+
+fn lexical_rule(tk: TokenKind) -> Option<&'static str> {
+    match tk {
+        TokenKind::Id => Some("[a-zA-Z_] [a-zA-Z_0-9]*"),
+        TokenKind::Int => Some("[0-9]+"),
+        TokenKind::String => Some("'\"' ( [^\"\\] | '\\' . )* '\"'"),
+        _ => None,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::lang::parser::rules::Rules;
+    use crate::lang::parser_export::ebnf::ebnf_of;
+
+    #[test]
+    fn test_ebnf_of() {
+        let rules: Rules = "
+%left + -
+S    -> expr ; S | EPSILON
+expr -> expr + expr | expr - expr | ( expr ) | ID | INT
+        "
+        .try_into()
+        .unwrap();
+
+        assert_eq!(
+            ebnf_of(&rules),
+            "\
+/* %left + - */
+S    ::= ( expr ';' S )?
+expr ::= expr '+' expr
+       | expr '-' expr
+       | '(' expr ')'
+       | ID
+       | INT
+
+ID  ::= [a-zA-Z_] [a-zA-Z_0-9]*
+INT ::= [0-9]+
+"
+        );
+    }
+}
@@ -0,0 +1,4 @@
+pub mod antlr4;
+pub mod dot;
+pub mod ebnf;
+pub mod railroad;
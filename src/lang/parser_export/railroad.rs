@@ -0,0 +1,335 @@
+use crate::lang::parser::rule::Rule;
+use crate::lang::parser::rule::RulePart;
+use crate::lang::parser::rules::Rules;
+
+const CHAR_WIDTH: usize = 8;
+const BOX_HEIGHT: usize = 22;
+const BOX_PADDING: usize = 10;
+const GAP: usize = 10;
+const ARC: usize = 10;
+
+const MARGIN: usize = 20;
+const TITLE_HEIGHT: usize = 30;
+
+const STYLE: &str = "<style>\
+path, rect { fill: none; stroke: black; stroke-width: 1.5 } \
+text { font: 13px monospace; text-anchor: middle } \
+.title { font-weight: bold; text-anchor: start } \
+.terminal rect { fill: #fff8d0 } \
+.nonterminal rect { fill: #dceeff } \
+.comment text { font-style: italic }\
+</style>";
+
+// Railroad diagrams, one SVG per rule in declaration order: terminals in rounded boxes, rules in
+// square ones, an epsilon alternative is a line passing by the others.
+pub fn railroad_of(rules: &Rules) -> Vec<(String, String)> {
+    rules
+        .rules()
+        .iter()
+        .map(|rule| {
+            let rule = rule.borrow();
+            (rule.name().to_string(), svg_of(&rule))
+        })
+        .collect()
+}
+
+// All diagrams on a single page, for publishing the grammar.
+pub fn railroad_html_of(
+    rules: &Rules,
+    title: &str,
+) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{}</title></head>\n<body>\n",
+        escape(title)
+    );
+    for (name, svg) in railroad_of(rules) {
+        html += &format!(
+            "<h2 id=\"{}\">{}</h2>\n{}",
+            escape(&name),
+            escape(&name),
+            svg
+        );
+    }
+    html += "</body>\n</html>\n";
+    html
+}
+
+
+enum Diagram {
+    Terminal(String),
+    NonTerminal(String),
+    Comment(String),
+    Skip,
+    Sequence(Vec<Diagram>),
+    Choice(Vec<Diagram>),
+}
+
+impl Diagram {
+    fn of_rule(rule: &Rule) -> Self {
+        let mut alternatives = rule
+            .alternatives
+            .iter()
+            .map(|alt| {
+                let mut parts = alt
+                    .iter()
+                    .filter(|it| !it.is_epsilon())
+                    .map(Self::of_rule_part)
+                    .collect::<Vec<_>>();
+                match parts.len() {
+                    0 => Diagram::Skip,
+                    1 => parts.remove(0),
+                    _ => Diagram::Sequence(parts),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        match alternatives.len() {
+            0 => Diagram::Skip,
+            1 => alternatives.remove(0),
+            _ => Diagram::Choice(alternatives),
+        }
+    }
+
+    fn of_rule_part(part: &RulePart) -> Self {
+        match part {
+            RulePart::Rule(rule) => Diagram::NonTerminal(rule.borrow().name().to_string()),
+            RulePart::Token(tk) => Diagram::Terminal(tk.repr_or_name().to_string()),
+            RulePart::Predicate(predicate, target) => {
+                Diagram::Comment(format!("{}{}", predicate.repr(), target.name()))
+            },
+        }
+    }
+
+    fn width(&self) -> usize {
+        match self {
+            Diagram::Terminal(text) | Diagram::NonTerminal(text) | Diagram::Comment(text) => {
+                text.chars().count() * CHAR_WIDTH + 2 * BOX_PADDING
+            },
+            Diagram::Skip => 0,
+            Diagram::Sequence(items) => {
+                items.iter().map(|it| it.width()).sum::<usize>() + GAP * (items.len() - 1)
+            },
+            Diagram::Choice(branches) => {
+                branches.iter().map(|it| it.width()).max().unwrap() + 4 * ARC
+            },
+        }
+    }
+
+    // Above the line the diagram is entered and left on.
+    fn up(&self) -> usize {
+        match self {
+            Diagram::Terminal(_) | Diagram::NonTerminal(_) | Diagram::Comment(_) => BOX_HEIGHT / 2,
+            Diagram::Skip => 0,
+            Diagram::Sequence(items) => items.iter().map(|it| it.up()).max().unwrap(),
+            Diagram::Choice(branches) => branches[0].up(),
+        }
+    }
+
+    // Below that line.
+    fn down(&self) -> usize {
+        match self {
+            Diagram::Terminal(_) | Diagram::NonTerminal(_) | Diagram::Comment(_) => BOX_HEIGHT / 2,
+            Diagram::Skip => 0,
+            Diagram::Sequence(items) => items.iter().map(|it| it.down()).max().unwrap(),
+            Diagram::Choice(branches) => {
+                let offsets = branch_offsets(branches);
+                offsets.last().unwrap() + branches.last().unwrap().down()
+            },
+        }
+    }
+
+    fn render(
+        &self,
+        x: usize,
+        y: usize,
+        svg: &mut String,
+    ) {
+        match self {
+            Diagram::Terminal(text) => render_box("terminal", 10, text, x, y, svg),
+            Diagram::NonTerminal(text) => render_box("nonterminal", 0, text, x, y, svg),
+            Diagram::Comment(text) => {
+                svg.push_str(&format!(
+                    "<g class=\"comment\"><text x=\"{}\" y=\"{}\">{}</text></g>\n",
+                    x + self.width() / 2,
+                    y - 4,
+                    escape(text)
+                ));
+                svg.push_str(&format!("<path d=\"M {} {} h {}\"/>\n", x, y, self.width()));
+            },
+            Diagram::Skip => {},
+            Diagram::Sequence(items) => {
+                let mut x = x;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        svg.push_str(&format!("<path d=\"M {} {} h {}\"/>\n", x, y, GAP));
+                        x += GAP;
+                    }
+                    item.render(x, y, svg);
+                    x += item.width();
+                }
+            },
+            Diagram::Choice(branches) => {
+                let inner = self.width() - 4 * ARC;
+                let offsets = branch_offsets(branches);
+
+                for (branch, offset) in branches.iter().zip(offsets) {
+                    let branch_y = y + offset;
+                    let (left, right) = match offset {
+                        0 => (format!("h {}", 2 * ARC), format!("h {}", 2 * ARC)),
+                        _ => {
+                            let v = offset - 2 * ARC;
+                            (
+                                format!(
+                                    "a {a} {a} 0 0 1 {a} {a} v {v} a {a} {a} 0 0 0 {a} {a}",
+                                    a = ARC,
+                                    v = v
+                                ),
+                                format!(
+                                    "a {a} {a} 0 0 0 {a} -{a} v -{v} a {a} {a} 0 0 1 {a} -{a}",
+                                    a = ARC,
+                                    v = v
+                                ),
+                            )
+                        },
+                    };
+
+                    let branch_x = x + 2 * ARC;
+                    let branch_end = branch_x + branch.width();
+                    svg.push_str(&format!("<path d=\"M {} {} {}\"/>\n", x, y, left));
+                    branch.render(branch_x, branch_y, svg);
+                    svg.push_str(&format!(
+                        "<path d=\"M {} {} h {} {}\"/>\n",
+                        branch_end,
+                        branch_y,
+                        branch_x + inner - branch_end,
+                        right
+                    ));
+                }
+            },
+        }
+    }
+}
+
+// The distance of each branch's line from the line of the first one.
+fn branch_offsets(branches: &[Diagram]) -> Vec<usize> {
+    let mut offsets = vec![0];
+    for pair in branches.windows(2) {
+        let distance = (pair[0].down() + GAP + pair[1].up()).max(2 * ARC);
+        offsets.push(offsets.last().unwrap() + distance);
+    }
+    offsets
+}
+
+fn render_box(
+    class: &str,
+    radius: usize,
+    text: &str,
+    x: usize,
+    y: usize,
+    svg: &mut String,
+) {
+    let width = text.chars().count() * CHAR_WIDTH + 2 * BOX_PADDING;
+    svg.push_str(&format!(
+        "<g class=\"{}\"><rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"{}\"/>\
+         <text x=\"{}\" y=\"{}\">{}</text></g>\n",
+        class,
+        x,
+        y - BOX_HEIGHT / 2,
+        width,
+        BOX_HEIGHT,
+        radius,
+        x + width / 2,
+        y + 4,
+        escape(text)
+    ));
+}
+
+fn svg_of(rule: &Rule) -> String {
+    let diagram = Diagram::of_rule(rule);
+
+    let width = diagram.width() + 2 * MARGIN;
+    let height = TITLE_HEIGHT + diagram.up() + diagram.down() + MARGIN / 2;
+    let y = TITLE_HEIGHT + diagram.up();
+    let end = MARGIN + diagram.width();
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n",
+        w = width,
+        h = height
+    );
+    svg.push_str(STYLE);
+    svg.push('\n');
+    svg.push_str(&format!(
+        "<text class=\"title\" x=\"{}\" y=\"{}\">{}</text>\n",
+        MARGIN / 2,
+        MARGIN - 4,
+        escape(rule.name())
+    ));
+
+    // Entry and exit bars.
+    svg.push_str(&format!(
+        "<path d=\"M {s} {top} v 16 M {s} {y} h {gap}\"/>\n",
+        s = MARGIN / 2,
+        top = y - 8,
+        y = y,
+        gap = MARGIN / 2
+    ));
+    diagram.render(MARGIN, y, &mut svg);
+    svg.push_str(&format!(
+        "<path d=\"M {e} {y} h {gap} M {s} {top} v 16\"/>\n",
+        e = end,
+        y = y,
+        gap = MARGIN / 2,
+        s = end + MARGIN / 2,
+        top = y - 8
+    ));
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::lang::parser::rules::Rules;
+    use crate::lang::parser_export::railroad::railroad_html_of;
+    use crate::lang::parser_export::railroad::railroad_of;
+
+    #[test]
+    fn test_railroad_of() {
+        let rules: Rules = "
+S    -> ID = args ;
+args -> INT , args | INT | EPSILON
+        "
+        .try_into()
+        .unwrap();
+
+        let diagrams = railroad_of(&rules);
+        assert_eq!(diagrams.len(), 2);
+
+        // ID, =, args and ; in a row: 36 + 28 + 52 + 28 wide, 3 gaps and 2 margins.
+        let (name, svg) = &diagrams[0];
+        assert_eq!(name, "S");
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"214\""));
+        assert!(svg.contains("<g class=\"terminal\"><rect x=\"20\" y=\"30\" width=\"36\""));
+        assert!(svg.contains("<text x=\"130\" y=\"45\">args</text>"));
+        assert_eq!(svg.matches("<rect").count(), 4);
+
+        // Three branches, the last one passing by.
+        let (_, svg) = &diagrams[1];
+        assert_eq!(svg.matches("<rect").count(), 4);
+        assert_eq!(svg.matches(" a 10 10 0 0 1 10 10 v ").count(), 2);
+        assert!(svg.ends_with("</svg>\n"));
+
+        let html = railroad_html_of(&rules, "Grammar");
+        assert_eq!(html.matches("<svg ").count(), 2);
+        assert!(html.contains("<h2 id=\"args\">args</h2>"));
+    }
+}
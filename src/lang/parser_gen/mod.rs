@@ -0,0 +1 @@
+pub mod recursive_descent_gen;
@@ -0,0 +1,623 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::lang::error::GenerateError;
+use crate::lang::lexer::token::TokenKind;
+use crate::lang::parser::rule::RulePart;
+use crate::lang::parser::rules::Rules;
+
+// Writes the generated parser into the given file, meant to be called from a build.rs:
+//
+//   let mut rules = Rules::parse(GRAMMAR)?;
+//   rules.make_ready_for_recursive_decent(128)?;
+//   let out = Path::new(&env::var("OUT_DIR").unwrap()).join("parser.rs");
+//   write_recursive_descent_parser(&rules, "toylang1", &out)?;
+//
+// and then included with: include!(concat!(env!("OUT_DIR"), "/parser.rs"));
+pub fn write_recursive_descent_parser(
+    rules: &Rules,
+    crate_path: &str,
+    out: &Path,
+) -> Result<(), GenerateError> {
+    let source = generate_recursive_descent_parser(rules, crate_path)?;
+
+    fs::write(out, source).map_err(|err| GenerateError::Io {
+        path: out.display().to_string(),
+        error: err.to_string(),
+    })
+}
+
+// Generates the source of a rust module with one parse function per rule, building a Tree with
+// the rules known by name only, so it needs no Rules at run time. The crate_path is how the
+// generated code refers to this crate: `crate` from within, `toylang1` from elsewhere.
+pub fn generate_recursive_descent_parser(
+    rules: &Rules,
+    crate_path: &str,
+) -> Result<String, GenerateError> {
+    rules.validate()?;
+    rules.is_backtrack_free()?;
+
+    let mut expression_rules = rules.expression_rules().into_keys().collect::<Vec<_>>();
+    if !expression_rules.is_empty() {
+        expression_rules.sort();
+        return Err(GenerateError::ExpressionRules(expression_rules));
+    }
+
+    let fn_names = fn_names(rules)?;
+    let first = rules.first_set();
+    let follow = rules.follow_set_with_eof();
+    let start = rules.start_set_with_eof();
+
+    let mut out = String::new();
+
+    let start_rule = rules.rules().first().unwrap().borrow().name().to_string();
+    out.push_str(&HEADER.replace("{crate}", crate_path));
+    write!(
+        out,
+        "
+pub fn parse<'a, T: Iterator<Item = LexerResult<'a>>>(tokens: T) -> TreeParseResult<'a> {{
+    GeneratedParser::new({}, tokens).parse()
+}}
+
+",
+        const_name_of(&fn_names[&start_rule])
+    )
+    .unwrap();
+    out.push_str(&format!(
+        "pub const GRAMMAR: &str = r#\"\n{}\"#;\n\n",
+        grammar_of(rules)
+    ));
+
+    out.push_str("// The rules, by the names of their nodes in the tree.\n");
+    for rule in rules.rules() {
+        let name = rule.borrow().name().to_string();
+        writeln!(
+            out,
+            "const {}: &str = \"{}\";",
+            const_name_of(&fn_names[&name]),
+            name
+        )
+        .unwrap();
+    }
+    out.push_str(HELPERS);
+
+    out.push_str("\n    // ============================================================================================\n");
+
+    out.push_str(&format!(
+        "
+    fn parse(mut self) -> TreeParseResult<'a> {{
+        self.{}()?;
+
+        if self.has_peek() && !self.peek_is(TokenKind::Eof) {{
+            let err = format!(
+                \"unexpected token, expecting end of input, got: {{}}\",
+                self.peek().unwrap(),
+            );
+            self.err_unexpected(None, &[TokenKind::Eof], err)?;
+        }}
+
+        Ok(self.tree.build())
+    }}
+",
+        fn_names[&start_rule]
+    ));
+
+    for rule in rules.rules() {
+        let rule = rule.borrow();
+        let my_name = rule.name();
+
+        let mut body = String::new();
+
+        let epsilon_alt = rule
+            .alternatives
+            .iter()
+            .position(|it| it.len() == 1 && it[0].is_epsilon());
+
+        let start_tokens = names_of(&first[my_name]);
+        let follow_tokens = names_of(&follow[my_name]);
+        let my_const = const_name_of(&fn_names[my_name]);
+        let err_rule = match epsilon_alt {
+            None => format!(
+                "self.err_rule({}, \"{}\", None, &[{}])",
+                my_const,
+                start_tokens,
+                variants_of(&first[my_name])
+            ),
+            Some(_) => format!(
+                "self.err_rule({}, \"{}\", Some(\"{}\"), &[{}])",
+                my_const,
+                start_tokens,
+                follow_tokens,
+                variants_of(&first[my_name].union(&follow[my_name]).copied().collect())
+            ),
+        };
+
+        if rule.alternatives.len() == 1 && epsilon_alt.is_none() {
+            // Nothing to choose from, just as the hand written fn_call.
+            body.push_str("        self.set_alt(0);\n");
+            body.push_str(&parts_of(&rule.alternatives[0], &fn_names, "        "));
+            body.push_str("\n        Ok(())\n");
+        }
+        else {
+            let mut branches = vec![];
+
+            for (alt_no, alt) in rule.alternatives.iter().enumerate() {
+                if Some(alt_no) == epsilon_alt {
+                    continue;
+                }
+
+                let alt_start = &start[&(my_name.to_string(), alt_no)];
+                branches.push(format!(
+                    "if self.peek_is_in(&[{}]) {{\n            self.set_alt({});\n{}\n            Ok(())\n        }}",
+                    variants_of(alt_start),
+                    alt_no,
+                    parts_of(alt, &fn_names, "            "),
+                ));
+            }
+
+            if let Some(epsilon_alt) = epsilon_alt {
+                branches.push(format!(
+                    "if self.peek_is_in(&[{}]) {{\n            self.set_alt({});\n            Ok(())\n        }}",
+                    variants_of(&follow[my_name]),
+                    epsilon_alt,
+                ));
+            }
+
+            body.push_str("        self.check_lexer_error()?;\n\n        ");
+            body.push_str(&branches.join("\n        else "));
+            body.push_str(&format!(
+                "\n        else {{\n            {}\n        }}\n",
+                err_rule
+            ));
+        }
+
+        write!(
+            out,
+            "
+    fn {}(&mut self) -> Parsed<'a> {{
+        trace!(\"parsing {}\");
+
+{}    }}
+",
+            fn_names[my_name], my_name, body
+        )
+        .unwrap();
+    }
+
+    out.push_str("}\n");
+
+    Ok(out)
+}
+
+fn fn_names(rules: &Rules) -> Result<HashMap<String, String>, GenerateError> {
+    let mut fn_names = HashMap::new();
+    let mut seen = HashSet::new();
+
+    for rule in rules.rules() {
+        let name = rule.borrow().name().to_string();
+        let fn_name = format!("parse_{}", name.to_lowercase().replace("__", "_"));
+
+        if !seen.insert(fn_name.clone()) {
+            return Err(GenerateError::FnNameClash {
+                rule: name,
+                fn_name,
+            });
+        }
+
+        fn_names.insert(name, fn_name);
+    }
+
+    Ok(fn_names)
+}
+
+// The constant naming the rule of a parse function, RULE_TERMS for parse_terms.
+fn const_name_of(fn_name: &str) -> String {
+    format!(
+        "RULE_{}",
+        fn_name.trim_start_matches("parse_").to_uppercase()
+    )
+}
+
+fn grammar_of(rules: &Rules) -> String {
+    let mut grammar = String::new();
+
+    for rule in rules.rules() {
+        let alternatives = rule
+            .borrow()
+            .alternatives
+            .iter()
+            .map(|alt| {
+                alt.iter()
+                    .map(|part| match part {
+                        RulePart::Rule(rule) => rule.borrow().name().to_string(),
+                        RulePart::Token(tk) => tk.repr_or_name().to_string(),
+                        RulePart::Predicate(..) => unreachable!("rejected by validate"),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join(" | ");
+
+        writeln!(grammar, "{} -> {}", rule.borrow().name(), alternatives).unwrap();
+    }
+
+    grammar
+}
+
+fn parts_of(
+    alt: &[RulePart],
+    fn_names: &HashMap<String, String>,
+    indent: &str,
+) -> String {
+    alt.iter()
+        .filter(|it| !it.is_epsilon())
+        .map(|part| match part {
+            RulePart::Token(tk) => format!("{}self.match_tk(TokenKind::{:?})?;", indent, tk),
+            RulePart::Rule(rule) => {
+                let fn_name = &fn_names[rule.borrow().name()];
+                format!(
+                    "{}self.parse_rule({}, Self::{})?;",
+                    indent,
+                    const_name_of(fn_name),
+                    fn_name,
+                )
+            },
+            RulePart::Predicate(..) => unreachable!("rejected by validate"),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn variants_of(token_kinds: &HashSet<TokenKind>) -> String {
+    let mut token_kinds: Vec<_> = token_kinds.iter().filter(|it| !it.is_epsilon()).collect();
+    token_kinds.sort();
+
+    token_kinds
+        .into_iter()
+        .map(|it| format!("TokenKind::{:?}", it))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn names_of(token_kinds: &HashSet<TokenKind>) -> String {
+    let mut names: Vec<_> = token_kinds.iter().map(|it| it.name()).collect();
+    names.sort();
+    names.join(", ")
+}
+
+
+const HEADER: &str = "\
+// Generated by toylang1 parser_gen, do not edit.
+
+use std::iter::Peekable;
+
+use log::trace;
+
+use {crate}::lang::error::ParseErrorKind;
+use {crate}::lang::lexer::token::Token;
+use {crate}::lang::lexer::token::TokenKind;
+use {crate}::lang::lexer::v0::LexerResult;
+use {crate}::lang::parser::tree::NodeId;
+use {crate}::lang::parser::tree::TreeBuilder;
+use {crate}::lang::parser::tree::TreeParseError;
+use {crate}::lang::parser::tree::TreeParseResult;
+";
+
+const HELPERS: &str = "
+
+type Parsed<'a> = Result<(), TreeParseError<'a>>;
+
+struct GeneratedParser<'a, T: Iterator<Item = LexerResult<'a>>> {
+    tokens: Peekable<T>,
+    tree: TreeBuilder<'a>,
+    focus: NodeId,
+}
+
+// Not every grammar needs every helper.
+#[allow(dead_code)]
+impl<'a, T: Iterator<Item = LexerResult<'a>>> GeneratedParser<'a, T> {
+    fn new(
+        start_rule: &str,
+        tokens: T,
+    ) -> Self {
+        let mut tree = TreeBuilder::new();
+        let focus = tree.add_rule(None, start_rule);
+
+        Self {
+            tokens: tokens.peekable(),
+            tree,
+            focus,
+        }
+    }
+
+
+    // The tree as far as it got, the parser is done with after an error.
+    fn _err(
+        &mut self,
+        kind: ParseErrorKind,
+        msg: String,
+    ) -> Parsed<'a> {
+        let partial_tree = std::mem::take(&mut self.tree).build();
+
+        Err(TreeParseError::with_error(partial_tree, kind, msg))
+    }
+
+    fn err_unexpected(
+        &mut self,
+        rule: Option<&str>,
+        expected: &[TokenKind],
+        msg: String,
+    ) -> Parsed<'a> {
+        let found = match self.has_peek() {
+            true => self.peek().ok().copied(),
+            false => None,
+        };
+        let kind = ParseErrorKind::unexpected_token(rule, expected.iter().copied(), found.as_ref());
+        self._err(kind, msg)
+    }
+
+    fn err_rule(
+        &mut self,
+        this_rule: &str,
+        start_tokens: &str,
+        follow: Option<&str>,
+        expected: &[TokenKind],
+    ) -> Parsed<'a> {
+        let err = match (follow, self.has_peek()) {
+            (Some(follow), false) => format!(
+                \"rule: {} /// unexpected end of input, expecting one of tokens: {} /// OR because of epsilon one of: {}\",
+                this_rule, start_tokens, follow,
+            ),
+            (Some(follow), true) => format!(
+                \"rule: {} /// unexpected token, expecting one of tokens: {} /// OR because of epsilon one of: {}, got: {}\",
+                this_rule, start_tokens, follow, self.peek().unwrap(),
+            ),
+            (None, true) => format!(
+                \"rule: {} /// unexpected token, expecting one of tokens: {} got: {}\",
+                this_rule, start_tokens, self.peek().unwrap(),
+            ),
+            (None, false) => format!(
+                \"rule: {} /// unexpected end of input, expecting one of tokens: {}\",
+                this_rule, start_tokens,
+            ),
+        };
+
+        self.err_unexpected(Some(this_rule), expected, err)
+    }
+
+
+    fn set_alt(
+        &mut self,
+        alt_no: usize,
+    ) {
+        self.tree.set_alt(self.focus, alt_no);
+    }
+
+    fn parse_rule(
+        &mut self,
+        rule_name: &str,
+        parse: fn(&mut Self) -> Parsed<'a>,
+    ) -> Parsed<'a> {
+        self.focus = self.tree.add_rule(Some(self.focus), rule_name);
+
+        parse(self)?;
+
+        self.focus = self.tree.parent(self.focus).unwrap();
+        Ok(())
+    }
+
+
+    fn has_peek(&mut self) -> bool {
+        self.tokens.peek().is_some()
+    }
+
+    fn peek(&mut self) -> Result<&Token<'a>, ParseErrorKind> {
+        match self.tokens.peek() {
+            None => {
+                panic!(\"peek called while no more token is remaining\")
+            },
+            Some(peek) => match peek {
+                Ok(peek) => Ok(peek),
+                Err(err) => Err(err.clone().into()),
+            },
+        }
+    }
+
+    fn check_lexer_error(&mut self) -> Parsed<'a> {
+        if self.has_peek() {
+            if let Err(kind) = self.peek() {
+                let msg = kind.to_string();
+                return self._err(kind, msg);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn peek_is_in(
+        &mut self,
+        expecting: &[TokenKind],
+    ) -> bool {
+        self.has_peek() && expecting.contains(&self.peek().unwrap().token_kind)
+    }
+
+    fn peek_is(
+        &mut self,
+        tk: TokenKind,
+    ) -> bool {
+        self.peek_is_in(&[tk])
+    }
+
+
+    fn match_tk(
+        &mut self,
+        expecting: TokenKind,
+    ) -> Parsed<'a> {
+        if !self.has_peek() {
+            return self.err_unexpected(
+                None,
+                &[expecting],
+                format!(\"unexpected end of input, expecting: {}, got nothing\", expecting),
+            );
+        }
+
+        self.check_lexer_error()?;
+
+        trace!(
+            \"match tk, expecting: {}, current: {}\",
+            expecting.name(),
+            self.peek().unwrap().text
+        );
+
+        if self.peek().unwrap().token_kind == expecting {
+            let node = self.tree.add_token(self.focus, expecting);
+            let token = self.tokens.next().unwrap().unwrap();
+            self.tree.set_token(node, token);
+        }
+        else {
+            let err = format!(
+                \"unexpected token kind, expecting: {}, got: {}\",
+                expecting,
+                self.peek().unwrap(),
+            );
+            return self.err_unexpected(None, &[expecting], err);
+        }
+
+        self.check_lexer_error()
+    }
+";
+
+
+#[cfg(test)]
+mod test {
+    use crate::lang::error::GenerateError;
+    use crate::lang::error::GrammarError;
+    use crate::lang::error::ParseErrorKind;
+    use crate::lang::lexer::token::TokenKind;
+    use crate::lang::lexer::v0::Lexer;
+    use crate::lang::parser::rules::Rules;
+    use crate::lang::parser::tree::Tree;
+    use crate::lang::parser_gen::recursive_descent_gen::generate_recursive_descent_parser;
+    use crate::lang::parser_impl::recursive_descent_parser::recursive_descent_parse;
+
+    // Generated of GRAMMAR, test_generate_checked_in tells when it is to be generated again.
+    mod statements_parser {
+        include!("statements_parser.rs");
+    }
+
+    const GRAMMAR: &str = "
+S           -> statement S | EPSILON
+statement   -> ID = expressions ; | RETURN expressions ;
+expressions -> terms + expressions | terms - expressions | terms
+terms       -> factor * terms | factor / terms | factor
+factor      -> ( expressions ) | INT | ID
+";
+
+    fn rules() -> Rules {
+        let mut rules: Rules = GRAMMAR.try_into().unwrap();
+        rules.make_ready_for_recursive_decent(128).unwrap();
+        rules
+    }
+
+    #[test]
+    fn test_generate() {
+        let rules = rules();
+
+        let source = generate_recursive_descent_parser(&rules, "crate").unwrap();
+
+        assert!(source.contains("use crate::lang::parser::tree::TreeBuilder;"));
+        assert!(source.contains("    fn parse_expressions_0(&mut self) -> Parsed<'a> {"));
+        assert!(source.contains("const RULE_TERMS: &str = \"terms\";"));
+        assert!(source.contains("self.parse_rule(RULE_TERMS, Self::parse_terms)?;"));
+        assert!(source.contains("if self.peek_is_in(&[TokenKind::Plus]) {"));
+        assert!(!source.contains("HashMap"));
+        assert!(!source.contains("Rules"));
+
+        let regenerated: Rules = source
+            .split("r#\"")
+            .nth(1)
+            .unwrap()
+            .split("\"#")
+            .next()
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let mut regenerated = regenerated
+            .to_string()
+            .lines()
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        let mut expected = rules
+            .to_string()
+            .lines()
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        regenerated.sort();
+        expected.sort();
+        assert_eq!(regenerated, expected);
+    }
+
+    #[test]
+    fn test_generate_checked_in() {
+        let source = generate_recursive_descent_parser(&rules(), "crate").unwrap();
+
+        assert_eq!(source, include_str!("statements_parser.rs"));
+    }
+
+    #[test]
+    fn test_generated_parse() {
+        let program = "a = 1 + 2 * (b - 3); return a / 4;";
+
+        let lexer: Lexer = program.into();
+        let tree = statements_parser::parse(lexer.into_iter()).unwrap();
+
+        // The same tree as of the parser the code was generated after.
+        let rules: Rules = statements_parser::GRAMMAR.try_into().unwrap();
+        let lexer: Lexer = program.into();
+        let node = recursive_descent_parse(&rules, lexer.into_iter()).unwrap();
+        assert_eq!(tree.to_string(), Tree::from(&node).to_string());
+        assert_eq!(tree.tokens().len(), 17);
+        assert_eq!(tree.node(tree.root()).alt_no(), Some(0));
+
+        let lexer: Lexer = "a = 1 +; return a;".into();
+        let err = statements_parser::parse(lexer.into_iter()).unwrap_err();
+        match err.kind() {
+            ParseErrorKind::UnexpectedToken {
+                rule,
+                expected,
+                found,
+            } => {
+                assert_eq!(rule.as_deref(), Some("factor"));
+                assert!(expected.contains(&TokenKind::Int));
+                assert_eq!(found.unwrap().0, TokenKind::Semicolon);
+            },
+            kind => panic!("unexpected error kind: {:?}", kind),
+        }
+        assert_eq!(
+            err.partial_tree().node(err.partial_tree().root()).name(),
+            "S"
+        );
+    }
+
+    #[test]
+    fn test_generate_not_backtrack_free() {
+        let rules: Rules = "
+S  -> r0 | r1
+r0 -> ID
+r1 -> ID
+        "
+        .try_into()
+        .unwrap();
+
+        assert!(matches!(
+            generate_recursive_descent_parser(&rules, "crate"),
+            Err(GenerateError::Grammar(
+                GrammarError::NotBacktrackFree { .. }
+            ))
+        ));
+    }
+}
@@ -0,0 +1,378 @@
+// Generated by toylang1 parser_gen, do not edit.
+
+use std::iter::Peekable;
+
+use log::trace;
+
+use crate::lang::error::ParseErrorKind;
+use crate::lang::lexer::token::Token;
+use crate::lang::lexer::token::TokenKind;
+use crate::lang::lexer::v0::LexerResult;
+use crate::lang::parser::tree::NodeId;
+use crate::lang::parser::tree::TreeBuilder;
+use crate::lang::parser::tree::TreeParseError;
+use crate::lang::parser::tree::TreeParseResult;
+
+pub fn parse<'a, T: Iterator<Item = LexerResult<'a>>>(tokens: T) -> TreeParseResult<'a> {
+    GeneratedParser::new(RULE_S, tokens).parse()
+}
+
+pub const GRAMMAR: &str = r#"
+S -> statement S | EPSILON
+statement -> ID = expressions ; | return expressions ;
+expressions -> terms expressions__0
+terms -> factor terms__0
+factor -> ( expressions ) | INT | ID
+expressions__0 -> + expressions | - expressions | EPSILON
+terms__0 -> * terms | / terms | EPSILON
+"#;
+
+// The rules, by the names of their nodes in the tree.
+const RULE_S: &str = "S";
+const RULE_STATEMENT: &str = "statement";
+const RULE_EXPRESSIONS: &str = "expressions";
+const RULE_TERMS: &str = "terms";
+const RULE_FACTOR: &str = "factor";
+const RULE_EXPRESSIONS_0: &str = "expressions__0";
+const RULE_TERMS_0: &str = "terms__0";
+
+
+type Parsed<'a> = Result<(), TreeParseError<'a>>;
+
+struct GeneratedParser<'a, T: Iterator<Item = LexerResult<'a>>> {
+    tokens: Peekable<T>,
+    tree: TreeBuilder<'a>,
+    focus: NodeId,
+}
+
+// Not every grammar needs every helper.
+#[allow(dead_code)]
+impl<'a, T: Iterator<Item = LexerResult<'a>>> GeneratedParser<'a, T> {
+    fn new(
+        start_rule: &str,
+        tokens: T,
+    ) -> Self {
+        let mut tree = TreeBuilder::new();
+        let focus = tree.add_rule(None, start_rule);
+
+        Self {
+            tokens: tokens.peekable(),
+            tree,
+            focus,
+        }
+    }
+
+
+    // The tree as far as it got, the parser is done with after an error.
+    fn _err(
+        &mut self,
+        kind: ParseErrorKind,
+        msg: String,
+    ) -> Parsed<'a> {
+        let partial_tree = std::mem::take(&mut self.tree).build();
+
+        Err(TreeParseError::with_error(partial_tree, kind, msg))
+    }
+
+    fn err_unexpected(
+        &mut self,
+        rule: Option<&str>,
+        expected: &[TokenKind],
+        msg: String,
+    ) -> Parsed<'a> {
+        let found = match self.has_peek() {
+            true => self.peek().ok().copied(),
+            false => None,
+        };
+        let kind = ParseErrorKind::unexpected_token(rule, expected.iter().copied(), found.as_ref());
+        self._err(kind, msg)
+    }
+
+    fn err_rule(
+        &mut self,
+        this_rule: &str,
+        start_tokens: &str,
+        follow: Option<&str>,
+        expected: &[TokenKind],
+    ) -> Parsed<'a> {
+        let err = match (follow, self.has_peek()) {
+            (Some(follow), false) => format!(
+                "rule: {} /// unexpected end of input, expecting one of tokens: {} /// OR because of epsilon one of: {}",
+                this_rule, start_tokens, follow,
+            ),
+            (Some(follow), true) => format!(
+                "rule: {} /// unexpected token, expecting one of tokens: {} /// OR because of epsilon one of: {}, got: {}",
+                this_rule, start_tokens, follow, self.peek().unwrap(),
+            ),
+            (None, true) => format!(
+                "rule: {} /// unexpected token, expecting one of tokens: {} got: {}",
+                this_rule, start_tokens, self.peek().unwrap(),
+            ),
+            (None, false) => format!(
+                "rule: {} /// unexpected end of input, expecting one of tokens: {}",
+                this_rule, start_tokens,
+            ),
+        };
+
+        self.err_unexpected(Some(this_rule), expected, err)
+    }
+
+
+    fn set_alt(
+        &mut self,
+        alt_no: usize,
+    ) {
+        self.tree.set_alt(self.focus, alt_no);
+    }
+
+    fn parse_rule(
+        &mut self,
+        rule_name: &str,
+        parse: fn(&mut Self) -> Parsed<'a>,
+    ) -> Parsed<'a> {
+        self.focus = self.tree.add_rule(Some(self.focus), rule_name);
+
+        parse(self)?;
+
+        self.focus = self.tree.parent(self.focus).unwrap();
+        Ok(())
+    }
+
+
+    fn has_peek(&mut self) -> bool {
+        self.tokens.peek().is_some()
+    }
+
+    fn peek(&mut self) -> Result<&Token<'a>, ParseErrorKind> {
+        match self.tokens.peek() {
+            None => {
+                panic!("peek called while no more token is remaining")
+            },
+            Some(peek) => match peek {
+                Ok(peek) => Ok(peek),
+                Err(err) => Err(err.clone().into()),
+            },
+        }
+    }
+
+    fn check_lexer_error(&mut self) -> Parsed<'a> {
+        if self.has_peek() {
+            if let Err(kind) = self.peek() {
+                let msg = kind.to_string();
+                return self._err(kind, msg);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn peek_is_in(
+        &mut self,
+        expecting: &[TokenKind],
+    ) -> bool {
+        self.has_peek() && expecting.contains(&self.peek().unwrap().token_kind)
+    }
+
+    fn peek_is(
+        &mut self,
+        tk: TokenKind,
+    ) -> bool {
+        self.peek_is_in(&[tk])
+    }
+
+
+    fn match_tk(
+        &mut self,
+        expecting: TokenKind,
+    ) -> Parsed<'a> {
+        if !self.has_peek() {
+            return self.err_unexpected(
+                None,
+                &[expecting],
+                format!("unexpected end of input, expecting: {}, got nothing", expecting),
+            );
+        }
+
+        self.check_lexer_error()?;
+
+        trace!(
+            "match tk, expecting: {}, current: {}",
+            expecting.name(),
+            self.peek().unwrap().text
+        );
+
+        if self.peek().unwrap().token_kind == expecting {
+            let node = self.tree.add_token(self.focus, expecting);
+            let token = self.tokens.next().unwrap().unwrap();
+            self.tree.set_token(node, token);
+        }
+        else {
+            let err = format!(
+                "unexpected token kind, expecting: {}, got: {}",
+                expecting,
+                self.peek().unwrap(),
+            );
+            return self.err_unexpected(None, &[expecting], err);
+        }
+
+        self.check_lexer_error()
+    }
+
+    // ============================================================================================
+
+    fn parse(mut self) -> TreeParseResult<'a> {
+        self.parse_s()?;
+
+        if self.has_peek() && !self.peek_is(TokenKind::Eof) {
+            let err = format!(
+                "unexpected token, expecting end of input, got: {}",
+                self.peek().unwrap(),
+            );
+            self.err_unexpected(None, &[TokenKind::Eof], err)?;
+        }
+
+        Ok(self.tree.build())
+    }
+
+    fn parse_s(&mut self) -> Parsed<'a> {
+        trace!("parsing S");
+
+        self.check_lexer_error()?;
+
+        if self.peek_is_in(&[TokenKind::Id, TokenKind::Return]) {
+            self.set_alt(0);
+            self.parse_rule(RULE_STATEMENT, Self::parse_statement)?;
+            self.parse_rule(RULE_S, Self::parse_s)?;
+            Ok(())
+        }
+        else if self.peek_is_in(&[TokenKind::Eof]) {
+            self.set_alt(1);
+            Ok(())
+        }
+        else {
+            self.err_rule(RULE_S, "epsilon, id, return", Some("eof"), &[TokenKind::Eof, TokenKind::Id, TokenKind::Return])
+        }
+    }
+
+    fn parse_statement(&mut self) -> Parsed<'a> {
+        trace!("parsing statement");
+
+        self.check_lexer_error()?;
+
+        if self.peek_is_in(&[TokenKind::Id]) {
+            self.set_alt(0);
+            self.match_tk(TokenKind::Id)?;
+            self.match_tk(TokenKind::Equal)?;
+            self.parse_rule(RULE_EXPRESSIONS, Self::parse_expressions)?;
+            self.match_tk(TokenKind::Semicolon)?;
+            Ok(())
+        }
+        else if self.peek_is_in(&[TokenKind::Return]) {
+            self.set_alt(1);
+            self.match_tk(TokenKind::Return)?;
+            self.parse_rule(RULE_EXPRESSIONS, Self::parse_expressions)?;
+            self.match_tk(TokenKind::Semicolon)?;
+            Ok(())
+        }
+        else {
+            self.err_rule(RULE_STATEMENT, "id, return", None, &[TokenKind::Id, TokenKind::Return])
+        }
+    }
+
+    fn parse_expressions(&mut self) -> Parsed<'a> {
+        trace!("parsing expressions");
+
+        self.set_alt(0);
+        self.parse_rule(RULE_TERMS, Self::parse_terms)?;
+        self.parse_rule(RULE_EXPRESSIONS_0, Self::parse_expressions_0)?;
+        Ok(())
+    }
+
+    fn parse_terms(&mut self) -> Parsed<'a> {
+        trace!("parsing terms");
+
+        self.set_alt(0);
+        self.parse_rule(RULE_FACTOR, Self::parse_factor)?;
+        self.parse_rule(RULE_TERMS_0, Self::parse_terms_0)?;
+        Ok(())
+    }
+
+    fn parse_factor(&mut self) -> Parsed<'a> {
+        trace!("parsing factor");
+
+        self.check_lexer_error()?;
+
+        if self.peek_is_in(&[TokenKind::LeftParen]) {
+            self.set_alt(0);
+            self.match_tk(TokenKind::LeftParen)?;
+            self.parse_rule(RULE_EXPRESSIONS, Self::parse_expressions)?;
+            self.match_tk(TokenKind::RightParen)?;
+            Ok(())
+        }
+        else if self.peek_is_in(&[TokenKind::Int]) {
+            self.set_alt(1);
+            self.match_tk(TokenKind::Int)?;
+            Ok(())
+        }
+        else if self.peek_is_in(&[TokenKind::Id]) {
+            self.set_alt(2);
+            self.match_tk(TokenKind::Id)?;
+            Ok(())
+        }
+        else {
+            self.err_rule(RULE_FACTOR, "id, integer, left_paren", None, &[TokenKind::Id, TokenKind::Int, TokenKind::LeftParen])
+        }
+    }
+
+    fn parse_expressions_0(&mut self) -> Parsed<'a> {
+        trace!("parsing expressions__0");
+
+        self.check_lexer_error()?;
+
+        if self.peek_is_in(&[TokenKind::Plus]) {
+            self.set_alt(0);
+            self.match_tk(TokenKind::Plus)?;
+            self.parse_rule(RULE_EXPRESSIONS, Self::parse_expressions)?;
+            Ok(())
+        }
+        else if self.peek_is_in(&[TokenKind::Minus]) {
+            self.set_alt(1);
+            self.match_tk(TokenKind::Minus)?;
+            self.parse_rule(RULE_EXPRESSIONS, Self::parse_expressions)?;
+            Ok(())
+        }
+        else if self.peek_is_in(&[TokenKind::RightParen, TokenKind::Semicolon]) {
+            self.set_alt(2);
+            Ok(())
+        }
+        else {
+            self.err_rule(RULE_EXPRESSIONS_0, "epsilon, minus, plus", Some("right_paren, semicolon"), &[TokenKind::RightParen, TokenKind::Semicolon, TokenKind::Minus, TokenKind::Plus])
+        }
+    }
+
+    fn parse_terms_0(&mut self) -> Parsed<'a> {
+        trace!("parsing terms__0");
+
+        self.check_lexer_error()?;
+
+        if self.peek_is_in(&[TokenKind::Star]) {
+            self.set_alt(0);
+            self.match_tk(TokenKind::Star)?;
+            self.parse_rule(RULE_TERMS, Self::parse_terms)?;
+            Ok(())
+        }
+        else if self.peek_is_in(&[TokenKind::Slash]) {
+            self.set_alt(1);
+            self.match_tk(TokenKind::Slash)?;
+            self.parse_rule(RULE_TERMS, Self::parse_terms)?;
+            Ok(())
+        }
+        else if self.peek_is_in(&[TokenKind::RightParen, TokenKind::Semicolon, TokenKind::Minus, TokenKind::Plus]) {
+            self.set_alt(2);
+            Ok(())
+        }
+        else {
+            self.err_rule(RULE_TERMS_0, "epsilon, slash, star", Some("minus, plus, right_paren, semicolon"), &[TokenKind::RightParen, TokenKind::Semicolon, TokenKind::Slash, TokenKind::Star, TokenKind::Minus, TokenKind::Plus])
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use std::fmt::Debug;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use crate::lang::error::GrammarError;
+use crate::lang::lexer::token::TokenKind;
+use crate::lang::lexer::v0::Lexer;
+use crate::lang::parser::node::display_of;
+use crate::lang::parser::rules::Rules;
+use crate::lang::parser::sentences::sentences_up_to;
+use crate::lang::parser::sentences::source_of;
+use crate::lang::parser_impl::earley_parser::earley_parse;
+use crate::lang::parser_impl::lr_parser::LrTable;
+
+#[derive(Debug)]
+pub enum AmbiguityCheck {
+    // An LR(1) grammar has no sentence with two trees.
+    Unambiguous,
+    // No sentence of up to max_len tokens has two trees, longer ones may.
+    NoneFound { max_len: usize },
+    Ambiguous(Box<Ambiguity>),
+}
+
+
+// A sentence with more than one tree, None trees when there are infinitely many of them. The first
+// two trees, as the Earley parser orders them, are kept displayed, their tokens borrow the source.
+pub struct Ambiguity {
+    pub sentence: Vec<TokenKind>,
+    pub source: String,
+    pub num_trees: Option<usize>,
+    pub trees: (String, String),
+}
+
+impl Debug for Ambiguity {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(f, "Ambiguity[{}]", self.source.trim_end())
+    }
+}
+
+impl Display for Ambiguity {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(
+            f,
+            "ambiguous sentence: {}\n{}\n<vs>\n{}",
+            self.source.trim_end(),
+            self.trees.0,
+            self.trees.1
+        )
+    }
+}
+
+
+// Bounded: LR(1) grammars are taken as proven unambiguous, for the others the sentences are
+// tried shortest first. That a grammar is not LR(1) is not proof it is ambiguous, only that the
+// search is needed.
+pub fn check_ambiguity(
+    rules: &Rules,
+    max_len: usize,
+) -> Result<AmbiguityCheck, GrammarError> {
+    rules.validate()?;
+
+    if LrTable::canonical_lr1(rules).is_ok() {
+        return Ok(AmbiguityCheck::Unambiguous);
+    }
+
+    for sentence in sentences_up_to(rules, max_len)? {
+        let source = source_of(&sentence, 0);
+        let lexer: Lexer = source.as_str().into();
+        let sppf = earley_parse(rules, lexer.into_iter())
+            .unwrap_or_else(|err| panic!("sentence of the rules rejected: {}, {}", source, err));
+
+        let num_trees = sppf.count_trees();
+        if num_trees == Some(1) {
+            continue;
+        }
+        if let [first, second] = sppf.trees(2).as_slice() {
+            let trees = (display_of(first), display_of(second));
+            return Ok(AmbiguityCheck::Ambiguous(Box::new(Ambiguity {
+                sentence,
+                source,
+                num_trees,
+                trees,
+            })));
+        }
+    }
+
+    Ok(AmbiguityCheck::NoneFound {
+        max_len,
+    })
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::lang::lexer::token::TokenKind;
+    use crate::lang::parser::rules::Rules;
+    use crate::lang::parser_impl::ambiguity::check_ambiguity;
+    use crate::lang::parser_impl::ambiguity::AmbiguityCheck;
+
+    #[test]
+    fn test_dangling_else() {
+        // FN for `if`, RETURN for `else`.
+        let rules: Rules = "
+S    -> stmt
+stmt -> FN ( ID ) stmt | FN ( ID ) stmt RETURN stmt | ID ;
+        "
+        .try_into()
+        .unwrap();
+
+        let ambiguity = match check_ambiguity(&rules, 13).unwrap() {
+            AmbiguityCheck::Ambiguous(ambiguity) => ambiguity,
+            check => panic!("expecting ambiguous, got: {:?}", check),
+        };
+        assert_eq!(ambiguity.sentence.len(), 13);
+        assert_eq!(ambiguity.sentence[4], TokenKind::Fn);
+        assert_eq!(ambiguity.num_trees, Some(2));
+
+        let (first, second) = &ambiguity.trees;
+        assert_ne!(first, second);
+        // The else belongs to the inner if in one, to the outer in the other: the children of the
+        // outer stmt are at the second level.
+        let outer_children =
+            |tree: &str| tree.lines().filter(|it| it.starts_with("|____ ")).count();
+        assert_eq!(outer_children(first), 5);
+        assert_eq!(outer_children(second), 7);
+        assert!(ambiguity
+            .to_string()
+            .starts_with("ambiguous sentence: fn ("));
+    }
+
+    #[test]
+    fn test_unambiguous() {
+        let rules: Rules = "
+S    -> expr
+expr -> expr + term | term
+term -> INT | ( expr )
+        "
+        .try_into()
+        .unwrap();
+        assert!(matches!(
+            check_ambiguity(&rules, 7).unwrap(),
+            AmbiguityCheck::Unambiguous
+        ));
+
+        // Not LR(1) for needing two tokens of lookahead, but not ambiguous either.
+        let rules: Rules = "
+S -> a ID ID | b ID INT
+a -> INT
+b -> INT
+        "
+        .try_into()
+        .unwrap();
+        assert!(matches!(
+            check_ambiguity(&rules, 5).unwrap(),
+            AmbiguityCheck::NoneFound {
+                max_len: 5
+            }
+        ));
+    }
+
+    #[test]
+    fn test_ambiguous_expression() {
+        let rules: Rules = "S -> S + S | INT".try_into().unwrap();
+
+        match check_ambiguity(&rules, 5).unwrap() {
+            AmbiguityCheck::Ambiguous(ambiguity) => {
+                assert_eq!(
+                    ambiguity.sentence,
+                    [
+                        TokenKind::Int,
+                        TokenKind::Plus,
+                        TokenKind::Int,
+                        TokenKind::Plus,
+                        TokenKind::Int
+                    ]
+                );
+                assert_eq!(ambiguity.num_trees, Some(2));
+            },
+            check => panic!("expecting ambiguous, got: {:?}", check),
+        }
+    }
+}
@@ -3,13 +3,15 @@ use std::rc::Rc;
 
 use log::trace;
 
+use crate::lang::error::ParseErrorKind;
 use crate::lang::lexer::token::Token;
 use crate::lang::lexer::token::TokenKind;
+use crate::lang::parser::actions::Actions;
 use crate::lang::parser::node::display_of;
 use crate::lang::parser::node::Node;
 use crate::lang::parser::node::ParseError;
 use crate::lang::parser::node::ParseResult;
-use crate::lang::parser::rule::RulePart;
+use crate::lang::parser::rule::AltRef;
 use crate::lang::parser::rules::Rules;
 
 fn print_stack(stack: &[Rc<RefCell<Node>>]) {
@@ -92,15 +94,16 @@ fn is_eof(
     node.is_none() && word.is_some() && word.unwrap().token_kind == TokenKind::Eof
 }
 
-fn backtrack_push_back<'a>(
+fn backtrack_push_back<'a, V>(
     focus: Rc<RefCell<Node<'a>>>,
     tokens: &mut Vec<Token<'a>>,
     stack: &mut Vec<Rc<RefCell<Node>>>,
+    values: &mut Vec<(usize, V)>,
 ) {
     if !focus.borrow().children().is_empty() {
         trace!("KILLING CHILDREN OF: {}", focus.borrow().rule_part().name());
         for child in focus.borrow().children().iter().rev() {
-            backtrack_push_back(Rc::clone(child), tokens, stack);
+            backtrack_push_back(Rc::clone(child), tokens, stack, values);
         }
         print_stack(stack);
     }
@@ -117,12 +120,14 @@ fn backtrack_push_back<'a>(
 
     let num = focus.borrow().num();
     stack.retain_mut(|it| it.borrow().num() != num);
+    values.retain(|it| it.0 != num);
 }
 
-fn backtrack<'a>(
+fn backtrack<'a, V>(
     focus: Option<Rc<RefCell<Node<'a>>>>,
     tokens: &mut Vec<Token<'a>>,
     stack: &mut Vec<Rc<RefCell<Node>>>,
+    values: &mut Vec<(usize, V)>,
 ) -> Result<Option<Rc<RefCell<Node<'a>>>>, String> {
     trace!(
         "NO MATCH backtracking:\n{}\n>>>>>>>>",
@@ -143,7 +148,7 @@ fn backtrack<'a>(
     }
     else {
         trace!("LET'S SEE");
-        backtrack_push_back(Rc::clone(&focus), tokens, stack);
+        backtrack_push_back(Rc::clone(&focus), tokens, stack, values);
 
         if !focus.borrow().rule_part().is_token() && focus.borrow().has_next_alt() {
             trace!("going next");
@@ -161,7 +166,7 @@ fn backtrack<'a>(
                     .map_or("?".to_string(), |it| it.borrow().rule_part().name()),
             );
             let ff = Some(Rc::clone(focus.borrow_mut().parent().as_ref().unwrap()));
-            backtrack(ff, tokens, stack)
+            backtrack(ff, tokens, stack, values)
         }
         else {
             unreachable!("either should have next alt or parent, this is a bug");
@@ -169,22 +174,121 @@ fn backtrack<'a>(
     }
 }
 
+// The farthest position a token was expected at, and the tokens expected there, is where the input
+// is reported wrong once no alternative is left.
+struct Farthest<'a> {
+    position: usize,
+    word: Option<Token<'a>>,
+    expecting: Vec<TokenKind>,
+}
+
+impl<'a> Farthest<'a> {
+    fn expect(
+        &mut self,
+        position: usize,
+        word: Option<Token<'a>>,
+        tk: TokenKind,
+    ) {
+        if position < self.position {
+            return;
+        }
+        if position > self.position {
+            self.position = position;
+            self.expecting.clear();
+        }
+        self.word = word;
+        if !self.expecting.contains(&tk) {
+            self.expecting.push(tk);
+        }
+    }
+
+    fn kind(&self) -> ParseErrorKind {
+        ParseErrorKind::unexpected_token(None, self.expecting.clone(), self.word.as_ref())
+    }
+}
+
+// Gives a matched node its value, then its parent too if it was the last child, and so on up.
+fn complete<V>(
+    node: &Rc<RefCell<Node<'_>>>,
+    actions: Option<&Actions<V>>,
+    values: &mut Vec<(usize, V)>,
+) -> Result<(), String> {
+    let actions = match actions {
+        Some(actions) => actions,
+        None => return Ok(()),
+    };
+
+    let node = node.borrow();
+    if let Some(token) = node.token() {
+        values.push((node.num(), actions.token_value(token)));
+    }
+    else if node.rule_part().is_rule() {
+        // The values of the children are the last ones, EPSILON having none.
+        let nums = node
+            .children()
+            .iter()
+            .map(|it| it.borrow().num())
+            .collect::<Vec<_>>();
+        let mut children_values = vec![];
+        while values.last().is_some_and(|it| nums.contains(&it.0)) {
+            children_values.push(values.pop().unwrap().1);
+        }
+        children_values.reverse();
+
+        let alt = AltRef::new(node.alt(), &node.rule_part().get_rule());
+        values.push((node.num(), actions.reduce(&alt, children_values)?));
+    }
+
+    match node.parent() {
+        Some(parent) if parent.borrow().children().last().unwrap().borrow().num() == node.num() => {
+            complete(&parent, Some(actions), values)
+        },
+        _ => Ok(()),
+    }
+}
+
 pub fn parse_with_backtracking<'a, 'b, T: DoubleEndedIterator<Item = Token<'a>>>(
     rules: &Rules,
     tokens: T,
+) -> ParseResult<'a> {
+    parse::<(), T>(rules, None, tokens, &mut vec![])
+}
+
+// Parses straight into the values of the actions, see Actions.
+pub fn parse_with_backtracking_actions<'a, V, T: DoubleEndedIterator<Item = Token<'a>>>(
+    rules: &Rules,
+    actions: &Actions<V>,
+    tokens: T,
+) -> Result<V, ParseError<'a>> {
+    let mut values = vec![];
+    parse(rules, Some(actions), tokens, &mut values)?;
+    Ok(values.pop().unwrap().1)
+}
+
+fn parse<'a, V, T: DoubleEndedIterator<Item = Token<'a>>>(
+    rules: &Rules,
+    actions: Option<&Actions<V>>,
+    tokens: T,
+    values: &mut Vec<(usize, V)>,
 ) -> ParseResult<'a> {
     trace!("matching against: {}", rules);
 
     // We're backtracking parser, one more inefficiency is that we need to collect into vector so
     // that we can rewind (is there any rewind-capable rust iterator? if yes let's use that).
     let mut tokens: Vec<Token<'a>> = tokens.rev().collect();
+    let len = tokens.len();
     let mut word = tokens.pop();
+    let mut farthest = Farthest {
+        position: 0,
+        word,
+        expecting: vec![],
+    };
     trace!("starting with word: {:?}", word);
 
     let mut next_num = 0;
 
     let root = {
-        let rule_part: RulePart = rules.rules().first().unwrap().into();
+        let rule_part = rules.start_part();
         let root: Node<'_> = Node::new(rule_part, next_num);
         next_num += 1;
         let root: Rc<RefCell<Node<'_>>> = root.into();
@@ -192,13 +296,13 @@ pub fn parse_with_backtracking<'a, 'b, T: DoubleEndedIterator<Item = Token<'a>>>
     };
 
     if let Err(err) = rules.validate() {
-        return Err(ParseError::new(&root, format!("invalid rules: {}", err)));
+        return Err(ParseError::of(&root, ParseErrorKind::Grammar(err)));
     }
 
     let mut focus: Option<Rc<RefCell<Node>>> = Some(Rc::clone(&root));
     let mut stack: Vec<Rc<RefCell<Node>>> = vec![];
 
-    let error: String = loop {
+    let error: Option<(ParseErrorKind, String)> = loop {
         if focus.is_some() {
             println!(
                 "FOCUS: {}",
@@ -232,7 +336,13 @@ pub fn parse_with_backtracking<'a, 'b, T: DoubleEndedIterator<Item = Token<'a>>>
             for child in children.iter().rev() {
                 stack.push(Rc::clone(child));
             }
+            let is_empty = children.is_empty();
             focus.as_mut().unwrap().borrow_mut().set_children(children);
+            if is_empty {
+                if let Err(err) = complete(focus.as_ref().unwrap(), actions, values) {
+                    break Some((ParseErrorKind::Other(err.clone()), err));
+                }
+            }
             focus = stack.pop();
             trace!("===========================================================");
             trace!("AFTER\n{}", display_of(&root));
@@ -240,10 +350,13 @@ pub fn parse_with_backtracking<'a, 'b, T: DoubleEndedIterator<Item = Token<'a>>>
             trace!("===========================================================");
         }
         else if is_empty_program_rule(&focus) && !tokens.is_empty() {
-            break "could not match input".to_string();
+            break Some((farthest.kind(), "could not match input".to_string()));
         }
         else if is_epsilon(&focus) {
             trace!("happy epsilon while at: {}", word.as_ref().unwrap().text);
+            if let Err(err) = complete(focus.as_ref().unwrap(), actions, values) {
+                break Some((ParseErrorKind::Other(err.clone()), err));
+            }
             focus = stack.pop();
             if focus.is_some() {
                 trace!(
@@ -267,6 +380,9 @@ pub fn parse_with_backtracking<'a, 'b, T: DoubleEndedIterator<Item = Token<'a>>>
                 .unwrap()
                 .borrow_mut()
                 .set_token(word.unwrap());
+            if let Err(err) = complete(focus.as_ref().unwrap(), actions, values) {
+                break Some((ParseErrorKind::Other(err.clone()), err));
+            }
             word = tokens.pop();
             focus = stack.pop();
             match &word {
@@ -286,31 +402,40 @@ pub fn parse_with_backtracking<'a, 'b, T: DoubleEndedIterator<Item = Token<'a>>>
         }
         else if is_eof(&focus, &word) {
             trace!("fin!");
-            break String::with_capacity(0);
+            break None;
         }
         else {
+            let position = len - tokens.len() - usize::from(word.is_some());
+            match &focus {
+                Some(node) if node.borrow().rule_part().is_token() => {
+                    farthest.expect(position, word, *node.borrow().rule_part().get_token_kind())
+                },
+                Some(_) => {},
+                None => farthest.expect(position, word, TokenKind::Eof),
+            }
+
             if let Some(word) = word {
                 tokens.push(word);
             }
-            match backtrack(focus, &mut tokens, &mut stack) {
+            match backtrack(focus, &mut tokens, &mut stack, values) {
                 Ok(ff) => focus = ff,
-                Err(err) => break err,
+                Err(err) => break Some((farthest.kind(), err)),
             }
             word = tokens.pop();
         }
     };
 
-    if error.is_empty() {
-        Ok(root)
-    }
-    else {
-        Err(ParseError::new(&root, error))
+    match error {
+        None => Ok(root),
+        Some((kind, err)) => Err(ParseError::with_error(&root, kind, err)),
     }
 }
 
 
 #[cfg(test)]
 mod test {
+    use crate::lang::error::ParseErrorKind;
+    use crate::lang::lexer::token::TokenKind;
     use crate::lang::lexer::v0::Lexer;
     use crate::lang::parser::rules::Rules;
     use crate::lang::parser_impl::backtracking_parser::parse_with_backtracking;
@@ -330,4 +455,28 @@ mod test {
 
         parse_with_backtracking(&rules, tokens.into_iter()).unwrap();
     }
+
+    #[test]
+    fn test_unexpected_token() {
+        let rules: Rules = "\
+            S    ->   ID = r0 ; | ID ( ) ;
+            r0   ->   INT | STRING
+        "
+        .try_into()
+        .unwrap();
+
+        let tokens = Lexer::parse("a = b ;").unwrap();
+        let err = parse_with_backtracking(&rules, tokens.into_iter())
+            .err()
+            .unwrap();
+
+        assert!(matches!(
+            err.kind(),
+            ParseErrorKind::UnexpectedToken {
+                rule: None,
+                expected,
+                found: Some((TokenKind::Id, _)),
+            } if expected == &[TokenKind::Int, TokenKind::String]
+        ));
+    }
 }
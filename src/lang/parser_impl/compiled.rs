@@ -0,0 +1,238 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::lang::error::GrammarError;
+use crate::lang::lexer::token::TokenKind;
+use crate::lang::parser::grammar::Grammar;
+use crate::lang::parser::rules::Rules;
+use crate::lang::parser_impl::ll1_parser::Ll1Table;
+use crate::lang::parser_impl::lr_parser::LrKind;
+use crate::lang::parser_impl::lr_parser::LrTable;
+
+// Bumped whenever the layout below, or that of anything it holds, changes.
+pub const FORMAT_VERSION: u32 = 1;
+
+// Rules ready for parsing, with their FIRST, FOLLOW and START sets and the parse tables asked
+// for, saved as JSON so that tools can load them instead of transforming the grammar and
+// computing all of it on every start.
+#[derive(Serialize, Deserialize)]
+pub struct Compiled {
+    version: u32,
+    grammar: Grammar,
+    first_set: BTreeMap<String, BTreeSet<TokenKind>>,
+    follow_set: BTreeMap<String, BTreeSet<TokenKind>>,
+    // (rule, alt_no, tokens)
+    start_set: Vec<(String, usize, BTreeSet<TokenKind>)>,
+    ll1_table: Option<Ll1Table>,
+    lr_table: Option<LrTable>,
+}
+
+impl Compiled {
+    pub fn new(
+        rules: &Rules,
+        ll1: bool,
+        lr: Option<LrKind>,
+    ) -> Result<Self, GrammarError> {
+        rules.validate()?;
+        let grammar = Grammar::try_from(rules)?;
+
+        let sorted = |set: HashMap<String, HashSet<TokenKind>>| {
+            set.into_iter()
+                .map(|(name, tokens)| (name, tokens.into_iter().collect()))
+                .collect()
+        };
+        let mut start_set = rules
+            .start_set()
+            .into_iter()
+            .map(|((name, alt_no), tokens)| (name, alt_no, tokens.into_iter().collect()))
+            .collect::<Vec<_>>();
+        start_set.sort();
+
+        let ll1_table = match ll1 {
+            true => Some(Ll1Table::new(rules)?),
+            false => None,
+        };
+        let lr_table = match lr {
+            Some(kind) => Some(LrTable::new(rules, kind)?),
+            None => None,
+        };
+
+        Ok(Self {
+            version: FORMAT_VERSION,
+            grammar,
+            first_set: sorted(rules.first_set()),
+            follow_set: sorted(rules.follow_set()),
+            start_set,
+            ll1_table,
+            lr_table,
+        })
+    }
+
+    pub fn grammar(&self) -> &Grammar {
+        &self.grammar
+    }
+
+    // The rules, their sets already known.
+    pub fn rules(&self) -> Result<Rules, GrammarError> {
+        let rules = self.grammar.to_rules();
+
+        let mut start = HashMap::new();
+        for (name, alt_no, tokens) in &self.start_set {
+            if self.grammar.rule_id(name).is_none() {
+                return Err(GrammarError::UnknownRule(name.clone()));
+            }
+            start.insert((name.clone(), *alt_no), tokens.iter().cloned().collect());
+        }
+
+        let unsorted = |set: &BTreeMap<String, BTreeSet<TokenKind>>| {
+            set.iter()
+                .map(|(name, tokens)| (name.clone(), tokens.iter().cloned().collect()))
+                .collect()
+        };
+        rules.prime_sets(unsorted(&self.first_set), unsorted(&self.follow_set), start);
+
+        Ok(rules)
+    }
+
+    pub fn ll1_table(&self) -> Option<&Ll1Table> {
+        self.ll1_table.as_ref()
+    }
+
+    pub fn lr_table(&self) -> Option<&LrTable> {
+        self.lr_table.as_ref()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, GrammarError> {
+        let json_err = |it: serde_json::Error| GrammarError::Json(it.to_string());
+
+        // The version is checked first, a layout it does not describe may not deserialize.
+        let value: serde_json::Value = serde_json::from_str(json).map_err(json_err)?;
+        match value.get("version").and_then(|it| it.as_u64()) {
+            Some(version) if version == FORMAT_VERSION as u64 => {},
+            version => {
+                return Err(GrammarError::UnsupportedVersion {
+                    version,
+                    expecting: FORMAT_VERSION,
+                })
+            },
+        }
+
+        serde_json::from_value(value).map_err(json_err)
+    }
+
+    pub fn save(
+        &self,
+        path: &Path,
+    ) -> Result<(), GrammarError> {
+        fs::write(path, self.to_json()).map_err(|it| io_err(path, it))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, GrammarError> {
+        let json = fs::read_to_string(path).map_err(|it| io_err(path, it))?;
+        Self::from_json(&json)
+    }
+}
+
+fn io_err(
+    path: &Path,
+    err: std::io::Error,
+) -> GrammarError {
+    GrammarError::Io {
+        path: path.display().to_string(),
+        error: err.to_string(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::lang::error::GrammarError;
+    use crate::lang::lexer::v0::Lexer;
+    use crate::lang::parser::node::display_of;
+    use crate::lang::parser::rules::Rules;
+    use crate::lang::parser_impl::compiled::Compiled;
+    use crate::lang::parser_impl::ll1_parser::ll1_parse;
+    use crate::lang::parser_impl::ll1_parser::parse_with_ll1_table;
+    use crate::lang::parser_impl::lr_parser::lr_parse;
+    use crate::lang::parser_impl::lr_parser::parse_with_lr_table;
+    use crate::lang::parser_impl::lr_parser::LrKind;
+
+    #[test]
+    fn test_save_and_load() {
+        let rules: Rules = "
+S         -> statement S | EPSILON
+statement -> ID = expr ; | RETURN expr ;
+expr      -> term expr_r
+expr_r    -> + term expr_r | - term expr_r | EPSILON
+term      -> ( expr ) | INT | ID
+        "
+        .try_into()
+        .unwrap();
+
+        let compiled = Compiled::new(&rules, true, Some(LrKind::Lalr1)).unwrap();
+        let path = std::env::temp_dir().join(format!("compiled-{}.json", std::process::id()));
+        compiled.save(&path).unwrap();
+        let loaded = Compiled::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let loaded_rules = loaded.rules().unwrap();
+        assert_eq!(loaded_rules.to_string(), rules.to_string());
+        assert_eq!(loaded_rules.first_set(), rules.first_set());
+        assert_eq!(loaded_rules.follow_set(), rules.follow_set());
+        assert!(loaded_rules.start_set() == rules.start_set());
+        assert_eq!(loaded.to_json(), compiled.to_json());
+
+        let program = "x = (1 + y) - 2; return x;";
+
+        let ll1_table = loaded.ll1_table().unwrap();
+        assert_eq!(
+            ll1_table.to_string(),
+            compiled.ll1_table().unwrap().to_string()
+        );
+        let lexer: Lexer = program.into();
+        let loaded_tree = parse_with_ll1_table(&loaded_rules, ll1_table, lexer.into_iter());
+        let lexer: Lexer = program.into();
+        let tree = ll1_parse(&rules, lexer.into_iter());
+        assert_eq!(
+            display_of(&loaded_tree.unwrap()),
+            display_of(&tree.unwrap())
+        );
+
+        let lr_table = loaded.lr_table().unwrap();
+        assert_eq!(lr_table.kind(), LrKind::Lalr1);
+        let lexer: Lexer = program.into();
+        let loaded_tree = parse_with_lr_table(&loaded_rules, lr_table, lexer.into_iter());
+        let lexer: Lexer = program.into();
+        let tree = lr_parse(&rules, LrKind::Lalr1, lexer.into_iter());
+        assert_eq!(
+            display_of(&loaded_tree.unwrap()),
+            display_of(&tree.unwrap())
+        );
+
+        let json = compiled
+            .to_json()
+            .replacen("\"version\":1", "\"version\":0", 1);
+        assert_eq!(
+            Compiled::from_json(&json).err().unwrap(),
+            GrammarError::UnsupportedVersion {
+                version: Some(0),
+                expecting: 1
+            }
+        );
+        assert_eq!(
+            Compiled::from_json("{}").err().unwrap().to_string(),
+            "not a compiled grammar, no version"
+        );
+    }
+}
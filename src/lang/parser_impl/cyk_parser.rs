@@ -0,0 +1,459 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use log::trace;
+
+use crate::lang::error::GrammarError;
+use crate::lang::error::ParseErrorKind;
+use crate::lang::lexer::token::Token;
+use crate::lang::lexer::token::TokenKind;
+use crate::lang::lexer::v0::LexerResult;
+use crate::lang::parser::node::Node;
+use crate::lang::parser::node::ParseError;
+use crate::lang::parser::node::ParseResult;
+use crate::lang::parser::normal_form::is_chomsky_normal_form;
+use crate::lang::parser::normal_form::Derivation;
+use crate::lang::parser::normal_form::NormalForm;
+use crate::lang::parser::rule::RulePart;
+use crate::lang::parser::rules::Rules;
+
+// How a rule derives a span of the tokens, the first way found: a token alternative, or a two
+// rule alternative with the length of the span of its first rule.
+#[derive(Copy, Clone, Debug)]
+enum Cell {
+    Token { alt: usize },
+    Split { alt: usize, at: usize },
+}
+
+// A derivation in the normal form, Token(i) for the i-th token.
+enum Tree {
+    Token(usize),
+    Rule {
+        rule: usize,
+        alt: usize,
+        children: Vec<Tree>,
+    },
+}
+
+struct Cyk<'n> {
+    normal_form: &'n NormalForm,
+    names: Vec<String>,
+    // Token kind => (rule, alt) of the alternatives deriving it.
+    by_token: HashMap<TokenKind, Vec<(usize, usize)>>,
+    // (rule, alt, first rule, second rule) of the two rule alternatives.
+    pairs: Vec<(usize, usize, usize, usize)>,
+}
+
+impl<'n> Cyk<'n> {
+    fn new(normal_form: &'n NormalForm) -> Self {
+        let rules = normal_form.rules().rules();
+        let names = rules
+            .iter()
+            .map(|it| it.borrow().name().to_string())
+            .collect::<Vec<_>>();
+        let index_of = |part: &RulePart| names.iter().position(|it| *it == part.name()).unwrap();
+
+        let mut by_token: HashMap<TokenKind, Vec<(usize, usize)>> = HashMap::new();
+        let mut pairs = vec![];
+        for (rule_no, rule) in rules.iter().enumerate() {
+            for (alt_no, alt) in rule.borrow().alternatives.iter().enumerate() {
+                match alt.as_slice() {
+                    [RulePart::Token(tk)] if !tk.is_epsilon() => {
+                        by_token.entry(*tk).or_default().push((rule_no, alt_no));
+                    },
+                    [first, second] => {
+                        pairs.push((rule_no, alt_no, index_of(first), index_of(second)));
+                    },
+                    _ => {},
+                }
+            }
+        }
+
+        Self {
+            normal_form,
+            names,
+            by_token,
+            pairs,
+        }
+    }
+
+    // table[len - 1][start]: rule => how it derives tokens[start..start + len]. For each span
+    // the lowest alternative wins, then the longest span for its first rule.
+    fn recognize(
+        &self,
+        words: &[Token<'_>],
+    ) -> Vec<Vec<HashMap<usize, Cell>>> {
+        let n = words.len();
+        let mut table: Vec<Vec<HashMap<usize, Cell>>> = vec![];
+
+        table.push(
+            words
+                .iter()
+                .map(|word| {
+                    self.by_token
+                        .get(&word.token_kind)
+                        .map_or_else(HashMap::new, |alts| {
+                            let mut cells = HashMap::new();
+                            for (rule, alt) in alts {
+                                cells.entry(*rule).or_insert(Cell::Token {
+                                    alt: *alt,
+                                });
+                            }
+                            cells
+                        })
+                })
+                .collect(),
+        );
+
+        for len in 2..=n {
+            let mut row = vec![];
+            for start in 0..=(n - len) {
+                let mut cells = HashMap::new();
+                for (rule, alt, first, second) in &self.pairs {
+                    if cells.contains_key(rule) {
+                        continue;
+                    }
+                    for at in (1..len).rev() {
+                        if table[at - 1][start].contains_key(first)
+                            && table[len - at - 1][start + at].contains_key(second)
+                        {
+                            cells.insert(
+                                *rule,
+                                Cell::Split {
+                                    alt: *alt,
+                                    at,
+                                },
+                            );
+                            break;
+                        }
+                    }
+                }
+                row.push(cells);
+            }
+            table.push(row);
+        }
+
+        trace!("table has {} rows", table.len());
+        table
+    }
+
+    fn tree(
+        &self,
+        table: &[Vec<HashMap<usize, Cell>>],
+        rule: usize,
+        start: usize,
+        len: usize,
+    ) -> Tree {
+        match table[len - 1][start][&rule] {
+            Cell::Token {
+                alt,
+            } => Tree::Rule {
+                rule,
+                alt,
+                children: vec![Tree::Token(start)],
+            },
+            Cell::Split {
+                alt,
+                at,
+            } => {
+                let (first, second) = self
+                    .pairs
+                    .iter()
+                    .find(|it| it.0 == rule && it.1 == alt)
+                    .map(|it| (it.2, it.3))
+                    .unwrap();
+                Tree::Rule {
+                    rule,
+                    alt,
+                    children: vec![
+                        self.tree(table, first, start, at),
+                        self.tree(table, second, start + at, len - at),
+                    ],
+                }
+            },
+        }
+    }
+
+    // =========================================================================
+    // Back to the original rules.
+
+    fn node_of<'a>(
+        &self,
+        tree: &Tree,
+        words: &[Token<'a>],
+        parent: Option<&Rc<RefCell<Node<'a>>>>,
+        num: &mut usize,
+    ) -> Rc<RefCell<Node<'a>>> {
+        match tree {
+            Tree::Token(index) => {
+                let token = words[*index];
+                let node = self.new_node(RulePart::Token(token.token_kind), parent, num);
+                node.borrow_mut().set_token(token);
+                node
+            },
+            Tree::Rule {
+                rule,
+                alt,
+                children,
+            } => {
+                let derivation = self
+                    .normal_form
+                    .derivation(&self.names[*rule], *alt)
+                    .unwrap_or_else(|| panic!("helper rule on its own: {}", self.names[*rule]));
+                let mut leaves = vec![];
+                self.unfold(children, &mut leaves);
+                self.node_of_derivation(derivation, &leaves, words, parent, num)
+            },
+        }
+    }
+
+    // The parts of an alternative, with the helper rules replaced by their parts.
+    fn unfold<'t>(
+        &self,
+        trees: &'t [Tree],
+        leaves: &mut Vec<&'t Tree>,
+    ) {
+        for tree in trees {
+            match tree {
+                Tree::Rule {
+                    rule,
+                    alt,
+                    children,
+                } if self
+                    .normal_form
+                    .derivation(&self.names[*rule], *alt)
+                    .is_none() =>
+                {
+                    self.unfold(children, leaves)
+                },
+                tree => leaves.push(tree),
+            }
+        }
+    }
+
+    fn node_of_derivation<'a>(
+        &self,
+        derivation: &Derivation,
+        leaves: &[&Tree],
+        words: &[Token<'a>],
+        parent: Option<&Rc<RefCell<Node<'a>>>>,
+        num: &mut usize,
+    ) -> Rc<RefCell<Node<'a>>> {
+        match derivation {
+            Derivation::Part(n) => self.node_of(leaves[*n], words, parent, num),
+            Derivation::Alt {
+                rule,
+                alt_no,
+                children,
+            } => {
+                let rule = self.normal_form.original().get_rule_by_name(rule);
+                let node = self.new_node(RulePart::Rule(rule), parent, num);
+                node.borrow_mut().set_alt(*alt_no);
+                let children = children
+                    .iter()
+                    .map(|it| self.node_of_derivation(it, leaves, words, Some(&node), num))
+                    .collect();
+                node.borrow_mut().set_children(children);
+                node
+            },
+        }
+    }
+
+    fn new_node<'a>(
+        &self,
+        rule_part: RulePart,
+        parent: Option<&Rc<RefCell<Node<'a>>>>,
+        num: &mut usize,
+    ) -> Rc<RefCell<Node<'a>>> {
+        let node = match parent {
+            None => Node::new(rule_part, *num),
+            Some(parent) => Node::new_with_parent(rule_part, *num, parent),
+        };
+        *num += 1;
+        node.into()
+    }
+}
+
+
+pub fn cyk_parse<'a, T: Iterator<Item = LexerResult<'a>>>(
+    rules: &Rules,
+    tokens: T,
+) -> ParseResult<'a> {
+    match NormalForm::chomsky(rules) {
+        Ok(normal_form) => parse_with_chomsky_normal_form(&normal_form, tokens),
+        Err(err) => {
+            let root: Rc<RefCell<Node<'a>>> = Node::new(rules.start_part(), 0).into();
+            Err(ParseError::of(&root, ParseErrorKind::Grammar(err)))
+        },
+    }
+}
+
+// The tree is of the rules the normal form was made of.
+pub fn parse_with_chomsky_normal_form<'a, T: Iterator<Item = LexerResult<'a>>>(
+    normal_form: &NormalForm,
+    tokens: T,
+) -> ParseResult<'a> {
+    let root: Rc<RefCell<Node<'a>>> = Node::new(normal_form.original().start_part(), 0).into();
+
+    if !normal_form.has_derivations() || !is_chomsky_normal_form(normal_form.rules()) {
+        return Err(ParseError::of(
+            &root,
+            ParseErrorKind::Grammar(GrammarError::NotChomskyNormalForm),
+        ));
+    }
+
+    let mut words: Vec<Token<'a>> = vec![];
+    for word in tokens {
+        match word {
+            Ok(word) if word.token_kind == TokenKind::Eof => break,
+            Ok(word) => words.push(word),
+            Err(err) => return Err(ParseError::of(&root, err.into())),
+        }
+    }
+
+    let cyk = Cyk::new(normal_form);
+
+    let tree = if words.is_empty() {
+        normal_form.rules().rules()[0]
+            .borrow()
+            .alternatives
+            .iter()
+            .position(|alt| alt.len() == 1 && alt[0].is_epsilon())
+            .map(|alt| Tree::Rule {
+                rule: 0,
+                alt,
+                children: vec![],
+            })
+    }
+    else {
+        let table = cyk.recognize(&words);
+        table[words.len() - 1][0]
+            .contains_key(&0)
+            .then(|| cyk.tree(&table, 0, 0, words.len()))
+    };
+
+    match tree {
+        Some(tree) => Ok(cyk.node_of(&tree, &words, None, &mut 0)),
+        // No position the input goes wrong at is known, only that the start rule derives none of it.
+        None => Err(ParseError::with_error(
+            &root,
+            ParseErrorKind::unexpected_token(
+                Some(root.borrow().rule_part().name().as_str()),
+                [],
+                None,
+            ),
+            format!(
+                "no derivation of the input by the rules, tokens: {}",
+                words.len()
+            ),
+        )),
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::lang::error::ParseErrorKind;
+    use crate::lang::lexer::v0::Lexer;
+    use crate::lang::parser::node::display_of;
+    use crate::lang::parser::normal_form::NormalForm;
+    use crate::lang::parser::rules::Rules;
+    use crate::lang::parser_impl::cyk_parser::cyk_parse;
+    use crate::lang::parser_impl::cyk_parser::parse_with_chomsky_normal_form;
+    use crate::lang::parser_impl::earley_parser::parse_with_earley;
+
+    #[test]
+    fn test_same_as_earley() {
+        let rules: Rules = "
+S         -> statement S |
+statement -> ID = expr ; | RETURN expr ; | block
+block     -> { S }
+expr      -> expr + term | term
+term      -> term * factor | factor
+factor    -> ( expr ) | INT | ID
+        "
+        .try_into()
+        .unwrap();
+        let normal_form = NormalForm::chomsky(&rules).unwrap();
+
+        for text in [
+            "",
+            "x = 1;",
+            "x = 1 + 2 * (y + 3); { return x; {} }",
+            "{ { x = y; } return (1); }",
+        ] {
+            let lexer: Lexer = text.into();
+            let tree = parse_with_chomsky_normal_form(&normal_form, lexer.into_iter()).unwrap();
+
+            let lexer: Lexer = text.into();
+            let expected = parse_with_earley(&rules, lexer.into_iter()).unwrap();
+
+            assert_eq!(display_of(&tree), display_of(&expected), "text: {}", text);
+        }
+    }
+
+    #[test]
+    fn test_epsilon_and_units() {
+        let rules: Rules = "
+S    -> S , item | item |
+item -> ID opt
+opt  -> INT |
+        "
+        .try_into()
+        .unwrap();
+
+        let lexer: Lexer = "a 1, b".into();
+        let tree = cyk_parse(&rules, lexer.into_iter()).unwrap();
+        assert_eq!(
+            display_of(&tree),
+            "\
+S
+|_ S
+|____ item
+|______ ID[a]
+|______ opt
+|________ INT[1]
+|_ COMMA[,]
+|_ item
+|____ ID[b]
+|____ opt"
+        );
+
+        let lexer: Lexer = ", b".into();
+        let tree = cyk_parse(&rules, lexer.into_iter()).unwrap();
+        assert_eq!(
+            display_of(&tree),
+            "\
+S
+|_ S
+|_ COMMA[,]
+|_ item
+|____ ID[b]
+|____ opt"
+        );
+    }
+
+    #[test]
+    fn test_not_derived() {
+        let rules: Rules = "S -> S + INT | INT".try_into().unwrap();
+
+        let lexer: Lexer = "1 + + 2".into();
+        let err = cyk_parse(&rules, lexer.into_iter()).err().unwrap();
+        assert_eq!(
+            err.error(),
+            "no derivation of the input by the rules, tokens: 4"
+        );
+        assert!(matches!(
+            err.kind(),
+            ParseErrorKind::UnexpectedToken {
+                rule: Some(rule),
+                found: None,
+                ..
+            } if rule == "S"
+        ));
+
+        let lexer: Lexer = "".into();
+        assert!(cyk_parse(&rules, lexer.into_iter()).is_err());
+    }
+}
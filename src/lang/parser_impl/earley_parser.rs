@@ -0,0 +1,825 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use log::trace;
+
+use crate::lang::error::ParseErrorKind;
+use crate::lang::lexer::token::Token;
+use crate::lang::lexer::token::TokenKind;
+use crate::lang::lexer::v0::LexerResult;
+use crate::lang::parser::node::Node;
+use crate::lang::parser::node::ParseError;
+use crate::lang::parser::node::ParseResult;
+use crate::lang::parser::rule::Rule;
+use crate::lang::parser::rule::RulePart;
+use crate::lang::parser::rules::Rules;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+enum Symbol {
+    Rule(usize),
+    Token(TokenKind),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+struct Item {
+    rule: usize,
+    alt: usize,
+    dot: usize,
+    origin: usize,
+}
+
+#[derive(Default)]
+struct ChartSet {
+    items: Vec<Item>,
+    index: HashSet<Item>,
+}
+
+impl ChartSet {
+    fn add(
+        &mut self,
+        item: Item,
+    ) {
+        if self.index.insert(item) {
+            self.items.push(item);
+        }
+    }
+
+    fn contains(
+        &self,
+        item: &Item,
+    ) -> bool {
+        self.index.contains(item)
+    }
+}
+
+
+// Shared packed parse forest. Symbol nodes stand for a rule deriving tokens[start..end], their
+// families are the alternatives doing so. Partial nodes stand for the first `dot` parts of an
+// alternative, their families are the ways to split the span between the first `dot - 1` parts
+// (left) and the last one (right). This binarization keeps the forest cubic in size.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+enum SppfKey {
+    Symbol {
+        rule: usize,
+        start: usize,
+        end: usize,
+    },
+    Partial {
+        rule: usize,
+        alt: usize,
+        dot: usize,
+        start: usize,
+        end: usize,
+    },
+    Token {
+        index: usize,
+    },
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Family {
+    alt: usize,
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+struct SppfNode {
+    key: SppfKey,
+    families: Vec<Family>,
+}
+
+// A single derivation extracted from the forest.
+enum Tree {
+    Token(usize),
+    Rule {
+        rule: usize,
+        alt: usize,
+        children: Vec<Rc<Tree>>,
+    },
+}
+
+pub struct Sppf<'a> {
+    rules: Vec<Rc<RefCell<Rule>>>,
+    tokens: Vec<Token<'a>>,
+    nodes: Vec<SppfNode>,
+}
+
+impl<'a> Sppf<'a> {
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_ambiguous(&self) -> bool {
+        self.nodes.iter().any(|it| it.families.len() > 1)
+    }
+
+    // None when the forest has cycles, i.e. the number of trees is infinite.
+    pub fn count_trees(&self) -> Option<usize> {
+        let mut counts: HashMap<usize, Option<usize>> = HashMap::new();
+        self.count_trees0(0, &mut counts)
+    }
+
+    // All trees, up to max, the first one is the same as tree(). Derivations going through the
+    // same forest node twice (cycles) are left out.
+    pub fn trees(
+        &self,
+        max: usize,
+    ) -> Vec<Rc<RefCell<Node<'a>>>> {
+        let mut path = HashSet::new();
+        self.symbol_trees(0, &mut path, max)
+            .iter()
+            .map(|it| {
+                let mut num = 0;
+                self.node_of(it, None, &mut num)
+            })
+            .collect()
+    }
+
+    // Disambiguated tree: the lowest alternative is preferred, then the longest span for the
+    // leftmost parts, which makes ambiguous binary operators left-associative.
+    pub fn tree(&self) -> Rc<RefCell<Node<'a>>> {
+        self.trees(1).pop().unwrap()
+    }
+
+    // =========================================================================
+
+    fn count_trees0(
+        &self,
+        id: usize,
+        counts: &mut HashMap<usize, Option<usize>>,
+    ) -> Option<usize> {
+        if let Some(count) = counts.get(&id) {
+            return *count;
+        }
+        // Visiting, meeting it again means a cycle.
+        counts.insert(id, None);
+
+        let mut count: usize = 0;
+        if let SppfKey::Token {
+            ..
+        } = self.nodes[id].key
+        {
+            count = 1;
+        }
+        for family in &self.nodes[id].families {
+            let left = match family.left {
+                None => 1,
+                Some(left) => self.count_trees0(left, counts)?,
+            };
+            let right = match family.right {
+                None => 1,
+                Some(right) => self.count_trees0(right, counts)?,
+            };
+            count = count.saturating_add(left.saturating_mul(right));
+        }
+
+        counts.insert(id, Some(count));
+        Some(count)
+    }
+
+    fn symbol_trees(
+        &self,
+        id: usize,
+        path: &mut HashSet<usize>,
+        max: usize,
+    ) -> Vec<Rc<Tree>> {
+        let node = &self.nodes[id];
+
+        let rule = match node.key {
+            SppfKey::Token {
+                index,
+            } => return vec![Rc::new(Tree::Token(index))],
+            SppfKey::Symbol {
+                rule, ..
+            } => rule,
+            SppfKey::Partial {
+                ..
+            } => unreachable!("partial node: {:?}", node.key),
+        };
+
+        if !path.insert(id) {
+            return vec![];
+        }
+
+        let mut trees = vec![];
+        for family in &node.families {
+            let sequences = match family.right {
+                None => vec![vec![]],
+                Some(partial) => self.partial_trees(partial, path, max - trees.len()),
+            };
+            for children in sequences {
+                trees.push(Rc::new(Tree::Rule {
+                    rule,
+                    alt: family.alt,
+                    children,
+                }));
+            }
+            if trees.len() >= max {
+                break;
+            }
+        }
+
+        path.remove(&id);
+        trees
+    }
+
+    fn partial_trees(
+        &self,
+        id: usize,
+        path: &mut HashSet<usize>,
+        max: usize,
+    ) -> Vec<Vec<Rc<Tree>>> {
+        let mut sequences = vec![];
+
+        // Later split points first, see tree().
+        for family in self.nodes[id].families.iter().rev() {
+            let rights = self.symbol_trees(family.right.unwrap(), path, max);
+            if rights.is_empty() {
+                continue;
+            }
+            let lefts = match family.left {
+                None => vec![vec![]],
+                Some(left) => self.partial_trees(left, path, max),
+            };
+
+            'combine: for left in &lefts {
+                for right in &rights {
+                    if sequences.len() >= max {
+                        break 'combine;
+                    }
+                    let mut sequence = left.clone();
+                    sequence.push(Rc::clone(right));
+                    sequences.push(sequence);
+                }
+            }
+            if sequences.len() >= max {
+                break;
+            }
+        }
+
+        sequences
+    }
+
+    fn node_of(
+        &self,
+        tree: &Tree,
+        parent: Option<&Rc<RefCell<Node<'a>>>>,
+        num: &mut usize,
+    ) -> Rc<RefCell<Node<'a>>> {
+        let rule_part = match tree {
+            Tree::Token(index) => RulePart::Token(self.tokens[*index].token_kind),
+            Tree::Rule {
+                rule, ..
+            } => RulePart::Rule(Rc::clone(&self.rules[*rule])),
+        };
+
+        let node: Rc<RefCell<Node<'a>>> = match parent {
+            None => Node::new(rule_part, *num).into(),
+            Some(parent) => Node::new_with_parent(rule_part, *num, parent).into(),
+        };
+        *num += 1;
+
+        match tree {
+            Tree::Token(index) => node.borrow_mut().set_token(self.tokens[*index]),
+            Tree::Rule {
+                alt,
+                children,
+                ..
+            } => {
+                node.borrow_mut().set_alt(*alt);
+                let children = children
+                    .iter()
+                    .map(|it| self.node_of(it, Some(&node), num))
+                    .collect();
+                node.borrow_mut().set_children(children);
+            },
+        }
+
+        node
+    }
+}
+
+
+struct Earley<'r> {
+    rules: &'r Rules,
+    alts: Vec<Vec<Vec<Symbol>>>,
+    nullable: Vec<bool>,
+}
+
+impl<'r> Earley<'r> {
+    fn new(rules: &'r Rules) -> Self {
+        let index_of: HashMap<String, usize> = rules
+            .rules()
+            .iter()
+            .enumerate()
+            .map(|(index, rule)| (rule.borrow().name().to_string(), index))
+            .collect();
+
+        let alts = rules
+            .rules()
+            .iter()
+            .map(|rule| {
+                rule.borrow()
+                    .alternatives
+                    .iter()
+                    .map(|alt| {
+                        alt.iter()
+                            .filter(|it| !it.is_epsilon())
+                            .map(|part| match part {
+                                RulePart::Rule(_) => Symbol::Rule(index_of[&part.name()]),
+                                RulePart::Token(tk) => Symbol::Token(*tk),
+                                RulePart::Predicate(..) => unreachable!("rejected by validate"),
+                            })
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let nullable_set = rules.nullable_set();
+        let nullable = rules
+            .rules()
+            .iter()
+            .map(|it| nullable_set.contains(it.borrow().name()))
+            .collect();
+
+        Self {
+            rules,
+            alts,
+            nullable,
+        }
+    }
+
+    fn next_symbol(
+        &self,
+        item: &Item,
+    ) -> Option<Symbol> {
+        self.alts[item.rule][item.alt].get(item.dot).cloned()
+    }
+
+    fn advance(item: &Item) -> Item {
+        Item {
+            dot: item.dot + 1,
+            ..*item
+        }
+    }
+
+    // Nullable rules are advanced over when predicted (Aycock-Horspool), so completions of
+    // empty derivations don't get lost.
+    fn recognize(
+        &self,
+        tokens: &[Token<'_>],
+    ) -> Vec<ChartSet> {
+        let mut chart: Vec<ChartSet> = (0..=tokens.len()).map(|_| ChartSet::default()).collect();
+
+        for alt in 0..self.alts[0].len() {
+            chart[0].add(Item {
+                rule: 0,
+                alt,
+                dot: 0,
+                origin: 0,
+            });
+        }
+
+        for k in 0..=tokens.len() {
+            let mut i = 0;
+            while i < chart[k].items.len() {
+                let item = chart[k].items[i];
+                i += 1;
+
+                match self.next_symbol(&item) {
+                    Some(Symbol::Token(tk)) => {
+                        if k < tokens.len() && tokens[k].token_kind == tk {
+                            chart[k + 1].add(Self::advance(&item));
+                        }
+                    },
+                    Some(Symbol::Rule(rule)) => {
+                        for alt in 0..self.alts[rule].len() {
+                            chart[k].add(Item {
+                                rule,
+                                alt,
+                                dot: 0,
+                                origin: k,
+                            });
+                        }
+                        if self.nullable[rule] {
+                            chart[k].add(Self::advance(&item));
+                        }
+                    },
+                    None => {
+                        let completed: Vec<Item> = chart[item.origin]
+                            .items
+                            .iter()
+                            .filter(|it| self.next_symbol(it) == Some(Symbol::Rule(item.rule)))
+                            .map(Self::advance)
+                            .collect();
+                        for it in completed {
+                            chart[k].add(it);
+                        }
+                    },
+                }
+            }
+        }
+
+        chart
+    }
+
+    fn is_accepted(
+        &self,
+        chart: &[ChartSet],
+    ) -> bool {
+        let n = chart.len() - 1;
+        (0..self.alts[0].len()).any(|alt| {
+            chart[n].contains(&Item {
+                rule: 0,
+                alt,
+                dot: self.alts[0][alt].len(),
+                origin: 0,
+            })
+        })
+    }
+
+    fn err(
+        &self,
+        chart: &[ChartSet],
+        tokens: &[Token<'_>],
+    ) -> (ParseErrorKind, String) {
+        let position = (0..chart.len())
+            .rev()
+            .find(|k| !chart[*k].items.is_empty())
+            .unwrap();
+
+        let mut expecting: Vec<TokenKind> = chart[position]
+            .items
+            .iter()
+            .filter_map(|it| match self.next_symbol(it) {
+                Some(Symbol::Token(tk)) => Some(tk),
+                _ => None,
+            })
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        expecting.sort();
+        let kind = ParseErrorKind::unexpected_token(None, expecting.clone(), tokens.get(position));
+        let expecting = expecting
+            .iter()
+            .map(|it| it.name().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let err = match tokens.get(position) {
+            None => format!(
+                "position: {} /// unexpected end of input, expecting one of tokens: {}",
+                position, expecting,
+            ),
+            Some(word) => format!(
+                "position: {} /// unexpected token, expecting one of tokens: {} got: {}",
+                position, expecting, word,
+            ),
+        };
+        (kind, err)
+    }
+
+    // (rule, start, end) for each rule deriving tokens[start..end].
+    fn completed(
+        &self,
+        chart: &[ChartSet],
+    ) -> HashSet<(usize, usize, usize)> {
+        let mut completed = HashSet::new();
+        for (end, set) in chart.iter().enumerate() {
+            for item in set.items.iter().filter(|it| self.next_symbol(it).is_none()) {
+                completed.insert((item.rule, item.origin, end));
+            }
+        }
+        completed
+    }
+
+    fn forest<'a>(
+        &self,
+        chart: &[ChartSet],
+        tokens: Vec<Token<'a>>,
+    ) -> Sppf<'a> {
+        let completed = self.completed(chart);
+
+        let mut nodes: Vec<SppfNode> = vec![];
+        let mut index_of: HashMap<SppfKey, usize> = HashMap::new();
+
+        let mut id_of = |key: SppfKey, nodes: &mut Vec<SppfNode>| -> usize {
+            *index_of.entry(key).or_insert_with(|| {
+                nodes.push(SppfNode {
+                    key,
+                    families: vec![],
+                });
+                nodes.len() - 1
+            })
+        };
+
+        id_of(
+            SppfKey::Symbol {
+                rule: 0,
+                start: 0,
+                end: tokens.len(),
+            },
+            &mut nodes,
+        );
+
+        let mut id = 0;
+        while id < nodes.len() {
+            let mut families = vec![];
+
+            match nodes[id].key {
+                SppfKey::Token {
+                    ..
+                } => {},
+                SppfKey::Symbol {
+                    rule,
+                    start,
+                    end,
+                } => {
+                    for alt in 0..self.alts[rule].len() {
+                        let len = self.alts[rule][alt].len();
+                        let item = Item {
+                            rule,
+                            alt,
+                            dot: len,
+                            origin: start,
+                        };
+                        if !chart[end].contains(&item) {
+                            continue;
+                        }
+                        let right = match len {
+                            0 => None,
+                            _ => Some(id_of(
+                                SppfKey::Partial {
+                                    rule,
+                                    alt,
+                                    dot: len,
+                                    start,
+                                    end,
+                                },
+                                &mut nodes,
+                            )),
+                        };
+                        families.push(Family {
+                            alt,
+                            left: None,
+                            right,
+                        });
+                    }
+                },
+                SppfKey::Partial {
+                    rule,
+                    alt,
+                    dot,
+                    start,
+                    end,
+                } => {
+                    let prefix = Item {
+                        rule,
+                        alt,
+                        dot: dot - 1,
+                        origin: start,
+                    };
+                    let splits: Vec<usize> = match self.alts[rule][alt][dot - 1] {
+                        Symbol::Token(tk) => {
+                            if end > start && tokens[end - 1].token_kind == tk {
+                                vec![end - 1]
+                            }
+                            else {
+                                vec![]
+                            }
+                        },
+                        Symbol::Rule(sub_rule) => (start..=end)
+                            .filter(|k| completed.contains(&(sub_rule, *k, end)))
+                            .collect(),
+                    };
+
+                    for k in splits {
+                        if !chart[k].contains(&prefix) {
+                            continue;
+                        }
+                        if dot == 1 && k != start {
+                            continue;
+                        }
+
+                        let right_key = match self.alts[rule][alt][dot - 1] {
+                            Symbol::Token(_) => SppfKey::Token {
+                                index: k,
+                            },
+                            Symbol::Rule(sub_rule) => SppfKey::Symbol {
+                                rule: sub_rule,
+                                start: k,
+                                end,
+                            },
+                        };
+                        let left = match dot {
+                            1 => None,
+                            _ => Some(id_of(
+                                SppfKey::Partial {
+                                    rule,
+                                    alt,
+                                    dot: dot - 1,
+                                    start,
+                                    end: k,
+                                },
+                                &mut nodes,
+                            )),
+                        };
+                        let right = Some(id_of(right_key, &mut nodes));
+
+                        families.push(Family {
+                            alt,
+                            left,
+                            right,
+                        });
+                    }
+                },
+            }
+
+            nodes[id].families = families;
+            id += 1;
+        }
+
+        trace!("forest has {} nodes", nodes.len());
+
+        Sppf {
+            rules: self.rules.rules().clone(),
+            tokens,
+            nodes,
+        }
+    }
+}
+
+
+pub fn earley_parse<'a, T: Iterator<Item = LexerResult<'a>>>(
+    rules: &Rules,
+    tokens: T,
+) -> Result<Sppf<'a>, ParseError<'a>> {
+    let root: Rc<RefCell<Node<'a>>> = Node::new(rules.start_part(), 0).into();
+
+    if let Err(err) = rules.validate() {
+        return Err(ParseError::of(&root, ParseErrorKind::Grammar(err)));
+    }
+
+    let mut words: Vec<Token<'a>> = vec![];
+    for word in tokens {
+        match word {
+            Ok(word) if word.token_kind == TokenKind::Eof => break,
+            Ok(word) => words.push(word),
+            Err(err) => return Err(ParseError::of(&root, err.into())),
+        }
+    }
+
+    let earley = Earley::new(rules);
+    let chart = earley.recognize(&words);
+
+    if !earley.is_accepted(&chart) {
+        let (kind, err) = earley.err(&chart, &words);
+        return Err(ParseError::with_error(&root, kind, err));
+    }
+
+    Ok(earley.forest(&chart, words))
+}
+
+pub fn parse_with_earley<'a, T: Iterator<Item = LexerResult<'a>>>(
+    rules: &Rules,
+    tokens: T,
+) -> ParseResult<'a> {
+    earley_parse(rules, tokens).map(|it| it.tree())
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::lang::error::ParseErrorKind;
+    use crate::lang::lexer::token::TokenKind;
+    use crate::lang::lexer::v0::Lexer;
+    use crate::lang::parser::node::display_of;
+    use crate::lang::parser::rules::Rules;
+    use crate::lang::parser_impl::earley_parser::earley_parse;
+    use crate::lang::parser_impl::earley_parser::parse_with_earley;
+
+    #[test]
+    fn test_ambiguous_grammar() {
+        let rules: Rules = "S -> S + S | INT".try_into().unwrap();
+
+        let lexer: Lexer = "1 + 2 + 3".into();
+        let sppf = earley_parse(&rules, lexer.into_iter()).unwrap();
+
+        assert!(sppf.is_ambiguous());
+        assert_eq!(sppf.count_trees(), Some(2));
+
+        let trees = sppf.trees(10);
+        assert_eq!(trees.len(), 2);
+        assert_eq!(
+            display_of(&trees[0]),
+            "\
+S
+|_ S
+|____ S
+|______ INT[1]
+|____ PLUS[+]
+|____ S
+|______ INT[2]
+|_ PLUS[+]
+|_ S
+|____ INT[3]"
+        );
+        assert_eq!(
+            display_of(&trees[1]),
+            "\
+S
+|_ S
+|____ INT[1]
+|_ PLUS[+]
+|_ S
+|____ S
+|______ INT[2]
+|____ PLUS[+]
+|____ S
+|______ INT[3]"
+        );
+        assert_eq!(display_of(&sppf.tree()), display_of(&trees[0]));
+    }
+
+    #[test]
+    fn test_catalan_number_of_trees() {
+        let rules: Rules = "S -> S + S | INT".try_into().unwrap();
+
+        let lexer: Lexer = "1 + 2 + 3 + 4 + 5 + 6".into();
+        let sppf = earley_parse(&rules, lexer.into_iter()).unwrap();
+
+        assert_eq!(sppf.count_trees(), Some(42));
+        assert_eq!(sppf.trees(100).len(), 42);
+        assert_eq!(sppf.trees(5).len(), 5);
+    }
+
+    #[test]
+    fn test_nullable_and_left_recursive() {
+        let rules: Rules = "
+S    -> S , item | item
+item -> ID opt
+opt  -> INT |
+        "
+        .try_into()
+        .unwrap();
+
+        let lexer: Lexer = "a 1, b".into();
+        let sppf = earley_parse(&rules, lexer.into_iter()).unwrap();
+
+        assert!(!sppf.is_ambiguous());
+        assert_eq!(
+            display_of(&sppf.tree()),
+            "\
+S
+|_ S
+|____ item
+|______ ID[a]
+|______ opt
+|________ INT[1]
+|_ COMMA[,]
+|_ item
+|____ ID[b]
+|____ opt"
+        );
+    }
+
+    #[test]
+    fn test_cycle() {
+        let rules: Rules = "
+S     -> inner | ID
+inner -> S
+        "
+        .try_into()
+        .unwrap();
+
+        let lexer: Lexer = "a".into();
+        let sppf = earley_parse(&rules, lexer.into_iter()).unwrap();
+
+        assert_eq!(sppf.count_trees(), None);
+        assert_eq!(display_of(&sppf.tree()), "S\n|_ ID[a]");
+        assert_eq!(sppf.trees(10).len(), 1);
+    }
+
+    #[test]
+    fn test_unexpected_token() {
+        let rules: Rules = "S -> S + INT | INT".try_into().unwrap();
+
+        let lexer: Lexer = "1 + + 2".into();
+        let err = parse_with_earley(&rules, lexer.into_iter()).err().unwrap();
+
+        assert!(err.error().starts_with(
+            "position: 2 /// unexpected token, expecting one of tokens: integer got:"
+        ));
+        assert!(matches!(
+            err.kind(),
+            ParseErrorKind::UnexpectedToken {
+                rule: None,
+                expected,
+                found: Some((TokenKind::Plus, _)),
+            } if expected == &[TokenKind::Int]
+        ));
+    }
+}
@@ -0,0 +1,355 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::rc::Rc;
+
+use log::trace;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::lang::error::GrammarError;
+use crate::lang::error::ParseErrorKind;
+use crate::lang::lexer::token::Token;
+use crate::lang::lexer::token::TokenKind;
+use crate::lang::lexer::v0::LexerResult;
+use crate::lang::parser::node::Node;
+use crate::lang::parser::node::ParseError;
+use crate::lang::parser::node::ParseResult;
+use crate::lang::parser::rule::RulePart;
+use crate::lang::parser::rules::Rules;
+
+#[derive(Serialize, Deserialize)]
+pub struct Ll1Table {
+    #[serde(with = "crate::lang::util::map_entries")]
+    table: HashMap<String, BTreeMap<TokenKind, usize>>,
+}
+
+impl Ll1Table {
+    pub fn new(rules: &Rules) -> Result<Self, GrammarError> {
+        rules.validate()?;
+
+        let start = rules.start_set_with_eof();
+
+        let mut table: HashMap<String, BTreeMap<TokenKind, usize>> = HashMap::new();
+
+        for rule in rules.rules() {
+            let row = table.entry(rule.borrow().name().to_string()).or_default();
+
+            for alt_no in 0..rule.borrow().alternatives.len() {
+                for tk in &start[&(rule.borrow().name().to_string(), alt_no)] {
+                    if let Some(already) = row.insert(*tk, alt_no) {
+                        if already != alt_no {
+                            return Err(GrammarError::NotLl1 {
+                                rule: rule.borrow().name().to_string(),
+                                alt_nos: (already, alt_no),
+                                token: *tk,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            table,
+        })
+    }
+
+    pub fn get(
+        &self,
+        rule_name: &str,
+        token_kind: TokenKind,
+    ) -> Option<usize> {
+        self.table
+            .get(rule_name)
+            .and_then(|it| it.get(&token_kind))
+            .cloned()
+    }
+
+    pub fn expecting(
+        &self,
+        rule_name: &str,
+    ) -> Vec<TokenKind> {
+        let mut expecting: Vec<TokenKind> = self
+            .table
+            .get(rule_name)
+            .map_or_else(Vec::new, |it| it.keys().cloned().collect());
+        expecting.sort();
+        expecting
+    }
+}
+
+impl Display for Ll1Table {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        let sorted: BTreeMap<_, BTreeMap<_, _>> = self
+            .table
+            .iter()
+            .map(|(rule, row)| (rule, row.iter().collect()))
+            .collect();
+
+        write!(f, "Ll1Table[")?;
+        for (rule, row) in sorted {
+            let cells = row
+                .into_iter()
+                .map(|(tk, alt_no)| format!("{}=>{}", tk.upper_name(), alt_no))
+                .collect::<Vec<_>>()
+                .join(", ");
+            write!(f, "\n  {: <20} -> {}", rule, cells)?;
+        }
+        write!(f, "\n]")
+    }
+}
+
+
+pub fn ll1_parse<'a, T: Iterator<Item = LexerResult<'a>>>(
+    rules: &Rules,
+    tokens: T,
+) -> ParseResult<'a> {
+    let table = match Ll1Table::new(rules) {
+        Ok(table) => table,
+        Err(err) => {
+            let root: Rc<RefCell<Node<'a>>> = Node::new(rules.start_part(), 0).into();
+            return Err(ParseError::of(&root, ParseErrorKind::Grammar(err)));
+        },
+    };
+
+    parse_with_ll1_table(rules, &table, tokens)
+}
+
+pub fn parse_with_ll1_table<'a, T: Iterator<Item = LexerResult<'a>>>(
+    rules: &Rules,
+    table: &Ll1Table,
+    mut tokens: T,
+) -> ParseResult<'a> {
+    let root: Rc<RefCell<Node<'a>>> = Node::new(rules.start_part(), 0).into();
+    let mut next_num = 1;
+
+    let mut word: Option<Token<'a>> = match tokens.next().transpose() {
+        Ok(word) => word,
+        Err(err) => return Err(ParseError::of(&root, err.into())),
+    };
+
+    let mut stack: Vec<Rc<RefCell<Node<'a>>>> = vec![Rc::clone(&root)];
+
+    while let Some(focus) = stack.pop() {
+        let word_kind = word.map_or(TokenKind::Eof, |it| it.token_kind);
+        let rule_part = focus.borrow().rule_part().clone();
+
+        match rule_part {
+            RulePart::Token(expecting) => {
+                if expecting != word_kind {
+                    let err = match word {
+                        None => format!(
+                            "unexpected end of input, expecting: {}, got nothing",
+                            expecting,
+                        ),
+                        Some(word) => format!(
+                            "unexpected token kind, expecting: {}, got: {}",
+                            expecting, word,
+                        ),
+                    };
+                    let kind = ParseErrorKind::unexpected_token(None, [expecting], word.as_ref());
+                    return Err(ParseError::with_error(&root, kind, err));
+                }
+
+                trace!("match tk: {}", expecting.name());
+                focus.borrow_mut().set_token(word.unwrap());
+
+                word = match tokens.next().transpose() {
+                    Ok(word) => word,
+                    Err(err) => return Err(ParseError::of(&root, err.into())),
+                };
+            },
+            RulePart::Rule(rule) => {
+                let rule_name = rule.borrow().name().to_string();
+
+                let alt_no = match table.get(&rule_name, word_kind) {
+                    Some(alt_no) => alt_no,
+                    None => {
+                        let err = err_rule(table, &rule_name, word);
+                        let kind = ParseErrorKind::unexpected_token(
+                            Some(&rule_name),
+                            table.expecting(&rule_name),
+                            word.as_ref(),
+                        );
+                        return Err(ParseError::with_error(&root, kind, err));
+                    },
+                };
+                trace!("expanding {} with alt {}", rule_name, alt_no);
+
+                focus.borrow_mut().set_alt(alt_no);
+
+                let mut children: Vec<Rc<RefCell<Node<'a>>>> = vec![];
+                for part in rule.borrow().alternatives[alt_no]
+                    .iter()
+                    .filter(|it| !it.is_epsilon())
+                {
+                    let child: Node<'a> = Node::new_with_parent(part.clone(), next_num, &focus);
+                    next_num += 1;
+                    children.push(child.into());
+                }
+                for child in children.iter().rev() {
+                    stack.push(Rc::clone(child));
+                }
+                focus.borrow_mut().set_children(children);
+            },
+            RulePart::Predicate(..) => unreachable!("rejected by validate"),
+        }
+    }
+
+    match word {
+        None => Ok(root),
+        Some(word) if word.token_kind == TokenKind::Eof => Ok(root),
+        Some(word) => Err(ParseError::with_error(
+            &root,
+            ParseErrorKind::unexpected_token(None, [TokenKind::Eof], Some(&word)),
+            format!("unexpected token, expecting end of input, got: {}", word),
+        )),
+    }
+}
+
+fn err_rule(
+    table: &Ll1Table,
+    rule_name: &str,
+    word: Option<Token<'_>>,
+) -> String {
+    let expecting = table
+        .expecting(rule_name)
+        .into_iter()
+        .map(|it| it.name().to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    match word {
+        None => format!(
+            "rule: {} /// unexpected end of input, expecting one of tokens: {}",
+            rule_name, expecting,
+        ),
+        Some(word) => format!(
+            "rule: {} /// unexpected token, expecting one of tokens: {} got: {}",
+            rule_name, expecting, word,
+        ),
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::lang::ast::grammar::GRAMMAR_0;
+    use crate::lang::error::GrammarError;
+    use crate::lang::error::ParseErrorKind;
+    use crate::lang::lexer::token::TokenKind;
+    use crate::lang::lexer::v0::Lexer;
+    use crate::lang::parser::node::display_of;
+    use crate::lang::parser::rules::Rules;
+    use crate::lang::parser_impl::ll1_parser::ll1_parse;
+    use crate::lang::parser_impl::ll1_parser::Ll1Table;
+
+    fn grammar() -> Rules {
+        let mut rules: Rules = GRAMMAR_0.try_into().unwrap();
+
+        rules.make_ready_for_recursive_decent(128).unwrap();
+        rules
+    }
+
+    #[test]
+    fn test_correct_program() {
+        let rules = grammar();
+
+        let lexer: Lexer = "\
+            fn my_thing42(int j, string q) {
+                x1 = 1 * 30;
+                int y;
+                print(\"foo\");
+                return x1 + y;
+            }"
+        .into();
+
+        let tree = ll1_parse(&rules, lexer.into_iter()).unwrap();
+
+        let display = display_of(&tree);
+        assert!(display.starts_with("S\n|_ fn_call_or_decl\n|____ fn_declaration\n|______ FN[fn]"));
+        assert!(display.contains("STRING[foo]"));
+        assert!(display.contains("RETURN[return]"));
+    }
+
+    #[test]
+    fn test_empty_program() {
+        let rules = grammar();
+
+        let lexer: Lexer = "".into();
+
+        let tree = ll1_parse(&rules, lexer.into_iter()).unwrap();
+        assert_eq!(display_of(&tree), "S");
+    }
+
+    #[test]
+    fn test_incorrect_program() {
+        let rules = grammar();
+
+        let lexer: Lexer = "fn my_thing42(int j) {".into();
+
+        let err = ll1_parse(&rules, lexer.into_iter()).err().unwrap();
+        assert!(err.error().starts_with("rule: statements"));
+    }
+
+    #[test]
+    fn test_nullable_first_part() {
+        // The start of S goes on past `a`, which may derive nothing.
+        let rules: Rules = "
+S -> a ID
+a -> INT | EPSILON
+        "
+        .try_into()
+        .unwrap();
+        rules.is_backtrack_free().unwrap();
+
+        for program in ["x", "1 x"] {
+            let lexer: Lexer = program.into();
+            ll1_parse(&rules, lexer.into_iter()).unwrap();
+        }
+
+        let lexer: Lexer = "".into();
+        let err = ll1_parse(&rules, lexer.into_iter()).err().unwrap();
+        assert!(matches!(
+            err.kind(),
+            ParseErrorKind::UnexpectedToken {
+                rule: Some(rule),
+                expected,
+                found: Some((TokenKind::Eof, _)),
+            } if rule == "S" && expected == &[TokenKind::Id, TokenKind::Int]
+        ));
+    }
+
+    #[test]
+    fn test_not_ll1() {
+        let rules: Rules = "
+S  -> r0 | r1
+r0 -> ID
+r1 -> ID
+        "
+        .try_into()
+        .unwrap();
+
+        assert_eq!(
+            Ll1Table::new(&rules).err().unwrap(),
+            GrammarError::NotLl1 {
+                rule: "S".to_string(),
+                alt_nos: (0, 1),
+                token: TokenKind::Id,
+            }
+        );
+
+        let lexer: Lexer = "a".into();
+        let err = ll1_parse(&rules, lexer.into_iter()).err().unwrap();
+        assert!(matches!(
+            err.kind(),
+            ParseErrorKind::Grammar(GrammarError::NotLl1 { .. })
+        ));
+    }
+}
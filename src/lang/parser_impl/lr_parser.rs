@@ -0,0 +1,828 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::rc::Rc;
+
+use log::trace;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::lang::error::GrammarError;
+use crate::lang::error::ParseErrorKind;
+use crate::lang::lexer::token::Token;
+use crate::lang::lexer::token::TokenKind;
+use crate::lang::lexer::v0::LexerResult;
+use crate::lang::parser::node::Node;
+use crate::lang::parser::node::ParseError;
+use crate::lang::parser::node::ParseResult;
+use crate::lang::parser::rule::RulePart;
+use crate::lang::parser::rules::Rules;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum LrKind {
+    CanonicalLr1,
+    Lalr1,
+}
+
+impl Display for LrKind {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        match self {
+            LrKind::CanonicalLr1 => write!(f, "LR(1)"),
+            LrKind::Lalr1 => write!(f, "LALR(1)"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum LrAction {
+    Shift(usize),
+    Reduce(usize),
+    Accept,
+}
+
+impl Display for LrAction {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        match self {
+            LrAction::Shift(state) => write!(f, "shift {}", state),
+            LrAction::Reduce(production) => write!(f, "reduce {}", production),
+            LrAction::Accept => write!(f, "accept"),
+        }
+    }
+}
+
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Ord, PartialOrd, Serialize, Deserialize)]
+enum Symbol {
+    Rule(usize),
+    Token(TokenKind),
+}
+
+// A rule alternative, epsilon alternatives have an empty rhs. Production 0 is the augmented
+// start: S' -> S
+#[derive(Serialize, Deserialize)]
+struct Production {
+    rule: usize,
+    alt_no: usize,
+    rhs: Vec<Symbol>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Ord, PartialOrd, Serialize, Deserialize)]
+struct Item {
+    production: usize,
+    dot: usize,
+    lookahead: TokenKind,
+}
+
+type ItemSet = BTreeSet<Item>;
+
+
+#[derive(Serialize, Deserialize)]
+pub struct LrTable {
+    kind: LrKind,
+    rule_names: Vec<String>,
+    productions: Vec<Production>,
+    states: Vec<ItemSet>,
+    #[serde(with = "crate::lang::util::map_entries")]
+    action: HashMap<(usize, TokenKind), LrAction>,
+    #[serde(with = "crate::lang::util::map_entries")]
+    goto: HashMap<(usize, usize), usize>,
+}
+
+impl LrTable {
+    pub fn canonical_lr1(rules: &Rules) -> Result<Self, GrammarError> {
+        Self::new(rules, LrKind::CanonicalLr1)
+    }
+
+    pub fn lalr1(rules: &Rules) -> Result<Self, GrammarError> {
+        Self::new(rules, LrKind::Lalr1)
+    }
+
+    pub fn new(
+        rules: &Rules,
+        kind: LrKind,
+    ) -> Result<Self, GrammarError> {
+        rules.validate()?;
+
+        let mut table = Self {
+            kind,
+            rule_names: vec![],
+            productions: vec![],
+            states: vec![],
+            action: HashMap::new(),
+            goto: HashMap::new(),
+        };
+
+        table.make_productions(rules);
+
+        let first = rules.first_set();
+        let builder = Builder {
+            table: &table,
+            first: &first,
+            productions_of: table.productions_of(),
+        };
+
+        let (states, transitions) = builder.canonical_collection();
+        let (states, transitions) = match kind {
+            LrKind::CanonicalLr1 => (states, transitions),
+            LrKind::Lalr1 => merge_same_cores(states, transitions),
+        };
+
+        table.states = states;
+        table.make_actions(&transitions)?;
+
+        Ok(table)
+    }
+
+    pub fn kind(&self) -> LrKind {
+        self.kind
+    }
+
+    pub fn num_states(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn action(
+        &self,
+        state: usize,
+        token_kind: TokenKind,
+    ) -> Option<LrAction> {
+        self.action.get(&(state, token_kind)).cloned()
+    }
+
+    pub fn expecting(
+        &self,
+        state: usize,
+    ) -> Vec<TokenKind> {
+        let mut expecting: Vec<TokenKind> = self
+            .action
+            .keys()
+            .filter(|it| it.0 == state)
+            .map(|it| it.1)
+            .collect();
+        expecting.sort();
+        expecting
+    }
+
+    // =========================================================================
+
+    fn make_productions(
+        &mut self,
+        rules: &Rules,
+    ) {
+        self.rule_names = rules
+            .rules()
+            .iter()
+            .map(|it| it.borrow().name().to_string())
+            .collect();
+
+        let index_of: HashMap<String, usize> = self
+            .rule_names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| (name.to_string(), index))
+            .collect();
+
+        // Augmented start rule.
+        self.productions.push(Production {
+            rule: self.rule_names.len(),
+            alt_no: 0,
+            rhs: vec![Symbol::Rule(0)],
+        });
+
+        for (rule_index, rule) in rules.rules().iter().enumerate() {
+            for (alt_no, alt) in rule.borrow().alternatives.iter().enumerate() {
+                let rhs = alt
+                    .iter()
+                    .filter(|it| !it.is_epsilon())
+                    .map(|part| match part {
+                        RulePart::Rule(_) => Symbol::Rule(index_of[&part.name()]),
+                        RulePart::Token(tk) => Symbol::Token(*tk),
+                        RulePart::Predicate(..) => unreachable!("rejected by validate"),
+                    })
+                    .collect();
+
+                self.productions.push(Production {
+                    rule: rule_index,
+                    alt_no,
+                    rhs,
+                });
+            }
+        }
+    }
+
+    fn productions_of(&self) -> Vec<Vec<usize>> {
+        let mut productions_of = vec![vec![]; self.rule_names.len() + 1];
+        for (index, production) in self.productions.iter().enumerate() {
+            productions_of[production.rule].push(index);
+        }
+        productions_of
+    }
+
+    fn make_actions(
+        &mut self,
+        transitions: &HashMap<(usize, Symbol), usize>,
+    ) -> Result<(), GrammarError> {
+        let mut conflicts = vec![];
+
+        let mut sorted_transitions: Vec<_> = transitions.iter().collect();
+        sorted_transitions.sort();
+
+        for (&(state, symbol), &target) in sorted_transitions {
+            match symbol {
+                Symbol::Rule(rule) => {
+                    self.goto.insert((state, rule), target);
+                },
+                Symbol::Token(tk) => {
+                    self.put_action(state, tk, LrAction::Shift(target), &mut conflicts);
+                },
+            }
+        }
+
+        for state in 0..self.states.len() {
+            let completed: Vec<Item> = self.states[state]
+                .iter()
+                .filter(|it| it.dot == self.productions[it.production].rhs.len())
+                .cloned()
+                .collect();
+
+            for item in completed {
+                let action = match item.production {
+                    0 => LrAction::Accept,
+                    production => LrAction::Reduce(production),
+                };
+                self.put_action(state, item.lookahead, action, &mut conflicts);
+            }
+        }
+
+        if conflicts.is_empty() {
+            Ok(())
+        }
+        else {
+            Err(GrammarError::LrConflicts {
+                kind: self.kind.to_string(),
+                conflicts,
+            })
+        }
+    }
+
+    fn put_action(
+        &mut self,
+        state: usize,
+        tk: TokenKind,
+        action: LrAction,
+        conflicts: &mut Vec<String>,
+    ) {
+        match self.action.get(&(state, tk)) {
+            None => {
+                self.action.insert((state, tk), action);
+            },
+            Some(already) if *already == action => {},
+            Some(already) => {
+                let kind = match (already, action) {
+                    (LrAction::Shift(_), _) | (_, LrAction::Shift(_)) => "shift/reduce",
+                    _ => "reduce/reduce",
+                };
+                conflicts.push(format!(
+                    "{} conflict in state {} on {}: {} <vs> {}, items:\n{}",
+                    kind,
+                    state,
+                    tk.upper_name(),
+                    self.display_action(*already),
+                    self.display_action(action),
+                    self.display_items_involved(state, tk),
+                ));
+            },
+        }
+    }
+
+    // =========================================================================
+
+    fn rule_name(
+        &self,
+        rule: usize,
+    ) -> &str {
+        match self.rule_names.get(rule) {
+            Some(name) => name,
+            None => "S'",
+        }
+    }
+
+    fn display_symbol(
+        &self,
+        symbol: &Symbol,
+    ) -> String {
+        match symbol {
+            Symbol::Rule(rule) => self.rule_name(*rule).to_string(),
+            Symbol::Token(tk) => tk.repr_or_name().to_string(),
+        }
+    }
+
+    fn display_production(
+        &self,
+        production: usize,
+    ) -> String {
+        let production = &self.productions[production];
+        let rhs = production
+            .rhs
+            .iter()
+            .map(|it| self.display_symbol(it))
+            .collect::<Vec<_>>();
+        format!(
+            "{} -> {}",
+            self.rule_name(production.rule),
+            match rhs.is_empty() {
+                true => "EPSILON".to_string(),
+                false => rhs.join(" "),
+            }
+        )
+    }
+
+    fn display_action(
+        &self,
+        action: LrAction,
+    ) -> String {
+        match action {
+            LrAction::Reduce(production) => {
+                format!("reduce {}", self.display_production(production))
+            },
+            action => action.to_string(),
+        }
+    }
+
+    fn display_item(
+        &self,
+        item: &Item,
+    ) -> String {
+        let production = &self.productions[item.production];
+        let mut rhs = production
+            .rhs
+            .iter()
+            .map(|it| self.display_symbol(it))
+            .collect::<Vec<_>>();
+        rhs.insert(item.dot, ".".to_string());
+        format!(
+            "[{} -> {}, {}]",
+            self.rule_name(production.rule),
+            rhs.join(" "),
+            item.lookahead.repr_or_name()
+        )
+    }
+
+    fn display_items_involved(
+        &self,
+        state: usize,
+        tk: TokenKind,
+    ) -> String {
+        self.states[state]
+            .iter()
+            .filter(|item| {
+                let rhs = &self.productions[item.production].rhs;
+                if item.dot == rhs.len() {
+                    item.lookahead == tk
+                }
+                else {
+                    rhs[item.dot] == Symbol::Token(tk)
+                }
+            })
+            .map(|it| format!("  {}", self.display_item(it)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl Display for LrTable {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(f, "LrTable[{}", self.kind)?;
+        for (state, items) in self.states.iter().enumerate() {
+            write!(f, "\n  state {}:", state)?;
+            for item in items {
+                write!(f, "\n    {}", self.display_item(item))?;
+            }
+        }
+        write!(f, "\n]")
+    }
+}
+
+
+struct Builder<'t> {
+    table: &'t LrTable,
+    first: &'t HashMap<String, HashSet<TokenKind>>,
+    productions_of: Vec<Vec<usize>>,
+}
+
+impl Builder<'_> {
+    fn first_of(
+        &self,
+        symbols: &[Symbol],
+        lookahead: TokenKind,
+    ) -> HashSet<TokenKind> {
+        let mut first = HashSet::new();
+
+        for symbol in symbols {
+            match symbol {
+                Symbol::Token(tk) => {
+                    first.insert(*tk);
+                    return first;
+                },
+                Symbol::Rule(rule) => {
+                    let rule_first = &self.first[self.table.rule_name(*rule)];
+                    first.extend(rule_first.iter().filter(|it| !it.is_epsilon()));
+                    if !rule_first.contains(&TokenKind::Epsilon) {
+                        return first;
+                    }
+                },
+            }
+        }
+
+        first.insert(lookahead);
+        first
+    }
+
+    fn closure(
+        &self,
+        items: ItemSet,
+    ) -> ItemSet {
+        let mut closure = items;
+        let mut work: Vec<Item> = closure.iter().cloned().collect();
+
+        while let Some(item) = work.pop() {
+            let rhs = &self.table.productions[item.production].rhs;
+            if item.dot >= rhs.len() {
+                continue;
+            }
+
+            if let Symbol::Rule(rule) = rhs[item.dot] {
+                let lookaheads = self.first_of(&rhs[item.dot + 1..], item.lookahead);
+                for production in &self.productions_of[rule] {
+                    for lookahead in &lookaheads {
+                        let new = Item {
+                            production: *production,
+                            dot: 0,
+                            lookahead: *lookahead,
+                        };
+                        if closure.insert(new) {
+                            work.push(new);
+                        }
+                    }
+                }
+            }
+        }
+
+        closure
+    }
+
+    fn goto(
+        &self,
+        items: &ItemSet,
+        symbol: Symbol,
+    ) -> ItemSet {
+        let moved = items
+            .iter()
+            .filter(|item| {
+                let rhs = &self.table.productions[item.production].rhs;
+                item.dot < rhs.len() && rhs[item.dot] == symbol
+            })
+            .map(|item| Item {
+                production: item.production,
+                dot: item.dot + 1,
+                lookahead: item.lookahead,
+            })
+            .collect();
+
+        self.closure(moved)
+    }
+
+    fn canonical_collection(&self) -> (Vec<ItemSet>, HashMap<(usize, Symbol), usize>) {
+        let start = self.closure(
+            [Item {
+                production: 0,
+                dot: 0,
+                lookahead: TokenKind::Eof,
+            }]
+            .into_iter()
+            .collect(),
+        );
+
+        let mut states: Vec<ItemSet> = vec![start.clone()];
+        let mut index_of: HashMap<ItemSet, usize> = HashMap::new();
+        index_of.insert(start, 0);
+
+        let mut transitions: HashMap<(usize, Symbol), usize> = HashMap::new();
+
+        let mut state = 0;
+        while state < states.len() {
+            let symbols: BTreeSet<Symbol> = states[state]
+                .iter()
+                .filter_map(|item| {
+                    self.table.productions[item.production]
+                        .rhs
+                        .get(item.dot)
+                        .cloned()
+                })
+                .collect();
+
+            for symbol in symbols {
+                let target = self.goto(&states[state], symbol);
+                let target = match index_of.get(&target) {
+                    Some(index) => *index,
+                    None => {
+                        states.push(target.clone());
+                        index_of.insert(target, states.len() - 1);
+                        states.len() - 1
+                    },
+                };
+                transitions.insert((state, symbol), target);
+            }
+
+            state += 1;
+        }
+
+        trace!("canonical LR(1) collection has {} states", states.len());
+
+        (states, transitions)
+    }
+}
+
+// LALR(1): states with the same LR(0) core are merged, their lookaheads are combined.
+fn merge_same_cores(
+    states: Vec<ItemSet>,
+    transitions: HashMap<(usize, Symbol), usize>,
+) -> (Vec<ItemSet>, HashMap<(usize, Symbol), usize>) {
+    let mut merged: Vec<ItemSet> = vec![];
+    let mut core_index: HashMap<BTreeSet<(usize, usize)>, usize> = HashMap::new();
+    let mut new_index: Vec<usize> = vec![];
+
+    for state in states {
+        let core: BTreeSet<(usize, usize)> = state
+            .iter()
+            .map(|item| (item.production, item.dot))
+            .collect();
+
+        match core_index.get(&core) {
+            Some(index) => {
+                merged[*index].extend(state);
+                new_index.push(*index);
+            },
+            None => {
+                merged.push(state);
+                core_index.insert(core, merged.len() - 1);
+                new_index.push(merged.len() - 1);
+            },
+        }
+    }
+
+    let transitions = transitions
+        .into_iter()
+        .map(|((state, symbol), target)| ((new_index[state], symbol), new_index[target]))
+        .collect();
+
+    trace!("LALR(1) collection has {} states", merged.len());
+
+    (merged, transitions)
+}
+
+
+pub fn lr_parse<'a, T: Iterator<Item = LexerResult<'a>>>(
+    rules: &Rules,
+    kind: LrKind,
+    tokens: T,
+) -> ParseResult<'a> {
+    let table = match LrTable::new(rules, kind) {
+        Ok(table) => table,
+        Err(err) => {
+            let root: Rc<RefCell<Node<'a>>> = Node::new(rules.start_part(), 0).into();
+            return Err(ParseError::of(&root, ParseErrorKind::Grammar(err)));
+        },
+    };
+
+    parse_with_lr_table(rules, &table, tokens)
+}
+
+pub fn parse_with_lr_table<'a, T: Iterator<Item = LexerResult<'a>>>(
+    rules: &Rules,
+    table: &LrTable,
+    mut tokens: T,
+) -> ParseResult<'a> {
+    let mut next_num = 0;
+    let mut num = move || {
+        let n = next_num;
+        next_num += 1;
+        n
+    };
+
+    let mut states: Vec<usize> = vec![0];
+    let mut nodes: Vec<Rc<RefCell<Node<'a>>>> = vec![];
+
+    // On error, what's parsed so far hangs from a start rule node.
+    let partial_tree = |nodes: &Vec<Rc<RefCell<Node<'a>>>>| {
+        let root: Rc<RefCell<Node<'a>>> = Node::new(rules.start_part(), usize::MAX).into();
+        root.borrow_mut().set_children(nodes.clone());
+        root
+    };
+
+    let mut word: Option<Token<'a>> = match tokens.next().transpose() {
+        Ok(word) => word,
+        Err(err) => return Err(ParseError::of(&partial_tree(&nodes), err.into())),
+    };
+
+    loop {
+        let state = *states.last().unwrap();
+        let word_kind = word.map_or(TokenKind::Eof, |it| it.token_kind);
+
+        match table.action(state, word_kind) {
+            Some(LrAction::Shift(target)) => {
+                trace!("shift {} => {}", word_kind.name(), target);
+
+                let node: Rc<RefCell<Node<'a>>> =
+                    Node::new(RulePart::Token(word_kind), num()).into();
+                node.borrow_mut().set_token(word.unwrap());
+                nodes.push(node);
+                states.push(target);
+
+                word = match tokens.next().transpose() {
+                    Ok(word) => word,
+                    Err(err) => return Err(ParseError::of(&partial_tree(&nodes), err.into())),
+                };
+            },
+            Some(LrAction::Reduce(production)) => {
+                trace!("reduce {}", table.display_production(production));
+
+                let production = &table.productions[production];
+                let rule = rules.get_rule_by_name(table.rule_name(production.rule));
+
+                let len = production.rhs.len();
+                let children = nodes.split_off(nodes.len() - len);
+                states.truncate(states.len() - len);
+
+                let node: Rc<RefCell<Node<'a>>> = Node::new(RulePart::Rule(rule), num()).into();
+                node.borrow_mut().set_alt(production.alt_no);
+                for child in &children {
+                    child.borrow_mut().set_parent(&node);
+                }
+                node.borrow_mut().set_children(children);
+                nodes.push(node);
+
+                let state = *states.last().unwrap();
+                states.push(table.goto[&(state, production.rule)]);
+            },
+            Some(LrAction::Accept) => {
+                trace!("accept");
+                return Ok(nodes.pop().unwrap());
+            },
+            None => {
+                let expecting = table
+                    .expecting(state)
+                    .into_iter()
+                    .map(|it| it.name().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                let err = match word {
+                    None => format!(
+                        "state: {} /// unexpected end of input, expecting one of tokens: {}",
+                        state, expecting,
+                    ),
+                    Some(word) => format!(
+                        "state: {} /// unexpected token, expecting one of tokens: {} got: {}",
+                        state, expecting, word,
+                    ),
+                };
+                let kind =
+                    ParseErrorKind::unexpected_token(None, table.expecting(state), word.as_ref());
+                return Err(ParseError::with_error(&partial_tree(&nodes), kind, err));
+            },
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::lang::error::GrammarError;
+    use crate::lang::lexer::v0::Lexer;
+    use crate::lang::parser::node::display_of;
+    use crate::lang::parser::rules::Rules;
+    use crate::lang::parser_impl::lr_parser::lr_parse;
+    use crate::lang::parser_impl::lr_parser::LrKind;
+    use crate::lang::parser_impl::lr_parser::LrTable;
+
+    #[test]
+    fn test_left_recursive_grammar_as_written() {
+        let rules: Rules = "
+S      -> S + term | S - term | term
+term   -> term * factor | factor
+factor -> ( S ) | INT | ID
+        "
+        .try_into()
+        .unwrap();
+
+        let lexer: Lexer = "a - b - c".into();
+        let tree = lr_parse(&rules, LrKind::Lalr1, lexer.into_iter()).unwrap();
+
+        assert_eq!(
+            display_of(&tree),
+            "\
+S
+|_ S
+|____ S
+|______ term
+|________ factor
+|__________ ID[a]
+|____ MINUS[-]
+|____ term
+|______ factor
+|________ ID[b]
+|_ MINUS[-]
+|_ term
+|____ factor
+|______ ID[c]"
+        );
+    }
+
+    #[test]
+    fn test_epsilon() {
+        let rules: Rules = "S -> S ID | EPSILON".try_into().unwrap();
+
+        let lexer: Lexer = "a b".into();
+        let tree = lr_parse(&rules, LrKind::CanonicalLr1, lexer.into_iter()).unwrap();
+
+        assert_eq!(
+            display_of(&tree),
+            "\
+S
+|_ S
+|____ S
+|____ ID[a]
+|_ ID[b]"
+        );
+    }
+
+    #[test]
+    fn test_lr1_but_not_lalr1() {
+        let rules: Rules = "
+S  -> ID r0 ; | ID r1 , | INT r1 ; | INT r0 ,
+r0 -> RETURN
+r1 -> RETURN
+        "
+        .try_into()
+        .unwrap();
+
+        let lr1 = LrTable::canonical_lr1(&rules).unwrap();
+        let lalr1 = LrTable::lalr1(&rules).err().unwrap().to_string();
+
+        assert!(lr1.num_states() > 0);
+        assert!(lalr1.starts_with("grammar is not LALR(1)"));
+        assert!(lalr1.contains("reduce/reduce conflict"));
+        assert!(lalr1.contains("[r0 -> return ., ;]"));
+        assert!(lalr1.contains("[r1 -> return ., ;]"));
+
+        let lexer: Lexer = "1 return ,".into();
+        lr_parse(&rules, LrKind::CanonicalLr1, lexer.into_iter()).unwrap();
+    }
+
+    #[test]
+    fn test_shift_reduce_conflict() {
+        let rules: Rules = "S -> S + S | INT".try_into().unwrap();
+
+        let err = match LrTable::lalr1(&rules) {
+            Err(GrammarError::LrConflicts {
+                kind,
+                conflicts,
+            }) => {
+                assert_eq!(kind, "LALR(1)");
+                conflicts.join("\n")
+            },
+            _ => panic!("expecting conflicts"),
+        };
+
+        assert!(err.contains("shift/reduce conflict"));
+        assert!(err.contains("[S -> S + S ., +]"));
+        assert!(err.contains("[S -> S . + S, +]"));
+    }
+
+    #[test]
+    fn test_unexpected_token() {
+        let rules: Rules = "S -> S ID | ID".try_into().unwrap();
+
+        let lexer: Lexer = "a 1".into();
+        let err = lr_parse(&rules, LrKind::Lalr1, lexer.into_iter())
+            .err()
+            .unwrap();
+
+        assert!(err
+            .error()
+            .contains("unexpected token, expecting one of tokens: eof, id"));
+    }
+}
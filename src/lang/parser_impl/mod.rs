@@ -1,2 +1,9 @@
+pub mod ambiguity;
 pub mod backtracking_parser;
+pub mod compiled;
+pub mod cyk_parser;
+pub mod earley_parser;
+pub mod ll1_parser;
+pub mod lr_parser;
+pub mod packrat_parser;
 pub mod recursive_descent_parser;
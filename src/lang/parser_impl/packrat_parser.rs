@@ -0,0 +1,399 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use log::trace;
+
+use crate::lang::error::ParseErrorKind;
+use crate::lang::lexer::token::Token;
+use crate::lang::lexer::token::TokenKind;
+use crate::lang::parser::node::Node;
+use crate::lang::parser::node::ParseError;
+use crate::lang::parser::node::ParseResult;
+use crate::lang::parser::rule::Predicate;
+use crate::lang::parser::rule::Rule;
+use crate::lang::parser::rule::RulePart;
+use crate::lang::parser::rules::Rules;
+
+// A parsed rule, turned into nodes once the whole input is matched, memoized results are shared.
+enum Tree {
+    Token(usize),
+    Rule {
+        rule: usize,
+        alt: usize,
+        children: Vec<Rc<Tree>>,
+    },
+}
+
+type Memo = HashMap<(usize, usize), Option<(usize, Rc<Tree>)>>;
+
+// PEG semantics over the same grammar: alternatives are an ordered choice, the first one to match
+// wins and is never revisited, so with every (rule, position) memoized parsing is linear.
+struct PackratParser<'a> {
+    rules: Vec<Rc<RefCell<Rule>>>,
+    index_of: HashMap<String, usize>,
+    tokens: Vec<Token<'a>>,
+    memo: Memo,
+
+    // The farthest position a token was expected at, for error reporting.
+    farthest: usize,
+    expecting: HashSet<TokenKind>,
+    in_predicate: usize,
+}
+
+impl<'a> PackratParser<'a> {
+    fn new(
+        rules: &Rules,
+        tokens: Vec<Token<'a>>,
+    ) -> Self {
+        Self {
+            rules: rules.rules().clone(),
+            index_of: rules
+                .rules()
+                .iter()
+                .enumerate()
+                .map(|(index, rule)| (rule.borrow().name().to_string(), index))
+                .collect(),
+            tokens,
+            memo: HashMap::new(),
+            farthest: 0,
+            expecting: HashSet::new(),
+            in_predicate: 0,
+        }
+    }
+
+    fn parse(mut self) -> ParseResult<'a> {
+        let parsed = self.parse_rule(0, 0);
+
+        match parsed {
+            Some((end, tree)) if end == self.tokens.len() => {
+                let mut num = 0;
+                Ok(self.node_of(&tree, None, &mut num))
+            },
+            Some((end, tree)) => {
+                let mut num = 0;
+                let partial_tree = self.node_of(&tree, None, &mut num);
+                let (kind, err) = match self.farthest > end {
+                    true => self.err(),
+                    false => (
+                        ParseErrorKind::unexpected_token(
+                            None,
+                            [TokenKind::Eof],
+                            Some(&self.tokens[end]),
+                        ),
+                        format!(
+                            "position: {} /// unexpected token, expecting end of input, got: {}",
+                            end, self.tokens[end],
+                        ),
+                    ),
+                };
+                Err(ParseError::with_error(&partial_tree, kind, err))
+            },
+            None => {
+                let root: Rc<RefCell<Node<'a>>> =
+                    Node::new(RulePart::Rule(Rc::clone(&self.rules[0])), 0).into();
+                let (kind, err) = self.err();
+                Err(ParseError::with_error(&root, kind, err))
+            },
+        }
+    }
+
+    fn parse_rule(
+        &mut self,
+        rule: usize,
+        position: usize,
+    ) -> Option<(usize, Rc<Tree>)> {
+        if let Some(parsed) = self.memo.get(&(rule, position)) {
+            return parsed.clone();
+        }
+
+        // Left recursion fails instead of looping forever.
+        self.memo.insert((rule, position), None);
+
+        let parsed = self.parse_rule0(rule, position);
+        self.memo.insert((rule, position), parsed.clone());
+        parsed
+    }
+
+    fn parse_rule0(
+        &mut self,
+        rule: usize,
+        position: usize,
+    ) -> Option<(usize, Rc<Tree>)> {
+        let rule_rc = Rc::clone(&self.rules[rule]);
+        let rule_ref = rule_rc.borrow();
+
+        for (alt, parts) in rule_ref.alternatives.iter().enumerate() {
+            if let Some((end, children)) = self.parse_parts(parts, position) {
+                trace!(
+                    "{} alt {} matched {}..{}",
+                    rule_ref.name(),
+                    alt,
+                    position,
+                    end
+                );
+                return Some((
+                    end,
+                    Rc::new(Tree::Rule {
+                        rule,
+                        alt,
+                        children,
+                    }),
+                ));
+            }
+        }
+
+        None
+    }
+
+    fn parse_parts(
+        &mut self,
+        parts: &[RulePart],
+        position: usize,
+    ) -> Option<(usize, Vec<Rc<Tree>>)> {
+        let mut position = position;
+        let mut children = vec![];
+
+        for part in parts {
+            let (end, child) = self.parse_part(part, position)?;
+            position = end;
+            children.extend(child);
+        }
+
+        Some((position, children))
+    }
+
+    fn parse_part(
+        &mut self,
+        part: &RulePart,
+        position: usize,
+    ) -> Option<(usize, Option<Rc<Tree>>)> {
+        match part {
+            RulePart::Token(tk) if tk.is_epsilon() => Some((position, None)),
+            RulePart::Token(tk) => match self.tokens.get(position) {
+                Some(word) if word.token_kind == *tk => {
+                    Some((position + 1, Some(Rc::new(Tree::Token(position)))))
+                },
+                _ => {
+                    self.expected(*tk, position);
+                    None
+                },
+            },
+            RulePart::Rule(rule) => {
+                let rule = self.index_of[rule.borrow().name()];
+                self.parse_rule(rule, position)
+                    .map(|(end, tree)| (end, Some(tree)))
+            },
+            RulePart::Predicate(predicate, target) => {
+                self.in_predicate += 1;
+                let matched = self.parse_part(target, position).is_some();
+                self.in_predicate -= 1;
+
+                match (predicate, matched) {
+                    (Predicate::And, true) | (Predicate::Not, false) => Some((position, None)),
+                    _ => None,
+                }
+            },
+        }
+    }
+
+    fn expected(
+        &mut self,
+        tk: TokenKind,
+        position: usize,
+    ) {
+        // What a predicate looks at is not what the input is expected to be.
+        if self.in_predicate > 0 || position < self.farthest {
+            return;
+        }
+        if position > self.farthest {
+            self.farthest = position;
+            self.expecting.clear();
+        }
+        self.expecting.insert(tk);
+    }
+
+    fn err(&self) -> (ParseErrorKind, String) {
+        let mut expecting = self.expecting.iter().cloned().collect::<Vec<_>>();
+        expecting.sort();
+        let kind = ParseErrorKind::unexpected_token(
+            None,
+            expecting.clone(),
+            self.tokens.get(self.farthest),
+        );
+        let expecting = expecting
+            .iter()
+            .map(|it| it.name().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let err = match self.tokens.get(self.farthest) {
+            None => format!(
+                "position: {} /// unexpected end of input, expecting one of tokens: {}",
+                self.farthest, expecting,
+            ),
+            Some(word) => format!(
+                "position: {} /// unexpected token, expecting one of tokens: {} got: {}",
+                self.farthest, expecting, word,
+            ),
+        };
+        (kind, err)
+    }
+
+    fn node_of(
+        &self,
+        tree: &Tree,
+        parent: Option<&Rc<RefCell<Node<'a>>>>,
+        num: &mut usize,
+    ) -> Rc<RefCell<Node<'a>>> {
+        let rule_part = match tree {
+            Tree::Token(index) => RulePart::Token(self.tokens[*index].token_kind),
+            Tree::Rule {
+                rule, ..
+            } => RulePart::Rule(Rc::clone(&self.rules[*rule])),
+        };
+
+        let node: Rc<RefCell<Node<'a>>> = match parent {
+            None => Node::new(rule_part, *num).into(),
+            Some(parent) => Node::new_with_parent(rule_part, *num, parent).into(),
+        };
+        *num += 1;
+
+        match tree {
+            Tree::Token(index) => node.borrow_mut().set_token(self.tokens[*index]),
+            Tree::Rule {
+                alt,
+                children,
+                ..
+            } => {
+                node.borrow_mut().set_alt(*alt);
+                let children = children
+                    .iter()
+                    .map(|it| self.node_of(it, Some(&node), num))
+                    .collect();
+                node.borrow_mut().set_children(children);
+            },
+        }
+
+        node
+    }
+}
+
+
+pub fn parse_with_packrat<'a, T: Iterator<Item = Token<'a>>>(
+    rules: &Rules,
+    tokens: T,
+) -> ParseResult<'a> {
+    trace!("matching against: {}", rules);
+
+    if let Err(err) = rules.validate_peg() {
+        let root: Rc<RefCell<Node<'a>>> = Node::new(rules.start_part(), 0).into();
+        return Err(ParseError::of(&root, ParseErrorKind::Grammar(err)));
+    }
+
+    let tokens = tokens
+        .take_while(|it| it.token_kind != TokenKind::Eof)
+        .collect();
+
+    PackratParser::new(rules, tokens).parse()
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::lang::error::ParseErrorKind;
+    use crate::lang::lexer::token::TokenKind;
+    use crate::lang::lexer::v0::Lexer;
+    use crate::lang::parser::node::display_of;
+    use crate::lang::parser::rules::Rules;
+    use crate::lang::parser_impl::packrat_parser::parse_with_packrat;
+
+    #[test]
+    fn test_ordered_choice() {
+        let rules: Rules = "
+S   -> ID ( args ) | ID
+args -> INT , args | INT |
+        "
+        .try_into()
+        .unwrap();
+
+        let tokens = Lexer::parse("f(1, 2)").unwrap();
+        let tree = parse_with_packrat(&rules, tokens.into_iter()).unwrap();
+        assert_eq!(tree.borrow().alt(), 0);
+
+        let tokens = Lexer::parse("f").unwrap();
+        let tree = parse_with_packrat(&rules, tokens.into_iter()).unwrap();
+        assert_eq!(display_of(&tree), "S\n|_ ID[f]");
+    }
+
+    #[test]
+    fn test_not_predicate() {
+        // Without the predicate the first alternative would win and the call would not parse.
+        let rules: Rules = "S -> ID !( | ID ( )".try_into().unwrap();
+
+        let tokens = Lexer::parse("f()").unwrap();
+        let tree = parse_with_packrat(&rules, tokens.into_iter()).unwrap();
+        assert_eq!(tree.borrow().alt(), 1);
+
+        let tokens = Lexer::parse("f").unwrap();
+        let tree = parse_with_packrat(&rules, tokens.into_iter()).unwrap();
+        assert_eq!(tree.borrow().alt(), 0);
+    }
+
+    #[test]
+    fn test_and_predicate_beyond_context_free() {
+        // a^n b^n c^n, with identifiers, integers and strings.
+        let rules: Rules = "
+S     -> &ahead ids bc
+ahead -> ab STRING
+ab    -> ID ab INT | ID INT
+ids   -> ID ids | ID
+bc    -> INT bc STRING | INT STRING
+        "
+        .try_into()
+        .unwrap();
+
+        let tokens = Lexer::parse("a b 1 2 \"x\" \"y\"").unwrap();
+        let tree = parse_with_packrat(&rules, tokens.into_iter()).unwrap();
+        assert!(display_of(&tree).starts_with("S\n|_ ids\n|____ ID[a]"));
+
+        for wrong in ["a b 1 2 \"x\"", "a 1 2 \"x\" \"y\"", "a b 1 \"x\" \"y\""] {
+            let tokens = Lexer::parse(wrong).unwrap();
+            assert!(parse_with_packrat(&rules, tokens.into_iter()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_memoized_backtracking() {
+        // Each level tries `t` three times, exponential without memoization.
+        let rules: Rules = "
+S -> t + S | t - S | t
+t -> ( S ) | INT
+        "
+        .try_into()
+        .unwrap();
+
+        let program = format!("{}1{}", "(".repeat(40), ")".repeat(40));
+        let tokens = Lexer::parse(&program).unwrap();
+        parse_with_packrat(&rules, tokens.into_iter()).unwrap();
+    }
+
+    #[test]
+    fn test_left_recursion_fails() {
+        let rules: Rules = "S -> S + INT | INT".try_into().unwrap();
+
+        let tokens = Lexer::parse("1 + 2").unwrap();
+        let err = parse_with_packrat(&rules, tokens.into_iter())
+            .err()
+            .unwrap();
+        assert!(err.error().starts_with("position: 1 /// unexpected token"));
+        assert!(matches!(
+            err.kind(),
+            ParseErrorKind::UnexpectedToken {
+                found: Some((TokenKind::Plus, _)),
+                ..
+            }
+        ));
+    }
+}
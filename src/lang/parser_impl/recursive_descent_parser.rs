@@ -1,81 +1,233 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
-use std::iter::Peekable;
+use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::rc::Rc;
 
 use log::trace;
 
+use crate::lang::error::ParseErrorKind;
 use crate::lang::lexer::token::Token;
 use crate::lang::lexer::token::TokenKind;
 use crate::lang::lexer::v0::LexerResult;
-use crate::lang::parser::node::Node;
+use crate::lang::parser::actions::Actions;
 use crate::lang::parser::node::ParseError;
 use crate::lang::parser::node::ParseResult;
+use crate::lang::parser::precedence::ExpressionRule;
+use crate::lang::parser::precedence::Fixity;
+use crate::lang::parser::rule::AltRef;
+use crate::lang::parser::rule::Rule;
 use crate::lang::parser::rule::RulePart;
 use crate::lang::parser::rules::Rules;
+use crate::lang::parser::tree::NodeId;
+use crate::lang::parser::tree::TreeBuilder;
+use crate::lang::parser::tree::TreeParseError;
+use crate::lang::parser::tree::TreeParseResult;
 
 pub fn recursive_descent_parse<'a, T: Iterator<Item = LexerResult<'a>>>(
     rules: &Rules,
     tokens: T,
 ) -> ParseResult<'a> {
-    RecursiveDescentParser::new(rules, tokens.peekable()).parse_s()
+    to_node_result(rules, recursive_descent_parse_tree(rules, tokens))
 }
 
+// As recursive_descent_parse, into the Tree the parser builds, without making a Node of it.
+pub fn recursive_descent_parse_tree<'a, T: Iterator<Item = LexerResult<'a>>>(
+    rules: &Rules,
+    tokens: T,
+) -> TreeParseResult<'a> {
+    RecursiveDescentParser::<_, ()>::new(rules, 1, None, tokens)?.parse()
+}
 
-struct RecursiveDescentParser<'a, 'b, T: Iterator<Item = LexerResult<'a>>> {
-    rules: &'b Rules,
+// Alternatives are chosen by the next k tokens, see Rules::is_ll_k.
+pub fn recursive_descent_parse_k<'a, T: Iterator<Item = LexerResult<'a>>>(
+    rules: &Rules,
+    k: usize,
+    tokens: T,
+) -> ParseResult<'a> {
+    let tree = RecursiveDescentParser::<_, ()>::new(rules, k.max(1), None, tokens)
+        .and_then(|parser| parser.parse());
+    to_node_result(rules, tree)
+}
 
+// Parses straight into the values of the actions, see Actions.
+pub fn recursive_descent_parse_actions<'a, V, T: Iterator<Item = LexerResult<'a>>>(
+    rules: &Rules,
+    actions: &Actions<V>,
+    tokens: T,
+) -> Result<V, ParseError<'a>> {
+    let mut parser = match RecursiveDescentParser::new(rules, 1, Some(actions), tokens) {
+        Ok(parser) => parser,
+        Err(err) => return Err(to_node_error(rules, err)),
+    };
+    let parsed = parser.expand().and_then(|_| parser.expect_end());
+    match parsed {
+        Ok(_) => Ok(parser.values.pop().unwrap()),
+        Err(err) => Err(to_node_error(rules, err)),
+    }
+}
+
+fn to_node_result<'a>(
+    rules: &Rules,
+    tree: TreeParseResult<'a>,
+) -> ParseResult<'a> {
+    match tree {
+        Ok(tree) => Ok(tree.to_node(rules)),
+        Err(err) => Err(to_node_error(rules, err)),
+    }
+}
+
+fn to_node_error<'a>(
+    rules: &Rules,
+    err: TreeParseError<'a>,
+) -> ParseError<'a> {
+    let partial_tree = err.partial_tree().to_node(rules);
+    ParseError::with_error(&partial_tree, err.kind().clone(), err.error().to_string())
+}
+
+type Parsed<'a> = Result<(), TreeParseError<'a>>;
+
+
+struct RecursiveDescentParser<'a, 'b, T: Iterator<Item = LexerResult<'a>>, V> {
     first_set: HashMap<String, Vec<TokenKind>>,
     follow_set: HashMap<String, Vec<TokenKind>>,
+    start_set: HashMap<String, Vec<Vec<TokenKind>>>,
+    expression_rules: HashMap<String, ExpressionRule>,
 
-    tokens: Peekable<T>,
-    focus: Rc<RefCell<Node<'a>>>,
+    // Only with k > 1, otherwise start_set is used.
+    k: usize,
+    start_k_set: HashMap<String, Vec<HashSet<Vec<TokenKind>>>>,
+
+    rules_by_name: HashMap<String, Rc<RefCell<Rule>>>,
+
+    tokens: T,
+    lookahead: VecDeque<LexerResult<'a>>,
+    tree: TreeBuilder<'a>,
+    focus: NodeId,
+
+    // Each expanded rule and matched token leaves its value here, EPSILON none.
+    actions: Option<&'b Actions<V>>,
+    values: Vec<V>,
 }
 
-impl<'a, 'b, T: Iterator<Item = LexerResult<'a>>> RecursiveDescentParser<'a, 'b, T> {
+impl<'a, 'b, T: Iterator<Item = LexerResult<'a>>, V> RecursiveDescentParser<'a, 'b, T, V> {
     fn new(
         rules: &'b Rules,
+        k: usize,
+        actions: Option<&'b Actions<V>>,
         tokens: T,
-    ) -> Self {
-        let rule_part: RulePart = rules.rules().first().unwrap().into();
-        let root: Node<'a> = Node::new(rule_part, 0);
-
-        Self {
-            rules,
-            tokens: tokens.peekable(),
-            focus: root.into(),
+    ) -> Result<Self, TreeParseError<'a>> {
+        let rule_part = rules.start_part();
+        let mut tree = TreeBuilder::new();
+        let focus = tree.add(None, &rule_part);
+
+        // Before the sets are made, which expect valid rules without predicates.
+        if let Err(err) = rules.validate() {
+            return Err(TreeParseError::of(
+                tree.build(),
+                ParseErrorKind::Grammar(err),
+            ));
+        }
+
+        let start_set = rules.start_set_with_eof();
+        let start_k_set = match k {
+            1 => HashMap::new(),
+            _ => rules.start_k_set(k),
+        };
+
+        Ok(Self {
+            rules_by_name: rules
+                .rules()
+                .iter()
+                .map(|rule| (rule.borrow().name().to_string(), Rc::clone(rule)))
+                .collect(),
+            tokens,
+            lookahead: VecDeque::new(),
+            tree,
+            focus,
             first_set: rules
                 .first_set()
                 .into_iter()
                 .map(|it| (it.0, it.1.into_iter().collect::<Vec<_>>()))
                 .collect(),
             follow_set: rules
-                .follow_set()
+                .follow_set_with_eof()
                 .into_iter()
                 .map(|it| (it.0, it.1.into_iter().collect::<Vec<_>>()))
                 .collect(),
-        }
+            start_set: rules
+                .rules()
+                .iter()
+                .map(|rule| {
+                    let name = rule.borrow().name().to_string();
+                    let alt_starts = (0..rule.borrow().alternatives.len())
+                        .map(|alt_no| start_set[&(name.clone(), alt_no)].iter().cloned().collect())
+                        .collect();
+                    (name, alt_starts)
+                })
+                .collect(),
+            expression_rules: rules.expression_rules(),
+            k,
+            start_k_set: rules
+                .rules()
+                .iter()
+                .filter(|_| k > 1)
+                .map(|rule| {
+                    let name = rule.borrow().name().to_string();
+                    let alt_starts = (0..rule.borrow().alternatives.len())
+                        .map(|alt_no| start_k_set[&(name.clone(), alt_no)].clone())
+                        .collect();
+                    (name, alt_starts)
+                })
+                .collect(),
+            actions,
+            values: vec![],
+        })
     }
 
 
-    fn ok_parent(&mut self) -> ParseResult<'a> {
+    fn ok_parent(&mut self) -> Parsed<'a> {
         self.pop_to_parent();
-        Ok(Rc::clone(&self.focus))
+        Ok(())
     }
 
+    // The tree as far as it got, the parser is done with after an error.
     fn _err(
         &mut self,
+        kind: ParseErrorKind,
+        msg: String,
+    ) -> Parsed<'a> {
+        let partial_tree = std::mem::take(&mut self.tree).build();
+
+        Err(TreeParseError::with_error(partial_tree, kind, msg))
+    }
+
+    fn err_unexpected(
+        &mut self,
+        rule: Option<&str>,
+        expected: Vec<TokenKind>,
         msg: String,
-    ) -> ParseResult<'a> {
-        self.pop_to_root();
+    ) -> Parsed<'a> {
+        let found = match self.has_peek() {
+            true => self.peek().ok().copied(),
+            false => None,
+        };
+        let kind = ParseErrorKind::unexpected_token(rule, expected, found.as_ref());
+        self._err(kind, msg)
+    }
 
-        Err(ParseError::new(&self.focus, msg))
+    fn err_lexer(
+        &mut self,
+        kind: ParseErrorKind,
+    ) -> Parsed<'a> {
+        let msg = kind.to_string();
+        self._err(kind, msg)
     }
 
     fn err_rule(
         &mut self,
         this_rule: &str,
-    ) -> ParseResult<'a> {
+    ) -> Parsed<'a> {
         let start_tokens = {
             let mut start_tokens = self.first_set[this_rule]
                 .iter()
@@ -85,11 +237,12 @@ impl<'a, 'b, T: Iterator<Item = LexerResult<'a>>> RecursiveDescentParser<'a, 'b,
             start_tokens.join(", ")
         };
 
-        let has_epsilon = self
-            .rules
-            .get_rule_by_name(this_rule)
-            .borrow()
-            .has_epsilon();
+        let has_epsilon = self.rules_by_name[this_rule].borrow().has_epsilon();
+
+        let mut expected = self.first_set[this_rule].clone();
+        if has_epsilon {
+            expected.extend(&self.follow_set[this_rule]);
+        }
 
         if has_epsilon {
             let follow = {
@@ -102,7 +255,7 @@ impl<'a, 'b, T: Iterator<Item = LexerResult<'a>>> RecursiveDescentParser<'a, 'b,
             };
 
             if !self.has_peek() {
-                self._err(format!(
+                self.err_unexpected(Some(this_rule), expected, format!(
                     "rule: {} /// unexpected end of input, expecting one of tokens: {} /// OR because of epsilon one of: {}",
                     this_rule,
                     start_tokens,
@@ -117,7 +270,7 @@ impl<'a, 'b, T: Iterator<Item = LexerResult<'a>>> RecursiveDescentParser<'a, 'b,
                     follow,
                     self.peek().unwrap(),
                 );
-                self._err(err)
+                self.err_unexpected(Some(this_rule), expected, err)
             }
         }
         else if self.has_peek() {
@@ -127,13 +280,17 @@ impl<'a, 'b, T: Iterator<Item = LexerResult<'a>>> RecursiveDescentParser<'a, 'b,
                 start_tokens,
                 self.peek().unwrap(),
             );
-            self._err(err)
+            self.err_unexpected(Some(this_rule), expected, err)
         }
         else {
-            self._err(format!(
-                "rule: {} /// unexpected end of input, expecting one of tokens: {}",
-                this_rule, start_tokens,
-            ))
+            self.err_unexpected(
+                Some(this_rule),
+                expected,
+                format!(
+                    "rule: {} /// unexpected end of input, expecting one of tokens: {}",
+                    this_rule, start_tokens,
+                ),
+            )
         }
     }
 
@@ -141,68 +298,51 @@ impl<'a, 'b, T: Iterator<Item = LexerResult<'a>>> RecursiveDescentParser<'a, 'b,
     fn pop_to_parent(&mut self) {
         trace!(
             "popping to parent, we are at: {}",
-            self.focus.borrow().rule_part().name()
+            self.tree.node(self.focus).name()
         );
-        let parent = Rc::clone(self.focus.borrow().parent().as_ref().unwrap());
-        self.focus = parent;
-    }
-
-    fn pop_to_root(&mut self) {
-        trace!(
-            "popping to root, we are at: {}",
-            self.focus.borrow().rule_part().name()
-        );
-        while self.focus.borrow().parent().is_some() {
-            self.pop_to_parent();
-        }
+        self.focus = self.tree.parent(self.focus).unwrap();
     }
 
     fn push_to_rule(
         &mut self,
         rule_name: &str,
     ) {
-        let node = self.node_by_rule(rule_name);
-        self.focus.borrow_mut().append_child(&node);
-        self.focus = node;
+        self.focus = self.tree.add_rule(Some(self.focus), rule_name);
     }
 
-    fn node_by_rule(
-        &mut self,
-        rule_name: &str,
-    ) -> Rc<RefCell<Node<'a>>> {
-        let rule = self.rules.get_rule_by_name(rule_name);
-        let node = Node::new_with_parent(
-            RulePart::Rule(rule),
-            self.focus.borrow().next_num(),
-            &self.focus,
-        );
-        node.into()
+    // Makes the focus the first child of a new node of the same rule, the new node becomes the
+    // focus and takes the place of the old one in its parent.
+    fn wrap_focus(&mut self) {
+        self.focus = self.tree.wrap(self.focus);
     }
 
-    fn node_by_token_kind(
+
+    fn fill_lookahead(
         &mut self,
-        token_kind: TokenKind,
-    ) -> Rc<RefCell<Node<'a>>> {
-        let node = Node::new(RulePart::Token(token_kind), self.focus.borrow().next_num());
-        node.into()
+        len: usize,
+    ) {
+        while self.lookahead.len() < len {
+            match self.tokens.next() {
+                None => break,
+                Some(token) => self.lookahead.push_back(token),
+            }
+        }
     }
 
-
     fn has_peek(&mut self) -> bool {
-        self.tokens.peek().is_some()
+        self.fill_lookahead(1);
+        !self.lookahead.is_empty()
     }
 
-    fn peek(&mut self) -> Result<&Token<'a>, String> {
-        match self.tokens.peek() {
+    fn peek(&mut self) -> Result<&Token<'a>, ParseErrorKind> {
+        self.fill_lookahead(1);
+        match self.lookahead.front() {
             None => {
                 panic!("peek called while no more token is remaining")
             },
             Some(peek) => match peek {
                 Ok(peek) => Ok(peek),
-                Err(err) => Err(format!(
-                    "lexer error, position: {} line: {}, error: {}",
-                    err.position, err.line, err.error
-                )),
+                Err(err) => Err(err.clone().into()),
             },
         }
     }
@@ -214,16 +354,42 @@ impl<'a, 'b, T: Iterator<Item = LexerResult<'a>>> RecursiveDescentParser<'a, 'b,
         self.has_peek() && expecting.contains(&self.peek().unwrap().token_kind)
     }
 
-    fn peek_is_in_rule_first(
+    // The next k token kinds, up to and including EOF, which is added if the input just ends.
+    fn peek_k(&mut self) -> Vec<TokenKind> {
+        self.fill_lookahead(self.k);
+
+        let mut window = vec![];
+        for token in self.lookahead.iter().take(self.k) {
+            match token {
+                Ok(token) if token.token_kind == TokenKind::Eof => {
+                    window.push(TokenKind::Eof);
+                    return window;
+                },
+                Ok(token) => window.push(token.token_kind),
+                Err(_) => return window,
+            }
+        }
+        if window.len() < self.k {
+            window.push(TokenKind::Eof);
+        }
+        window
+    }
+
+    fn peek_is_in_alt_start(
         &mut self,
         rule_name: &str,
+        alt_no: usize,
     ) -> bool {
-        if !self.has_peek() {
+        if self.k > 1 {
+            let window = self.peek_k();
+            self.start_k_set[rule_name][alt_no].contains(&window)
+        }
+        else if !self.has_peek() {
             false
         }
         else {
             let tk = self.peek().unwrap().token_kind;
-            self.first_set[rule_name].contains(&tk)
+            self.start_set[rule_name][alt_no].contains(&tk)
         }
     }
 
@@ -247,24 +413,32 @@ impl<'a, 'b, T: Iterator<Item = LexerResult<'a>>> RecursiveDescentParser<'a, 'b,
         self.peek_is_in(&[tk])
     }
 
+    fn peek_token_kind(&mut self) -> Option<TokenKind> {
+        match self.has_peek() {
+            true => self.peek().ok().map(|it| it.token_kind),
+            false => None,
+        }
+    }
+
 
     fn match_tk(
         &mut self,
         expecting: TokenKind,
-    ) -> ParseResult<'a> {
+    ) -> Parsed<'a> {
         if !self.has_peek() {
-            return self._err(format!(
-                "unexpected end of input, expecting: {}, got nothing",
-                expecting,
-            ));
+            return self.err_unexpected(
+                None,
+                vec![expecting],
+                format!(
+                    "unexpected end of input, expecting: {}, got nothing",
+                    expecting,
+                ),
+            );
         }
 
         match self.peek() {
             Ok(_) => {},
-            Err(err) => {
-                self.pop_to_root();
-                return Err(ParseError::new(&self.focus, err));
-            },
+            Err(err) => return self.err_lexer(err),
         }
 
         trace!(
@@ -274,10 +448,12 @@ impl<'a, 'b, T: Iterator<Item = LexerResult<'a>>> RecursiveDescentParser<'a, 'b,
         );
 
         if self.peek().unwrap().token_kind == expecting {
-            let node = self.node_by_token_kind(expecting);
-            node.borrow_mut()
-                .set_token(self.tokens.next().unwrap().unwrap());
-            self.focus.borrow_mut().append_child(&node);
+            let node = self.tree.add_token(self.focus, expecting);
+            let token = self.lookahead.pop_front().unwrap().unwrap();
+            if let Some(actions) = self.actions {
+                self.values.push(actions.token_value(&token));
+            }
+            self.tree.set_token(node, token);
         }
         else {
             let err = format!(
@@ -285,410 +461,450 @@ impl<'a, 'b, T: Iterator<Item = LexerResult<'a>>> RecursiveDescentParser<'a, 'b,
                 expecting,
                 self.peek().unwrap(),
             );
-            return self._err(err);
+            return self.err_unexpected(None, vec![expecting], err);
         }
 
         match self.peek() {
-            Ok(_) => Ok(Rc::clone(&self.focus)),
-            Err(err) => {
-                self.pop_to_root();
-                return Err(ParseError::new(&self.focus, err));
-            },
+            Ok(_) => Ok(()),
+            Err(err) => self.err_lexer(err),
         }
     }
 
 
-    // ============================================================================================
-
-    fn parse_s(mut self) -> ParseResult<'a> {
-        trace!("parsing S");
-        let node = self.node_by_rule("S");
-        self.focus.borrow_mut().append_child(&node);
-        self.focus = node;
-
-        if !self.has_peek() {
-            trace!("FIN Without start");
-            self.pop_to_root();
-            Ok(Rc::clone(&self.focus))
-        }
-        else if self.peek_is_in_rule_first("fn_call") {
-            self.parse_fn_call()
-        }
-        else if self.peek_is_in_rule_first("fn_declaration") {
-            self.parse_fn_declaration()
-        }
-        else {
-            self.err_rule("S")
+    // Replaces the values left since mark by the value of the focus.
+    fn reduce(
+        &mut self,
+        mark: usize,
+    ) -> Parsed<'a> {
+        if let Some(actions) = self.actions {
+            let focus = self.tree.node(self.focus);
+            let alt = AltRef::new(focus.alt_no().unwrap(), &self.rules_by_name[focus.name()]);
+            let values = self.values.split_off(mark);
+            match actions.reduce(&alt, values) {
+                Ok(value) => self.values.push(value),
+                Err(err) => return self._err(ParseErrorKind::Other(err.clone()), err),
+            }
         }
+        Ok(())
     }
 
-    fn parse_fn_call(&mut self) -> ParseResult<'a> {
-        let my_name = "fn_call";
-        trace!("parsing {}", my_name);
-
-        self.push_to_rule(my_name);
-
-        self.match_tk(TokenKind::Id)?;
-        self.match_tk(TokenKind::LeftParen)?;
-
-        self.parse_args()?;
-
-        self.match_tk(TokenKind::RightParen)?;
-        self.match_tk(TokenKind::Semicolon)?;
-
-        self.ok_parent()
-    }
 
-    fn parse_args(&mut self) -> ParseResult<'a> {
-        let my_name = "args";
-        trace!("parsing {}", my_name);
+    // ============================================================================================
 
-        self.push_to_rule(my_name);
+    fn parse(mut self) -> TreeParseResult<'a> {
+        trace!("parsing {}", self.tree.node(self.focus).name());
 
-        if self.peek_is_in_rule_first("arg") {
-            self.parse_arg()?;
-            self.parse_args_0()?;
-            self.ok_parent()
-        }
-        else if self.peek_is_in_rule_follow(my_name) {
-            self.ok_parent()
-        }
-        else {
-            self.err_rule(my_name)
-        }
+        self.expand()?;
+        self.expect_end()?;
+        Ok(self.tree.build())
     }
 
-    fn parse_arg(&mut self) -> ParseResult<'a> {
-        let my_name = "arg";
-        trace!("parsing {}", my_name);
-
-        self.push_to_rule(my_name);
-
-        if !self.has_peek() {
-            self.err_rule(my_name)
-        }
-        else if self.peek_is(TokenKind::String) {
-            self.match_tk(TokenKind::String)?;
-            self.ok_parent()
-        }
-        else if self.peek_is(TokenKind::Id) {
-            self.match_tk(TokenKind::Id)?;
-            self.ok_parent()
-        }
-        else if self.peek_is(TokenKind::Int) {
-            self.match_tk(TokenKind::Int)?;
-            self.ok_parent()
-        }
-        else {
-            self.err_rule(my_name)
+    fn expect_end(&mut self) -> Parsed<'a> {
+        if self.has_peek() && !self.peek_is(TokenKind::Eof) {
+            let err = format!(
+                "unexpected token, expecting end of input, got: {}",
+                self.peek().unwrap(),
+            );
+            return self.err_unexpected(None, vec![TokenKind::Eof], err);
         }
-    }
-
-    fn parse_args_0(&mut self) -> ParseResult<'a> {
-        let my_name = "args__0";
-        trace!("parsing {}", my_name);
 
-        self.push_to_rule(my_name);
-
-        if self.peek_is(TokenKind::Comma) {
-            self.match_tk(TokenKind::Comma)?;
-            self.parse_args()?;
-            self.ok_parent()
-        }
-        else if self.peek_is_in_rule_follow(my_name) {
-            self.ok_parent()
-        }
-        else {
-            self.err_rule(my_name)
-        }
+        Ok(())
     }
 
-    fn parse_fn_declaration(&mut self) -> ParseResult<'a> {
-        let my_name = "fn_declaration";
-        trace!("parsing {}", my_name);
-
-        self.push_to_rule(my_name);
-
-        self.match_tk(TokenKind::Fn)?;
-        self.match_tk(TokenKind::Id)?;
-        self.match_tk(TokenKind::LeftParen)?;
-
-        self.parse_params()?;
-
-        self.match_tk(TokenKind::RightParen)?;
-        self.match_tk(TokenKind::LeftBraces)?;
-
-        self.parse_statements()?;
-
-        self.match_tk(TokenKind::RightBraces)?;
+    fn parse_rule(
+        &mut self,
+        rule_name: &str,
+    ) -> Parsed<'a> {
+        trace!("parsing {}", rule_name);
 
+        self.push_to_rule(rule_name);
+        self.expand()?;
         self.ok_parent()
     }
 
-    fn parse_params(&mut self) -> ParseResult<'a> {
-        let my_name = "params";
-        trace!("parsing {}", my_name);
+    fn expand(&mut self) -> Parsed<'a> {
+        let rule_name = self.tree.node(self.focus).name().to_string();
 
-        self.push_to_rule(my_name);
-
-        if self.peek_is_in_rule_first("param") {
-            self.parse_param()?;
-            self.parse_params_0()?;
-            self.ok_parent()
-        }
-        else if self.peek_is_in_rule_follow(my_name) {
-            self.ok_parent()
+        match self.expression_rules.contains_key(&rule_name) {
+            true => self.expand_expression(0),
+            false => self.expand_focus(),
         }
-        else {
-            self.err_rule(my_name)
-        }
-    }
-
-    fn parse_param(&mut self) -> ParseResult<'a> {
-        let my_name = "param";
-        trace!("parsing {}", my_name);
-
-        self.push_to_rule(my_name);
-
-        self.match_tk(TokenKind::Id)?;
-        self.match_tk(TokenKind::Id)?;
-
-        self.ok_parent()
     }
 
-    fn parse_params_0(&mut self) -> ParseResult<'a> {
-        let my_name = "params__0";
-        trace!("parsing {}", my_name);
-
-        self.push_to_rule(my_name);
-
-        if self.peek_is(TokenKind::Comma) {
-            self.match_tk(TokenKind::Comma)?;
-            self.parse_params()?;
-            self.ok_parent()
-        }
-        else if self.peek_is_in_rule_follow(my_name) {
-            self.ok_parent()
-        }
-        else {
-            self.err_rule(my_name)
+    // Precedence climbing: parses an operand, then keeps wrapping the focus into binary operator
+    // nodes while the operators bind at least as tight as min_precedence. Left associative
+    // operators parse their right operand one level tighter, so `a - b - c` is `(a - b) - c`.
+    fn expand_expression(
+        &mut self,
+        min_precedence: usize,
+    ) -> Parsed<'a> {
+        let rule_name = self.tree.node(self.focus).name().to_string();
+
+        let prefix = self
+            .peek_token_kind()
+            .and_then(|tk| self.expression_rules[&rule_name].prefix.get(&tk).cloned());
+
+        match prefix {
+            Some((alt_no, precedence)) => {
+                let mark = self.values.len();
+                self.tree.set_alt(self.focus, alt_no);
+                let tk = self.peek_token_kind().unwrap();
+                self.match_tk(tk)?;
+
+                self.push_to_rule(&rule_name);
+                self.expand_expression(precedence)?;
+                self.pop_to_parent();
+                self.reduce(mark)?;
+            },
+            None => {
+                self.expand_focus()?;
+            },
         }
-    }
 
-    fn parse_statements(&mut self) -> ParseResult<'a> {
-        let my_name = "statements";
-        trace!("parsing {}", my_name);
+        loop {
+            let binary = self
+                .peek_token_kind()
+                .and_then(|tk| self.expression_rules[&rule_name].binary.get(&tk).cloned());
 
-        self.push_to_rule(my_name);
+            let (alt_no, precedence, fixity) = match binary {
+                Some(binary) if binary.1 >= min_precedence => binary,
+                _ => break,
+            };
+            trace!(
+                "binary operator at precedence {} in {}",
+                precedence,
+                rule_name
+            );
 
-        if self.peek_is_in_rule_first("statement") {
-            self.parse_statement()?;
-            self.parse_statements_0()?;
-            self.ok_parent()
-        }
-        else if self.peek_is_in_rule_follow(my_name) {
-            self.ok_parent()
-        }
-        else {
-            self.err_rule(my_name)
+            // The left operand, already reduced, is the first value of the wrapper.
+            let mark = self.values.len().saturating_sub(1);
+            self.wrap_focus();
+            self.tree.set_alt(self.focus, alt_no);
+            let tk = self.peek_token_kind().unwrap();
+            self.match_tk(tk)?;
+
+            self.push_to_rule(&rule_name);
+            self.expand_expression(match fixity {
+                Fixity::Left => precedence + 1,
+                _ => precedence,
+            })?;
+            self.pop_to_parent();
+            self.reduce(mark)?;
         }
-    }
-
-    fn parse_statement(&mut self) -> ParseResult<'a> {
-        let my_name = "statement";
-        trace!("parsing {}", my_name);
 
-        self.push_to_rule(my_name);
-
-        if self.peek_is(TokenKind::Id) {
-            self.match_tk(TokenKind::Id)?;
-            self.parse_statement_0()?;
-            self.ok_parent()
-        }
-        else if self.peek_is_in_rule_first("ret") {
-            self.parse_ret()?;
-            self.ok_parent()
-        }
-        else {
-            self.err_rule(my_name)
-        }
+        Ok(())
     }
 
-    fn parse_statements_0(&mut self) -> ParseResult<'a> {
-        let my_name = "statements__0";
-        trace!("parsing {}", my_name);
+    fn expand_focus(&mut self) -> Parsed<'a> {
+        let my_name = self.tree.node(self.focus).name().to_string();
+        let rule = Rc::clone(&self.rules_by_name[&my_name]);
 
-        self.push_to_rule(my_name);
-
-        if self.peek_is(TokenKind::Id) {
-            self.match_tk(TokenKind::Id)?;
-            self.parse_statement_0()?;
-            self.parse_statements_0()?;
-            self.ok_parent()
-        }
-        else if self.peek_is_in_rule_first("ret") {
-            self.match_tk(TokenKind::Return)?;
-            self.parse_expressions()?;
-            self.match_tk(TokenKind::Semicolon)?;
-            self.parse_statements_0()?;
-            self.ok_parent()
-        }
-        else if self.peek_is_in_rule_follow(my_name) {
-            self.ok_parent()
-        }
-        else {
-            self.err_rule(my_name)
+        if self.has_peek() {
+            if let Err(err) = self.peek() {
+                return self.err_lexer(err);
+            }
         }
-    }
 
-    fn parse_statement_0(&mut self) -> ParseResult<'a> {
-        let my_name = "statement__0";
-        trace!("parsing {}", my_name);
-
-        self.push_to_rule(my_name);
-
-        if !self.has_peek() {
-            self.err_rule(my_name)
-        }
-        else if self.peek_is(TokenKind::Id) {
-            self.match_tk(TokenKind::Id)?;
-            self.match_tk(TokenKind::Semicolon)?;
-            self.ok_parent()
-        }
-        else if self.peek_is(TokenKind::Equal) {
-            self.match_tk(TokenKind::Equal)?;
-            self.parse_expressions()?;
-            self.match_tk(TokenKind::Semicolon)?;
-            self.ok_parent()
-        }
-        else if self.peek_is(TokenKind::LeftParen) {
-            self.match_tk(TokenKind::LeftParen)?;
-            self.parse_args()?;
-            self.match_tk(TokenKind::RightParen)?;
-            self.match_tk(TokenKind::Semicolon)?;
-            self.ok_parent()
-        }
-        else {
-            self.err_rule(my_name)
+        let alt_no = match self.peek_alt(&my_name) {
+            Some(alt_no) => alt_no,
+            None => return self.err_rule(&my_name),
+        };
+        self.tree.set_alt(self.focus, alt_no);
+        let mark = self.values.len();
+
+        let alt = rule.borrow().alternatives[alt_no].clone();
+        for part in alt {
+            match part {
+                RulePart::Token(tk) if tk.is_epsilon() => {},
+                RulePart::Token(tk) => {
+                    self.match_tk(tk)?;
+                },
+                RulePart::Rule(sub_rule) => {
+                    let sub_rule_name = sub_rule.borrow().name().to_string();
+                    self.parse_rule(&sub_rule_name)?;
+                },
+                RulePart::Predicate(..) => unreachable!("rejected by validate"),
+            }
         }
-    }
-
-    fn parse_ret(&mut self) -> ParseResult<'a> {
-        let my_name = "ret";
-        trace!("parsing {}", my_name);
-
-        self.push_to_rule(my_name);
-
-        self.match_tk(TokenKind::Return)?;
-        self.parse_expressions()?;
-        self.match_tk(TokenKind::Semicolon)?;
-
-        self.ok_parent()
-    }
 
-    fn parse_expressions(&mut self) -> ParseResult<'a> {
-        let my_name = "expressions";
-        trace!("parsing {}", my_name);
-
-        self.push_to_rule(my_name);
-
-        self.parse_terms()?;
-        self.parse_expressions_0()?;
-
-        self.ok_parent()
+        self.reduce(mark)
     }
 
-    fn parse_terms(&mut self) -> ParseResult<'a> {
-        let my_name = "terms";
-        trace!("parsing {}", my_name);
-
-        self.push_to_rule(my_name);
-
-        self.parse_factor()?;
-        self.parse_terms_0()?;
+    // Chooses the alternative by its start set, epsilon alternative is taken only when nothing
+    // else matches and the current token may follow the rule.
+    fn peek_alt(
+        &mut self,
+        rule_name: &str,
+    ) -> Option<usize> {
+        let epsilon_alt = self.rules_by_name[rule_name]
+            .borrow()
+            .alternatives
+            .iter()
+            .position(|it| it.len() == 1 && it[0].is_epsilon());
+
+        for alt_no in 0..self.start_set[rule_name].len() {
+            let is_operator_alt = self
+                .expression_rules
+                .get(rule_name)
+                .is_some_and(|it| it.is_operator_alt(alt_no));
+
+            if Some(alt_no) != epsilon_alt
+                && !is_operator_alt
+                && self.peek_is_in_alt_start(rule_name, alt_no)
+            {
+                return Some(alt_no);
+            }
+        }
 
-        self.ok_parent()
+        match epsilon_alt {
+            Some(alt_no) if self.k > 1 && self.peek_is_in_alt_start(rule_name, alt_no) => {
+                epsilon_alt
+            },
+            Some(_) if self.k == 1 && self.peek_is_in_rule_follow(rule_name) => epsilon_alt,
+            _ => None,
+        }
     }
+}
 
-    fn parse_expressions_0(&mut self) -> ParseResult<'a> {
-        let my_name = "expressions__0";
-        trace!("parsing {}", my_name);
-
-        self.push_to_rule(my_name);
 
-        if !self.has_peek() {
-            self.err_rule(my_name)
-        }
-        else if self.peek_is(TokenKind::Plus) {
-            self.match_tk(TokenKind::Plus)?;
-            self.parse_expressions()?;
-            self.ok_parent()
-        }
-        else if self.peek_is(TokenKind::Minus) {
-            self.match_tk(TokenKind::Minus)?;
-            self.parse_expressions()?;
-            self.ok_parent()
-        }
-        else if self.peek_is_in_rule_follow(my_name) {
-            self.ok_parent()
-        }
-        else {
-            self.err_rule(my_name)
-        }
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::lang::error::GrammarError;
+    use crate::lang::error::ParseErrorKind;
+    use crate::lang::error::Span;
+    use crate::lang::lexer::token::TokenKind;
+    use crate::lang::lexer::v0::Lexer;
+    use crate::lang::parser::node::display_of;
+    use crate::lang::parser::node::Node;
+    use crate::lang::parser::node::ParseError;
+    use crate::lang::parser::rules::Rules;
+    use crate::lang::parser_impl::backtracking_parser::parse_with_backtracking;
+    use crate::lang::parser_impl::cyk_parser::cyk_parse;
+    use crate::lang::parser_impl::earley_parser::earley_parse;
+    use crate::lang::parser_impl::ll1_parser::ll1_parse;
+    use crate::lang::parser_impl::lr_parser::lr_parse;
+    use crate::lang::parser_impl::lr_parser::LrKind;
+    use crate::lang::parser_impl::packrat_parser::parse_with_packrat;
+    use crate::lang::parser_impl::recursive_descent_parser::recursive_descent_parse;
+    use crate::lang::parser_impl::recursive_descent_parser::recursive_descent_parse_k;
+
+    #[test]
+    fn test_same_tree_as_ll1() {
+        let mut rules: Rules = "
+S           -> statement S | EPSILON
+statement   -> ID = expressions ; | RETURN expressions ;
+expressions -> terms + expressions | terms - expressions | terms
+terms       -> factor * terms | factor / terms | factor
+factor      -> ( expressions ) | INT | ID
+        "
+        .try_into()
+        .unwrap();
+        rules.make_ready_for_recursive_decent(128).unwrap();
+        rules.is_backtrack_free().unwrap();
+
+        let program = "x = (1 + y) * 2; return x - 1;";
+
+        let lexer: Lexer = program.into();
+        let recursive = recursive_descent_parse(&rules, lexer.into_iter()).unwrap();
+
+        let lexer: Lexer = program.into();
+        let ll1 = ll1_parse(&rules, lexer.into_iter()).unwrap();
+
+        assert_eq!(display_of(&recursive), display_of(&ll1));
+    }
+
+    #[test]
+    fn test_nullable_first_part() {
+        let rules: Rules = "
+S -> a ID
+a -> INT | EPSILON
+        "
+        .try_into()
+        .unwrap();
+
+        for program in ["x", "1 x"] {
+            let lexer: Lexer = program.into();
+            recursive_descent_parse(&rules, lexer.into_iter()).unwrap();
+            let lexer: Lexer = program.into();
+            recursive_descent_parse_k(&rules, 2, lexer.into_iter()).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_no_rules() {
+        // Each parser reports what validate() finds, rather than fail to find a start rule.
+        let rules = Rules::new();
+        let no_rules = ParseErrorKind::Grammar(GrammarError::NoRules);
+        let lexer = || -> Lexer { "x".into() };
+        let tokens = || Lexer::parse("x").unwrap();
+        fn kind_of<T>(result: Result<T, ParseError>) -> ParseErrorKind {
+            result.err().unwrap().kind().clone()
+        }
+
+        let kinds = [
+            kind_of(recursive_descent_parse(&rules, lexer().into_iter())),
+            kind_of(ll1_parse(&rules, lexer().into_iter())),
+            kind_of(lr_parse(&rules, LrKind::Lalr1, lexer().into_iter())),
+            kind_of(earley_parse(&rules, lexer().into_iter())),
+            kind_of(cyk_parse(&rules, lexer().into_iter())),
+            kind_of(parse_with_packrat(&rules, tokens().into_iter())),
+            kind_of(parse_with_backtracking(&rules, tokens().into_iter())),
+        ];
+        for kind in kinds {
+            assert_eq!(kind, no_rules);
+        }
+    }
+
+    #[test]
+    fn test_trailing_input() {
+        let rules: Rules = "
+S -> ID ;
+        "
+        .try_into()
+        .unwrap();
+
+        let lexer: Lexer = "x ; y".into();
+        let err = recursive_descent_parse(&rules, lexer.into_iter())
+            .err()
+            .unwrap();
+
+        assert!(err
+            .error()
+            .starts_with("unexpected token, expecting end of input"));
+        assert_eq!(
+            *err.kind(),
+            ParseErrorKind::UnexpectedToken {
+                rule: None,
+                expected: vec![TokenKind::Eof],
+                found: Some((
+                    TokenKind::Id,
+                    Span {
+                        start: 4,
+                        end: 5,
+                        line: 1
+                    }
+                )),
+            }
+        );
     }
 
-    fn parse_factor(&mut self) -> ParseResult<'a> {
-        let my_name = "factor";
-        trace!("parsing {}", my_name);
+    #[test]
+    fn test_predicates_rejected() {
+        let rules: Rules = "S -> ID !( | ID ( )".try_into().unwrap();
 
-        self.push_to_rule(my_name);
+        let lexer: Lexer = "x".into();
+        let err = recursive_descent_parse(&rules, lexer.into_iter())
+            .err()
+            .unwrap();
+        assert_eq!(
+            *err.kind(),
+            ParseErrorKind::Grammar(GrammarError::Predicates(vec!["S".to_string()]))
+        );
 
-        if !self.has_peek() {
-            self.err_rule(my_name)
-        }
-        else if self.peek_is(TokenKind::LeftParen) {
-            self.match_tk(TokenKind::LeftParen)?;
-            self.parse_expressions()?;
-            self.match_tk(TokenKind::RightParen)?;
-            self.ok_parent()
-        }
-        else if self.peek_is(TokenKind::Int) {
-            self.match_tk(TokenKind::Int)?;
-            self.ok_parent()
-        }
-        else if self.peek_is(TokenKind::Id) {
-            self.match_tk(TokenKind::Id)?;
-            self.ok_parent()
-        }
-        else {
-            self.err_rule(my_name)
+        let lexer: Lexer = "x".into();
+        let err = recursive_descent_parse_k(&rules, 2, lexer.into_iter())
+            .err()
+            .unwrap();
+        assert!(matches!(err.kind(), ParseErrorKind::Grammar(_)));
+    }
+
+    #[test]
+    fn test_two_token_lookahead() {
+        let rules: Rules = "
+S           -> statement S | EPSILON
+statement   -> ID ID ; | ID = expressions ; | ID ( ) ; | RETURN expressions ;
+expressions -> terms + expressions | terms
+terms       -> INT | ID
+        "
+        .try_into()
+        .unwrap();
+        rules.is_ll_k(2).unwrap();
+
+        let lexer: Lexer = "int x; x = 1 + y; f(); return x;".into();
+        let tree = recursive_descent_parse_k(&rules, 2, lexer.into_iter()).unwrap();
+
+        let display = display_of(&tree);
+        assert!(display.starts_with("S\n|_ statement\n|____ ID[int]\n|____ ID[x]"));
+        assert!(display.contains("|________ ID[f]\n|________ LEFT_PAREN[(]"));
+
+        let lexer: Lexer = "x + 1;".into();
+        let err = recursive_descent_parse_k(&rules, 2, lexer.into_iter())
+            .err()
+            .unwrap();
+        assert!(err.error().starts_with("rule: S /// unexpected token"));
+    }
+
+    fn parenthesized(node: &Rc<RefCell<Node<'_>>>) -> String {
+        let node = node.borrow();
+        match node.token() {
+            Some(token) => token.text.to_string(),
+            None if node.children().len() == 1 => parenthesized(&node.children()[0]),
+            None => format!(
+                "({})",
+                node.children()
+                    .iter()
+                    .map(parenthesized)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+        }
+    }
+
+    #[test]
+    fn test_operator_precedence() {
+        let mut rules: Rules = "
+%left + -
+%left * /
+%prefix -
+%right ^
+S         -> statement S | EPSILON
+statement -> ID = expr ; | RETURN expr ;
+expr      -> expr + expr | expr - expr | expr * expr | expr / expr | expr ^ expr | - expr | ( expr ) | INT | ID
+        "
+        .try_into()
+        .unwrap();
+        rules.make_ready_for_recursive_decent(128).unwrap();
+        rules.is_backtrack_free().unwrap();
+
+        let expectations = [
+            ("x = a - b - c;", "(a - b) - c"),
+            ("x = 1 + 2 * 3 - 4;", "(1 + (2 * 3)) - 4"),
+            ("x = 2 ^ 3 ^ 4;", "2 ^ (3 ^ 4)"),
+            ("x = -a * b;", "(- a) * b"),
+            ("x = -a ^ 2;", "- (a ^ 2)"),
+        ];
+
+        for (program, expected) in expectations {
+            let lexer: Lexer = program.into();
+            let tree = recursive_descent_parse(&rules, lexer.into_iter()).unwrap();
+
+            let statement = Rc::clone(&tree.borrow().children()[0]);
+            let expr = Rc::clone(&statement.borrow().children()[2]);
+            assert_eq!(
+                parenthesized(&expr),
+                format!("({})", expected),
+                "{}",
+                program
+            );
         }
     }
 
-    fn parse_terms_0(&mut self) -> ParseResult<'a> {
-        let my_name = "terms__0";
-        trace!("parsing {}", my_name);
+    #[test]
+    fn test_expression_start_rule() {
+        let rules: Rules = "
+%left + -
+S -> S + S | S - S | INT
+        "
+        .try_into()
+        .unwrap();
 
-        self.push_to_rule(my_name);
+        let lexer: Lexer = "1 - 2 + 3".into();
+        let tree = recursive_descent_parse(&rules, lexer.into_iter()).unwrap();
 
-        if !self.has_peek() {
-            self.err_rule(my_name)
-        }
-        else if self.peek_is(TokenKind::Star) {
-            self.match_tk(TokenKind::Star)?;
-            self.parse_terms()?;
-            self.ok_parent()
-        }
-        else if self.peek_is(TokenKind::Slash) {
-            self.match_tk(TokenKind::Slash)?;
-            self.parse_terms()?;
-            self.ok_parent()
-        }
-        else if self.peek_is_in_rule_follow(my_name) {
-            self.ok_parent()
-        }
-        else {
-            self.err_rule(my_name)
-        }
+        assert_eq!(parenthesized(&tree), "((1 - 2) + 3)");
+        assert!(tree.borrow().parent().is_none());
     }
 }
@@ -0,0 +1,721 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use crate::lang::error::ImportError;
+use crate::lang::parser_import::skip_braces;
+use crate::lang::parser_import::skip_quoted;
+use crate::lang::parser_import::strip_comments;
+use crate::lang::parser_import::token_kind_of_literal;
+use crate::lang::parser_import::token_kind_of_name;
+use crate::lang::parser_import::unquote;
+use crate::lang::parser_import::Collected;
+use crate::lang::parser_import::Imported;
+use crate::lang::parser_import::Symbol;
+
+// The parser rules of an ANTLR4 grammar as rules, the first one being the start rule. Lexer rules
+// matching a single literal tell which token a name stands for, other tokens map onto the lexer's
+// by name. EBNF suffixes and groups become generated rules named after the rule they appear in
+// and clear of the rules of the grammar, labels are dropped. `EOF`, actions, predicates, options
+// and the precedence ANTLR takes from the order of left-recursive alternatives are left out, with
+// a warning. An alternative using `~`, `.` or a token without counterpart is dropped, along with
+// the rules generated for it.
+pub fn import_antlr4(source: &str) -> Result<Imported, ImportError> {
+    let lexemes = strip_comments(source)
+        .and_then(|source| lexemes_of(&source))
+        .map_err(ImportError::Syntax)?;
+
+    let mut importer = Antlr4Importer {
+        lexemes,
+        pos: 0,
+        collected: Collected::default(),
+        warnings: vec![],
+        literals: HashMap::new(),
+        rule_names: HashSet::new(),
+        helpers: vec![],
+        generated: 0,
+    };
+    importer.grammar().map_err(ImportError::Syntax)?;
+    importer.collected.into_imported(importer.warnings)
+}
+
+
+#[derive(Clone, PartialEq, Debug)]
+enum Lexeme {
+    Ident(String),
+    Literal(String),
+    Action(String),
+    // `[ ... ]`, arguments and character sets.
+    Brackets,
+    // `< ... >`, element options.
+    Options(String),
+    Punct(char),
+    Arrow,
+    PlusAssign,
+}
+
+fn lexemes_of(source: &[char]) -> Result<Vec<Lexeme>, String> {
+    let mut lexemes = vec![];
+    let mut pos = 0;
+    while pos < source.len() {
+        let c = source[pos];
+        match c {
+            c if c.is_whitespace() => pos += 1,
+            '{' => {
+                let end = skip_braces(source, pos)?;
+                lexemes.push(Lexeme::Action(source[pos + 1..end - 1].iter().collect()));
+                pos = end;
+            },
+            '\'' => {
+                let end = skip_quoted(source, pos, c)?;
+                lexemes.push(Lexeme::Literal(source[pos..end].iter().collect()));
+                pos = end;
+            },
+            '[' => {
+                while pos < source.len() && source[pos] != ']' {
+                    if source[pos] == '\\' {
+                        pos += 1;
+                    }
+                    pos += 1;
+                }
+                pos += 1;
+                lexemes.push(Lexeme::Brackets);
+            },
+            '<' => {
+                let start = pos + 1;
+                while pos < source.len() && source[pos] != '>' {
+                    pos += 1;
+                }
+                lexemes.push(Lexeme::Options(source[start..pos].iter().collect()));
+                pos += 1;
+            },
+            '-' if source.get(pos + 1) == Some(&'>') => {
+                lexemes.push(Lexeme::Arrow);
+                pos += 2;
+            },
+            '+' if source.get(pos + 1) == Some(&'=') => {
+                lexemes.push(Lexeme::PlusAssign);
+                pos += 2;
+            },
+            c if c.is_ascii_alphanumeric() || c == '_' => {
+                let start = pos;
+                while pos < source.len()
+                    && (source[pos].is_ascii_alphanumeric() || source[pos] == '_')
+                {
+                    pos += 1;
+                }
+                lexemes.push(Lexeme::Ident(source[start..pos].iter().collect()));
+            },
+            c => {
+                lexemes.push(Lexeme::Punct(c));
+                pos += 1;
+            },
+        }
+    }
+    Ok(lexemes)
+}
+
+
+// An element of a parser rule, before the EBNF is expanded.
+enum Element {
+    Rule(String),
+    Token(String),
+    Literal(String),
+    Group(Vec<Vec<Element>>),
+    Suffix(Box<Element>, char),
+    // Something rules can not express, dropping the alternative.
+    Unsupported(String),
+}
+
+struct Antlr4Importer {
+    lexemes: Vec<Lexeme>,
+    pos: usize,
+    collected: Collected,
+    warnings: Vec<String>,
+    // Lexer rule names to the literal they match.
+    literals: HashMap<String, String>,
+    // The names of the parser rules, which generated rules must not take.
+    rule_names: HashSet<String>,
+    // The generated rules of the alternative being converted, collected once all of it is.
+    helpers: Vec<(String, Vec<Vec<Symbol>>)>,
+    generated: usize,
+}
+
+impl Antlr4Importer {
+    fn grammar(&mut self) -> Result<(), String> {
+        match (self.peek(0), self.peek(1)) {
+            (Some(Lexeme::Ident(kind)), Some(Lexeme::Ident(grammar)))
+                if grammar == "grammar" && (kind == "parser" || kind == "lexer") =>
+            {
+                if kind == "lexer" {
+                    return Err("a lexer grammar has no parser rules".to_string());
+                }
+                self.pos += 1;
+            },
+            _ => {},
+        }
+        self.expect_ident("grammar")?;
+        self.skip_past(';');
+
+        // Lexer rules first, parser rules may refer to the ones declared after them.
+        let mut parser_rules = vec![];
+        while self.pos < self.lexemes.len() {
+            match self.lexemes[self.pos].clone() {
+                Lexeme::Ident(name) if name == "fragment" => {
+                    self.skip_past(';');
+                },
+                Lexeme::Ident(name) if name.starts_with(|c: char| c.is_ascii_uppercase()) => {
+                    self.lexer_rule(&name);
+                },
+                Lexeme::Ident(name) => match (name.as_str(), self.peek(1)) {
+                    ("options" | "channels", Some(Lexeme::Action(_))) => {
+                        self.warnings.push(format!("{} ignored", name));
+                        self.pos += 2;
+                    },
+                    ("tokens", Some(Lexeme::Action(_))) => self.pos += 2,
+                    ("import" | "mode", _) => {
+                        self.warnings.push(format!("{} ignored", name));
+                        self.skip_past(';');
+                    },
+                    _ => {
+                        self.rule_names.insert(name);
+                        parser_rules.push(self.pos);
+                        self.skip_rule();
+                    },
+                },
+                Lexeme::Punct('@') => {
+                    self.warnings.push("named action ignored".to_string());
+                    while !matches!(self.peek(0), Some(Lexeme::Action(_)) | None) {
+                        self.pos += 1;
+                    }
+                    self.pos += 1;
+                },
+                it => return Err(format!("unexpected: {:?}", it)),
+            }
+        }
+        if parser_rules.is_empty() {
+            return Err("no parser rules".to_string());
+        }
+
+        for pos in parser_rules {
+            self.pos = pos;
+            self.parser_rule()?;
+        }
+        Ok(())
+    }
+
+    fn lexer_rule(
+        &mut self,
+        name: &str,
+    ) {
+        if let (
+            Some(Lexeme::Punct(':')),
+            Some(Lexeme::Literal(literal)),
+            Some(Lexeme::Punct(';')),
+        ) = (self.peek(1), self.peek(2), self.peek(3))
+        {
+            self.literals.insert(name.to_string(), unquote(literal));
+        }
+        self.skip_past(';');
+    }
+
+    fn parser_rule(&mut self) -> Result<(), String> {
+        let Some(Lexeme::Ident(rule_name)) = self.peek(0).cloned()
+        else {
+            return Err("expected a rule name".to_string());
+        };
+        self.pos += 1;
+
+        // Arguments, returns, locals, options and actions, up to the `:`.
+        while let Some(lexeme) = self.peek(0).cloned() {
+            match lexeme {
+                Lexeme::Punct(':') => break,
+                Lexeme::Brackets => {},
+                Lexeme::Ident(name) => self
+                    .warnings
+                    .push(format!("{}: {} ignored", rule_name, name)),
+                Lexeme::Punct('@') => {
+                    self.warnings
+                        .push(format!("{}: named action ignored", rule_name));
+                    self.pos += 1;
+                },
+                _ => {},
+            }
+            self.pos += 1;
+        }
+        self.expect(':')?;
+
+        let alternatives = self.alternatives(&rule_name)?;
+        self.expect(';')?;
+
+        // Exception handlers.
+        while let Some(Lexeme::Ident(name)) = self.peek(0) {
+            if name != "catch" && name != "finally" {
+                break;
+            }
+            self.warnings
+                .push(format!("{}: {} ignored", rule_name, name));
+            while !matches!(self.peek(0), Some(Lexeme::Action(_)) | None) {
+                self.pos += 1;
+            }
+            self.pos += 1;
+        }
+
+        let left_recursive = alternatives
+            .iter()
+            .any(|alt| matches!(alt.first(), Some(Element::Rule(name)) if *name == rule_name));
+        if left_recursive {
+            self.warnings.push(format!(
+                "{}: left recursive, the precedence taken from the order of alternatives is not \
+                 imported",
+                rule_name
+            ));
+        }
+
+        self.generated = 0;
+        self.collected.add_rule(&rule_name);
+        for (alt_no, alt) in alternatives.iter().enumerate() {
+            let mark = self.mark();
+            match self.symbols_of(&rule_name, alt) {
+                Ok(symbols) => {
+                    self.collected.add_alt(&rule_name, symbols);
+                    for (name, alternatives) in self.helpers.drain(..) {
+                        for alt in alternatives {
+                            self.collected.add_alt(&name, alt);
+                        }
+                    }
+                },
+                Err(reason) => {
+                    self.drop_helpers(mark);
+                    self.warnings.push(format!(
+                        "{}: alternative {} dropped, {}",
+                        rule_name, alt_no, reason
+                    ))
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn alternatives(
+        &mut self,
+        rule_name: &str,
+    ) -> Result<Vec<Vec<Element>>, String> {
+        let mut alternatives = vec![self.alternative(rule_name)?];
+        while self.peek(0) == Some(&Lexeme::Punct('|')) {
+            self.pos += 1;
+            alternatives.push(self.alternative(rule_name)?);
+        }
+        Ok(alternatives)
+    }
+
+    fn alternative(
+        &mut self,
+        rule_name: &str,
+    ) -> Result<Vec<Element>, String> {
+        let mut elements = vec![];
+        while let Some(lexeme) = self.peek(0).cloned() {
+            self.pos += 1;
+            let element = match lexeme {
+                Lexeme::Punct('|' | ')' | ';') => {
+                    self.pos -= 1;
+                    break;
+                },
+                // Labels.
+                Lexeme::Ident(_)
+                    if matches!(self.peek(0), Some(Lexeme::Punct('=') | Lexeme::PlusAssign)) =>
+                {
+                    self.pos += 1;
+                    continue;
+                },
+                Lexeme::Punct('#') => {
+                    self.pos += 1;
+                    continue;
+                },
+                // The parsers expect the end of input after the start rule anyway.
+                Lexeme::Ident(name) if name == "EOF" => {
+                    self.warnings.push(format!("{}: EOF ignored", rule_name));
+                    continue;
+                },
+                Lexeme::Ident(name) if name.starts_with(|c: char| c.is_ascii_uppercase()) => {
+                    Element::Token(name)
+                },
+                Lexeme::Ident(name) => {
+                    if self.peek(0) == Some(&Lexeme::Brackets) {
+                        self.pos += 1;
+                    }
+                    Element::Rule(name)
+                },
+                Lexeme::Literal(literal) => Element::Literal(literal),
+                Lexeme::Punct('(') => {
+                    let alternatives = self.alternatives(rule_name)?;
+                    self.expect(')')?;
+                    Element::Group(alternatives)
+                },
+                Lexeme::Punct('~') => {
+                    self.alternative_element(rule_name)?;
+                    Element::Unsupported("~".to_string())
+                },
+                Lexeme::Punct('.') => Element::Unsupported(".".to_string()),
+                Lexeme::Action(_) => {
+                    let kind = match self.peek(0) {
+                        Some(Lexeme::Punct('?')) => {
+                            self.pos += 1;
+                            "predicate"
+                        },
+                        _ => "action",
+                    };
+                    self.warnings
+                        .push(format!("{}: {} ignored", rule_name, kind));
+                    continue;
+                },
+                Lexeme::Options(options) => {
+                    self.warnings
+                        .push(format!("{}: <{}> ignored", rule_name, options));
+                    continue;
+                },
+                Lexeme::Arrow => {
+                    self.warnings.push(format!("{}: -> ignored", rule_name));
+                    while !matches!(self.peek(0), Some(Lexeme::Punct('|' | ')' | ';')) | None) {
+                        self.pos += 1;
+                    }
+                    continue;
+                },
+                it => return Err(format!("{}: unexpected: {:?}", rule_name, it)),
+            };
+            elements.push(self.suffixed(element));
+        }
+        Ok(elements)
+    }
+
+    // The element following `~`, parsed to be dropped.
+    fn alternative_element(
+        &mut self,
+        rule_name: &str,
+    ) -> Result<(), String> {
+        match self.peek(0) {
+            Some(Lexeme::Punct('(')) => {
+                self.pos += 1;
+                self.alternatives(rule_name)?;
+                self.expect(')')
+            },
+            Some(_) => {
+                self.pos += 1;
+                Ok(())
+            },
+            None => Err(format!("{}: expected an element after ~", rule_name)),
+        }
+    }
+
+    fn suffixed(
+        &mut self,
+        element: Element,
+    ) -> Element {
+        match self.peek(0) {
+            Some(Lexeme::Punct(suffix @ ('?' | '*' | '+'))) => {
+                let suffix = *suffix;
+                self.pos += 1;
+                // Non-greedy.
+                if self.peek(0) == Some(&Lexeme::Punct('?')) {
+                    self.pos += 1;
+                }
+                Element::Suffix(Box::new(element), suffix)
+            },
+            _ => element,
+        }
+    }
+
+    // The generated rules go to the helpers, for the caller to collect or drop.
+    fn symbols_of(
+        &mut self,
+        rule_name: &str,
+        alt: &[Element],
+    ) -> Result<Vec<Symbol>, String> {
+        let mut symbols = vec![];
+        for element in alt {
+            symbols.extend(self.symbols_of_element(rule_name, element)?);
+        }
+        Ok(symbols)
+    }
+
+    fn symbols_of_element(
+        &mut self,
+        rule_name: &str,
+        element: &Element,
+    ) -> Result<Vec<Symbol>, String> {
+        match element {
+            Element::Rule(name) => Ok(vec![Symbol::Rule(name.clone())]),
+            Element::Token(name) => {
+                let tk = match self.literals.get(name) {
+                    Some(literal) => token_kind_of_literal(literal),
+                    None => token_kind_of_name(name),
+                };
+                tk.map(|tk| vec![Symbol::Token(tk)])
+                    .ok_or_else(|| format!("token {} without counterpart", name))
+            },
+            Element::Literal(literal) => token_kind_of_literal(&unquote(literal))
+                .map(|tk| vec![Symbol::Token(tk)])
+                .ok_or_else(|| format!("literal {} without counterpart", literal)),
+            Element::Group(alternatives) if alternatives.len() == 1 => {
+                self.symbols_of(rule_name, &alternatives[0])
+            },
+            Element::Group(alternatives) => {
+                let mut generated_alternatives = vec![];
+                for alt in alternatives {
+                    let mark = self.mark();
+                    match self.symbols_of(rule_name, alt) {
+                        Ok(symbols) => generated_alternatives.push(symbols),
+                        Err(reason) => {
+                            self.drop_helpers(mark);
+                            self.warnings.push(format!(
+                                "{}: alternative of a group dropped, {}",
+                                rule_name, reason
+                            ))
+                        },
+                    }
+                }
+                if generated_alternatives.is_empty() {
+                    return Err("no alternative of a group left".to_string());
+                }
+                let name = self.generate(rule_name, "group");
+                self.helpers.push((name.clone(), generated_alternatives));
+                Ok(vec![Symbol::Rule(name)])
+            },
+            Element::Suffix(element, suffix) => {
+                let inner = self.symbols_of_element(rule_name, element)?;
+                let (kind, alternatives) = match suffix {
+                    '?' => ("opt", vec![inner, vec![]]),
+                    '*' => ("star", vec![inner, vec![]]),
+                    _ => ("plus", vec![inner.clone(), inner]),
+                };
+                let name = self.generate(rule_name, kind);
+                let alternatives = alternatives
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, mut alt)| {
+                        if index == 0 && *suffix != '?' {
+                            alt.push(Symbol::Rule(name.clone()));
+                        }
+                        alt
+                    })
+                    .collect();
+                self.helpers.push((name.clone(), alternatives));
+                Ok(vec![Symbol::Rule(name)])
+            },
+            Element::Unsupported(what) => Err(format!("{} not supported", what)),
+        }
+    }
+
+    // Named as `rule_kind1`, counting the generated rules of the rule, skipping the names of the
+    // parser rules.
+    fn generate(
+        &mut self,
+        rule_name: &str,
+        kind: &str,
+    ) -> String {
+        loop {
+            self.generated += 1;
+            let name = format!("{}_{}{}", rule_name, kind, self.generated);
+            if !self.rule_names.contains(&name) {
+                return name;
+            }
+        }
+    }
+
+    // Where the generated rules stand, to go back to when an alternative is dropped.
+    fn mark(&self) -> (usize, usize) {
+        (self.helpers.len(), self.generated)
+    }
+
+    fn drop_helpers(
+        &mut self,
+        (helpers, generated): (usize, usize),
+    ) {
+        self.helpers.truncate(helpers);
+        self.generated = generated;
+    }
+
+    fn peek(
+        &self,
+        offset: usize,
+    ) -> Option<&Lexeme> {
+        self.lexemes.get(self.pos + offset)
+    }
+
+    fn expect(
+        &mut self,
+        punct: char,
+    ) -> Result<(), String> {
+        match self.peek(0) {
+            Some(Lexeme::Punct(c)) if *c == punct => {
+                self.pos += 1;
+                Ok(())
+            },
+            it => Err(format!("expected {}, found: {:?}", punct, it)),
+        }
+    }
+
+    fn expect_ident(
+        &mut self,
+        ident: &str,
+    ) -> Result<(), String> {
+        match self.peek(0) {
+            Some(Lexeme::Ident(it)) if it == ident => {
+                self.pos += 1;
+                Ok(())
+            },
+            it => Err(format!("expected {}, found: {:?}", ident, it)),
+        }
+    }
+
+    fn skip_past(
+        &mut self,
+        punct: char,
+    ) {
+        while self.pos < self.lexemes.len() && self.lexemes[self.pos] != Lexeme::Punct(punct) {
+            self.pos += 1;
+        }
+        self.pos += 1;
+    }
+
+    // A parser rule with its exception handlers.
+    fn skip_rule(&mut self) {
+        self.skip_past(';');
+        while let Some(Lexeme::Ident(name)) = self.peek(0) {
+            if name != "catch" && name != "finally" {
+                break;
+            }
+            while !matches!(self.peek(0), Some(Lexeme::Action(_)) | None) {
+                self.pos += 1;
+            }
+            self.pos += 1;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::lang::parser_import::antlr4::import_antlr4;
+
+    #[test]
+    fn test_import_antlr4() {
+        let imported = import_antlr4(
+            "
+grammar Functions;
+
+options { language = Java; }
+
+program
+    : function* EOF
+    ;
+
+// fn f(a, b) { return a + b; }
+function
+    : FN name=ID '(' (ID (',' ID)*)? ')' '{' statement+ '}'
+    ;
+
+statement
+    : RETURN expr ';'        # Return
+    | ID '=' expr ';'        # Assign
+    | '@' ID                 # Annotation
+    ;
+
+expr
+    : <assoc=right> expr '^' expr
+    | expr op=('*' | '/') expr
+    | '(' expr ')'
+    | INT {System.out.println($INT.text);}
+    ;
+
+FN     : 'fn' ;
+RETURN : 'return' ;
+ID     : [a-zA-Z_] [a-zA-Z_0-9]* ;
+INT    : [0-9]+ ;
+WS     : [ \\t\\r\\n]+ -> skip ;
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            imported.rules.to_string(),
+            "\
+Rules[
+  S                    -> program_star1
+  program_star1        -> function program_star1 | EPSILON
+  function             -> FN ID ( function_opt2 ) { function_plus3 }
+  function_opt2        -> ID function_star1 | EPSILON
+  function_plus3       -> statement function_plus3 | statement
+  function_star1       -> , ID function_star1 | EPSILON
+  statement            -> RETURN expr ; | ID = expr ;
+  expr                 -> expr ^ expr | expr expr_group1 expr | ( expr ) | INT
+  expr_group1          -> * | /
+]"
+        );
+        assert_eq!(
+            imported.warnings,
+            [
+                "options ignored",
+                "program: EOF ignored",
+                "statement: alternative 2 dropped, literal '@' without counterpart",
+                "expr: <assoc=right> ignored",
+                "expr: action ignored",
+                "expr: left recursive, the precedence taken from the order of alternatives is \
+                 not imported",
+                "start rule program renamed to S",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_antlr4_group_alternative_dropped() {
+        let imported = import_antlr4(
+            "
+grammar Sums;
+
+sum : ID (('+' | '@') ID)* ;
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            imported.rules.to_string(),
+            "\
+Rules[
+  S                    -> ID sum_star2
+  sum_star2            -> sum_group1 ID sum_star2 | EPSILON
+  sum_group1           -> +
+]"
+        );
+        assert_eq!(
+            imported.warnings,
+            [
+                "sum: alternative of a group dropped, literal '@' without counterpart",
+                "start rule sum renamed to S",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_antlr4_generated_rules() {
+        let imported = import_antlr4(
+            "
+grammar Pairs;
+
+pair        : (ID | INT)* '@' | ID (ID | INT) pair_group1 ;
+pair_group1 : '+' ID ;
+",
+        )
+        .unwrap();
+
+        // Nothing left of the dropped alternative, no rule of the grammar taken over.
+        assert_eq!(
+            imported.rules.to_string(),
+            "\
+Rules[
+  S                    -> ID pair_group2 pair_group1
+  pair_group2          -> ID | INT
+  pair_group1          -> + ID
+]"
+        );
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use crate::lang::error::GrammarError;
+use crate::lang::error::ImportError;
+use crate::lang::lexer::token::TokenKind;
+use crate::lang::parser::rules::Rules;
+
+pub mod antlr4;
+pub mod yacc;
+
+// An imported grammar, with what could not be carried over reported in the warnings.
+pub struct Imported {
+    pub rules: Rules,
+    pub warnings: Vec<String>,
+}
+
+
+#[derive(Clone)]
+enum Symbol {
+    Rule(String),
+    Token(TokenKind),
+}
+
+// Rules as read by an importer, start rule first, an empty alternative is epsilon.
+#[derive(Default)]
+struct Collected {
+    rules: Vec<(String, Vec<Vec<Symbol>>)>,
+    precedence: Vec<String>,
+}
+
+impl Collected {
+    fn add_rule(
+        &mut self,
+        rule_name: &str,
+    ) -> &mut Vec<Vec<Symbol>> {
+        let index = match self.rules.iter().position(|(name, _)| name == rule_name) {
+            Some(index) => index,
+            None => {
+                self.rules.push((rule_name.to_string(), vec![]));
+                self.rules.len() - 1
+            },
+        };
+        &mut self.rules[index].1
+    }
+
+    fn add_alt(
+        &mut self,
+        rule_name: &str,
+        alt: Vec<Symbol>,
+    ) {
+        self.add_rule(rule_name).push(alt);
+    }
+
+    fn into_imported(
+        mut self,
+        mut warnings: Vec<String>,
+    ) -> Result<Imported, ImportError> {
+        let start_rule = match self.rules.first() {
+            Some((name, _)) => name.clone(),
+            None => return Err(GrammarError::NoRules.into()),
+        };
+
+        // Alternatives referring to a rule without any alternative go, that may empty more rules.
+        loop {
+            let empty: HashSet<String> = self
+                .rules
+                .iter()
+                .filter(|(_, alternatives)| alternatives.is_empty())
+                .map(|(name, _)| name.clone())
+                .collect();
+            if empty.is_empty() {
+                break;
+            }
+
+            if empty.contains(&start_rule) {
+                return Err(ImportError::NoAlternativeLeft {
+                    start_rule,
+                    warnings,
+                });
+            }
+            for name in &empty {
+                warnings.push(format!("rule {} has no alternative left, removed", name));
+            }
+            self.rules.retain(|(name, _)| !empty.contains(name));
+            for (_, alternatives) in &mut self.rules {
+                alternatives.retain(|alt| {
+                    !alt.iter()
+                        .any(|it| matches!(it, Symbol::Rule(name) if empty.contains(name)))
+                });
+            }
+        }
+
+        let names = self.rule_names(&mut warnings);
+
+        let mut lines = self.precedence.clone();
+        for (name, alternatives) in &self.rules {
+            let alternatives = alternatives
+                .iter()
+                .map(|alt| match alt.is_empty() {
+                    true => TokenKind::Epsilon.upper_name().to_string(),
+                    false => alt
+                        .iter()
+                        .map(|it| match it {
+                            Symbol::Rule(name) => names[name].clone(),
+                            Symbol::Token(tk) => tk.upper_name().to_string(),
+                        })
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                })
+                .collect::<Vec<_>>()
+                .join(" | ");
+            lines.push(format!("{} -> {}", names[name], alternatives));
+        }
+
+        let rules = Rules::parse(&lines.join("\n"))?;
+        rules.validate()?;
+
+        Ok(Imported {
+            rules,
+            warnings,
+        })
+    }
+
+    // The start rule is `S`, other names are made of alphanumerics and `_` only, and can not be
+    // taken for a token.
+    fn rule_names(
+        &self,
+        warnings: &mut Vec<String>,
+    ) -> HashMap<String, String> {
+        let mut taken: HashSet<String> = HashSet::new();
+
+        self.rules
+            .iter()
+            .enumerate()
+            .map(|(index, (name, _))| {
+                if index == 0 {
+                    let renamed = "S".to_string();
+                    if renamed != *name {
+                        warnings.push(format!("start rule {} renamed to {}", name, renamed));
+                    }
+                    taken.insert(renamed.clone());
+                    return (name.clone(), renamed);
+                }
+
+                let mut renamed = name
+                    .chars()
+                    .map(|c| match c.is_ascii_alphanumeric() {
+                        true => c,
+                        false => '_',
+                    })
+                    .collect::<String>();
+                while TokenKind::from_name(&renamed).is_ok()
+                    || TokenKind::from_repr(&renamed).is_ok()
+                    || taken.contains(&renamed)
+                {
+                    renamed.push('_');
+                }
+
+                if renamed != *name {
+                    warnings.push(format!("rule {} renamed to {}", name, renamed));
+                }
+                taken.insert(renamed.clone());
+                (name.clone(), renamed)
+            })
+            .collect()
+    }
+}
+
+// A literal token, by its text.
+fn token_kind_of_literal(literal: &str) -> Option<TokenKind> {
+    TokenKind::from_repr(literal).ok()
+}
+
+// A named token, by the name the lexer gives it or one grammars commonly give it.
+fn token_kind_of_name(name: &str) -> Option<TokenKind> {
+    match name.to_uppercase().as_str() {
+        "IDENTIFIER" | "IDENT" | "NAME" => Some(TokenKind::Id),
+        "NUMBER" | "NUM" | "INTEGER" => Some(TokenKind::Int),
+        "STRING_LITERAL" | "STR" => Some(TokenKind::String),
+        _ => TokenKind::from_name(name)
+            .ok()
+            .filter(|it| !matches!(it, TokenKind::Error | TokenKind::Eof | TokenKind::Epsilon)),
+    }
+}
+
+// The text of a quoted literal, `'a'` or `"a"`, with the usual escapes.
+fn unquote(literal: &str) -> String {
+    let mut text = String::new();
+    let mut chars = literal[1..literal.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => text.push('\n'),
+                Some('t') => text.push('\t'),
+                Some('r') => text.push('\r'),
+                Some(c) => text.push(c),
+                None => {},
+            },
+            c => text.push(c),
+        }
+    }
+    text
+}
+
+// The index after a `{ ... }` block starting at start, nested braces and quoted text skipped.
+fn skip_braces(
+    source: &[char],
+    start: usize,
+) -> Result<usize, String> {
+    let mut depth = 0;
+    let mut pos = start;
+    while pos < source.len() {
+        match source[pos] {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(pos + 1);
+                }
+            },
+            quote @ ('\'' | '"') => pos = skip_quoted(source, pos, quote)? - 1,
+            _ => {},
+        }
+        pos += 1;
+    }
+
+    Err(format!("unterminated block starting at: {}", start))
+}
+
+// The index after a quoted text starting at start.
+fn skip_quoted(
+    source: &[char],
+    start: usize,
+    quote: char,
+) -> Result<usize, String> {
+    let mut pos = start + 1;
+    while pos < source.len() {
+        match source[pos] {
+            '\\' => pos += 1,
+            c if c == quote => return Ok(pos + 1),
+            '\n' => break,
+            _ => {},
+        }
+        pos += 1;
+    }
+
+    Err(format!("unterminated quote starting at: {}", start))
+}
+
+// Source without `//` and `/* */` comments, comment markers in quotes are kept.
+fn strip_comments(source: &str) -> Result<Vec<char>, String> {
+    let source = source.chars().collect::<Vec<_>>();
+    let mut stripped = vec![];
+    let mut pos = 0;
+    while pos < source.len() {
+        match (source[pos], source.get(pos + 1)) {
+            ('/', Some('/')) => {
+                while pos < source.len() && source[pos] != '\n' {
+                    pos += 1;
+                }
+            },
+            ('/', Some('*')) => {
+                pos += 2;
+                while pos < source.len()
+                    && !(source[pos] == '*' && source.get(pos + 1) == Some(&'/'))
+                {
+                    pos += 1;
+                }
+                pos += 2;
+                stripped.push(' ');
+            },
+            (quote @ ('\'' | '"'), _) => {
+                let end = skip_quoted(&source, pos, quote)?;
+                stripped.extend(&source[pos..end]);
+                pos = end;
+            },
+            (c, _) => {
+                stripped.push(c);
+                pos += 1;
+            },
+        }
+    }
+    Ok(stripped)
+}
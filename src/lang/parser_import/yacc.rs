@@ -0,0 +1,465 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use crate::lang::error::ImportError;
+use crate::lang::lexer::token::TokenKind;
+use crate::lang::parser_import::skip_braces;
+use crate::lang::parser_import::skip_quoted;
+use crate::lang::parser_import::strip_comments;
+use crate::lang::parser_import::token_kind_of_literal;
+use crate::lang::parser_import::token_kind_of_name;
+use crate::lang::parser_import::unquote;
+use crate::lang::parser_import::Collected;
+use crate::lang::parser_import::Imported;
+use crate::lang::parser_import::Symbol;
+
+// The rules section of a yacc/bison grammar as rules. `%token` aliases, `%left`, `%right` and
+// `%start` are taken from the declarations, `%{ %}` blocks and the epilogue are skipped. Tokens
+// map onto the lexer's by their literal or name, an alternative using a token without counterpart
+// or the `error` token is dropped. Actions and `%prec` are left out, with a warning.
+pub fn import_yacc(source: &str) -> Result<Imported, ImportError> {
+    let source = strip_comments(source)
+        .map_err(ImportError::Syntax)?
+        .into_iter()
+        .collect::<String>();
+
+    let mut sections = vec![String::new()];
+    for line in source.lines() {
+        match line.trim() {
+            "%%" => sections.push(String::new()),
+            _ => {
+                let section = sections.last_mut().unwrap();
+                section.push_str(line);
+                section.push('\n');
+            },
+        }
+    }
+    if sections.len() < 2 {
+        return Err(ImportError::Syntax(
+            "no rules section, expected a line with %%".to_string(),
+        ));
+    }
+
+    let mut importer = YaccImporter::default();
+    importer.declarations(&lexemes_of(&sections[0]).map_err(ImportError::Syntax)?);
+    lexemes_of(&sections[1])
+        .and_then(|lexemes| importer.rules(&lexemes))
+        .map_err(ImportError::Syntax)?;
+    importer.into_imported()
+}
+
+
+#[derive(Clone, PartialEq, Debug)]
+enum Lexeme {
+    Ident(String),
+    Literal(String),
+    Directive(String),
+    Colon,
+    Bar,
+    Semicolon,
+    Action,
+    Tag,
+}
+
+fn lexemes_of(section: &str) -> Result<Vec<Lexeme>, String> {
+    let source = section.chars().collect::<Vec<_>>();
+    let mut lexemes = vec![];
+    let mut pos = 0;
+    while pos < source.len() {
+        let c = source[pos];
+        match c {
+            c if c.is_whitespace() => pos += 1,
+            '%' if source.get(pos + 1) == Some(&'{') => {
+                while pos < source.len()
+                    && !(source[pos] == '%' && source.get(pos + 1) == Some(&'}'))
+                {
+                    pos += 1;
+                }
+                pos += 2;
+            },
+            '%' => {
+                let end = end_of_ident(&source, pos + 1);
+                lexemes.push(Lexeme::Directive(source[pos..end].iter().collect()));
+                pos = end;
+            },
+            '{' => {
+                pos = skip_braces(&source, pos)?;
+                lexemes.push(Lexeme::Action);
+            },
+            '\'' | '"' => {
+                let end = skip_quoted(&source, pos, c)?;
+                lexemes.push(Lexeme::Literal(source[pos..end].iter().collect()));
+                pos = end;
+            },
+            '<' => {
+                while pos < source.len() && source[pos] != '>' {
+                    pos += 1;
+                }
+                pos += 1;
+                lexemes.push(Lexeme::Tag);
+            },
+            ':' => {
+                lexemes.push(Lexeme::Colon);
+                pos += 1;
+            },
+            '|' => {
+                lexemes.push(Lexeme::Bar);
+                pos += 1;
+            },
+            ';' => {
+                lexemes.push(Lexeme::Semicolon);
+                pos += 1;
+            },
+            // Old style `= { ... }` actions.
+            '=' if source[pos + 1..].iter().find(|it| !it.is_whitespace()) == Some(&'{') => {
+                pos += 1
+            },
+            c if c.is_ascii_alphanumeric() || c == '_' || c == '.' => {
+                let end = end_of_ident(&source, pos);
+                lexemes.push(Lexeme::Ident(source[pos..end].iter().collect()));
+                pos = end;
+            },
+            c => return Err(format!("unexpected character: {}", c)),
+        }
+    }
+    Ok(lexemes)
+}
+
+fn end_of_ident(
+    source: &[char],
+    start: usize,
+) -> usize {
+    let mut end = start;
+    while end < source.len() && (source[end].is_ascii_alphanumeric() || "_.-".contains(source[end]))
+    {
+        end += 1;
+    }
+    end
+}
+
+
+#[derive(Default)]
+struct YaccImporter {
+    collected: Collected,
+    warnings: Vec<String>,
+    // Token names to the literal aliasing them in `%token NAME "literal"`.
+    aliases: HashMap<String, String>,
+    start: Option<String>,
+}
+
+impl YaccImporter {
+    fn declarations(
+        &mut self,
+        lexemes: &[Lexeme],
+    ) {
+        let mut directive = String::new();
+        let mut precedence: Vec<String> = vec![];
+        let mut last_token: Option<String> = None;
+
+        for lexeme in lexemes.iter().chain([&Lexeme::Directive(String::new())]) {
+            match lexeme {
+                Lexeme::Directive(name) => {
+                    if !precedence.is_empty() {
+                        self.collected.precedence.push(precedence.join(" "));
+                        precedence.clear();
+                    }
+                    directive = name.clone();
+                    match name.as_str() {
+                        "" | "%token" | "%left" | "%right" | "%start" | "%type" => {},
+                        "%nonassoc" | "%precedence" => self.warnings.push(format!(
+                            "{} has no counterpart, declared precedence is not imported",
+                            name
+                        )),
+                        _ => self.warnings.push(format!("{} ignored", name)),
+                    }
+                },
+                Lexeme::Ident(name) if name.starts_with(|c: char| c.is_ascii_digit()) => {},
+                Lexeme::Ident(name) => match directive.as_str() {
+                    "%token" => last_token = Some(name.clone()),
+                    "%left" | "%right" => match self.token_of_name(name) {
+                        Some(tk) => {
+                            if precedence.is_empty() {
+                                precedence.push(directive.clone());
+                            }
+                            precedence.push(tk.upper_name().to_string());
+                        },
+                        None => self.warnings.push(format!(
+                            "{} {} has no counterpart, left out",
+                            directive, name
+                        )),
+                    },
+                    "%start" => self.start = Some(name.clone()),
+                    _ => {},
+                },
+                Lexeme::Literal(literal) => match directive.as_str() {
+                    "%token" => {
+                        if let Some(name) = last_token.take() {
+                            self.aliases.insert(name, unquote(literal));
+                        }
+                    },
+                    "%left" | "%right" => match self.token_of_literal(literal) {
+                        Some(tk) => {
+                            if precedence.is_empty() {
+                                precedence.push(directive.clone());
+                            }
+                            precedence.push(tk.upper_name().to_string());
+                        },
+                        None => self.warnings.push(format!(
+                            "{} {} has no counterpart, left out",
+                            directive, literal
+                        )),
+                    },
+                    _ => {},
+                },
+                _ => {},
+            }
+        }
+    }
+
+    fn rules(
+        &mut self,
+        lexemes: &[Lexeme],
+    ) -> Result<(), String> {
+        let rule_names: HashSet<&str> = lexemes
+            .windows(2)
+            .filter_map(|pair| match pair {
+                [Lexeme::Ident(name), Lexeme::Colon] => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+
+        let mut pos = 0;
+        while pos < lexemes.len() {
+            let (Lexeme::Ident(rule_name), Some(Lexeme::Colon)) =
+                (&lexemes[pos], lexemes.get(pos + 1))
+            else {
+                return Err(format!("expected a rule, found: {:?}", lexemes[pos]));
+            };
+            pos += 2;
+            self.collected.add_rule(rule_name);
+
+            let mut alt: Option<Vec<Symbol>> = Some(vec![]);
+            let mut alt_no = 0;
+            let mut actions = 0;
+            loop {
+                match lexemes.get(pos) {
+                    None | Some(Lexeme::Semicolon) | Some(Lexeme::Bar) => {
+                        if let Some(alt) = alt.take() {
+                            self.collected.add_alt(rule_name, alt);
+                        }
+                        match lexemes.get(pos) {
+                            Some(Lexeme::Bar) => {
+                                alt = Some(vec![]);
+                                alt_no += 1;
+                            },
+                            Some(_) => {
+                                pos += 1;
+                                break;
+                            },
+                            None => break,
+                        }
+                    },
+                    Some(Lexeme::Ident(_)) if lexemes.get(pos + 1) == Some(&Lexeme::Colon) => {
+                        if let Some(alt) = alt.take() {
+                            self.collected.add_alt(rule_name, alt);
+                        }
+                        break;
+                    },
+                    Some(Lexeme::Ident(name)) if rule_names.contains(name.as_str()) => {
+                        if let Some(alt) = &mut alt {
+                            alt.push(Symbol::Rule(name.clone()));
+                        }
+                    },
+                    Some(Lexeme::Ident(name)) => {
+                        let tk = self.token_of_name(name);
+                        if alt.is_some() && tk.is_none() {
+                            let reason = match name.as_str() {
+                                "error" => "error recovery".to_string(),
+                                _ => format!("token {} without counterpart", name),
+                            };
+                            self.drop_alt(rule_name, alt_no, &reason);
+                        }
+                        alt = alt.zip(tk).map(|(mut alt, tk)| {
+                            alt.push(Symbol::Token(tk));
+                            alt
+                        });
+                    },
+                    Some(Lexeme::Literal(literal)) => {
+                        let tk = self.token_of_literal(literal);
+                        if alt.is_some() && tk.is_none() {
+                            let reason = format!("literal {} without counterpart", literal);
+                            self.drop_alt(rule_name, alt_no, &reason);
+                        }
+                        alt = alt.zip(tk).map(|(mut alt, tk)| {
+                            alt.push(Symbol::Token(tk));
+                            alt
+                        });
+                    },
+                    Some(Lexeme::Action) => actions += 1,
+                    Some(Lexeme::Directive(name)) if name == "%empty" => {},
+                    Some(Lexeme::Directive(name)) => {
+                        self.warnings
+                            .push(format!("{}: {} ignored", rule_name, name));
+                        if name == "%prec" {
+                            pos += 1;
+                        }
+                    },
+                    Some(Lexeme::Tag) => {},
+                    Some(Lexeme::Colon) => return Err(format!("{}: unexpected :", rule_name)),
+                }
+                pos += 1;
+            }
+
+            if actions > 0 {
+                self.warnings
+                    .push(format!("{}: {} actions ignored", rule_name, actions));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn into_imported(mut self) -> Result<Imported, ImportError> {
+        if let Some(start) = &self.start {
+            let Some(index) = self
+                .collected
+                .rules
+                .iter()
+                .position(|(name, _)| name == start)
+            else {
+                return Err(ImportError::Syntax(format!(
+                    "%start {} is not a rule",
+                    start
+                )));
+            };
+            let rule = self.collected.rules.remove(index);
+            self.collected.rules.insert(0, rule);
+        }
+        self.collected.into_imported(self.warnings)
+    }
+
+    fn drop_alt(
+        &mut self,
+        rule_name: &str,
+        alt_no: usize,
+        reason: &str,
+    ) {
+        self.warnings.push(format!(
+            "{}: alternative {} dropped, {}",
+            rule_name, alt_no, reason
+        ));
+    }
+
+    fn token_of_name(
+        &self,
+        name: &str,
+    ) -> Option<TokenKind> {
+        match self.aliases.get(name) {
+            Some(alias) => token_kind_of_literal(alias).or_else(|| token_kind_of_name(name)),
+            None => token_kind_of_name(name),
+        }
+    }
+
+    // A string literal may be the alias of a token declared with `%token`.
+    fn token_of_literal(
+        &self,
+        literal: &str,
+    ) -> Option<TokenKind> {
+        let text = unquote(literal);
+        match self.aliases.iter().find(|(_, alias)| **alias == text) {
+            Some((name, _)) => self.token_of_name(name),
+            None => token_kind_of_literal(&text),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::lang::error::ImportError;
+    use crate::lang::parser_import::yacc::import_yacc;
+
+    #[test]
+    fn test_import_yacc() {
+        let imported = import_yacc(
+            r#"
+%{
+#include <stdio.h>
+%}
+%token <num> NUMBER
+%token <str> IDENTIFIER
+%token FN "fn" ARROW "->"
+%left '+' '-'
+%left '*' '/'
+%nonassoc UMINUS
+%start program
+%%
+/* a program is a list of functions */
+program   : program function { $$ = append($1, $2); }
+          | %empty
+          ;
+function  : "fn" IDENTIFIER '(' ')' '{' body '}'
+          | "fn" IDENTIFIER ARROW expr ';'
+          ;
+body      : expr ';' body { $$ = $3; }
+          | error ';'
+          |
+          ;
+expr      : expr '+' expr | expr '-' expr | expr '*' expr | expr '/' expr
+          | '-' expr %prec UMINUS
+          | '(' expr ')'
+          | NUMBER
+          | IDENTIFIER
+          ;
+%%
+int main() { return yyparse(); }
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            imported.rules.to_string(),
+            "\
+Rules[
+  %left + -
+  %left * /
+  S                    -> S function | EPSILON
+  function             -> FN ID ( ) { body }
+  body                 -> expr ; body | EPSILON
+  expr                 -> expr + expr | expr - expr | expr * expr | expr / expr | - expr | ( expr ) | INT | ID
+]"
+        );
+        assert_eq!(
+            imported.warnings,
+            [
+                "%nonassoc has no counterpart, declared precedence is not imported",
+                "program: 1 actions ignored",
+                "function: alternative 1 dropped, token ARROW without counterpart",
+                "body: alternative 1 dropped, error recovery",
+                "body: 1 actions ignored",
+                "expr: %prec ignored",
+                "start rule program renamed to S",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_yacc_nothing_left() {
+        let err = import_yacc("%%\nS: 'a' ;\n").err().unwrap();
+
+        match err {
+            ImportError::NoAlternativeLeft {
+                start_rule,
+                warnings,
+            } => {
+                assert_eq!(start_rule, "S");
+                assert!(
+                    warnings[0].starts_with("S: alternative 0 dropped"),
+                    "{:?}",
+                    warnings
+                );
+            },
+            err => panic!("unexpected error: {}", err),
+        }
+    }
+}
@@ -1,7 +1,13 @@
+use std::collections::HashMap;
 use std::collections::HashSet;
 use std::hash::BuildHasher;
 use std::hash::Hash;
 
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+
 pub(crate) fn extend<T, S, I: IntoIterator<Item = T>>(
     set: &mut HashSet<T, S>,
     with: I,
@@ -16,3 +22,35 @@ where
 
     before_len != after_len
 }
+
+
+// For `#[serde(with = "...")]` on maps keyed by something JSON has no keys for, as a list of
+// (key, value) pairs sorted by key.
+pub(crate) mod map_entries {
+    use super::*;
+
+    pub(crate) fn serialize<K, V, S>(
+        map: &HashMap<K, V>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        K: Serialize + Ord,
+        V: Serialize,
+        S: Serializer,
+    {
+        let mut entries = map.iter().collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        entries.serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, K, V, D>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+    where
+        K: Deserialize<'de> + Eq + Hash,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Ok(Vec::<(K, V)>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
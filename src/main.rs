@@ -1,12 +1,16 @@
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
+use std::error::Error;
 
 use log::trace;
 use pretty_env_logger::formatted_builder;
+use toylang1::lang::ast::grammar::GRAMMAR_0;
+use toylang1::lang::ast::lower::lower;
 use toylang1::lang::lexer::token::TokenKind;
 use toylang1::lang::lexer::v0::Lexer;
 use toylang1::lang::parser::node::display_of;
 use toylang1::lang::parser::rules::Rules;
+use toylang1::lang::parser::tree::Tree;
 use toylang1::lang::parser_impl::backtracking_parser::parse_with_backtracking;
 use toylang1::lang::parser_impl::recursive_descent_parser::recursive_descent_parse;
 
@@ -41,25 +45,6 @@ fn get(what: &str) -> &'static str {
     }
     ";
 
-    const GRAMMAR_0: &str = "
-
-S               -> fn_call_or_decl , S | fn_call_or_decl |
-fn_call_or_decl -> fn_call | fn_declaration
-fn_call         -> ID ( args ) ;
-args            -> arg , args | arg |
-arg             -> STRING | INT | ID
-fn_declaration  -> FN ID ( params ) { statements }
-params          -> param , params | param |
-param           -> ID ID
-statements      -> statement statements | statement |
-statement       -> ID ID ; | ID = expressions ; | fn_call | ret 
-expressions     -> terms + expressions | terms - expressions | terms
-terms           -> factor * terms | factor / terms | factor
-factor          -> ( expressions ) | INT | ID
-ret             -> RETURN expressions ;
-
-";
-
     match what {
         "correct_0" => SAMPLE_CORRECT_PROGRAM_0,
         "correct_1" => SAMPLE_CORRECT_PROGRAM_1,
@@ -113,7 +98,9 @@ fn first_follow_start(rules: &Rules) {
         .map(|it| (it.0, it.1.into_iter().collect::<BTreeSet<_>>()))
         .collect::<BTreeMap<_, _>>()
         .into_iter()
-        .for_each(|it| println!("follow of {} => {:?}", it.0, it.1));
+        .for_each(|((name, alt_no), tokens)| {
+            println!("follow of AltRef[{}#{}] => {:?}", name, alt_no, tokens)
+        });
 }
 
 
@@ -187,6 +174,8 @@ fn recursive_correct_program(rules: &Rules) -> Result<(), String> {
     match recursive_descent_parse(rules, lexer.into_iter()) {
         Ok(tree) => {
             println!("tree:\n{}", display_of(&tree));
+            let program = lower(&Tree::from(&tree)).map_err(|it| it.to_string())?;
+            println!("ast:\n{:#?}", program);
             Ok(())
         },
         Err(err) => {
@@ -242,7 +231,7 @@ fn recursive(rules: &Rules) -> Result<(), String> {
 }
 
 
-fn main() -> Result<(), String> {
+fn main() -> Result<(), Box<dyn Error>> {
     // en_log();
 
     println!("\n\n===================================================\n\n");
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+
+use proc_macro2::Delimiter;
+use proc_macro2::Span;
+use proc_macro2::TokenStream;
+use proc_macro2::TokenTree;
+use quote::quote;
+use quote::quote_spanned;
+use toylang1::lang::error::GrammarError;
+use toylang1::lang::parser::rules::Rules;
+
+// `grammar! { S -> ... }`, the grammar DSL of `Rules::parse` checked while compiling: rules are
+// parsed and validated, and with `#![backtrack_free]` in front checked to be backtrack free too.
+// Problems are compile errors pointing at the rule they are about. Expands to an expression of
+// type `Rules`.
+//
+// A rule starts at `name ->`, a precedence declaration at `%`, so rules do not need a line each.
+// Brackets have to be balanced, unbalanced ones are written by name: `LEFT_PAREN`.
+#[proc_macro]
+pub fn grammar(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand(input.into()).into()
+}
+
+
+fn expand(input: TokenStream) -> TokenStream {
+    let mut items = vec![];
+    flatten(input, &mut items);
+
+    let backtrack_free = match items.as_slice() {
+        [Item::Punct('#', _), Item::Punct('!', _), Item::Open(Delimiter::Bracket), Item::Word(flag, span), Item::Close(Delimiter::Bracket), ..] =>
+        {
+            if flag != "backtrack_free" {
+                return compile_error(&format!("unknown flag: {}", flag), *span);
+            }
+            items.drain(..5);
+            true
+        },
+        _ => false,
+    };
+
+    let (description, spans) = description_of(&items);
+
+    let rules = match Rules::parse(&description) {
+        Ok(rules) => rules,
+        Err(err) => return compile_error(&err.to_string(), spans.of(&err)),
+    };
+    if let Err(err) = rules.validate() {
+        return compile_error(&err.to_string(), spans.of(&err));
+    }
+    if backtrack_free {
+        if let Err(err) = rules.is_backtrack_free() {
+            return compile_error(&err.to_string(), spans.of(&err));
+        }
+    }
+
+    quote! {
+        ::toylang1::lang::parser::rules::Rules::parse(#description).unwrap()
+    }
+}
+
+enum Item {
+    Word(String, Span),
+    Punct(char, bool),
+    Open(Delimiter),
+    Close(Delimiter),
+}
+
+// Groups are opened and closed by items of their own, `( a )` is three items.
+fn flatten(
+    stream: TokenStream,
+    items: &mut Vec<Item>,
+) {
+    for tree in stream {
+        match tree {
+            TokenTree::Group(group) => {
+                items.push(Item::Open(group.delimiter()));
+                flatten(group.stream(), items);
+                items.push(Item::Close(group.delimiter()));
+            },
+            TokenTree::Ident(ident) => items.push(Item::Word(ident.to_string(), ident.span())),
+            TokenTree::Literal(literal) => {
+                items.push(Item::Word(literal.to_string(), literal.span()))
+            },
+            TokenTree::Punct(punct) => items.push(Item::Punct(
+                punct.as_char(),
+                punct.spacing() == proc_macro2::Spacing::Joint,
+            )),
+        }
+    }
+}
+
+// Where each rule is named, and where each word is first used, for a rule that is never named.
+#[derive(Default)]
+struct Spans {
+    rules: HashMap<String, Span>,
+    words: HashMap<String, Span>,
+}
+
+impl Spans {
+    // Where the rule the error is about is named or else used, else the rule mentioned first in
+    // the error, the whole grammar if none is.
+    fn of(
+        &self,
+        err: &GrammarError,
+    ) -> Span {
+        if let Some(rule) = err.rule() {
+            if let Some(span) = self.rules.get(rule).or(self.words.get(rule)) {
+                return *span;
+            }
+        }
+        err.to_string()
+            .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .find_map(|word| self.rules.get(word))
+            .cloned()
+            .unwrap_or_else(Span::call_site)
+    }
+}
+
+// The text `Rules::parse` takes, a line per rule and declaration, and where the words are.
+fn description_of(items: &[Item]) -> (String, Spans) {
+    let mut lines: Vec<String> = vec![];
+    let mut spans = Spans::default();
+
+    let mut index = 0;
+    while index < items.len() {
+        let starts_rule = matches!(
+            (&items[index], items.get(index + 1), items.get(index + 2)),
+            (
+                Item::Word(..),
+                Some(Item::Punct('-', true)),
+                Some(Item::Punct('>', _))
+            )
+        );
+        let starts_declaration = matches!(
+            (&items[index], items.get(index + 1)),
+            (Item::Punct('%', _), Some(Item::Word(..)))
+        );
+
+        if starts_rule || starts_declaration || lines.is_empty() {
+            lines.push(String::new());
+        }
+        let line = lines.last_mut().unwrap();
+
+        match &items[index] {
+            Item::Word(name, span) if starts_rule => {
+                spans.rules.entry(name.clone()).or_insert(*span);
+                *line += &format!("{} ->", name);
+                index += 3;
+                continue;
+            },
+            Item::Punct('%', _) if starts_declaration => {
+                let Item::Word(directive, _) = &items[index + 1]
+                else {
+                    unreachable!()
+                };
+                *line += &format!("%{}", directive);
+                index += 2;
+                continue;
+            },
+            Item::Word(word, span) => {
+                spans.words.entry(word.clone()).or_insert(*span);
+                *line += &format!(" {}", word)
+            },
+            Item::Punct(c, _) => *line += &format!(" {}", c),
+            Item::Open(delimiter) => *line += &format!(" {}", open_of(*delimiter)),
+            Item::Close(delimiter) => *line += &format!(" {}", close_of(*delimiter)),
+        }
+        index += 1;
+    }
+
+    (lines.join("\n"), spans)
+}
+
+fn open_of(delimiter: Delimiter) -> &'static str {
+    match delimiter {
+        Delimiter::Parenthesis => "(",
+        Delimiter::Brace => "{",
+        Delimiter::Bracket => "[",
+        Delimiter::None => "",
+    }
+}
+
+fn close_of(delimiter: Delimiter) -> &'static str {
+    match delimiter {
+        Delimiter::Parenthesis => ")",
+        Delimiter::Brace => "}",
+        Delimiter::Bracket => "]",
+        Delimiter::None => "",
+    }
+}
+
+fn compile_error(
+    message: &str,
+    span: Span,
+) -> TokenStream {
+    quote_spanned! { span =>
+        ::core::compile_error!(#message)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use crate::expand;
+
+    #[test]
+    fn test_expand() {
+        let expanded = expand(quote! {
+            %left + -
+            S    -> expr ; S | EPSILON
+            expr -> expr + expr | expr - expr | ( expr ) | ID
+        });
+        assert_eq!(
+            expanded.to_string(),
+            quote! {
+                ::toylang1::lang::parser::rules::Rules::parse(
+                    "%left + -\nS -> expr ; S | EPSILON\nexpr -> expr + expr | expr - expr | ( expr ) | ID"
+                ).unwrap()
+            }
+            .to_string()
+        );
+
+        let expanded = expand(quote! {
+            S    -> expr ; S | EPSILON
+            expr -> ID | exrp
+        });
+        assert!(expanded
+            .to_string()
+            .starts_with(":: core :: compile_error !"));
+        assert!(expanded.to_string().contains("exrp"));
+
+        let expanded = expand(quote! {
+            #![backtrack_free]
+            S -> ID = ID ; | ID ( ) ;
+        });
+        assert!(expanded.to_string().contains("compile_error"));
+    }
+}
@@ -0,0 +1,17 @@
+use toylang1::lang::lexer::v0::Lexer;
+use toylang1::lang::parser_impl::recursive_descent_parser::recursive_descent_parse;
+use toylang1_macros::grammar;
+
+#[test]
+fn test_grammar() {
+    let rules = grammar! {
+        #![backtrack_free]
+        S         -> statement S | EPSILON
+        statement -> ID = expr ; | RETURN expr ;
+        expr      -> ( expr ) | INT | ID
+    };
+    assert_eq!(rules.rules().len(), 3);
+
+    let lexer: Lexer = "x = (1); return x;".into();
+    recursive_descent_parse(&rules, lexer.into_iter()).unwrap();
+}